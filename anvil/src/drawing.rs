@@ -16,8 +16,8 @@ use smithay::{
     utils::{Logical, Point, Rectangle},
     wayland::{
         compositor::{
-            get_role, with_states, with_surface_tree_upward, Damage, SubsurfaceCachedState,
-            SurfaceAttributes, TraversalAction,
+            get_role, with_states, with_surface_tree_upward, SubsurfaceCachedState, SurfaceAttributes,
+            TraversalAction,
         },
         seat::CursorImageAttributes,
         shell::wlr_layer::Layer,
@@ -105,14 +105,11 @@ where
                 let attributes = states.cached_state.current::<SurfaceAttributes>();
                 if data.texture.is_none() {
                     if let Some(buffer) = data.buffer.take() {
+                        // TODO also apply transformations
                         let damage = attributes
                             .damage
                             .iter()
-                            .map(|dmg| match dmg {
-                                Damage::Buffer(rect) => *rect,
-                                // TODO also apply transformations
-                                Damage::Surface(rect) => rect.to_buffer(attributes.buffer_scale),
-                            })
+                            .map(|dmg| dmg.to_buffer(attributes.buffer_scale))
                             .collect::<Vec<_>>();
 
                         match renderer.import_buffer(&buffer, Some(states), &damage) {
@@ -160,7 +157,10 @@ where
             let mut location = *location;
             if let Some(data) = states.data_map.get::<RefCell<SurfaceData>>() {
                 let mut data = data.borrow_mut();
-                let buffer_scale = data.buffer_scale;
+                let (src, size) = match (data.buffer_src(), data.size()) {
+                    (Some(src), Some(size)) => (src, size),
+                    _ => return,
+                };
                 if let Some(texture) = data
                     .texture
                     .as_mut()
@@ -172,14 +172,12 @@ where
                         let current = states.cached_state.current::<SubsurfaceCachedState>();
                         location += current.location;
                     }
-                    if let Err(err) = frame.render_texture_at(
-                        &texture.texture,
-                        location.to_f64().to_physical(output_scale as f64).to_i32_round(),
-                        buffer_scale,
-                        output_scale as f64,
-                        Transform::Normal, /* TODO */
-                        1.0,
-                    ) {
+                    let dest = Rectangle::from_loc_and_size(location, size)
+                        .to_f64()
+                        .to_physical(output_scale as f64);
+                    if let Err(err) =
+                        frame.render_texture_from_to(&texture.texture, src, dest, data.buffer_transform, 1.0)
+                    {
                         result = Err(err.into());
                     }
                 }
@@ -24,19 +24,30 @@ use smithay::{
 #[cfg(any(feature = "winit", feature = "x11"))]
 use smithay::backend::input::PointerMotionAbsoluteEvent;
 
-#[cfg(feature = "udev")]
+#[cfg(any(feature = "udev", feature = "x11"))]
 use smithay::{
-    backend::{
-        input::{
-            Device, DeviceCapability, PointerMotionEvent, ProximityState, TabletToolButtonEvent,
-            TabletToolEvent, TabletToolProximityEvent, TabletToolTipEvent, TabletToolTipState,
-        },
-        session::Session,
+    backend::input::{
+        Device, DeviceCapability, ProximityState, TabletToolButtonEvent, TabletToolEvent,
+        TabletToolProximityEvent, TabletToolTipEvent, TabletToolTipState,
     },
-    utils::{Logical, Point},
     wayland::tablet_manager::{TabletDescriptor, TabletSeatTrait},
 };
 
+#[cfg(any(feature = "udev", feature = "x11"))]
+use smithay::backend::input::PointerMotionEvent;
+
+#[cfg(feature = "udev")]
+use smithay::{backend::session::Session, utils::Point};
+
+#[cfg(feature = "winit")]
+use smithay::{
+    backend::input::{TouchDownEvent, TouchMotionEvent, TouchUpEvent},
+    utils::Size,
+};
+
+#[cfg(any(feature = "udev", feature = "winit"))]
+use smithay::utils::Logical;
+
 impl<Backend> AnvilState<Backend> {
     fn keyboard_key_to_action<B: InputBackend>(&mut self, evt: B::KeyboardKeyEvent) -> KeyAction {
         let keycode = evt.key_code();
@@ -143,6 +154,128 @@ impl<Backend> AnvilState<Backend> {
     }
 }
 
+#[cfg(any(feature = "udev", feature = "x11"))]
+impl<Backend> AnvilState<Backend> {
+    fn on_tablet_tool_axis<B: InputBackend>(&mut self, evt: B::TabletToolAxisEvent) {
+        let output_map = self.output_map.borrow();
+        let pointer_location = &mut self.pointer_location;
+        let tablet_seat = self.seat.tablet_seat();
+        let window_map = self.window_map.borrow();
+
+        let output_geometry = output_map.with_primary().map(|o| o.geometry());
+
+        if let Some(rect) = output_geometry {
+            *pointer_location = evt.position_transformed(rect.size) + rect.loc.to_f64();
+
+            let under = window_map.get_surface_under(*pointer_location);
+            let tablet = tablet_seat.get_tablet(&TabletDescriptor::from(&evt.device()));
+            let tool = tablet_seat.get_tool(&evt.tool());
+
+            if let (Some(tablet), Some(tool)) = (tablet, tool) {
+                if evt.pressure_has_changed() {
+                    tool.pressure(evt.pressure());
+                }
+                if evt.distance_has_changed() {
+                    tool.distance(evt.distance());
+                }
+                if evt.tilt_has_changed() {
+                    tool.tilt(evt.tilt());
+                }
+                if evt.slider_has_changed() {
+                    tool.slider_position(evt.slider_position());
+                }
+                if evt.rotation_has_changed() {
+                    tool.rotation(evt.rotation());
+                }
+                if evt.wheel_has_changed() {
+                    tool.wheel(evt.wheel_delta(), evt.wheel_delta_discrete());
+                }
+
+                tool.motion(
+                    *pointer_location,
+                    under,
+                    &tablet,
+                    SCOUNTER.next_serial(),
+                    evt.time(),
+                );
+            }
+        }
+    }
+
+    fn on_tablet_tool_proximity<B: InputBackend>(&mut self, evt: B::TabletToolProximityEvent) {
+        let output_map = self.output_map.borrow();
+        let pointer_location = &mut self.pointer_location;
+        let tablet_seat = self.seat.tablet_seat();
+        let window_map = self.window_map.borrow();
+
+        let output_geometry = output_map.with_primary().map(|o| o.geometry());
+
+        if let Some(rect) = output_geometry {
+            let tool = evt.tool();
+            tablet_seat.add_tool(&tool);
+
+            *pointer_location = evt.position_transformed(rect.size) + rect.loc.to_f64();
+
+            let under = window_map.get_surface_under(*pointer_location);
+            let tablet = tablet_seat.get_tablet(&TabletDescriptor::from(&evt.device()));
+            let tool = tablet_seat.get_tool(&tool);
+
+            if let (Some(under), Some(tablet), Some(tool)) = (under, tablet, tool) {
+                match evt.state() {
+                    ProximityState::In => tool.proximity_in(
+                        *pointer_location,
+                        under,
+                        &tablet,
+                        SCOUNTER.next_serial(),
+                        evt.time(),
+                    ),
+                    ProximityState::Out => tool.proximity_out(evt.time()),
+                }
+            }
+        }
+    }
+
+    fn on_tablet_tool_tip<B: InputBackend>(&mut self, evt: B::TabletToolTipEvent) {
+        let tool = self.seat.tablet_seat().get_tool(&evt.tool());
+
+        if let Some(tool) = tool {
+            match evt.tip_state() {
+                TabletToolTipState::Down => {
+                    tool.tip_down(SCOUNTER.next_serial(), evt.time());
+
+                    // change the keyboard focus unless the pointer is grabbed
+                    if !self.pointer.is_grabbed() {
+                        let under = self
+                            .window_map
+                            .borrow_mut()
+                            .get_surface_and_bring_to_top(self.pointer_location);
+
+                        let serial = SCOUNTER.next_serial();
+                        self.keyboard
+                            .set_focus(under.as_ref().map(|&(ref s, _)| s), serial);
+                    }
+                }
+                TabletToolTipState::Up => {
+                    tool.tip_up(evt.time());
+                }
+            }
+        }
+    }
+
+    fn on_tablet_button<B: InputBackend>(&mut self, evt: B::TabletToolButtonEvent) {
+        let tool = self.seat.tablet_seat().get_tool(&evt.tool());
+
+        if let Some(tool) = tool {
+            tool.button(
+                evt.button(),
+                evt.button_state(),
+                SCOUNTER.next_serial(),
+                evt.time(),
+            );
+        }
+    }
+}
+
 #[cfg(feature = "winit")]
 impl AnvilState<WinitData> {
     pub fn process_input_event<B: InputBackend>(&mut self, event: InputEvent<B>) {
@@ -196,20 +329,47 @@ impl AnvilState<WinitData> {
             InputEvent::PointerMotionAbsolute { event, .. } => self.on_pointer_move_absolute::<B>(event),
             InputEvent::PointerButton { event, .. } => self.on_pointer_button::<B>(event),
             InputEvent::PointerAxis { event, .. } => self.on_pointer_axis::<B>(event),
+            InputEvent::TouchDown { event, .. } => self.on_touch_down::<B>(event),
+            InputEvent::TouchMotion { event, .. } => self.on_touch_motion::<B>(event),
+            InputEvent::TouchUp { event, .. } => {
+                let slot_id = event.slot().map_or(0, i32::from);
+                self.touch.up(SCOUNTER.next_serial(), event.time(), slot_id);
+            }
+            InputEvent::TouchCancel { .. } => self.touch.cancel(),
+            InputEvent::TouchFrame { .. } => self.touch.frame(),
             _ => {
                 // other events are not handled in anvil (yet)
             }
         }
     }
 
-    fn on_pointer_move_absolute<B: InputBackend>(&mut self, evt: B::PointerMotionAbsoluteEvent) {
-        let output_size = self
-            .output_map
+    fn winit_output_size(&self) -> Size<i32, Logical> {
+        self.output_map
             .borrow()
             .find_by_name(crate::winit::OUTPUT_NAME)
             .map(|o| o.size())
-            .unwrap();
-        let pos = evt.position_transformed(output_size);
+            .unwrap()
+    }
+
+    fn on_touch_down<B: InputBackend>(&mut self, evt: B::TouchDownEvent) {
+        let pos = evt.position_transformed(self.winit_output_size());
+        let serial = SCOUNTER.next_serial();
+        // touching a window focuses it, like clicking it does
+        let under = self.window_map.borrow_mut().get_surface_and_bring_to_top(pos);
+        self.keyboard
+            .set_focus(under.as_ref().map(|&(ref s, _)| s), serial);
+        self.touch
+            .down(serial, evt.time(), under, pos, evt.slot().map_or(0, i32::from));
+    }
+
+    fn on_touch_motion<B: InputBackend>(&mut self, evt: B::TouchMotionEvent) {
+        let pos = evt.position_transformed(self.winit_output_size());
+        self.touch
+            .motion(evt.time(), pos, evt.slot().map_or(0, i32::from));
+    }
+
+    fn on_pointer_move_absolute<B: InputBackend>(&mut self, evt: B::PointerMotionAbsoluteEvent) {
+        let pos = evt.position_transformed(self.winit_output_size());
         self.pointer_location = pos;
         let serial = SCOUNTER.next_serial();
         let under = self.window_map.borrow().get_surface_under(pos);
@@ -342,129 +502,13 @@ impl AnvilState<UdevData> {
         self.pointer_location = self.clamp_coords(self.pointer_location);
 
         let under = self.window_map.borrow().get_surface_under(self.pointer_location);
+        // the relative motion is sent first, so that it is part of the frame of the motion
+        self.pointer
+            .relative_motion(evt.delta(), evt.delta_unaccel(), evt.time() as u64 * 1000);
         self.pointer
             .motion(self.pointer_location, under, serial, evt.time());
     }
 
-    fn on_tablet_tool_axis<B: InputBackend>(&mut self, evt: B::TabletToolAxisEvent) {
-        let output_map = self.output_map.borrow();
-        let pointer_location = &mut self.pointer_location;
-        let tablet_seat = self.seat.tablet_seat();
-        let window_map = self.window_map.borrow();
-
-        let output_geometry = output_map.with_primary().map(|o| o.geometry());
-
-        if let Some(rect) = output_geometry {
-            *pointer_location = evt.position_transformed(rect.size) + rect.loc.to_f64();
-
-            let under = window_map.get_surface_under(*pointer_location);
-            let tablet = tablet_seat.get_tablet(&TabletDescriptor::from(&evt.device()));
-            let tool = tablet_seat.get_tool(&evt.tool());
-
-            if let (Some(tablet), Some(tool)) = (tablet, tool) {
-                if evt.pressure_has_changed() {
-                    tool.pressure(evt.pressure());
-                }
-                if evt.distance_has_changed() {
-                    tool.distance(evt.distance());
-                }
-                if evt.tilt_has_changed() {
-                    tool.tilt(evt.tilt());
-                }
-                if evt.slider_has_changed() {
-                    tool.slider_position(evt.slider_position());
-                }
-                if evt.rotation_has_changed() {
-                    tool.rotation(evt.rotation());
-                }
-                if evt.wheel_has_changed() {
-                    tool.wheel(evt.wheel_delta(), evt.wheel_delta_discrete());
-                }
-
-                tool.motion(
-                    *pointer_location,
-                    under,
-                    &tablet,
-                    SCOUNTER.next_serial(),
-                    evt.time(),
-                );
-            }
-        }
-    }
-
-    fn on_tablet_tool_proximity<B: InputBackend>(&mut self, evt: B::TabletToolProximityEvent) {
-        let output_map = self.output_map.borrow();
-        let pointer_location = &mut self.pointer_location;
-        let tablet_seat = self.seat.tablet_seat();
-        let window_map = self.window_map.borrow();
-
-        let output_geometry = output_map.with_primary().map(|o| o.geometry());
-
-        if let Some(rect) = output_geometry {
-            let tool = evt.tool();
-            tablet_seat.add_tool(&tool);
-
-            *pointer_location = evt.position_transformed(rect.size) + rect.loc.to_f64();
-
-            let under = window_map.get_surface_under(*pointer_location);
-            let tablet = tablet_seat.get_tablet(&TabletDescriptor::from(&evt.device()));
-            let tool = tablet_seat.get_tool(&tool);
-
-            if let (Some(under), Some(tablet), Some(tool)) = (under, tablet, tool) {
-                match evt.state() {
-                    ProximityState::In => tool.proximity_in(
-                        *pointer_location,
-                        under,
-                        &tablet,
-                        SCOUNTER.next_serial(),
-                        evt.time(),
-                    ),
-                    ProximityState::Out => tool.proximity_out(evt.time()),
-                }
-            }
-        }
-    }
-
-    fn on_tablet_tool_tip<B: InputBackend>(&mut self, evt: B::TabletToolTipEvent) {
-        let tool = self.seat.tablet_seat().get_tool(&evt.tool());
-
-        if let Some(tool) = tool {
-            match evt.tip_state() {
-                TabletToolTipState::Down => {
-                    tool.tip_down(SCOUNTER.next_serial(), evt.time());
-
-                    // change the keyboard focus unless the pointer is grabbed
-                    if !self.pointer.is_grabbed() {
-                        let under = self
-                            .window_map
-                            .borrow_mut()
-                            .get_surface_and_bring_to_top(self.pointer_location);
-
-                        let serial = SCOUNTER.next_serial();
-                        self.keyboard
-                            .set_focus(under.as_ref().map(|&(ref s, _)| s), serial);
-                    }
-                }
-                TabletToolTipState::Up => {
-                    tool.tip_up(evt.time());
-                }
-            }
-        }
-    }
-
-    fn on_tablet_button<B: InputBackend>(&mut self, evt: B::TabletToolButtonEvent) {
-        let tool = self.seat.tablet_seat().get_tool(&evt.tool());
-
-        if let Some(tool) = tool {
-            tool.button(
-                evt.button(),
-                evt.button_state(),
-                SCOUNTER.next_serial(),
-                evt.time(),
-            );
-        }
-    }
-
     fn clamp_coords(&self, pos: Point<f64, Logical>) -> Point<f64, Logical> {
         if self.output_map.borrow().is_empty() {
             return pos;
@@ -545,8 +589,28 @@ impl AnvilState<X11Data> {
             },
 
             InputEvent::PointerMotionAbsolute { event } => self.on_pointer_move_absolute::<B>(event),
+            // The absolute motion already moves the pointer, only forward the relative motion
+            InputEvent::PointerMotion { event } => {
+                self.pointer.relative_motion(
+                    event.delta(),
+                    event.delta_unaccel(),
+                    event.time() as u64 * 1000,
+                );
+                self.pointer.frame();
+            }
             InputEvent::PointerButton { event } => self.on_pointer_button::<B>(event),
             InputEvent::PointerAxis { event } => self.on_pointer_axis::<B>(event),
+            InputEvent::TabletToolAxis { event } => self.on_tablet_tool_axis::<B>(event),
+            InputEvent::TabletToolProximity { event } => self.on_tablet_tool_proximity::<B>(event),
+            InputEvent::TabletToolTip { event } => self.on_tablet_tool_tip::<B>(event),
+            InputEvent::TabletToolButton { event } => self.on_tablet_button::<B>(event),
+            InputEvent::DeviceAdded { device } => {
+                if device.has_capability(DeviceCapability::TabletTool) {
+                    self.seat
+                        .tablet_seat()
+                        .add_tablet(&TabletDescriptor::from(&device));
+                }
+            }
             _ => (), // other events are not handled in anvil (yet)
         }
     }
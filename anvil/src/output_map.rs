@@ -108,6 +108,10 @@ impl Output {
         self.current_mode
     }
 
+    pub fn output(&self) -> &output::Output {
+        &self.output
+    }
+
     /// Add a layer surface to this output
     pub fn add_layer_surface(&self, layer: wl_surface::WlSurface) {
         self.layer_surfaces.borrow_mut().push(layer);
@@ -5,7 +5,7 @@ use std::{
 };
 
 use smithay::{
-    backend::renderer::buffer_dimensions,
+    backend::renderer::{buffer_dimensions, surface_buffer_src, surface_logical_size, Transform},
     reexports::{
         wayland_protocols::xdg_shell::server::xdg_toplevel,
         wayland_server::{
@@ -13,7 +13,7 @@ use smithay::{
             Display,
         },
     },
-    utils::{Logical, Physical, Point, Rectangle, Size},
+    utils::{Buffer, Logical, Point, Rectangle, Size},
     wayland::{
         compositor::{
             compositor_init, is_sync_subsurface, with_states, with_surface_tree_upward, BufferAssignment,
@@ -28,6 +28,7 @@ use smithay::{
                 XdgPopupSurfaceRoleAttributes, XdgRequest, XdgToplevelSurfaceRoleAttributes,
             },
         },
+        viewporter::ViewportCachedState,
         Serial,
     },
 };
@@ -901,6 +902,14 @@ pub fn init_shell<BackendData: 'static>(display: Rc<RefCell<Display>>, log: ::sl
             }
 
             LayerShellRequest::AckConfigure { .. } => {}
+
+            LayerShellRequest::Destroyed { surface } => {
+                if let Some(anvil_state) = ddata.get::<AnvilState<BackendData>>() {
+                    if let Some(output) = anvil_state.output_map.borrow().find_by_layer_surface(&surface) {
+                        anvil_state.window_map.borrow_mut().layers.arange_layers(output);
+                    }
+                }
+            }
         },
         log.clone(),
     );
@@ -947,8 +956,10 @@ pub struct SurfaceData {
     pub texture: Option<Box<dyn std::any::Any + 'static>>,
     pub geometry: Option<Rectangle<i32, Logical>>,
     pub resize_state: ResizeState,
-    pub buffer_dimensions: Option<Size<i32, Physical>>,
+    pub buffer_dimensions: Option<Size<i32, Buffer>>,
     pub buffer_scale: i32,
+    pub buffer_transform: Transform,
+    pub viewport: ViewportCachedState,
 }
 
 impl SurfaceData {
@@ -958,6 +969,7 @@ impl SurfaceData {
                 // new contents
                 self.buffer_dimensions = buffer_dimensions(&buffer);
                 self.buffer_scale = attrs.buffer_scale;
+                self.buffer_transform = attrs.buffer_transform.into();
                 if let Some(old_buffer) = std::mem::replace(&mut self.buffer, Some(buffer)) {
                     old_buffer.release();
                 }
@@ -976,7 +988,13 @@ impl SurfaceData {
     /// Returns the size of the surface.
     pub fn size(&self) -> Option<Size<i32, Logical>> {
         self.buffer_dimensions
-            .map(|dims| dims.to_logical(self.buffer_scale))
+            .map(|dims| surface_logical_size(dims, self.buffer_scale, self.buffer_transform, &self.viewport))
+    }
+
+    /// Returns the part of the buffer displayed by the surface.
+    pub fn buffer_src(&self) -> Option<Rectangle<i32, Buffer>> {
+        self.buffer_dimensions
+            .map(|dims| surface_buffer_src(dims, self.buffer_scale, self.buffer_transform, &self.viewport))
     }
 
     /// Checks if the surface's input region contains the point.
@@ -1044,6 +1062,7 @@ fn surface_commit(
                     .unwrap()
                     .borrow_mut();
                 data.update_buffer(&mut *states.cached_state.current::<SurfaceAttributes>());
+                data.viewport = *states.cached_state.current::<ViewportCachedState>();
             },
             |_, _, _| true,
         );
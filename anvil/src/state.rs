@@ -17,10 +17,13 @@ use smithay::{
     wayland::{
         data_device::{default_action_chooser, init_data_device, set_data_device_focus, DataDeviceEvent},
         output::xdg::init_xdg_output_manager,
-        seat::{CursorImageStatus, KeyboardHandle, PointerHandle, Seat, XkbConfig},
+        primary_selection::{init_primary_selection, set_primary_focus},
+        relative_pointer::init_relative_pointer_manager_global,
+        seat::{CursorImageStatus, KeyboardHandle, PointerHandle, Seat, TouchHandle, XkbConfig},
         shell::xdg::decoration::{init_xdg_decoration_manager, XdgDecorationRequest},
         shm::init_shm_global,
         tablet_manager::{init_tablet_manager_global, TabletSeatTrait},
+        viewporter::init_viewporter_global,
         xdg_activation::{init_xdg_activation_global, XdgActivationEvent},
     },
 };
@@ -44,6 +47,7 @@ pub struct AnvilState<BackendData> {
     // input-related fields
     pub pointer: PointerHandle,
     pub keyboard: KeyboardHandle,
+    pub touch: TouchHandle,
     pub suppressed_keys: Vec<u32>,
     pub pointer_location: Point<f64, Logical>,
     pub cursor_status: Arc<Mutex<CursorImageStatus>>,
@@ -98,6 +102,7 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
         init_shell::<BackendData>(display.clone(), log.clone());
 
         init_xdg_output_manager(&mut display.borrow_mut(), log.clone());
+        init_viewporter_global(&mut display.borrow_mut(), log.clone());
         init_xdg_activation_global(
             &mut display.borrow_mut(),
             |state, req, mut ddata| {
@@ -124,18 +129,9 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
 
         init_xdg_decoration_manager(
             &mut display.borrow_mut(),
+            xdg_decoration::v1::server::zxdg_toplevel_decoration_v1::Mode::ClientSide,
             |req, _ddata| match req {
-                XdgDecorationRequest::NewToplevelDecoration { toplevel } => {
-                    use xdg_decoration::v1::server::zxdg_toplevel_decoration_v1::Mode;
-
-                    let res = toplevel.with_pending_state(|state| {
-                        state.decoration_mode = Some(Mode::ClientSide);
-                    });
-
-                    if res.is_ok() {
-                        toplevel.send_configure();
-                    }
-                }
+                XdgDecorationRequest::NewToplevelDecoration { .. } => {}
                 XdgDecorationRequest::SetMode { .. } => {}
                 XdgDecorationRequest::UnsetMode { .. } => {}
             },
@@ -176,6 +172,8 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
             log.clone(),
         );
 
+        init_primary_selection(&mut display.borrow_mut(), |_| {}, log.clone());
+
         // init input
         let seat_name = backend_data.seat_name();
 
@@ -190,6 +188,7 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
         });
 
         init_tablet_manager_global(&mut display.borrow_mut());
+        init_relative_pointer_manager_global(&mut display.borrow_mut(), log.clone());
 
         let cursor_status3 = cursor_status.clone();
         seat.tablet_seat().on_cursor_surface(move |_tool, new_status| {
@@ -199,10 +198,14 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
 
         let keyboard = seat
             .add_keyboard(XkbConfig::default(), 200, 25, |seat, focus| {
-                set_data_device_focus(seat, focus.and_then(|s| s.as_ref().client()))
+                let client = focus.and_then(|s| s.as_ref().client());
+                set_data_device_focus(seat, client.clone());
+                set_primary_focus(seat, client);
             })
             .expect("Failed to initialize the keyboard");
 
+        let touch = seat.add_touch();
+
         #[cfg(feature = "xwayland")]
         let xwayland = {
             let (xwayland, channel) = XWayland::new(handle.clone(), display.clone(), log.clone());
@@ -231,6 +234,7 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
             socket_name,
             pointer,
             keyboard,
+            touch,
             suppressed_keys: Vec::new(),
             cursor_status,
             pointer_location: (0.0, 0.0).into(),
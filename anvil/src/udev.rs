@@ -15,7 +15,7 @@ use slog::Logger;
 use smithay::{
     backend::{
         allocator::dmabuf::Dmabuf,
-        drm::{DrmDevice, DrmError, DrmEvent, GbmBufferedSurface},
+        drm::{DrmDevice, DrmError, DrmEvent, DrmEventMetadata, GbmBufferedSurface},
         egl::{EGLContext, EGLDisplay},
         libinput::{LibinputInputBackend, LibinputSessionInterface},
         renderer::{
@@ -42,7 +42,7 @@ use smithay::{
         },
         gbm::Device as GbmDevice,
         input::Libinput,
-        nix::{fcntl::OFlag, sys::stat::dev_t},
+        nix::{fcntl::OFlag, sys::stat::dev_t, time::ClockId},
         wayland_server::{
             protocol::{wl_output, wl_surface},
             Display,
@@ -54,6 +54,7 @@ use smithay::{
     },
     wayland::{
         output::{Mode, PhysicalProperties},
+        presentation::{init_presentation_global, PresentationFeedback, PresentationFlags},
         seat::CursorImageStatus,
     },
 };
@@ -147,6 +148,13 @@ pub fn run_udev(log: Logger) {
     };
     let mut state = AnvilState::init(display.clone(), event_loop.handle(), data, log.clone(), true);
 
+    // the page flip timestamps of DRM are measured with the monotonic clock
+    init_presentation_global(
+        &mut *display.borrow_mut(),
+        ClockId::CLOCK_MONOTONIC.as_raw() as u32,
+        log.clone(),
+    );
+
     // re-render timer
     event_loop
         .handle()
@@ -268,6 +276,8 @@ pub type RenderSurface = GbmBufferedSurface<SessionFd>;
 
 struct SurfaceData {
     surface: RenderSurface,
+    // feedbacks of the content of the frame waiting for its page flip
+    pending_presentation: PresentationFeedback,
     #[cfg(feature = "debug")]
     fps: fps_ticker::Fps,
 }
@@ -395,6 +405,7 @@ fn scan_connectors(
 
                     entry.insert(Rc::new(RefCell::new(SurfaceData {
                         surface: gbm_surface,
+                        pending_presentation: PresentationFeedback::default(),
                         #[cfg(feature = "debug")]
                         fps: fps_ticker::Fps::default(),
                     })));
@@ -456,6 +467,11 @@ impl AnvilState<UdevData> {
                 }
             };
 
+            match egl.drm_device_path() {
+                Ok(egl_path) => info!(self.log, "EGL is rendering {:?} on {:?}", path, egl_path),
+                Err(err) => debug!(self.log, "Unable to query EGL device of {:?}: {}", path, err),
+            }
+
             let context = match EGLContext::new(&egl, self.log.clone()) {
                 Ok(context) => context,
                 Err(err) => {
@@ -504,8 +520,11 @@ impl AnvilState<UdevData> {
             device.link(self.backend_data.signaler.clone());
             let event_dispatcher = Dispatcher::new(
                 device,
-                move |event, _, anvil_state: &mut AnvilState<_>| match event {
-                    DrmEvent::VBlank(crtc) => anvil_state.render(dev_id, Some(crtc)),
+                move |event, metadata, anvil_state: &mut AnvilState<_>| match event {
+                    DrmEvent::VBlank(crtc) => {
+                        anvil_state.frame_finish(dev_id, crtc, metadata);
+                        anvil_state.render(dev_id, Some(crtc));
+                    }
                     DrmEvent::Error(error) => {
                         error!(anvil_state.log, "{:?}", error);
                     }
@@ -617,6 +636,45 @@ impl AnvilState<UdevData> {
         }
     }
 
+    fn frame_finish(&mut self, dev_id: u64, crtc: crtc::Handle, metadata: &mut Option<DrmEventMetadata>) {
+        let surface = match self
+            .backend_data
+            .backends
+            .get(&dev_id)
+            .and_then(|backend| backend.surfaces.borrow().get(&crtc).cloned())
+        {
+            Some(surface) => surface,
+            None => return,
+        };
+        // the feedbacks are discarded when dropped, if the page flip has no timing information
+        let mut feedback = std::mem::take(&mut surface.borrow_mut().pending_presentation);
+
+        if let Some(metadata) = metadata {
+            let refresh = self
+                .output_map
+                .borrow()
+                .find(|o| {
+                    o.userdata().get::<UdevOutputId>()
+                        == Some(&UdevOutputId {
+                            device_id: dev_id,
+                            crtc,
+                        })
+                })
+                .map(|output| output.current_mode().refresh)
+                .filter(|&refresh| refresh > 0)
+                // the refresh rate is in mHz
+                .map(|refresh| Duration::from_nanos(1_000_000_000_000 / refresh as u64))
+                .unwrap_or_default();
+
+            feedback.presented(
+                metadata.time,
+                refresh,
+                metadata.sequence as u64,
+                PresentationFlags::VSYNC | PresentationFlags::HW_CLOCK | PresentationFlags::HW_COMPLETION,
+            );
+        }
+    }
+
     // If crtc is `Some()`, render it, else render all crtcs
     fn render(&mut self, dev_id: u64, crtc: Option<crtc::Handle>) {
         let device_backend = match self.backend_data.backends.get_mut(&dev_id) {
@@ -700,6 +758,19 @@ impl AnvilState<UdevData> {
                     );
                 }
             } else {
+                // the content of the windows is displayed at the next page flip of this crtc
+                let feedback = self.window_map.borrow().take_presentation_feedback();
+                if let Some(output) = self.output_map.borrow().find(|o| {
+                    o.userdata().get::<UdevOutputId>()
+                        == Some(&UdevOutputId {
+                            device_id: device_backend.dev_id,
+                            crtc,
+                        })
+                }) {
+                    feedback.sync_output(output.output());
+                }
+                surface.borrow_mut().pending_presentation.merge(feedback);
+
                 // TODO: only send drawn windows the frames callback
                 // Send frame events so that client start drawing their next frame
                 self.window_map
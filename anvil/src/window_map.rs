@@ -8,7 +8,11 @@ use smithay::{
     },
     utils::{Logical, Point, Rectangle},
     wayland::{
-        compositor::{with_states, with_surface_tree_downward, SubsurfaceCachedState, TraversalAction},
+        compositor::{
+            bbox_of_surface_tree, with_states, with_surface_tree_downward, SubsurfaceCachedState,
+            TraversalAction,
+        },
+        presentation::{take_presentation_feedback, PresentationFeedback},
         shell::{
             legacy::ShellSurface,
             wlr_layer::Layer,
@@ -181,36 +185,15 @@ impl Window {
     }
 
     fn self_update(&mut self) {
-        let mut bounding_box = Rectangle::from_loc_and_size(self.location, (0, 0));
-        if let Some(wl_surface) = self.toplevel.get_surface() {
-            with_surface_tree_downward(
-                wl_surface,
-                self.location,
-                |_, states, &loc| {
-                    let mut loc = loc;
-                    let data = states.data_map.get::<RefCell<SurfaceData>>();
-
-                    if let Some(size) = data.and_then(|d| d.borrow().size()) {
-                        if states.role == Some("subsurface") {
-                            let current = states.cached_state.current::<SubsurfaceCachedState>();
-                            loc += current.location;
-                        }
-
-                        // Update the bounding box.
-                        bounding_box = bounding_box.merge(Rectangle::from_loc_and_size(loc, size));
-
-                        TraversalAction::DoChildren(loc)
-                    } else {
-                        // If the parent surface is unmapped, then the child surfaces are hidden as
-                        // well, no need to consider them here.
-                        TraversalAction::SkipChildren
-                    }
-                },
-                |_, _, _| {},
-                |_, _, _| true,
-            );
-        }
-        self.bbox = bounding_box;
+        self.bbox = match self.toplevel.get_surface() {
+            Some(wl_surface) => bbox_of_surface_tree(wl_surface, self.location, |_, states| {
+                states
+                    .data_map
+                    .get::<RefCell<SurfaceData>>()
+                    .and_then(|d| d.borrow().size())
+            }),
+            None => Rectangle::from_loc_and_size(self.location, (0, 0)),
+        };
     }
 
     /// Returns the geometry of this window.
@@ -240,6 +223,34 @@ impl Window {
             );
         }
     }
+
+    /// Takes the presentation feedbacks of the surfaces of this window
+    pub fn take_presentation_feedback(&self) -> PresentationFeedback {
+        let mut feedback = PresentationFeedback::default();
+        if let Some(wl_surface) = self.toplevel.get_surface() {
+            feedback.merge(take_surface_tree_presentation_feedback(wl_surface));
+        }
+        feedback
+    }
+}
+
+/// Takes the presentation feedbacks of a surface and its subsurfaces
+fn take_surface_tree_presentation_feedback(wl_surface: &WlSurface) -> PresentationFeedback {
+    // the states of the surfaces are locked during the traversal
+    let mut surfaces = Vec::new();
+    with_surface_tree_downward(
+        wl_surface,
+        (),
+        |_, _, &()| TraversalAction::DoChildren(()),
+        |surface, _, &()| surfaces.push(surface.clone()),
+        |_, _, &()| true,
+    );
+
+    let mut feedback = PresentationFeedback::default();
+    for surface in surfaces {
+        feedback.merge(take_presentation_feedback(&surface));
+    }
+    feedback
 }
 
 #[derive(Debug)]
@@ -449,4 +460,13 @@ impl WindowMap {
         }
         self.layers.send_frames(time);
     }
+
+    pub fn take_presentation_feedback(&self) -> PresentationFeedback {
+        let mut feedback = PresentationFeedback::default();
+        for window in &self.windows {
+            feedback.merge(window.take_presentation_feedback());
+        }
+        feedback.merge(self.layers.take_presentation_feedback());
+        feedback
+    }
 }
@@ -4,11 +4,16 @@ use smithay::{
     reexports::wayland_server::protocol::wl_surface,
     utils::{Logical, Point, Rectangle},
     wayland::{
-        compositor::{with_states, with_surface_tree_downward, SubsurfaceCachedState, TraversalAction},
-        shell::wlr_layer::{self, Anchor, LayerSurfaceCachedState},
+        compositor::{
+            bbox_of_surface_tree, with_states, with_surface_tree_downward, SubsurfaceCachedState,
+            TraversalAction,
+        },
+        presentation::PresentationFeedback,
+        shell::wlr_layer::{self, LayerSurfaceCachedState},
     },
 };
 
+use super::take_surface_tree_presentation_feedback;
 use crate::{output_map::Output, shell::SurfaceData};
 
 #[derive(Debug)]
@@ -64,36 +69,15 @@ impl LayerSurface {
     }
 
     fn self_update(&mut self) {
-        let mut bounding_box = Rectangle::from_loc_and_size(self.location, (0, 0));
-        if let Some(wl_surface) = self.surface.get_surface() {
-            with_surface_tree_downward(
-                wl_surface,
-                self.location,
-                |_, states, &loc| {
-                    let mut loc = loc;
-                    let data = states.data_map.get::<RefCell<SurfaceData>>();
-
-                    if let Some(size) = data.and_then(|d| d.borrow().size()) {
-                        if states.role == Some("subsurface") {
-                            let current = states.cached_state.current::<SubsurfaceCachedState>();
-                            loc += current.location;
-                        }
-
-                        // Update the bounding box.
-                        bounding_box = bounding_box.merge(Rectangle::from_loc_and_size(loc, size));
-
-                        TraversalAction::DoChildren(loc)
-                    } else {
-                        // If the parent surface is unmapped, then the child surfaces are hidden as
-                        // well, no need to consider them here.
-                        TraversalAction::SkipChildren
-                    }
-                },
-                |_, _, _| {},
-                |_, _, _| true,
-            );
-        }
-        self.bbox = bounding_box;
+        self.bbox = match self.surface.get_surface() {
+            Some(wl_surface) => bbox_of_surface_tree(wl_surface, self.location, |_, states| {
+                states
+                    .data_map
+                    .get::<RefCell<SurfaceData>>()
+                    .and_then(|d| d.borrow().size())
+            }),
+            None => Rectangle::from_loc_and_size(self.location, (0, 0)),
+        };
 
         if let Some(surface) = self.surface.get_surface() {
             self.layer = with_states(surface, |states| {
@@ -121,6 +105,13 @@ impl LayerSurface {
             );
         }
     }
+
+    fn take_presentation_feedback(&self) -> PresentationFeedback {
+        self.surface
+            .get_surface()
+            .map(take_surface_tree_presentation_feedback)
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Default)]
@@ -196,53 +187,32 @@ impl LayerMap {
             .collect();
 
         // Find layers for this output
-        let filtered_layers = self.surfaces.iter_mut().filter(|l| {
-            l.surface
-                .get_surface()
-                .map(|s| surfaces.contains(s.as_ref()))
-                .unwrap_or(false)
-        });
-
-        for layer in filtered_layers {
-            let surface = if let Some(surface) = layer.surface.get_surface() {
-                surface
-            } else {
-                continue;
-            };
-
-            let data = with_states(surface, |states| {
-                *states.cached_state.current::<LayerSurfaceCachedState>()
+        let filtered_layers = self
+            .surfaces
+            .iter()
+            .filter(|l| {
+                l.surface
+                    .get_surface()
+                    .map(|s| surfaces.contains(s.as_ref()))
+                    .unwrap_or(false)
             })
-            .unwrap();
+            .map(|l| &l.surface);
 
-            let x = if data.size.w == 0 || data.anchor.contains(Anchor::LEFT) {
-                output_rect.loc.x
-            } else if data.anchor.contains(Anchor::RIGHT) {
-                output_rect.loc.x + (output_rect.size.w - data.size.w)
-            } else {
-                output_rect.loc.x + ((output_rect.size.w / 2) - (data.size.w / 2))
-            };
+        let arrangement = wlr_layer::arrange_layers(output_rect, filtered_layers);
 
-            let y = if data.size.h == 0 || data.anchor.contains(Anchor::TOP) {
-                output_rect.loc.y
-            } else if data.anchor.contains(Anchor::BOTTOM) {
-                output_rect.loc.y + (output_rect.size.h - data.size.h)
-            } else {
-                output_rect.loc.y + ((output_rect.size.h / 2) - (data.size.h / 2))
+        for (surface, geometry) in arrangement.geometries {
+            let layer = match self.surfaces.iter_mut().find(|l| l.surface == surface) {
+                Some(layer) => layer,
+                None => continue,
             };
 
-            let location: Point<i32, Logical> = (x, y).into();
-
-            layer
-                .surface
-                .with_pending_state(|state| {
-                    state.size = Some(output_rect.size);
-                })
-                .unwrap();
+            let _ = layer.surface.with_pending_state(|state| {
+                state.size = Some(geometry.size);
+            });
 
             layer.surface.send_configure();
 
-            layer.location = location;
+            layer.location = geometry.loc;
         }
     }
 
@@ -251,4 +221,12 @@ impl LayerMap {
             layer.send_frame(time);
         }
     }
+
+    pub fn take_presentation_feedback(&self) -> PresentationFeedback {
+        let mut feedback = PresentationFeedback::default();
+        for layer in &self.surfaces {
+            feedback.merge(layer.take_presentation_feedback());
+        }
+        feedback
+    }
 }
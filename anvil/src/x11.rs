@@ -7,7 +7,7 @@ use smithay::{
     backend::{
         egl::{EGLContext, EGLDisplay},
         renderer::{gles2::Gles2Renderer, Bind, ImportEgl, Renderer, Transform, Unbind},
-        x11::{X11Backend, X11Event, X11Surface},
+        x11::{X11Backend, X11Event, X11PresentError, X11Surface},
         SwapBuffersError,
     },
     reexports::{
@@ -17,6 +17,7 @@ use smithay::{
     wayland::{
         output::{Mode, PhysicalProperties},
         seat::CursorImageStatus,
+        SERIAL_COUNTER as SCOUNTER,
     },
 };
 
@@ -57,6 +58,14 @@ pub fn run_x11(log: Logger) {
         X11Backend::with_title("Anvil", log.clone()).expect("Failed to initialize X11 backend");
     let window = backend.window();
 
+    if !surface.is_hw_accelerated() {
+        error!(
+            log,
+            "Anvil requires the X server to be capable of direct rendering"
+        );
+        return;
+    }
+
     // Initialize EGL using the GBM device setup earlier.
     let egl = EGLDisplay::new(&surface, log.clone()).expect("Failed to create EGLDisplay");
     let context = EGLContext::new(&egl, log.clone()).expect("Failed to create EGLContext");
@@ -91,7 +100,7 @@ pub fn run_x11(log: Logger) {
 
     let mode = Mode {
         size,
-        refresh: 60_000,
+        refresh: backend.refresh_rate() as i32,
     };
 
     let data = X11Data {
@@ -141,17 +150,15 @@ pub fn run_x11(log: Logger) {
             X11Event::Resized(size) => {
                 let size = { (size.w as i32, size.h as i32).into() };
 
-                state.backend_data.mode = Mode {
+                let mode = Mode {
                     size,
-                    refresh: 60_000,
+                    refresh: state.backend_data.mode.refresh,
                 };
-                state.output_map.borrow_mut().update_mode_by_name(
-                    Mode {
-                        size,
-                        refresh: 60_000,
-                    },
-                    OUTPUT_NAME,
-                );
+                state.backend_data.mode = mode;
+                state
+                    .output_map
+                    .borrow_mut()
+                    .update_mode_by_name(mode, OUTPUT_NAME);
 
                 let output_mut = state.output_map.borrow();
                 let output = output_mut.find_by_name(OUTPUT_NAME).unwrap();
@@ -160,10 +167,17 @@ pub fn run_x11(log: Logger) {
                 state.backend_data.render = true;
             }
 
-            X11Event::PresentCompleted | X11Event::Refresh => {
+            X11Event::PresentCompleted { .. } | X11Event::Refresh => {
                 state.backend_data.render = true;
             }
 
+            X11Event::Focus(false) => {
+                // Release the keyboard focus so clients do not keep keys pressed while we are unfocused
+                state.keyboard.set_focus(None, SCOUNTER.next_serial());
+            }
+
+            X11Event::Focus(true) => {}
+
             X11Event::Input(event) => state.process_input_event(event),
         })
         .expect("Failed to insert X11 Backend into event loop");
@@ -202,7 +216,8 @@ pub fn run_x11(log: Logger) {
                     #[cfg(feature = "debug")]
                     let fps_texture = &backend_data.fps_texture;
 
-                    if let Err(err) = renderer.bind(present.buffer()) {
+                    // The surface is hardware accelerated, as checked on startup.
+                    if let Err(err) = renderer.bind(present.buffer().unwrap()) {
                         error!(log, "Error while binding buffer: {}", err);
                     }
 
@@ -300,12 +315,20 @@ pub fn run_x11(log: Logger) {
                     }
                 }
 
+                Err(X11PresentError::NoFreeBuffer) => {
+                    // render again on the next `PresentCompleted`, once the X server released a buffer
+                }
+
                 Err(err) => {
-                    error!(log, "Failed to allocate buffers to present to window: {}", err);
+                    error!(log, "Failed to present to window: {}", err);
                     state.running.store(false, Ordering::SeqCst);
                 }
             }
 
+            if let Some(err) = state.backend_data.surface.take_present_error() {
+                error!(log, "Error while presenting to window: {}", err);
+            }
+
             #[cfg(feature = "debug")]
             state.backend_data.fps.tick();
             window.set_cursor_visible(cursor_visible);
@@ -28,6 +28,10 @@ fn gl_generate() {
                 "EGL_KHR_image_base",
                 "EGL_EXT_image_dma_buf_import",
                 "EGL_EXT_image_dma_buf_import_modifiers",
+                "EGL_EXT_device_base",
+                "EGL_EXT_device_enumeration",
+                "EGL_EXT_device_query",
+                "EGL_EXT_device_drm",
             ],
         )
         .write_bindings(gl_generator::GlobalGenerator, &mut file)
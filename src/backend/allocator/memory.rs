@@ -0,0 +1,57 @@
+//! Buffers residing in system memory
+//!
+//! A [`MemoryBuffer`] is a simple cpu-accessible buffer, that may be used when no hardware
+//! accelerated buffer types are available (e.g. by a software renderer).
+
+use super::{Buffer, Format, Fourcc, Modifier};
+use crate::utils::{Buffer as BufferCoords, Size};
+
+/// A single-plane buffer residing in system memory
+///
+/// Only formats using 4 bytes per pixel are supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryBuffer {
+    data: Vec<u8>,
+    size: Size<i32, BufferCoords>,
+    format: Fourcc,
+}
+
+impl MemoryBuffer {
+    /// Allocates a new zero-initialized buffer of the given size and format
+    pub fn new(size: impl Into<Size<i32, BufferCoords>>, format: Fourcc) -> MemoryBuffer {
+        let size = size.into();
+        MemoryBuffer {
+            data: vec![0; (size.w.max(0) * size.h.max(0) * 4) as usize],
+            size,
+            format,
+        }
+    }
+
+    /// Returns the number of bytes per row of the buffer
+    pub fn stride(&self) -> i32 {
+        self.size.w.max(0) * 4
+    }
+
+    /// Returns the contents of the buffer
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns the contents of the buffer for modification
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
+
+impl Buffer for MemoryBuffer {
+    fn size(&self) -> Size<i32, BufferCoords> {
+        self.size
+    }
+
+    fn format(&self) -> Format {
+        Format {
+            code: self.format,
+            modifier: Modifier::Linear,
+        }
+    }
+}
@@ -11,6 +11,7 @@
 //! - [DumbBuffers](dumb::DumbBuffer)
 //! - [GbmBuffers](::gbm::BufferObject)
 //! - [DmaBufs](dmabuf::Dmabuf)
+//! - [MemoryBuffers](memory::MemoryBuffer)
 //!
 //! Helpers:
 //! - [`Swapchain`] to help with buffer management for framebuffers
@@ -20,6 +21,7 @@ pub mod dmabuf;
 pub mod dumb;
 #[cfg(feature = "backend_gbm")]
 pub mod gbm;
+pub mod memory;
 
 mod swapchain;
 use crate::utils::{Buffer as BufferCoords, Size};
@@ -3,6 +3,7 @@ use std::cell::RefCell;
 use std::os::unix::io::{AsRawFd, RawFd};
 use std::path::PathBuf;
 use std::sync::{atomic::AtomicBool, Arc};
+use std::time::Duration;
 
 use calloop::{EventSource, Interest, Poll, PostAction, Readiness, Token, TokenFactory};
 use drm::control::{connector, crtc, Device as ControlDevice, Event, Mode, ResourceHandles};
@@ -310,12 +311,21 @@ pub enum DrmEvent {
     Error(Error),
 }
 
+/// Timing of a page flip, provided alongside [`DrmEvent::VBlank`] events
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DrmEventMetadata {
+    /// The time of the page flip, measured on the `CLOCK_MONOTONIC` clock
+    pub time: Duration,
+    /// The vblank sequence number of the page flip
+    pub sequence: u32,
+}
+
 impl<A> EventSource for DrmDevice<A>
 where
     A: AsRawFd + 'static,
 {
     type Event = DrmEvent;
-    type Metadata = ();
+    type Metadata = Option<DrmEventMetadata>;
     type Ret = ();
 
     fn process_events<F>(
@@ -335,7 +345,11 @@ where
                 for event in events {
                     if let Event::PageFlip(event) = event {
                         trace!(self.logger, "Got a page-flip event for crtc ({:?})", event.crtc);
-                        callback(DrmEvent::VBlank(event.crtc), &mut ());
+                        let mut metadata = Some(DrmEventMetadata {
+                            time: event.duration,
+                            sequence: event.frame,
+                        });
+                        callback(DrmEvent::VBlank(event.crtc), &mut metadata);
                     } else {
                         trace!(
                             self.logger,
@@ -352,7 +366,7 @@ where
                         dev: self.dev_path(),
                         source,
                     }),
-                    &mut (),
+                    &mut None,
                 );
             }
         }
@@ -72,11 +72,12 @@
 pub(crate) mod device;
 pub(self) mod error;
 pub(self) mod node;
+pub mod scanout;
 #[cfg(feature = "backend_session")]
 pub(self) mod session;
 pub(self) mod surface;
 
-pub use device::{DevPath, DrmDevice, DrmEvent};
+pub use device::{DevPath, DrmDevice, DrmEvent, DrmEventMetadata};
 pub use error::Error as DrmError;
 pub use node::{ConvertErrorKind, ConvertNodeError, CreateDrmNodeError, DrmNode, NodeType};
 #[cfg(feature = "backend_gbm")]
@@ -7,7 +7,7 @@ use std::{
     fmt::{self, Display, Formatter},
     fs, io,
     os::unix::prelude::{AsRawFd, IntoRawFd, RawFd},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 
 use nix::{
@@ -62,6 +62,45 @@ impl DrmNode {
         })
     }
 
+    /// Opens the DRM node at the given path.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<DrmNode, CreateDrmNodeError> {
+        let fd = fcntl::open(path.as_ref(), OFlag::O_RDWR | OFlag::O_CLOEXEC, Mode::empty())
+            .map_err(Into::<io::Error>::into)?;
+
+        DrmNode::from_fd(fd).map_err(|err| {
+            let _ = close(fd);
+            err
+        })
+    }
+
+    /// Opens all render nodes available on the system.
+    ///
+    /// This scans `/dev/dri` for render nodes, which is useful to select a different gpu than
+    /// the one used by the display (e.g. on multi-gpu laptops) for offscreen rendering.
+    /// Nodes which cannot be opened are skipped.
+    ///
+    /// A `gbm::Device` may be created from any of the returned nodes.
+    pub fn render_nodes() -> io::Result<Vec<DrmNode>> {
+        let mut paths = fs::read_dir("/dev/dri")?
+            .flatten()
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(NodeType::Render.minor_name_prefix())
+            })
+            .map(|entry| entry.path())
+            .collect::<Vec<_>>();
+        // Keep a stable order, so the same gpu is picked across runs.
+        paths.sort();
+
+        Ok(paths
+            .into_iter()
+            .filter_map(|path| DrmNode::from_path(path).ok())
+            .filter(|node| node.ty() == NodeType::Render)
+            .collect())
+    }
+
     /// Creates a DRM node of the specified type using the same DRM device as the provided node.
     /// The provided node will be consumed if the new node is successfully created.
     ///
@@ -0,0 +1,124 @@
+//! Helpers to decide whether a client buffer may be scanned out directly
+//!
+//! Putting a client buffer straight onto a drm plane (instead of compositing it into the
+//! framebuffer of the output first) can save a lot of power, but is only correct under
+//! a number of conditions. [`can_scanout`] checks these conditions for a [`ScanoutCandidate`]
+//! against the formats a plane supports (see [`DrmSurface::supported_formats`](super::DrmSurface::supported_formats)).
+
+use std::collections::HashSet;
+
+use crate::backend::allocator::Format;
+use crate::backend::renderer::Transform;
+use crate::utils::{Physical, Rectangle, Size};
+
+/// Description of a surface considered for direct scanout
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ScanoutCandidate {
+    /// Format and modifier of the dmabuf attached to the surface
+    pub format: Format,
+    /// Transform the buffer needs to be displayed with
+    pub transform: Transform,
+    /// Geometry of the buffer on the output in physical coordinates
+    pub geometry: Rectangle<i32, Physical>,
+    /// Whether the buffer is fully opaque
+    pub opaque: bool,
+    /// Whether the buffer is cropped, clipped or covered by other content
+    pub clipped: bool,
+}
+
+/// Checks if a surface may be directly scanned out on a plane of an output of the given size
+///
+/// This returns `true` only if all of the following conditions hold:
+/// - the format and modifier of the buffer are supported by the plane
+/// - the buffer does not need to be transformed, as planes are not guaranteed to support rotation
+/// - the buffer is opaque and neither clipped nor covered by other content
+/// - the buffer covers the whole output exactly
+pub fn can_scanout(
+    candidate: &ScanoutCandidate,
+    plane_formats: &HashSet<Format>,
+    output_size: Size<i32, Physical>,
+) -> bool {
+    plane_formats.contains(&candidate.format)
+        && candidate.transform == Transform::Normal
+        && candidate.opaque
+        && !candidate.clipped
+        && candidate.geometry == Rectangle::from_loc_and_size((0, 0), output_size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::allocator::{Fourcc, Modifier};
+
+    fn plane_formats() -> HashSet<Format> {
+        let mut formats = HashSet::new();
+        formats.insert(Format {
+            code: Fourcc::Xrgb8888,
+            modifier: Modifier::Linear,
+        });
+        formats.insert(Format {
+            code: Fourcc::Argb8888,
+            modifier: Modifier::Linear,
+        });
+        formats
+    }
+
+    fn candidate() -> ScanoutCandidate {
+        ScanoutCandidate {
+            format: Format {
+                code: Fourcc::Xrgb8888,
+                modifier: Modifier::Linear,
+            },
+            transform: Transform::Normal,
+            geometry: Rectangle::from_loc_and_size((0, 0), (1920, 1080)),
+            opaque: true,
+            clipped: false,
+        }
+    }
+
+    #[test]
+    fn fullscreen_opaque_buffer() {
+        assert!(can_scanout(&candidate(), &plane_formats(), (1920, 1080).into()));
+    }
+
+    #[test]
+    fn unsupported_format_or_modifier() {
+        let mut candidate = candidate();
+        candidate.format.code = Fourcc::Nv12;
+        assert!(!can_scanout(&candidate, &plane_formats(), (1920, 1080).into()));
+
+        let mut candidate = self::candidate();
+        candidate.format.modifier = Modifier::Invalid;
+        assert!(!can_scanout(&candidate, &plane_formats(), (1920, 1080).into()));
+    }
+
+    #[test]
+    fn transformed_buffer() {
+        let mut candidate = candidate();
+        candidate.transform = Transform::_90;
+        assert!(!can_scanout(&candidate, &plane_formats(), (1920, 1080).into()));
+    }
+
+    #[test]
+    fn not_covering_output() {
+        let mut candidate = candidate();
+        candidate.geometry = Rectangle::from_loc_and_size((10, 0), (1920, 1080));
+        assert!(!can_scanout(&candidate, &plane_formats(), (1920, 1080).into()));
+        assert!(!can_scanout(
+            &self::candidate(),
+            &plane_formats(),
+            (2560, 1440).into()
+        ));
+    }
+
+    #[test]
+    fn translucent_or_clipped_buffer() {
+        let mut candidate = candidate();
+        candidate.opaque = false;
+        assert!(!can_scanout(&candidate, &plane_formats(), (1920, 1080).into()));
+
+        let mut candidate = self::candidate();
+        candidate.clipped = true;
+        assert!(!can_scanout(&candidate, &plane_formats(), (1920, 1080).into()));
+    }
+}
@@ -0,0 +1,117 @@
+//! EGL devices as exposed by `EGL_EXT_device_base`
+//!
+//! An [`EGLDevice`] represents the device an [`EGLDisplay`] is rendering on and may be used to find out
+//! which DRM node was actually picked by the EGL implementation.
+
+use std::ffi::CStr;
+use std::path::PathBuf;
+
+use super::{ffi, wrap_egl_call, EGLDisplay, EGLError, Error};
+
+/// Representation of an EGL device
+#[derive(Debug)]
+pub struct EGLDevice {
+    device: ffi::egl::types::EGLDeviceEXT,
+    extensions: Vec<String>,
+}
+
+// An EGLDeviceEXT is a static handle of the EGL implementation and not tied to any thread
+unsafe impl Send for EGLDevice {}
+
+impl EGLDevice {
+    /// Returns the device the given [`EGLDisplay`] is using.
+    ///
+    /// Requires the `EGL_EXT_device_query` client extension.
+    pub fn device_for_display(display: &EGLDisplay) -> Result<EGLDevice, Error> {
+        let client_extensions = ffi::make_sure_egl_is_loaded()?;
+        if !client_extensions
+            .iter()
+            .any(|ext| ext == "EGL_EXT_device_query" || ext == "EGL_EXT_device_base")
+        {
+            return Err(Error::EglExtensionNotSupported(&[
+                "EGL_EXT_device_query",
+                "EGL_EXT_device_base",
+            ]));
+        }
+
+        let mut device: ffi::egl::types::EGLAttrib = 0;
+        let result = wrap_egl_call(|| unsafe {
+            ffi::egl::QueryDisplayAttribEXT(
+                display.display.handle,
+                ffi::egl::DEVICE_EXT as ffi::EGLint,
+                &mut device as *mut _,
+            )
+        })
+        .map_err(Error::QueryDeviceFailed)?;
+
+        let device = device as ffi::egl::types::EGLDeviceEXT;
+        if result != ffi::egl::TRUE || device.is_null() {
+            return Err(Error::QueryDeviceFailed(EGLError::BadDevice));
+        }
+
+        let extensions = unsafe { query_device_string(device, ffi::egl::EXTENSIONS as ffi::EGLint) }
+            .map_err(Error::QueryDeviceProperty)?
+            .map(|list| list.split(' ').map(|e| e.to_string()).collect::<Vec<_>>())
+            .unwrap_or_default();
+
+        Ok(EGLDevice { device, extensions })
+    }
+
+    /// Returns the supported extensions of this device
+    pub fn extensions(&self) -> Vec<String> {
+        self.extensions.clone()
+    }
+
+    /// Returns the path of the DRM node this device corresponds to.
+    ///
+    /// This is usually a primary node (e.g. `/dev/dri/card0`). Requires the `EGL_EXT_device_drm` extension.
+    pub fn drm_device_path(&self) -> Result<PathBuf, Error> {
+        if !self.extensions.iter().any(|ext| ext == "EGL_EXT_device_drm") {
+            return Err(Error::EglExtensionNotSupported(&["EGL_EXT_device_drm"]));
+        }
+
+        unsafe { query_device_string(self.device, ffi::egl::DRM_DEVICE_FILE_EXT as ffi::EGLint) }
+            .map_err(Error::QueryDeviceProperty)?
+            .map(PathBuf::from)
+            .ok_or(Error::QueryDeviceProperty(EGLError::BadAttribute))
+    }
+
+    /// Returns the path of the DRM render node this device corresponds to.
+    ///
+    /// Returns `None`, if the device has no render node. Requires the `EGL_EXT_device_drm_render_node` extension.
+    pub fn drm_render_node_path(&self) -> Result<Option<PathBuf>, Error> {
+        if !self
+            .extensions
+            .iter()
+            .any(|ext| ext == "EGL_EXT_device_drm_render_node")
+        {
+            return Err(Error::EglExtensionNotSupported(&[
+                "EGL_EXT_device_drm_render_node",
+            ]));
+        }
+
+        unsafe { query_device_string(self.device, ffi::egl::DRM_RENDER_NODE_FILE_EXT as ffi::EGLint) }
+            .map(|path| path.map(PathBuf::from))
+            .map_err(Error::QueryDeviceProperty)
+    }
+}
+
+/// Queries a string of the given device, returns `None` if the implementation returned no string.
+unsafe fn query_device_string(
+    device: ffi::egl::types::EGLDeviceEXT,
+    name: ffi::EGLint,
+) -> Result<Option<String>, EGLError> {
+    let p = wrap_egl_call(|| ffi::egl::QueryDeviceStringEXT(device, name))?;
+    // On failure, the implementation returns NULL, which is also `EGL_FALSE`.
+    // Some do so without raising an error, so never interpret such a result as a string.
+    if p.is_null() {
+        return Ok(None);
+    }
+    let string = CStr::from_ptr(p).to_string_lossy().into_owned();
+    // an empty string is no more meaningful than no string, and would be a bogus path
+    if string.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(string))
+    }
+}
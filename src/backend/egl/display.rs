@@ -4,6 +4,7 @@ use std::collections::HashSet;
 use std::ffi::CStr;
 use std::mem::MaybeUninit;
 use std::ops::Deref;
+use std::path::PathBuf;
 use std::sync::Arc;
 #[cfg(all(feature = "wayland_frontend", feature = "use_system_lib"))]
 use std::sync::{Mutex, Weak};
@@ -21,7 +22,7 @@ use crate::backend::egl::{
     ffi,
     ffi::egl::types::EGLImage,
     native::EGLNativeDisplay,
-    wrap_egl_call, EGLError, Error,
+    wrap_egl_call, EGLDevice, EGLError, Error,
 };
 #[cfg(all(feature = "wayland_frontend", feature = "use_system_lib"))]
 use crate::backend::egl::{BufferAccessError, EGLBuffer, Format};
@@ -404,6 +405,34 @@ impl EGLDisplay {
         self.extensions.clone()
     }
 
+    /// Returns the path of the DRM node this display is rendering on.
+    ///
+    /// See [`EGLDevice::drm_device_path`].
+    pub fn drm_device_path(&self) -> Result<PathBuf, Error> {
+        EGLDevice::device_for_display(self)?.drm_device_path()
+    }
+
+    /// Returns the path of the DRM render node this display is rendering on, if the device has one.
+    ///
+    /// See [`EGLDevice::drm_render_node_path`].
+    pub fn drm_render_node_path(&self) -> Result<Option<PathBuf>, Error> {
+        EGLDevice::device_for_display(self)?.drm_render_node_path()
+    }
+
+    /// Returns a list of formats for dmabufs that can be rendered to.
+    ///
+    /// The formats are queried using `EGL_EXT_image_dma_buf_import_modifiers` if available.
+    pub fn dmabuf_render_formats(&self) -> &HashSet<DrmFormat> {
+        &self.dmabuf_render_formats
+    }
+
+    /// Returns a list of formats for dmabufs that can be used as textures.
+    ///
+    /// The formats are queried using `EGL_EXT_image_dma_buf_import_modifiers` if available.
+    pub fn dmabuf_texture_formats(&self) -> &HashSet<DrmFormat> {
+        &self.dmabuf_import_formats
+    }
+
     /// Imports a [`Dmabuf`] as an [`EGLImage`]
     pub fn create_image_from_dmabuf(&self, dmabuf: &Dmabuf) -> Result<EGLImage, Error> {
         if !self.extensions.iter().any(|s| s == "EGL_KHR_image_base")
@@ -839,7 +868,7 @@ impl EGLBufferReader {
     pub fn egl_buffer_dimensions(
         &self,
         buffer: &WlBuffer,
-    ) -> Option<crate::utils::Size<i32, crate::utils::Physical>> {
+    ) -> Option<crate::utils::Size<i32, crate::utils::Buffer>> {
         if !buffer.as_ref().is_alive() {
             debug!(self.logger, "Suplied buffer is no longer alive");
             return None;
@@ -45,6 +45,12 @@ pub enum Error {
     /// Failed to create `EGLBuffer` from the buffer
     #[error("Failed to create `EGLBuffer` from the buffer")]
     EGLImageCreationFailed,
+    /// Failed to query the `EGLDevice` of a display
+    #[error("Failed to query the `EGLDevice`. Err: {0:}")]
+    QueryDeviceFailed(#[source] EGLError),
+    /// Failed to query a property of an `EGLDevice`
+    #[error("Failed to query a property of the `EGLDevice`. Err: {0:}")]
+    QueryDeviceProperty(#[source] EGLError),
 }
 
 /// Raw EGL error
@@ -91,6 +97,9 @@ pub enum EGLError {
     /// A NativeWindowType argument does not refer to a valid native window.
     #[error("A NativeWindowType argument does not refer to a valid native window.")]
     BadNativeWindow,
+    /// An EGLDeviceEXT argument does not refer to a valid EGL device.
+    #[error("An EGLDeviceEXT argument does not refer to a valid EGL device.")]
+    BadDevice,
     #[cfg(feature = "backend_drm_eglstream")]
     /// The EGL operation failed due to temporary unavailability of a requested resource, but the arguments were otherwise valid, and a subsequent attempt may succeed.
     #[error("The EGL operation failed due to temporary unavailability of a requested resource, but the arguments were otherwise valid, and a subsequent attempt may succeed.")]
@@ -118,6 +127,7 @@ impl From<u32> for EGLError {
             ffi::egl::BAD_PARAMETER => EGLError::BadParameter,
             ffi::egl::BAD_NATIVE_PIXMAP => EGLError::BadNativePixmap,
             ffi::egl::BAD_NATIVE_WINDOW => EGLError::BadNativeWindow,
+            ffi::egl::BAD_DEVICE_EXT => EGLError::BadDevice,
             #[cfg(feature = "backend_drm_eglstream")]
             ffi::egl::RESOURCE_BUSY_EXT => EGLError::ResourceBusy,
             ffi::egl::CONTEXT_LOST => EGLError::ContextLost,
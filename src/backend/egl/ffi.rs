@@ -329,4 +329,8 @@ pub mod egl {
     // Accepted in the <attribute> parameter of eglQueryWaylandBufferWL:
     pub const EGL_TEXTURE_FORMAT: i32 = 0x3080;
     pub const WAYLAND_Y_INVERTED_WL: i32 = 0x31DB;
+
+    // EGL_EXT_device_drm_render_node is too recent for the registry of `gl_generator`.
+    // Accepted by the <name> parameter of eglQueryDeviceStringEXT:
+    pub const DRM_RENDER_NODE_FILE_EXT: c_uint = 0x3377;
 }
@@ -44,9 +44,11 @@ pub mod ffi;
 #[cfg(feature = "wayland_frontend")]
 use self::{display::EGLDisplayHandle, ffi::egl::types::EGLImage};
 
+pub mod device;
 pub mod display;
 pub mod native;
 pub mod surface;
+pub use self::device::EGLDevice;
 pub use self::display::EGLDisplay;
 pub use self::surface::EGLSurface;
 
@@ -171,20 +171,18 @@ impl EGLNativeDisplay for WinitWindow {
 #[cfg(feature = "backend_x11")]
 impl EGLNativeDisplay for X11Surface {
     fn supported_platforms(&self) -> Vec<EGLPlatform<'_>> {
+        let device = match self.device() {
+            Some(device) => device,
+            // Without a gbm device the surface cannot be used with EGL.
+            None => return Vec::new(),
+        };
+
         vec![
             // todo: https://www.khronos.org/registry/EGL/extensions/EXT/EGL_EXT_platform_device.txt
             // see: https://www.khronos.org/registry/EGL/extensions/KHR/EGL_KHR_platform_gbm.txt
-            egl_platform!(
-                PLATFORM_GBM_KHR,
-                self.device().as_raw(),
-                &["EGL_KHR_platform_gbm"]
-            ),
+            egl_platform!(PLATFORM_GBM_KHR, device.as_raw(), &["EGL_KHR_platform_gbm"]),
             // see: https://www.khronos.org/registry/EGL/extensions/MESA/EGL_MESA_platform_gbm.txt
-            egl_platform!(
-                PLATFORM_GBM_MESA,
-                self.device().as_raw(),
-                &["EGL_MESA_platform_gbm"]
-            ),
+            egl_platform!(PLATFORM_GBM_MESA, device.as_raw(), &["EGL_MESA_platform_gbm"]),
         ]
     }
 }
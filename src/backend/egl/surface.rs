@@ -12,6 +12,7 @@ use crate::backend::egl::{
     native::EGLNativeSurface,
     EGLError, SwapBuffersError,
 };
+use crate::utils::{Physical, Size};
 
 use slog::{debug, o};
 
@@ -141,6 +142,39 @@ impl EGLSurface {
         self.pixel_format
     }
 
+    /// Returns the current size of the surface, if it can be queried.
+    pub fn get_size(&self) -> Option<Size<i32, Physical>> {
+        let surface = self.surface.load(Ordering::SeqCst);
+        if surface.is_null() {
+            return None;
+        }
+
+        let mut width = 0;
+        let mut height = 0;
+        let ret_width = unsafe {
+            ffi::egl::QuerySurface(
+                **self.display,
+                surface as *const _,
+                ffi::egl::WIDTH as ffi::egl::types::EGLint,
+                &mut width,
+            )
+        };
+        let ret_height = unsafe {
+            ffi::egl::QuerySurface(
+                **self.display,
+                surface as *const _,
+                ffi::egl::HEIGHT as ffi::egl::types::EGLint,
+                &mut height,
+            )
+        };
+
+        if ret_width == ffi::egl::TRUE && ret_height == ffi::egl::TRUE {
+            Some(Size::from((width, height)))
+        } else {
+            None
+        }
+    }
+
     /// Tries to resize the underlying native surface.
     ///
     /// The two first arguments (width, height) are the new size of the surface,
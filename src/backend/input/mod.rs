@@ -263,6 +263,16 @@ pub trait PointerMotionEvent<B: InputBackend>: Event<B> {
     fn delta_x(&self) -> f64;
     /// Delta on the y axis between the last and new pointer device position interpreted as pixel movement
     fn delta_y(&self) -> f64;
+
+    /// Unaccelerated delta between the last and new pointer device position
+    fn delta_unaccel(&self) -> Point<f64, Logical> {
+        (self.delta_x_unaccel(), self.delta_y_unaccel()).into()
+    }
+
+    /// Unaccelerated delta on the x axis between the last and new pointer device position
+    fn delta_x_unaccel(&self) -> f64;
+    /// Unaccelerated delta on the y axis between the last and new pointer device position
+    fn delta_y_unaccel(&self) -> f64;
 }
 
 impl<B: InputBackend> PointerMotionEvent<B> for UnusedEvent {
@@ -273,6 +283,14 @@ impl<B: InputBackend> PointerMotionEvent<B> for UnusedEvent {
     fn delta_y(&self) -> f64 {
         match *self {}
     }
+
+    fn delta_x_unaccel(&self) -> f64 {
+        match *self {}
+    }
+
+    fn delta_y_unaccel(&self) -> f64 {
+        match *self {}
+    }
 }
 
 /// Trait for pointer events generated by absolute device positioning.
@@ -348,6 +366,13 @@ impl TouchSlot {
     }
 }
 
+impl From<TouchSlot> for i32 {
+    /// Converts the slot into the id of a touch point as used by the `wl_touch` protocol
+    fn from(slot: TouchSlot) -> i32 {
+        slot.id as i32
+    }
+}
+
 /// Trait for touch events starting at a given position.
 pub trait TouchDownEvent<B: InputBackend>: Event<B> {
     /// [`TouchSlot`], if the device has multi-touch capabilities
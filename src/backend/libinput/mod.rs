@@ -214,6 +214,12 @@ impl backend::PointerMotionEvent<LibinputInputBackend> for event::pointer::Point
     fn delta_y(&self) -> f64 {
         self.dy()
     }
+    fn delta_x_unaccel(&self) -> f64 {
+        self.dx_unaccelerated()
+    }
+    fn delta_y_unaccel(&self) -> f64 {
+        self.dy_unaccelerated()
+    }
 }
 
 impl backend::Event<LibinputInputBackend> for event::pointer::PointerMotionAbsoluteEvent {
@@ -0,0 +1,138 @@
+//! Helpers for damage tracking
+//!
+//! A software cursor usually moves a lot more often than the rest of the scene changes.
+//! [`CursorDamage`] keeps track of the area previously covered by the cursor, so that only
+//! the footprint of the cursor needs to be redrawn when it moves.
+//!
+//! Renderers draw every damage rectangle separately, so translucent content covered by overlapping
+//! damage would be blended multiple times. [`split_overlapping`] turns a list of damage rectangles
+//! into non-overlapping ones covering the same area.
+
+use crate::utils::{Physical, Rectangle};
+
+/// Splits a list of damage rectangles into rectangles that do not overlap
+///
+/// The returned rectangles cover the same area as the given ones. Empty rectangles are dropped.
+pub fn split_overlapping<Kind>(
+    damage: impl IntoIterator<Item = Rectangle<i32, Kind>>,
+) -> Vec<Rectangle<i32, Kind>> {
+    let mut result: Vec<Rectangle<i32, Kind>> = Vec::new();
+    for rect in damage {
+        if rect.size.w <= 0 || rect.size.h <= 0 {
+            continue;
+        }
+        let mut pieces = vec![rect];
+        for existing in result.iter() {
+            pieces = pieces
+                .into_iter()
+                .flat_map(|piece| piece.subtract_rect(*existing))
+                .collect();
+        }
+        result.extend(pieces);
+    }
+    result
+}
+
+/// Damage tracker for a cursor drawn on top of the scene
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CursorDamage {
+    last_geometry: Option<Rectangle<i32, Physical>>,
+}
+
+impl CursorDamage {
+    /// Creates a new tracker, that has not seen any cursor yet
+    pub fn new() -> CursorDamage {
+        CursorDamage::default()
+    }
+
+    /// Returns the geometry of the cursor last passed to [`CursorDamage::update`]
+    pub fn last_geometry(&self) -> Option<Rectangle<i32, Physical>> {
+        self.last_geometry
+    }
+
+    /// Updates the current geometry of the cursor and returns the damage caused by the change
+    ///
+    /// Pass `None` if the cursor is hidden. The returned damage contains the previous and the new
+    /// geometry of the cursor, merged into one rectangle if they overlap. If the geometry did not
+    /// change, no damage is returned.
+    pub fn update(&mut self, geometry: Option<Rectangle<i32, Physical>>) -> Vec<Rectangle<i32, Physical>> {
+        let last_geometry = std::mem::replace(&mut self.last_geometry, geometry);
+
+        match (last_geometry, geometry) {
+            (Some(last), Some(new)) if last == new => Vec::new(),
+            (Some(last), Some(new)) if last.overlaps(new) => vec![last.merge(new)],
+            (last, new) => last.into_iter().chain(new).collect(),
+        }
+    }
+
+    /// Forgets the previous geometry of the cursor
+    ///
+    /// Call this after the whole output was redrawn, as the cursor has already been cleared.
+    pub fn reset(&mut self) {
+        self.last_geometry = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn initial_and_unchanged_cursor() {
+        let mut damage = CursorDamage::new();
+        let cursor = Rectangle::from_loc_and_size((10, 10), (24, 24));
+
+        assert_eq!(damage.update(Some(cursor)), vec![cursor]);
+        assert_eq!(damage.update(Some(cursor)), vec![]);
+        assert_eq!(damage.last_geometry(), Some(cursor));
+    }
+
+    #[test]
+    fn moved_cursor() {
+        let mut damage = CursorDamage::new();
+        let old = Rectangle::from_loc_and_size((10, 10), (24, 24));
+        damage.update(Some(old));
+
+        // small movement, old and new position overlap
+        let new = Rectangle::from_loc_and_size((15, 12), (24, 24));
+        assert_eq!(
+            damage.update(Some(new)),
+            vec![Rectangle::from_loc_and_size((10, 10), (29, 26))]
+        );
+
+        // large movement, both positions need to be redrawn separately
+        let far = Rectangle::from_loc_and_size((500, 300), (24, 24));
+        assert_eq!(damage.update(Some(far)), vec![new, far]);
+    }
+
+    #[test]
+    fn overlapping_damage_is_split() {
+        let a = Rectangle::<i32, Physical>::from_loc_and_size((0, 0), (40, 40));
+        let b = Rectangle::from_loc_and_size((20, 20), (40, 40));
+        let inside = Rectangle::from_loc_and_size((5, 5), (10, 10));
+
+        let split = split_overlapping(vec![a, b, inside]);
+        for (i, rect) in split.iter().enumerate() {
+            assert!(split[i + 1..]
+                .iter()
+                .all(|other| rect.intersection(*other).is_none()));
+        }
+        let area: i32 = split.iter().map(|rect| rect.size.w * rect.size.h).sum();
+        assert_eq!(area, 40 * 40 * 2 - 20 * 20);
+        assert_eq!(split[0], a);
+    }
+
+    #[test]
+    fn hidden_cursor() {
+        let mut damage = CursorDamage::new();
+        let cursor = Rectangle::from_loc_and_size((10, 10), (24, 24));
+        damage.update(Some(cursor));
+
+        assert_eq!(damage.update(None), vec![cursor]);
+        assert_eq!(damage.update(None), vec![]);
+
+        damage.update(Some(cursor));
+        damage.reset();
+        assert_eq!(damage.update(Some(cursor)), vec![cursor]);
+    }
+}
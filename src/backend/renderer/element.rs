@@ -0,0 +1,290 @@
+//! Elements of a scene that may be rendered by a [`Frame`]
+//!
+//! Instead of drawing surfaces, cursors and decorations by hand, a compositor may describe them as
+//! a list of [`RenderElement`]s ordered from bottom to top and draw them using [`render_elements`].
+//! [`TextureElement`] covers the common case of drawing (part of) a texture.
+
+use super::{damage::split_overlapping, Frame, Texture, Transform};
+use crate::utils::{Buffer, Logical, Physical, Point, Rectangle, Size};
+
+/// A single element of a scene
+pub trait RenderElement<F: Frame> {
+    /// Geometry of the element on the output in physical coordinates
+    fn geometry(&self, scale: f64) -> Rectangle<i32, Physical>;
+
+    /// Parts of the element, that changed since the last frame, in physical output coordinates
+    ///
+    /// Defaults to the whole geometry of the element.
+    fn damage(&self, scale: f64) -> Vec<Rectangle<i32, Physical>> {
+        vec![self.geometry(scale)]
+    }
+
+    /// Draws the element into the given frame
+    ///
+    /// Only the given damaged regions of the frame, in physical output coordinates, need to be updated.
+    fn draw(&self, frame: &mut F, scale: f64, damage: &[Rectangle<i32, Physical>]) -> Result<(), F::Error>;
+}
+
+impl<'a, F: Frame, E: RenderElement<F> + ?Sized> RenderElement<F> for &'a E {
+    fn geometry(&self, scale: f64) -> Rectangle<i32, Physical> {
+        (**self).geometry(scale)
+    }
+
+    fn damage(&self, scale: f64) -> Vec<Rectangle<i32, Physical>> {
+        (**self).damage(scale)
+    }
+
+    fn draw(&self, frame: &mut F, scale: f64, damage: &[Rectangle<i32, Physical>]) -> Result<(), F::Error> {
+        (**self).draw(frame, scale, damage)
+    }
+}
+
+/// A [`RenderElement`] drawing (part of) a texture
+#[derive(Debug)]
+pub struct TextureElement<'a, T> {
+    /// The texture to draw
+    pub texture: &'a T,
+    /// Part of the texture to draw
+    pub src: Rectangle<i32, Buffer>,
+    /// Geometry of the element on the output in logical coordinates
+    pub geometry: Rectangle<f64, Logical>,
+    /// Transform to apply to the texture
+    pub transform: Transform,
+    /// Alpha value to draw the texture with
+    pub alpha: f32,
+    /// Damage of the texture relative to the geometry of the element, `None` if it was fully damaged
+    pub damage: Option<Vec<Rectangle<i32, Logical>>>,
+}
+
+impl<'a, T: Texture> TextureElement<'a, T> {
+    /// Creates an element drawing the whole texture untransformed at the given location
+    ///
+    /// The texture is considered fully damaged.
+    pub fn new(texture: &'a T, location: Point<f64, Logical>, texture_scale: i32) -> TextureElement<'a, T> {
+        let size = texture.size();
+
+        TextureElement {
+            texture,
+            src: Rectangle::from_loc_and_size((0, 0), size),
+            geometry: Rectangle::from_loc_and_size(location, size.to_logical(texture_scale).to_f64()),
+            transform: Transform::Normal,
+            alpha: 1.0,
+            damage: None,
+        }
+    }
+}
+
+impl<'a, F: Frame> RenderElement<F> for TextureElement<'a, F::TextureId> {
+    fn geometry(&self, scale: f64) -> Rectangle<i32, Physical> {
+        to_i32_round(self.geometry.to_physical(scale))
+    }
+
+    fn damage(&self, scale: f64) -> Vec<Rectangle<i32, Physical>> {
+        match self.damage {
+            Some(ref damage) => damage
+                .iter()
+                .map(|rect| {
+                    let mut rect = rect.to_f64();
+                    rect.loc += self.geometry.loc;
+                    to_i32_round(rect.to_physical(scale))
+                })
+                .collect(),
+            None => vec![RenderElement::<F>::geometry(self, scale)],
+        }
+    }
+
+    fn draw(&self, frame: &mut F, scale: f64, damage: &[Rectangle<i32, Physical>]) -> Result<(), F::Error> {
+        frame.render_texture_from_to_damaged(
+            self.texture,
+            self.src,
+            self.geometry.to_physical(scale),
+            damage,
+            self.transform,
+            self.alpha,
+        )
+    }
+}
+
+fn to_i32_round(rect: Rectangle<f64, Physical>) -> Rectangle<i32, Physical> {
+    Rectangle::from_loc_and_size(rect.loc.to_i32_round(), rect.size.to_i32_round())
+}
+
+/// Draws a list of elements ordered from bottom to top into a frame of an output of the given size
+///
+/// Elements not overlapping the output are skipped. The damage of all elements is collected first and
+/// split into non-overlapping rectangles, which are cleared with `clear_color`. Then every element
+/// redraws the parts of the damage it covers.
+/// Returns the damage in physical output coordinates, which may be used when presenting the frame.
+pub fn render_elements<F, E>(
+    frame: &mut F,
+    output_size: Size<i32, Physical>,
+    scale: f64,
+    elements: &[E],
+    clear_color: [f32; 4],
+) -> Result<Vec<Rectangle<i32, Physical>>, F::Error>
+where
+    F: Frame,
+    E: RenderElement<F>,
+{
+    let output_geometry = Rectangle::from_loc_and_size((0, 0), output_size);
+    let visible = elements
+        .iter()
+        .filter(|element| element.geometry(scale).overlaps(output_geometry))
+        .collect::<Vec<_>>();
+
+    let damage = split_overlapping(
+        visible
+            .iter()
+            .flat_map(|element| element.damage(scale))
+            .filter_map(|rect| rect.intersection(output_geometry)),
+    );
+
+    // the previous contents of the damaged regions must not shine through translucent elements
+    if !damage.is_empty() {
+        frame.clear_damaged(clear_color, &damage)?;
+    }
+
+    for element in visible {
+        let geometry = element.geometry(scale);
+        let element_damage = damage
+            .iter()
+            .filter_map(|rect| rect.intersection(geometry))
+            .collect::<Vec<_>>();
+
+        if !element_damage.is_empty() {
+            element.draw(frame, scale, &element_damage)?;
+        }
+    }
+
+    Ok(damage)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Matrix3, Vector2};
+
+    const CLEAR_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+
+    #[derive(Debug, PartialEq)]
+    struct TestTexture(u32);
+
+    impl Texture for TestTexture {
+        fn width(&self) -> u32 {
+            self.0
+        }
+
+        fn height(&self) -> u32 {
+            self.0
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct TestFrame {
+        drawn: Vec<u32>,
+        cleared: Vec<Rectangle<i32, Physical>>,
+        damage: Vec<Rectangle<i32, Physical>>,
+    }
+
+    impl Frame for TestFrame {
+        type Error = std::io::Error;
+        type TextureId = TestTexture;
+
+        fn clear(&mut self, _color: [f32; 4]) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn clear_damaged(
+            &mut self,
+            _color: [f32; 4],
+            damage: &[Rectangle<i32, Physical>],
+        ) -> Result<(), Self::Error> {
+            assert!(self.drawn.is_empty(), "damage cleared after drawing");
+            self.cleared.extend_from_slice(damage);
+            Ok(())
+        }
+
+        fn render_texture(
+            &mut self,
+            texture: &Self::TextureId,
+            _matrix: Matrix3<f32>,
+            _tex_coords: [Vector2<f32>; 4],
+            _alpha: f32,
+        ) -> Result<(), Self::Error> {
+            self.drawn.push(texture.0);
+            Ok(())
+        }
+
+        fn render_texture_damaged(
+            &mut self,
+            texture: &Self::TextureId,
+            matrix: Matrix3<f32>,
+            tex_coords: [Vector2<f32>; 4],
+            damage: &[Rectangle<i32, Physical>],
+            alpha: f32,
+        ) -> Result<(), Self::Error> {
+            self.damage.extend_from_slice(damage);
+            self.render_texture(texture, matrix, tex_coords, alpha)
+        }
+    }
+
+    #[test]
+    fn draws_visible_elements_in_order() {
+        let small = TestTexture(10);
+        let big = TestTexture(100);
+
+        let elements = [
+            TextureElement::new(&big, (0.0, 0.0).into(), 1),
+            TextureElement::new(&small, (2000.0, 0.0).into(), 1),
+            TextureElement::new(&small, (50.0, 50.0).into(), 1),
+        ];
+
+        let mut frame = TestFrame::default();
+        let damage = render_elements(&mut frame, (1920, 1080).into(), 1.0, &elements, CLEAR_COLOR).unwrap();
+
+        assert_eq!(frame.drawn, vec![100, 10]);
+        // the damage of the small element is covered by the damage of the big one
+        assert_eq!(damage, vec![Rectangle::from_loc_and_size((0, 0), (100, 100))]);
+        assert_eq!(frame.cleared, damage);
+    }
+
+    #[test]
+    fn overlapping_damage_is_drawn_once() {
+        let texture = TestTexture(100);
+        let mut element = TextureElement::new(&texture, (0.0, 0.0).into(), 1);
+        element.alpha = 0.5;
+        element.damage = Some(vec![
+            Rectangle::from_loc_and_size((10, 10), (40, 40)),
+            Rectangle::from_loc_and_size((30, 30), (40, 40)),
+        ]);
+
+        let mut frame = TestFrame::default();
+        let damage = render_elements(&mut frame, (1920, 1080).into(), 1.0, &[element], CLEAR_COLOR).unwrap();
+
+        assert_eq!(frame.damage, damage);
+        // no pixel of the translucent element may be blended twice
+        for (i, rect) in frame.damage.iter().enumerate() {
+            assert!(frame.damage[i + 1..]
+                .iter()
+                .all(|other| rect.intersection(*other).is_none()));
+        }
+        let area: i32 = frame.damage.iter().map(|rect| rect.size.w * rect.size.h).sum();
+        assert_eq!(area, 40 * 40 * 2 - 20 * 20);
+    }
+
+    #[test]
+    fn partial_damage_is_scaled() {
+        let texture = TestTexture(100);
+        let mut element = TextureElement::new(&texture, (10.0, 10.0).into(), 2);
+        element.damage = Some(vec![Rectangle::from_loc_and_size((5, 5), (10, 10))]);
+
+        assert_eq!(
+            RenderElement::<TestFrame>::geometry(&element, 2.0),
+            Rectangle::from_loc_and_size((20, 20), (100, 100))
+        );
+        assert_eq!(
+            RenderElement::<TestFrame>::damage(&element, 2.0),
+            vec![Rectangle::from_loc_and_size((30, 30), (20, 20))]
+        );
+    }
+}
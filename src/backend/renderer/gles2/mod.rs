@@ -11,22 +11,25 @@ use std::sync::{
 };
 use std::{collections::HashSet, os::raw::c_char};
 
-use cgmath::{prelude::*, Matrix3, Vector2};
+use cgmath::{prelude::*, Matrix3, Vector2, Vector3};
 
 mod shaders;
 mod version;
 
-use super::{Bind, Frame, Renderer, Texture, Transform, Unbind};
+use super::{
+    damage::split_overlapping, Bind, ExportMem, Frame, Offscreen, Renderer, Texture, Transform, Unbind,
+};
 use crate::backend::allocator::{
     dmabuf::{Dmabuf, WeakDmabuf},
-    Format,
+    memory::MemoryBuffer,
+    Buffer as _, Format, Fourcc,
 };
 use crate::backend::egl::{
     ffi::egl::{self as ffi_egl, types::EGLImage},
     EGLContext, EGLSurface, MakeCurrentError,
 };
 use crate::backend::SwapBuffersError;
-use crate::utils::{Buffer, Physical, Size};
+use crate::utils::{Buffer, Physical, Rectangle, Size};
 
 #[cfg(all(feature = "wayland_frontend", feature = "use_system_lib"))]
 use super::ImportEgl;
@@ -35,8 +38,6 @@ use super::{ImportDma, ImportShm};
 #[cfg(all(feature = "wayland_frontend", feature = "use_system_lib"))]
 use crate::backend::egl::{display::EGLBufferReader, Format as EGLFormat};
 #[cfg(feature = "wayland_frontend")]
-use crate::utils::Rectangle;
-#[cfg(feature = "wayland_frontend")]
 use wayland_server::protocol::{wl_buffer, wl_shm};
 
 use slog::{debug, error, info, o, trace, warn};
@@ -157,7 +158,7 @@ struct WeakGles2Buffer {
 #[derive(Debug)]
 struct Gles2Buffer {
     internal: WeakGles2Buffer,
-    _dmabuf: Dmabuf,
+    dmabuf: Dmabuf,
 }
 
 #[cfg(feature = "wayland_frontend")]
@@ -187,6 +188,7 @@ pub struct Gles2Renderer {
     buffers: Vec<WeakGles2Buffer>,
     target_buffer: Option<Gles2Buffer>,
     target_surface: Option<Rc<EGLSurface>>,
+    target_texture: Option<(Gles2Texture, ffi::types::GLuint)>,
     extensions: Vec<String>,
     programs: [Gles2Program; shaders::FRAGMENT_COUNT],
     #[cfg(feature = "wayland_frontend")]
@@ -207,6 +209,7 @@ pub struct Gles2Renderer {
 /// Handle to the currently rendered frame during [`Gles2Renderer::render`](Renderer::render)
 pub struct Gles2Frame {
     current_projection: Matrix3<f32>,
+    size: Size<i32, Physical>,
     gl: ffi::Gles2,
     programs: [Gles2Program; shaders::FRAGMENT_COUNT],
 }
@@ -215,6 +218,7 @@ impl fmt::Debug for Gles2Frame {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Gles2Frame")
             .field("current_projection", &self.current_projection)
+            .field("size", &self.size)
             .field("programs", &self.programs)
             .finish_non_exhaustive()
     }
@@ -227,6 +231,7 @@ impl fmt::Debug for Gles2Renderer {
             .field("buffers", &self.buffers)
             .field("target_buffer", &self.target_buffer)
             .field("target_surface", &self.target_surface)
+            .field("target_texture", &self.target_texture)
             .field("extensions", &self.extensions)
             .field("programs", &self.programs)
             // ffi::Gles2 does not implement Debug
@@ -264,6 +269,9 @@ pub enum Gles2Error {
     #[error("Unsupported pixel format: {0:?}")]
     #[cfg(feature = "wayland_frontend")]
     UnsupportedPixelFormat(wl_shm::Format),
+    /// The requested offscreen buffer format is not supported
+    #[error("Unsupported offscreen buffer format: {0:?}")]
+    UnsupportedFormat(Fourcc),
     /// The given buffer was not accessible
     #[error("Error accessing the buffer ({0:?})")]
     #[cfg(feature = "wayland_frontend")]
@@ -275,6 +283,12 @@ pub enum Gles2Error {
     /// This rendering operation was called without a previous `begin`-call
     #[error("Call begin before doing any rendering operations")]
     UnconstraintRenderingOperation,
+    /// No target was bound before reading back its contents
+    #[error("No target is bound")]
+    NoTarget,
+    /// The region to copy is not contained in the buffer
+    #[error("Invalid region to copy: {0:?}")]
+    InvalidRegion(Rectangle<i32, Buffer>),
 }
 
 impl From<Gles2Error> for SwapBuffersError {
@@ -289,9 +303,12 @@ impl From<Gles2Error> for SwapBuffersError {
             Gles2Error::ContextActivationError(err) => err.into(),
             x @ Gles2Error::FramebufferBindingError
             | x @ Gles2Error::BindBufferEGLError(_)
+            | x @ Gles2Error::UnsupportedFormat(_)
             | x @ Gles2Error::UnsupportedPixelFormat(_)
             | x @ Gles2Error::BufferAccessError(_)
-            | x @ Gles2Error::EGLBufferAccessError(_) => SwapBuffersError::TemporaryFailure(Box::new(x)),
+            | x @ Gles2Error::EGLBufferAccessError(_)
+            | x @ Gles2Error::NoTarget
+            | x @ Gles2Error::InvalidRegion(_) => SwapBuffersError::TemporaryFailure(Box::new(x)),
         }
     }
     #[cfg(not(feature = "wayland_frontend"))]
@@ -303,9 +320,11 @@ impl From<Gles2Error> for SwapBuffersError {
             | x @ Gles2Error::GLExtensionNotSupported(_)
             | x @ Gles2Error::UnconstraintRenderingOperation => SwapBuffersError::ContextLost(Box::new(x)),
             Gles2Error::ContextActivationError(err) => err.into(),
-            x @ Gles2Error::FramebufferBindingError | x @ Gles2Error::BindBufferEGLError(_) => {
-                SwapBuffersError::TemporaryFailure(Box::new(x))
-            }
+            x @ Gles2Error::FramebufferBindingError
+            | x @ Gles2Error::BindBufferEGLError(_)
+            | x @ Gles2Error::UnsupportedFormat(_)
+            | x @ Gles2Error::NoTarget
+            | x @ Gles2Error::InvalidRegion(_) => SwapBuffersError::TemporaryFailure(Box::new(x)),
         }
     }
 }
@@ -505,6 +524,7 @@ impl Gles2Renderer {
             programs,
             target_buffer: None,
             target_surface: None,
+            target_texture: None,
             buffers: Vec::new(),
             #[cfg(feature = "wayland_frontend")]
             dmabuf_cache: std::collections::HashMap::new(),
@@ -555,12 +575,11 @@ impl ImportShm for Gles2Renderer {
         surface: Option<&crate::wayland::compositor::SurfaceData>,
         damage: &[Rectangle<i32, Buffer>],
     ) -> Result<Gles2Texture, Gles2Error> {
-        use crate::wayland::shm::with_buffer_contents;
+        use crate::wayland::shm::with_buffer_contents_data;
 
-        with_buffer_contents(buffer, |slice, data| {
+        with_buffer_contents_data(buffer, |slice, data| {
             self.make_current()?;
 
-            let offset = data.offset as i32;
             let width = data.width as i32;
             let height = data.height as i32;
             let stride = data.stride as i32;
@@ -569,9 +588,6 @@ impl ImportShm for Gles2Renderer {
             // TODO: compute from data.format
             let pixelsize = 4i32;
 
-            // ensure consistency, the SHM handler of smithay should ensure this
-            assert!((offset + (height - 1) * stride + width * pixelsize) as usize <= slice.len());
-
             let (gl_format, shader_idx) = match data.format {
                 wl_shm::Format::Abgr8888 => (ffi::RGBA, 0),
                 wl_shm::Format::Xbgr8888 => (ffi::RGBA, 1),
@@ -624,7 +640,7 @@ impl ImportShm for Gles2Renderer {
                         0,
                         gl_format,
                         ffi::UNSIGNED_BYTE as u32,
-                        slice.as_ptr().offset(offset as isize) as *const _,
+                        slice.as_ptr() as *const _,
                     );
                 } else {
                     for region in damage.iter() {
@@ -640,7 +656,7 @@ impl ImportShm for Gles2Renderer {
                             region.size.h,
                             gl_format,
                             ffi::UNSIGNED_BYTE as u32,
-                            slice.as_ptr().offset(offset as isize) as *const _,
+                            slice.as_ptr() as *const _,
                         );
                         self.gl.PixelStorei(ffi::UNPACK_SKIP_PIXELS, 0);
                         self.gl.PixelStorei(ffi::UNPACK_SKIP_ROWS, 0);
@@ -882,7 +898,7 @@ impl Bind<Dmabuf> for Gles2Renderer {
                 Ok(Gles2Buffer {
                     internal: buf.clone(),
                     // we keep the dmabuf alive as long as we are bound
-                    _dmabuf: dmabuf,
+                    dmabuf,
                 })
             })
             .unwrap_or_else(|| {
@@ -929,7 +945,7 @@ impl Bind<Dmabuf> for Gles2Renderer {
 
                     Ok(Gles2Buffer {
                         internal: weak,
-                        _dmabuf: dmabuf,
+                        dmabuf,
                     })
                 }
             })?;
@@ -947,12 +963,96 @@ impl Bind<Dmabuf> for Gles2Renderer {
     }
 }
 
+impl Offscreen<Gles2Texture> for Gles2Renderer {
+    fn create_buffer(&mut self, size: Size<i32, Buffer>, format: Fourcc) -> Result<Gles2Texture, Gles2Error> {
+        // like shm buffers, the formats without alpha use a shader ignoring it
+        let (gl_format, texture_kind) = match format {
+            Fourcc::Abgr8888 => (ffi::RGBA, 0),
+            Fourcc::Xbgr8888 => (ffi::RGBA, 1),
+            Fourcc::Argb8888 => (ffi::BGRA_EXT, 0),
+            Fourcc::Xrgb8888 => (ffi::BGRA_EXT, 1),
+            format => return Err(Gles2Error::UnsupportedFormat(format)),
+        };
+
+        self.make_current()?;
+
+        let mut tex = 0;
+        unsafe {
+            self.gl.GenTextures(1, &mut tex);
+            self.gl.BindTexture(ffi::TEXTURE_2D, tex);
+            self.gl
+                .TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_S, ffi::CLAMP_TO_EDGE as i32);
+            self.gl
+                .TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_T, ffi::CLAMP_TO_EDGE as i32);
+            self.gl.TexImage2D(
+                ffi::TEXTURE_2D,
+                0,
+                gl_format as i32,
+                size.w,
+                size.h,
+                0,
+                gl_format,
+                ffi::UNSIGNED_BYTE,
+                ptr::null(),
+            );
+            self.gl.BindTexture(ffi::TEXTURE_2D, 0);
+        }
+
+        Ok(Gles2Texture(Rc::new(Gles2TextureInternal {
+            texture: tex,
+            texture_kind,
+            is_external: false,
+            // The framebuffer origin is the bottom left corner, so rendering results are stored upside down.
+            y_inverted: true,
+            size,
+            egl_images: None,
+            destruction_callback_sender: self.destruction_callback_sender.clone(),
+        })))
+    }
+}
+
+impl Bind<Gles2Texture> for Gles2Renderer {
+    fn bind(&mut self, texture: Gles2Texture) -> Result<(), Gles2Error> {
+        self.unbind()?;
+        unsafe {
+            self.egl.make_current()?;
+        }
+
+        unsafe {
+            let mut fbo = 0;
+            self.gl.GenFramebuffers(1, &mut fbo as *mut _);
+            self.gl.BindFramebuffer(ffi::FRAMEBUFFER, fbo);
+            self.gl.FramebufferTexture2D(
+                ffi::FRAMEBUFFER,
+                ffi::COLOR_ATTACHMENT0,
+                ffi::TEXTURE_2D,
+                texture.0.texture,
+                0,
+            );
+            let status = self.gl.CheckFramebufferStatus(ffi::FRAMEBUFFER);
+
+            if status != ffi::FRAMEBUFFER_COMPLETE {
+                self.gl.BindFramebuffer(ffi::FRAMEBUFFER, 0);
+                self.gl.DeleteFramebuffers(1, &fbo as *const _);
+                return Err(Gles2Error::FramebufferBindingError);
+            }
+
+            self.target_texture = Some((texture, fbo));
+        }
+
+        Ok(())
+    }
+}
+
 impl Unbind for Gles2Renderer {
     fn unbind(&mut self) -> Result<(), <Self as Renderer>::Error> {
         unsafe {
             self.egl.make_current()?;
         }
         unsafe { self.gl.BindFramebuffer(ffi::FRAMEBUFFER, 0) };
+        if let Some((_, fbo)) = self.target_texture.take() {
+            unsafe { self.gl.DeleteFramebuffers(1, &fbo as *const _) };
+        }
         self.target_buffer = None;
         self.target_surface = None;
         self.egl.unbind()?;
@@ -960,11 +1060,136 @@ impl Unbind for Gles2Renderer {
     }
 }
 
+impl ExportMem for Gles2Renderer {
+    fn copy_framebuffer(&mut self, region: Rectangle<i32, Buffer>) -> Result<MemoryBuffer, Gles2Error> {
+        let size = if let Some((texture, _)) = self.target_texture.as_ref() {
+            texture.0.size
+        } else if let Some(buffer) = self.target_buffer.as_ref() {
+            buffer.dmabuf.size()
+        } else if let Some(surface) = self.target_surface.as_ref() {
+            let size = surface.get_size().ok_or(Gles2Error::NoTarget)?;
+            Size::from((size.w, size.h))
+        } else {
+            return Err(Gles2Error::NoTarget);
+        };
+
+        self.make_current()?;
+        // Rendering flips the y-axis, so every framebuffer stores its contents upside down.
+        self.read_pixels(size, region, true)
+    }
+
+    fn copy_texture(
+        &mut self,
+        texture: &Gles2Texture,
+        region: Rectangle<i32, Buffer>,
+    ) -> Result<MemoryBuffer, Gles2Error> {
+        if texture.0.is_external {
+            return Err(Gles2Error::GLExtensionNotSupported(&[
+                "GL_OES_EGL_image_external",
+            ]));
+        }
+
+        self.make_current()?;
+
+        let mut fbo = 0;
+        let result = unsafe {
+            self.gl.GenFramebuffers(1, &mut fbo as *mut _);
+            self.gl.BindFramebuffer(ffi::FRAMEBUFFER, fbo);
+            self.gl.FramebufferTexture2D(
+                ffi::FRAMEBUFFER,
+                ffi::COLOR_ATTACHMENT0,
+                ffi::TEXTURE_2D,
+                texture.0.texture,
+                0,
+            );
+            if self.gl.CheckFramebufferStatus(ffi::FRAMEBUFFER) == ffi::FRAMEBUFFER_COMPLETE {
+                self.read_pixels(texture.0.size, region, texture.0.y_inverted)
+            } else {
+                Err(Gles2Error::FramebufferBindingError)
+            }
+        };
+
+        // restore the framebuffer of the bound target
+        let target_fbo = if let Some((_, fbo)) = self.target_texture.as_ref() {
+            *fbo
+        } else if let Some(buffer) = self.target_buffer.as_ref() {
+            buffer.internal.fbo
+        } else {
+            0
+        };
+        unsafe {
+            self.gl.BindFramebuffer(ffi::FRAMEBUFFER, target_fbo);
+            self.gl.DeleteFramebuffers(1, &fbo as *const _);
+        }
+
+        result
+    }
+}
+
+impl Gles2Renderer {
+    // Reads the given region of the currently bound framebuffer of the given size.
+    //
+    // If `flipped` is set the rows of the framebuffer are stored bottom to top
+    // and the region is given top to bottom.
+    fn read_pixels(
+        &self,
+        size: Size<i32, Buffer>,
+        region: Rectangle<i32, Buffer>,
+        flipped: bool,
+    ) -> Result<MemoryBuffer, Gles2Error> {
+        if region.loc.x < 0
+            || region.loc.y < 0
+            || region.size.w < 0
+            || region.size.h < 0
+            || region.loc.x + region.size.w > size.w
+            || region.loc.y + region.size.h > size.h
+        {
+            return Err(Gles2Error::InvalidRegion(region));
+        }
+
+        let y = if flipped {
+            size.h - region.loc.y - region.size.h
+        } else {
+            region.loc.y
+        };
+
+        // RGBA is the only format every implementation has to support for reading.
+        let mut buffer = MemoryBuffer::new(region.size, Fourcc::Abgr8888);
+        unsafe {
+            self.gl.PixelStorei(ffi::PACK_ALIGNMENT, 4);
+            self.gl.ReadPixels(
+                region.loc.x,
+                y,
+                region.size.w,
+                region.size.h,
+                ffi::RGBA,
+                ffi::UNSIGNED_BYTE,
+                buffer.data_mut().as_mut_ptr() as *mut _,
+            );
+        }
+
+        if flipped {
+            let stride = buffer.stride() as usize;
+            let data = buffer.data_mut();
+            let rows = data.len() / stride.max(1);
+            for row in 0..rows / 2 {
+                let (top, bottom) = data.split_at_mut((rows - row - 1) * stride);
+                top[row * stride..(row + 1) * stride].swap_with_slice(&mut bottom[..stride]);
+            }
+        }
+
+        Ok(buffer)
+    }
+}
+
 impl Drop for Gles2Renderer {
     fn drop(&mut self) {
         unsafe {
             if self.egl.make_current().is_ok() {
                 self.gl.BindFramebuffer(ffi::FRAMEBUFFER, 0);
+                if let Some((_, fbo)) = self.target_texture.take() {
+                    self.gl.DeleteFramebuffers(1, &fbo as *const _);
+                }
                 for program in &self.programs {
                     self.gl.DeleteProgram(program.program);
                 }
@@ -1052,6 +1277,7 @@ impl Renderer for Gles2Renderer {
             programs: self.programs.clone(),
             // output transformation passed in by the user
             current_projection: transform.matrix() * renderer,
+            size,
         };
 
         let result = rendering(self, &mut frame);
@@ -1173,4 +1399,187 @@ impl Frame for Gles2Frame {
 
         Ok(())
     }
+
+    fn clear_damaged(
+        &mut self,
+        color: [f32; 4],
+        damage: &[Rectangle<i32, Physical>],
+    ) -> Result<(), Self::Error> {
+        unsafe {
+            self.gl.ClearColor(color[0], color[1], color[2], color[3]);
+        }
+        for rect in self.damage_to_pixels(damage) {
+            unsafe {
+                self.gl.Scissor(rect.loc.x, rect.loc.y, rect.size.w, rect.size.h);
+                self.gl.Clear(ffi::COLOR_BUFFER_BIT);
+            }
+        }
+
+        unsafe {
+            self.gl.Scissor(0, 0, self.size.w, self.size.h);
+        }
+
+        Ok(())
+    }
+
+    fn render_texture_damaged(
+        &mut self,
+        tex: &Self::TextureId,
+        matrix: Matrix3<f32>,
+        tex_coords: [Vector2<f32>; 4],
+        damage: &[Rectangle<i32, Physical>],
+        alpha: f32,
+    ) -> Result<(), Self::Error> {
+        for rect in self.damage_to_pixels(damage) {
+            unsafe {
+                self.gl.Scissor(rect.loc.x, rect.loc.y, rect.size.w, rect.size.h);
+            }
+            self.render_texture(tex, matrix, tex_coords, alpha)?;
+        }
+
+        unsafe {
+            self.gl.Scissor(0, 0, self.size.w, self.size.h);
+        }
+
+        Ok(())
+    }
+}
+
+impl Gles2Frame {
+    /// Maps damage through the projection to find the affected pixels of the framebuffer
+    ///
+    /// The returned rectangles do not overlap.
+    fn damage_to_pixels(&self, damage: &[Rectangle<i32, Physical>]) -> Vec<Rectangle<i32, Physical>> {
+        let mut pixel_damage = Vec::with_capacity(damage.len());
+        for rect in damage {
+            let corners = [
+                self.current_projection * Vector3::new(rect.loc.x as f32, rect.loc.y as f32, 1.0),
+                self.current_projection
+                    * Vector3::new(
+                        (rect.loc.x + rect.size.w) as f32,
+                        (rect.loc.y + rect.size.h) as f32,
+                        1.0,
+                    ),
+            ];
+            let to_pixels = |ndc: f32, size: i32| (ndc + 1.0) / 2.0 * size as f32;
+            let (x0, x1) = (
+                to_pixels(corners[0].x, self.size.w),
+                to_pixels(corners[1].x, self.size.w),
+            );
+            let (y0, y1) = (
+                to_pixels(corners[0].y, self.size.h),
+                to_pixels(corners[1].y, self.size.h),
+            );
+
+            let x = (x0.min(x1).floor() as i32).max(0);
+            let y = (y0.min(y1).floor() as i32).max(0);
+            let width = (x0.max(x1).ceil() as i32).min(self.size.w) - x;
+            let height = (y0.max(y1).ceil() as i32).min(self.size.h) - y;
+            if width <= 0 || height <= 0 {
+                continue;
+            }
+            pixel_damage.push(Rectangle::<i32, Physical>::from_loc_and_size(
+                (x, y),
+                (width, height),
+            ));
+        }
+
+        // rounding to whole pixels may cause the damage to overlap, which would blend translucent
+        // textures multiple times
+        split_overlapping(pixel_damage)
+    }
+}
+
+#[cfg(all(test, feature = "backend_gbm"))]
+mod tests {
+    use super::*;
+    use crate::backend::{allocator::Buffer as _, egl::EGLDisplay};
+    use std::fs::{File, OpenOptions};
+
+    // Opens a render node, the tests are skipped on machines without a gpu.
+    fn render_node() -> Option<gbm::Device<File>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open("/dev/dri/renderD128")
+            .ok()?;
+        gbm::Device::new(file).ok()
+    }
+
+    fn renderer(device: &gbm::Device<File>) -> Option<Gles2Renderer> {
+        let display = EGLDisplay::new(device, None).ok()?;
+        let context = EGLContext::new(&display, None).ok()?;
+        unsafe { Gles2Renderer::new(context, None).ok() }
+    }
+
+    fn render_target(renderer: &mut Gles2Renderer) -> Gles2Texture {
+        let target: Gles2Texture = renderer.create_buffer((4, 4).into(), Fourcc::Abgr8888).unwrap();
+        renderer.bind(target.clone()).unwrap();
+        renderer
+            .render((4, 4).into(), Transform::Normal, |_, frame| {
+                frame.clear([0.0, 0.0, 0.0, 1.0])?;
+                frame.clear_damaged(
+                    [1.0, 0.0, 0.0, 1.0],
+                    &[Rectangle::from_loc_and_size((0, 0), (1, 1))],
+                )
+            })
+            .unwrap()
+            .unwrap();
+        target
+    }
+
+    #[test]
+    fn copy_framebuffer() {
+        let device = match render_node() {
+            Some(device) => device,
+            None => return,
+        };
+        let mut renderer = match renderer(&device) {
+            Some(renderer) => renderer,
+            None => return,
+        };
+        render_target(&mut renderer);
+
+        let copy = renderer
+            .copy_framebuffer(Rectangle::from_loc_and_size((0, 0), (2, 2)))
+            .unwrap();
+        assert_eq!(copy.size(), (2, 2).into());
+        assert_eq!(copy.format().code, Fourcc::Abgr8888);
+        // only the top left pixel was cleared to red
+        assert_eq!(
+            copy.data(),
+            &[255, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0, 255][..]
+        );
+
+        assert!(matches!(
+            renderer.copy_framebuffer(Rectangle::from_loc_and_size((2, 2), (4, 4))),
+            Err(Gles2Error::InvalidRegion(_))
+        ));
+
+        renderer.unbind().unwrap();
+        assert!(matches!(
+            renderer.copy_framebuffer(Rectangle::from_loc_and_size((0, 0), (1, 1))),
+            Err(Gles2Error::NoTarget)
+        ));
+    }
+
+    #[test]
+    fn copy_texture() {
+        let device = match render_node() {
+            Some(device) => device,
+            None => return,
+        };
+        let mut renderer = match renderer(&device) {
+            Some(renderer) => renderer,
+            None => return,
+        };
+        let texture = render_target(&mut renderer);
+        renderer.unbind().unwrap();
+
+        let copy = renderer
+            .copy_texture(&texture, Rectangle::from_loc_and_size((0, 0), (1, 2)))
+            .unwrap();
+        assert_eq!(copy.size(), (1, 2).into());
+        assert_eq!(copy.data(), &[255, 0, 0, 255, 0, 0, 0, 255][..]);
+    }
 }
@@ -6,20 +6,27 @@
 //! Supported rendering apis:
 //!
 //! - Raw OpenGL ES 2
+//! - Software rendering on the cpu
 
 use std::collections::HashSet;
 use std::error::Error;
 
-use crate::utils::{Buffer, Physical, Point, Rectangle, Size};
+use crate::backend::allocator::{memory::MemoryBuffer, Fourcc};
+use crate::utils::{Buffer, Coordinate, Physical, Point, Rectangle, Size};
 
 #[cfg(feature = "wayland_frontend")]
-use crate::wayland::compositor::SurfaceData;
+use crate::utils::Logical;
+#[cfg(feature = "wayland_frontend")]
+use crate::wayland::{compositor::SurfaceData, viewporter::ViewportCachedState};
 use cgmath::{prelude::*, Matrix3, Vector2, Vector3};
 #[cfg(feature = "wayland_frontend")]
 use wayland_server::protocol::{wl_buffer, wl_shm};
 
+pub mod damage;
+pub mod element;
 #[cfg(feature = "renderer_gl")]
 pub mod gles2;
+pub mod software;
 #[cfg(feature = "wayland_frontend")]
 use crate::backend::allocator::{dmabuf::Dmabuf, Format};
 #[cfg(all(
@@ -53,6 +60,12 @@ pub enum Transform {
     Flipped270,
 }
 
+impl Default for Transform {
+    fn default() -> Transform {
+        Transform::Normal
+    }
+}
+
 impl Transform {
     /// A projection matrix to apply this transformation
     pub fn matrix(&self) -> Matrix3<f32> {
@@ -70,7 +83,7 @@ impl Transform {
 
     /// Inverts any 90-degree transformation into 270-degree transformations and vise versa.
     ///
-    /// Flipping is preserved and 180/Normal transformation are uneffected.
+    /// Flipped transformations are their own inverse and 180/Normal transformation are uneffected.
     pub fn invert(&self) -> Transform {
         match self {
             Transform::Normal => Transform::Normal,
@@ -78,12 +91,95 @@ impl Transform {
             Transform::_90 => Transform::_270,
             Transform::_180 => Transform::_180,
             Transform::_270 => Transform::_90,
-            Transform::Flipped90 => Transform::Flipped270,
+            Transform::Flipped90 => Transform::Flipped90,
             Transform::Flipped180 => Transform::Flipped180,
-            Transform::Flipped270 => Transform::Flipped90,
+            Transform::Flipped270 => Transform::Flipped270,
+        }
+    }
+
+    /// Transformation equivalent to applying this transformation first and `other` afterwards.
+    pub fn compose(self, other: Transform) -> Transform {
+        // Every transformation flips the plane first (if at all) and then rotates it counter-clockwise.
+        // Flipping a rotated plane equals flipping it first and rotating it in the opposite direction.
+        let (flipped, rotation) = self.parts();
+        let (other_flipped, other_rotation) = other.parts();
+        let rotation = if other_flipped { 4 - rotation } else { rotation };
+
+        Transform::from_parts(flipped != other_flipped, (rotation + other_rotation) % 4)
+    }
+
+    fn parts(self) -> (bool, u8) {
+        match self {
+            Transform::Normal => (false, 0),
+            Transform::_90 => (false, 1),
+            Transform::_180 => (false, 2),
+            Transform::_270 => (false, 3),
+            Transform::Flipped => (true, 0),
+            Transform::Flipped90 => (true, 1),
+            Transform::Flipped180 => (true, 2),
+            Transform::Flipped270 => (true, 3),
+        }
+    }
+
+    fn from_parts(flipped: bool, rotation: u8) -> Transform {
+        match (flipped, rotation % 4) {
+            (false, 0) => Transform::Normal,
+            (false, 1) => Transform::_90,
+            (false, 2) => Transform::_180,
+            (false, _) => Transform::_270,
+            (true, 0) => Transform::Flipped,
+            (true, 1) => Transform::Flipped90,
+            (true, 2) => Transform::Flipped180,
+            (true, _) => Transform::Flipped270,
         }
     }
 
+    /// Transforms a point inside an area of the given size.
+    ///
+    /// The result is located inside the transformed area, whose size is swapped for 90 and 270
+    /// degree rotations.
+    pub fn transform_point_in<N: Coordinate, Kind>(
+        &self,
+        point: Point<N, Kind>,
+        area: &Size<N, Kind>,
+    ) -> Point<N, Kind> {
+        let (x, y) = (point.x, point.y);
+        let (w, h) = (area.w, area.h);
+
+        let (x, y) = match self {
+            Transform::Normal => (x, y),
+            Transform::_90 => (y, w - x),
+            Transform::_180 => (w - x, h - y),
+            Transform::_270 => (h - y, x),
+            Transform::Flipped => (w - x, y),
+            Transform::Flipped90 => (y, x),
+            Transform::Flipped180 => (x, h - y),
+            Transform::Flipped270 => (h - y, w - x),
+        };
+
+        Point::from((x, y))
+    }
+
+    /// Transforms a rectangle inside an area of the given size.
+    ///
+    /// See [`Transform::transform_point_in`].
+    pub fn transform_rect_in<N: Coordinate, Kind>(
+        &self,
+        rect: Rectangle<N, Kind>,
+        area: &Size<N, Kind>,
+    ) -> Rectangle<N, Kind> {
+        let first = self.transform_point_in(rect.loc, area);
+        let second = self.transform_point_in(rect.loc + rect.size, area);
+
+        let min = |a: N, b: N| if a < b { a } else { b };
+        let max = |a: N, b: N| if a < b { b } else { a };
+
+        Rectangle::from_extemities(
+            (min(first.x, second.x), min(first.y, second.y)),
+            (max(first.x, second.x), max(first.y, second.y)),
+        )
+    }
+
     /// Transformed size after applying this transformation.
     pub fn transform_size(&self, width: u32, height: u32) -> (u32, u32) {
         if *self == Transform::_90
@@ -116,6 +212,23 @@ impl From<wayland_server::protocol::wl_output::Transform> for Transform {
     }
 }
 
+#[cfg(feature = "wayland_frontend")]
+impl From<Transform> for wayland_server::protocol::wl_output::Transform {
+    fn from(transform: Transform) -> Self {
+        use wayland_server::protocol::wl_output::Transform as WlTransform;
+        match transform {
+            Transform::Normal => WlTransform::Normal,
+            Transform::_90 => WlTransform::_90,
+            Transform::_180 => WlTransform::_180,
+            Transform::_270 => WlTransform::_270,
+            Transform::Flipped => WlTransform::Flipped,
+            Transform::Flipped90 => WlTransform::Flipped90,
+            Transform::Flipped180 => WlTransform::Flipped180,
+            Transform::Flipped270 => WlTransform::Flipped270,
+        }
+    }
+}
+
 /// Abstraction for Renderers, that can render into different targets
 pub trait Bind<Target>: Unbind {
     /// Bind a given rendering target, which will contain the rendering results until `unbind` is called.
@@ -138,6 +251,23 @@ pub trait Unbind: Renderer {
     fn unbind(&mut self) -> Result<(), <Self as Renderer>::Error>;
 }
 
+/// Functionality to create offscreen rendering targets
+///
+/// A buffer created by [`Offscreen::create_buffer`] can be bound using [`Bind::bind`] to render into it.
+/// If the target type is the [`Renderer::TextureId`] of the renderer, the rendering results can be
+/// drawn in later frames, e.g. to display thumbnails of windows.
+pub trait Offscreen<Target>: Renderer + Bind<Target> {
+    /// Create a new buffer of the given size and format, that can be bound as a rendering target.
+    ///
+    /// Returns an error if the renderer cannot render into buffers of the given format.
+    /// The contents of the buffer are undefined until it was rendered to.
+    fn create_buffer(
+        &mut self,
+        size: Size<i32, Buffer>,
+        format: Fourcc,
+    ) -> Result<Target, <Self as Renderer>::Error>;
+}
+
 /// A two dimensional texture
 pub trait Texture {
     /// Size of the texture plane
@@ -163,6 +293,15 @@ pub trait Frame {
     /// This operation is only valid in between a `begin` and `finish`-call.
     /// If called outside this operation may error-out, do nothing or modify future rendering results in any way.
     fn clear(&mut self, color: [f32; 4]) -> Result<(), Self::Error>;
+    /// Clear the given damaged regions of the current target with a single given color.
+    ///
+    /// The damage is given in the coordinate space of the current target, like for
+    /// [`Frame::render_texture_damaged`]. The rest of the target must be left untouched.
+    fn clear_damaged(
+        &mut self,
+        color: [f32; 4],
+        damage: &[Rectangle<i32, Physical>],
+    ) -> Result<(), Self::Error>;
     /// Render a texture to the current target using given projection matrix and alpha.
     /// The given vertices are used to source the texture. This is mostly useful for cropping the texture.
     fn render_texture(
@@ -173,6 +312,26 @@ pub trait Frame {
         alpha: f32,
     ) -> Result<(), Self::Error>;
 
+    /// Render a texture to the current target using given projection matrix and alpha,
+    /// only updating the given damaged regions of the target.
+    ///
+    /// The damage is given in the coordinate space of the current target. Damage rectangles are
+    /// expected to not overlap, as overlapping regions might be drawn multiple times.
+    ///
+    /// The default implementation ignores the damage and renders the whole texture using
+    /// [`Frame::render_texture`].
+    fn render_texture_damaged(
+        &mut self,
+        texture: &Self::TextureId,
+        matrix: Matrix3<f32>,
+        tex_coords: [Vector2<f32>; 4],
+        damage: &[Rectangle<i32, Physical>],
+        alpha: f32,
+    ) -> Result<(), Self::Error> {
+        let _ = damage;
+        self.render_texture(texture, matrix, tex_coords, alpha)
+    }
+
     /// Render a texture to the current target as a flat 2d-plane at a given
     /// position and applying the given transformation with the given alpha value.
     fn render_texture_at(
@@ -209,6 +368,26 @@ pub trait Frame {
         dest: Rectangle<f64, Physical>,
         transform: Transform,
         alpha: f32,
+    ) -> Result<(), Self::Error> {
+        let damage = Rectangle::from_extemities(
+            dest.loc.to_i32_floor::<i32>(),
+            (dest.loc + dest.size).to_i32_ceil::<i32>(),
+        );
+        self.render_texture_from_to_damaged(texture, src, dest, &[damage], transform, alpha)
+    }
+
+    /// Render part of a texture as given by src to the current target into the rectangle described by dest
+    /// as a flat 2d-plane after applying the given transformations.
+    ///
+    /// Only the regions of the target given by `damage` are updated, see [`Frame::render_texture_damaged`].
+    fn render_texture_from_to_damaged(
+        &mut self,
+        texture: &Self::TextureId,
+        src: Rectangle<i32, Buffer>,
+        dest: Rectangle<f64, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        transform: Transform,
+        alpha: f32,
     ) -> Result<(), Self::Error> {
         let mut mat = Matrix3::<f32>::identity();
 
@@ -242,7 +421,7 @@ pub trait Frame {
             .truncate(), // bottom-right
             (texture_mat * Vector3::new(src.loc.x as f32, (src.loc.y + src.size.h) as f32, 0.0)).truncate(), // bottom-left
         ];
-        self.render_texture(texture, mat, verts, alpha)
+        self.render_texture_damaged(texture, mat, verts, damage, alpha)
     }
 }
 
@@ -272,6 +451,26 @@ pub trait Renderer {
         F: FnOnce(&mut Self, &mut Self::Frame) -> R;
 }
 
+/// Trait for renderers supporting reading back pixels into system memory.
+pub trait ExportMem: Renderer {
+    /// Copies the given region of the currently bound target into a new [`MemoryBuffer`].
+    ///
+    /// The region is given in the coordinates of the target buffer and must be contained in it.
+    fn copy_framebuffer(
+        &mut self,
+        region: Rectangle<i32, Buffer>,
+    ) -> Result<MemoryBuffer, <Self as Renderer>::Error>;
+
+    /// Copies the given region of a texture into a new [`MemoryBuffer`].
+    ///
+    /// The region is given in the coordinates of the texture and must be contained in it.
+    fn copy_texture(
+        &mut self,
+        texture: &<Self as Renderer>::TextureId,
+        region: Rectangle<i32, Buffer>,
+    ) -> Result<MemoryBuffer, <Self as Renderer>::Error>;
+}
+
 #[cfg(feature = "wayland_frontend")]
 /// Trait for Renderers supporting importing shm-based buffers.
 pub trait ImportShm: Renderer {
@@ -527,23 +726,175 @@ pub fn buffer_type(buffer: &wl_buffer::WlBuffer) -> Option<BufferType> {
 ///
 /// *Note*: This will only return dimensions for buffer types known to smithay (see [`buffer_type`])
 #[cfg(feature = "wayland_frontend")]
-pub fn buffer_dimensions(buffer: &wl_buffer::WlBuffer) -> Option<Size<i32, Physical>> {
-    use crate::backend::allocator::Buffer;
+pub fn buffer_dimensions(buffer: &wl_buffer::WlBuffer) -> Option<Size<i32, Buffer>> {
+    buffer_geometry(buffer).map(|geometry| geometry.size)
+}
+
+/// Geometry of a wl_buffer, see [`buffer_geometry`]
+#[cfg(feature = "wayland_frontend")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferGeometry {
+    /// Size of the buffer in pixels
+    pub size: Size<i32, Buffer>,
+    /// Pixel format of the buffer, if known
+    ///
+    /// This is `None` for buffers managed by EGL and for shm buffers using a format unknown to
+    /// [`DrmFourcc`](crate::backend::allocator::Fourcc).
+    pub format: Option<crate::backend::allocator::Fourcc>,
+}
+
+/// Returns the geometry of a wl_buffer
+///
+/// *Note*: This will only return a geometry for buffer types known to smithay (see [`buffer_type`])
+#[cfg(feature = "wayland_frontend")]
+pub fn buffer_geometry(buffer: &wl_buffer::WlBuffer) -> Option<BufferGeometry> {
+    use crate::backend::allocator::Buffer as _;
 
     if let Some(buf) = buffer.as_ref().user_data().get::<Dmabuf>() {
-        return Some((buf.width() as i32, buf.height() as i32).into());
+        return Some(BufferGeometry {
+            size: buf.size(),
+            format: Some(buf.format().code),
+        });
     }
 
     #[cfg(all(feature = "backend_egl", feature = "use_system_lib"))]
-    if let Some(dim) = BUFFER_READER
+    if let Some(size) = BUFFER_READER
         .lock()
         .unwrap()
         .as_ref()
         .and_then(|x| x.upgrade())
         .and_then(|x| x.egl_buffer_dimensions(buffer))
     {
-        return Some(dim);
+        return Some(BufferGeometry { size, format: None });
     }
 
-    crate::wayland::shm::with_buffer_contents(buffer, |_, data| (data.width, data.height).into()).ok()
+    crate::wayland::shm::with_buffer_contents(buffer, |_, data| BufferGeometry {
+        size: (data.width, data.height).into(),
+        format: crate::wayland::shm::shm_format_to_fourcc(data.format),
+    })
+    .ok()
+}
+
+/// Returns the logical size of a wl_buffer, given the buffer scale it was committed with
+///
+/// A client setting `wl_surface.set_buffer_scale` to `N` attaches buffers, that are `N` times
+/// the size of the surface in logical coordinates. This helper derives the logical surface size
+/// from the buffer dimensions (see [`buffer_dimensions`]) and that scale.
+///
+/// *Note*: This will only return a size for buffer types known to smithay (see [`buffer_type`])
+#[cfg(feature = "wayland_frontend")]
+pub fn buffer_logical_size(buffer: &wl_buffer::WlBuffer, buffer_scale: i32) -> Option<Size<i32, Logical>> {
+    buffer_dimensions(buffer).map(|dims| dims.to_logical(buffer_scale))
+}
+
+/// Returns the logical size of a surface, given the dimensions, scale and transform of its buffer and
+/// its viewport
+///
+/// This is the size of the buffer in logical coordinates (see [`buffer_logical_size`]), swapped for
+/// 90 and 270 degree transforms, unless the surface has a viewport (see
+/// [`viewporter`](crate::wayland::viewporter)) defining its size.
+#[cfg(feature = "wayland_frontend")]
+pub fn surface_logical_size(
+    buffer_dimensions: Size<i32, Buffer>,
+    buffer_scale: i32,
+    buffer_transform: Transform,
+    viewport: &ViewportCachedState,
+) -> Size<i32, Logical> {
+    viewport.size().unwrap_or_else(|| {
+        let (w, h) = buffer_transform.transform_size(buffer_dimensions.w as u32, buffer_dimensions.h as u32);
+        Size::<i32, Buffer>::from((w as i32, h as i32)).to_logical(buffer_scale)
+    })
+}
+
+/// Returns the part of the buffer of a surface that is displayed, given the dimensions, scale and
+/// transform of the buffer and the viewport of the surface
+///
+/// The source rectangle of the viewport is expressed in surface coordinates, it is scaled and
+/// transformed back into the buffer. This is the source rectangle to give to
+/// [`Frame::render_texture_from_to`], the destination should have the size returned by
+/// [`surface_logical_size`].
+#[cfg(feature = "wayland_frontend")]
+pub fn surface_buffer_src(
+    buffer_dimensions: Size<i32, Buffer>,
+    buffer_scale: i32,
+    buffer_transform: Transform,
+    viewport: &ViewportCachedState,
+) -> Rectangle<i32, Buffer> {
+    match viewport.src {
+        Some(src) => {
+            // the size of the transformed buffer, in which the source rectangle is located
+            let (w, h) =
+                buffer_transform.transform_size(buffer_dimensions.w as u32, buffer_dimensions.h as u32);
+            let area = Size::<f64, Buffer>::from((w as f64, h as f64));
+            let src = buffer_transform.transform_rect_in(src.to_buffer(buffer_scale as f64), &area);
+            Rectangle::from_loc_and_size(src.loc.to_i32_round::<i32>(), src.size.to_i32_round::<i32>())
+        }
+        None => Rectangle::from_loc_and_size((0, 0), buffer_dimensions),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Transform;
+    use crate::utils::{Logical, Rectangle, Size};
+
+    const TRANSFORMS: [Transform; 8] = [
+        Transform::Normal,
+        Transform::_90,
+        Transform::_180,
+        Transform::_270,
+        Transform::Flipped,
+        Transform::Flipped90,
+        Transform::Flipped180,
+        Transform::Flipped270,
+    ];
+
+    #[test]
+    fn compose_with_invert_is_normal() {
+        for transform in TRANSFORMS.iter().copied() {
+            assert_eq!(transform.compose(transform.invert()), Transform::Normal);
+            assert_eq!(transform.invert().compose(transform), Transform::Normal);
+        }
+    }
+
+    #[test]
+    fn compose_matches_matrix() {
+        for first in TRANSFORMS.iter().copied() {
+            for second in TRANSFORMS.iter().copied() {
+                assert_eq!(
+                    first.compose(second).matrix(),
+                    second.matrix() * first.matrix(),
+                    "{:?} composed with {:?}",
+                    first,
+                    second
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn transform_rect_twice_by_90() {
+        let area = Size::<i32, Logical>::from((100, 50));
+        let rect = Rectangle::<i32, Logical>::from_loc_and_size((10, 5), (20, 10));
+
+        let rotated = Transform::_90.transform_rect_in(rect, &area);
+        assert_eq!(rotated, Rectangle::from_loc_and_size((5, 70), (10, 20)));
+
+        let rotated_area = Size::<i32, Logical>::from((50, 100));
+        assert_eq!(
+            Transform::_90.transform_rect_in(rotated, &rotated_area),
+            Transform::_180.transform_rect_in(rect, &area)
+        );
+    }
+
+    #[test]
+    fn transformed_area_is_covered() {
+        let area = Size::<i32, Logical>::from((100, 50));
+
+        for transform in TRANSFORMS.iter().copied() {
+            let rect = transform.transform_rect_in(Rectangle::from_loc_and_size((0, 0), area), &area);
+            let (w, h) = transform.transform_size(100, 50);
+            assert_eq!(rect, Rectangle::from_loc_and_size((0, 0), (w as i32, h as i32)));
+        }
+    }
 }
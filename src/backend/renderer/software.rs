@@ -0,0 +1,1063 @@
+//! Implementation of the rendering traits on the cpu
+//!
+//! The [`SoftwareRenderer`] renders into [`MemoryBuffer`]s or linear [`Dmabuf`]s and does not need
+//! any gpu or graphics api. It is slow, but useful for testing, headless setups and tiny displays.
+//!
+//! Textures use premultiplied alpha and are sampled using bilinear filtering by default, like they
+//! are by the [`Gles2Renderer`](super::gles2::Gles2Renderer). Nearest neighbour filtering can be
+//! selected with [`SoftwareRenderer::set_filter`]. The rendered pixels can be read back through
+//! [`ExportMem`].
+//!
+//! The output transformation is applied like the gles2 renderer does, except that the framebuffer
+//! is not upside-down: rendering with [`Transform::Normal`] keeps the first row of the buffer at the
+//! top.
+//!
+//! Limitations:
+//!
+//! - Only `Argb8888` and `Xrgb8888` buffers are supported as targets and textures
+//! - Dmabufs must be linear and use a single plane, they are accessed through `mmap`
+//! - Dmabufs cannot be imported as textures, memory is imported through
+//!   [`SoftwareRenderer::import_memory`] as there is no generic trait for it
+//!
+//! ```rust,no_run
+//! # use smithay::backend::allocator::{memory::MemoryBuffer, Fourcc};
+//! # use smithay::backend::renderer::{software::SoftwareRenderer, Bind, Frame, Renderer, Transform};
+//! let mut renderer = SoftwareRenderer::new(None);
+//! renderer.bind(MemoryBuffer::new((640, 480), Fourcc::Argb8888)).unwrap();
+//! renderer
+//!     .render((640, 480).into(), Transform::Normal, |_renderer, frame| {
+//!         frame.clear([0.1, 0.1, 0.1, 1.0])
+//!     })
+//!     .unwrap()
+//!     .unwrap();
+//! let buffer = renderer.take_buffer().unwrap();
+//! ```
+
+use std::{os::unix::io::RawFd, ptr, rc::Rc, slice};
+
+use cgmath::{prelude::*, Matrix3, Vector2, Vector3};
+use nix::sys::mman;
+use slog::o;
+
+use super::{
+    damage::split_overlapping, Bind, ExportMem, Frame, Offscreen, Renderer, Texture, Transform, Unbind,
+};
+use crate::backend::allocator::{dmabuf::Dmabuf, memory::MemoryBuffer, Buffer as _, Fourcc, Modifier};
+use crate::utils::{Buffer, Physical, Rectangle, Size};
+
+#[cfg(feature = "wayland_frontend")]
+use super::ImportShm;
+#[cfg(feature = "wayland_frontend")]
+use wayland_server::protocol::{wl_buffer, wl_shm};
+
+/// Error returned by the [`SoftwareRenderer`]
+#[derive(thiserror::Error, Debug)]
+pub enum SoftwareError {
+    /// No target was bound before rendering
+    #[error("No target is bound")]
+    NoTarget,
+    /// The given buffer has an unsupported pixel format
+    #[error("Unsupported pixel format: {0:?}")]
+    UnsupportedFormat(Fourcc),
+    /// The given shm buffer has an unsupported pixel format
+    #[error("Unsupported pixel format: {0:?}")]
+    #[cfg(feature = "wayland_frontend")]
+    UnsupportedPixelFormat(wl_shm::Format),
+    /// The given buffer was not accessible
+    #[error("Error accessing the buffer ({0:?})")]
+    #[cfg(feature = "wayland_frontend")]
+    BufferAccessError(crate::wayland::shm::BufferAccessError),
+    /// Only linear single-plane dmabufs can be bound
+    #[error("Only linear single-plane dmabufs are supported")]
+    UnsupportedDmabuf,
+    /// The dmabuf could not be mapped or synchronized
+    #[error("Error accessing the dmabuf")]
+    DmabufAccess(#[source] nix::Error),
+    /// The region to copy is not contained in the buffer
+    #[error("Invalid region to copy: {0:?}")]
+    InvalidRegion(Rectangle<i32, Buffer>),
+}
+
+/// Filter used to sample textures
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextureFilter {
+    /// Interpolate between the four nearest pixels
+    Linear,
+    /// Use the nearest pixel
+    Nearest,
+}
+
+/// A texture residing in system memory
+///
+/// Cloning a texture is cheap, the pixel data is shared.
+#[derive(Debug, Clone)]
+pub struct SoftwareTexture(Rc<SoftwareTextureInternal>);
+
+#[derive(Debug)]
+struct SoftwareTextureInternal {
+    // pixels in `Fourcc::Argb8888` layout, premultiplied
+    data: Vec<u8>,
+    size: Size<i32, Buffer>,
+    opaque: bool,
+}
+
+impl Texture for SoftwareTexture {
+    fn size(&self) -> Size<i32, Buffer> {
+        self.0.size
+    }
+
+    fn width(&self) -> u32 {
+        self.0.size.w as u32
+    }
+
+    fn height(&self) -> u32 {
+        self.0.size.h as u32
+    }
+}
+
+impl SoftwareTexture {
+    /// Reads the pixel at the given position as premultiplied `[b, g, r, a]` values
+    fn pixel(&self, x: i32, y: i32) -> [f32; 4] {
+        let x = x.max(0).min(self.0.size.w - 1);
+        let y = y.max(0).min(self.0.size.h - 1);
+        let offset = ((y * self.0.size.w + x) * 4) as usize;
+        let pixel = &self.0.data[offset..offset + 4];
+
+        [
+            pixel[0] as f32 / 255.0,
+            pixel[1] as f32 / 255.0,
+            pixel[2] as f32 / 255.0,
+            if self.0.opaque {
+                1.0
+            } else {
+                pixel[3] as f32 / 255.0
+            },
+        ]
+    }
+
+    /// Samples the texture at the given position in pixels
+    fn sample(&self, x: f32, y: f32, filter: TextureFilter) -> [f32; 4] {
+        if filter == TextureFilter::Nearest {
+            return self.pixel(x.floor() as i32, y.floor() as i32);
+        }
+
+        // pixel centers are located at .5
+        let x = x - 0.5;
+        let y = y - 0.5;
+        let x0 = x.floor();
+        let y0 = y.floor();
+        let fx = x - x0;
+        let fy = y - y0;
+        let (x0, y0) = (x0 as i32, y0 as i32);
+
+        let top_left = self.pixel(x0, y0);
+        let top_right = self.pixel(x0 + 1, y0);
+        let bottom_left = self.pixel(x0, y0 + 1);
+        let bottom_right = self.pixel(x0 + 1, y0 + 1);
+
+        let mut color = [0.0; 4];
+        for (i, channel) in color.iter_mut().enumerate() {
+            let top = top_left[i] * (1.0 - fx) + top_right[i] * fx;
+            let bottom = bottom_left[i] * (1.0 - fx) + bottom_right[i] * fx;
+            *channel = top * (1.0 - fy) + bottom * fy;
+        }
+        color
+    }
+}
+
+// see linux/dma-buf.h
+mod dma_buf {
+    nix::ioctl_write_ptr!(dma_buf_sync, b'b', 0, DmaBufSync);
+    pub const DMA_BUF_SYNC_READ: u64 = 1 << 0;
+    pub const DMA_BUF_SYNC_WRITE: u64 = 1 << 1;
+    pub const DMA_BUF_SYNC_START: u64 = 0;
+    pub const DMA_BUF_SYNC_END: u64 = 1 << 2;
+    #[repr(C)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub struct DmaBufSync {
+        pub flags: u64,
+    }
+}
+use self::dma_buf::*;
+
+/// A linear dmabuf mapped into memory
+#[derive(Debug)]
+struct DmabufMapping {
+    dmabuf: Dmabuf,
+    ptr: *mut u8,
+    len: usize,
+    offset: usize,
+    stride: usize,
+}
+
+impl DmabufMapping {
+    fn new(dmabuf: Dmabuf) -> Result<DmabufMapping, SoftwareError> {
+        let size = dmabuf.size();
+        let offset = dmabuf.offsets().next().unwrap_or(0) as usize;
+        let stride = dmabuf.strides().next().unwrap_or(0) as usize;
+        if dmabuf.num_planes() != 1
+            || dmabuf.format().modifier != Modifier::Linear
+            || stride < size.w as usize * 4
+        {
+            return Err(SoftwareError::UnsupportedDmabuf);
+        }
+
+        let len = offset + stride * size.h as usize;
+        let ptr = unsafe {
+            mman::mmap(
+                ptr::null_mut(),
+                len,
+                mman::ProtFlags::PROT_READ | mman::ProtFlags::PROT_WRITE,
+                mman::MapFlags::MAP_SHARED,
+                dmabuf.handles().next().unwrap(),
+                0,
+            )
+        }
+        .map_err(SoftwareError::DmabufAccess)?;
+
+        Ok(DmabufMapping {
+            dmabuf,
+            ptr: ptr as *mut u8,
+            len,
+            offset,
+            stride,
+        })
+    }
+
+    fn fd(&self) -> RawFd {
+        self.dmabuf.handles().next().unwrap()
+    }
+
+    /// Signals the start or end of a cpu access to the kernel, to keep the caches coherent
+    fn sync(&self, flags: u64) -> Result<(), SoftwareError> {
+        unsafe { dma_buf_sync(self.fd(), &DmaBufSync { flags }) }
+            .map(|_| ())
+            .map_err(SoftwareError::DmabufAccess)
+    }
+}
+
+impl Drop for DmabufMapping {
+    fn drop(&mut self) {
+        let _ = unsafe { mman::munmap(self.ptr as *mut _, self.len) };
+    }
+}
+
+/// Buffer rendered into by the [`SoftwareRenderer`]
+#[derive(Debug)]
+enum Target {
+    Memory(MemoryBuffer),
+    Dmabuf(DmabufMapping),
+}
+
+impl Target {
+    fn size(&self) -> Size<i32, Buffer> {
+        match self {
+            Target::Memory(buffer) => buffer.size(),
+            Target::Dmabuf(mapping) => mapping.dmabuf.size(),
+        }
+    }
+
+    fn format(&self) -> Fourcc {
+        match self {
+            Target::Memory(buffer) => buffer.format().code,
+            Target::Dmabuf(mapping) => mapping.dmabuf.format().code,
+        }
+    }
+
+    fn stride(&self) -> usize {
+        match self {
+            Target::Memory(buffer) => buffer.stride() as usize,
+            Target::Dmabuf(mapping) => mapping.stride,
+        }
+    }
+
+    fn data(&self) -> &[u8] {
+        match self {
+            Target::Memory(buffer) => buffer.data(),
+            Target::Dmabuf(mapping) => unsafe {
+                slice::from_raw_parts(mapping.ptr.add(mapping.offset), mapping.len - mapping.offset)
+            },
+        }
+    }
+
+    fn data_mut(&mut self) -> &mut [u8] {
+        match self {
+            Target::Memory(buffer) => buffer.data_mut(),
+            Target::Dmabuf(mapping) => unsafe {
+                slice::from_raw_parts_mut(mapping.ptr.add(mapping.offset), mapping.len - mapping.offset)
+            },
+        }
+    }
+
+    fn sync(&self, flags: u64) -> Result<(), SoftwareError> {
+        match self {
+            Target::Memory(_) => Ok(()),
+            Target::Dmabuf(mapping) => mapping.sync(flags),
+        }
+    }
+}
+
+/// A renderer drawing into [`MemoryBuffer`]s or linear [`Dmabuf`]s on the cpu
+///
+/// Only `Argb8888` and `Xrgb8888` buffers are supported as targets.
+#[derive(Debug)]
+pub struct SoftwareRenderer {
+    target: Option<Target>,
+    filter: TextureFilter,
+    logger: ::slog::Logger,
+}
+
+/// Handle to the currently rendered frame during [`SoftwareRenderer::render`]
+#[derive(Debug)]
+pub struct SoftwareFrame {
+    target: Target,
+    size: Size<i32, Physical>,
+    // maps the coordinates of the frame to pixels of the target
+    projection: Matrix3<f32>,
+    filter: TextureFilter,
+}
+
+impl SoftwareRenderer {
+    /// Creates a new software renderer
+    pub fn new<L>(logger: L) -> SoftwareRenderer
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let logger = crate::slog_or_fallback(logger).new(o!("smithay_module" => "renderer_software"));
+
+        SoftwareRenderer {
+            target: None,
+            filter: TextureFilter::Linear,
+            logger,
+        }
+    }
+
+    /// Sets the filter used to sample textures, [`TextureFilter::Linear`] by default
+    pub fn set_filter(&mut self, filter: TextureFilter) {
+        self.filter = filter;
+    }
+
+    /// Returns the currently bound buffer, if it is a [`MemoryBuffer`]
+    pub fn buffer(&self) -> Option<&MemoryBuffer> {
+        match self.target {
+            Some(Target::Memory(ref buffer)) => Some(buffer),
+            _ => None,
+        }
+    }
+
+    /// Unbinds the current target and returns it, including anything rendered into it
+    ///
+    /// Returns `None` and keeps the target bound, if it is not a [`MemoryBuffer`].
+    pub fn take_buffer(&mut self) -> Option<MemoryBuffer> {
+        match self.target.take() {
+            Some(Target::Memory(buffer)) => Some(buffer),
+            target => {
+                self.target = target;
+                None
+            }
+        }
+    }
+
+    /// Imports the contents of a [`MemoryBuffer`] as a texture
+    ///
+    /// The buffer is expected to contain premultiplied pixels.
+    pub fn import_memory(&mut self, buffer: &MemoryBuffer) -> Result<SoftwareTexture, SoftwareError> {
+        let opaque = match buffer.format().code {
+            Fourcc::Argb8888 => false,
+            Fourcc::Xrgb8888 => true,
+            format => return Err(SoftwareError::UnsupportedFormat(format)),
+        };
+
+        Ok(SoftwareTexture(Rc::new(SoftwareTextureInternal {
+            data: buffer.data().to_vec(),
+            size: buffer.size(),
+            opaque,
+        })))
+    }
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl ImportShm for SoftwareRenderer {
+    fn import_shm_buffer(
+        &mut self,
+        buffer: &wl_buffer::WlBuffer,
+        _surface: Option<&crate::wayland::compositor::SurfaceData>,
+        _damage: &[Rectangle<i32, Buffer>],
+    ) -> Result<SoftwareTexture, SoftwareError> {
+        use crate::wayland::shm::{copy_region_to, with_buffer_contents_data};
+
+        let data =
+            with_buffer_contents_data(buffer, |_, data| data).map_err(SoftwareError::BufferAccessError)?;
+
+        let (swap_red_blue, opaque) = match data.format {
+            wl_shm::Format::Argb8888 => (false, false),
+            wl_shm::Format::Xrgb8888 => (false, true),
+            wl_shm::Format::Abgr8888 => (true, false),
+            wl_shm::Format::Xbgr8888 => (true, true),
+            format => return Err(SoftwareError::UnsupportedPixelFormat(format)),
+        };
+
+        let width = data.width as usize;
+        let height = data.height as usize;
+
+        let mut pixels = vec![0; width * height * 4];
+        copy_region_to(
+            buffer,
+            &mut pixels,
+            width * 4,
+            Rectangle::from_loc_and_size((0, 0), (data.width, data.height)),
+        )
+        .map_err(SoftwareError::BufferAccessError)?;
+
+        if swap_red_blue {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        slog::trace!(self.logger, "Imported shm buffer of size {}x{}", width, height);
+
+        Ok(SoftwareTexture(Rc::new(SoftwareTextureInternal {
+            data: pixels,
+            size: (data.width, data.height).into(),
+            opaque,
+        })))
+    }
+
+    fn shm_formats(&self) -> &[wl_shm::Format] {
+        &[
+            wl_shm::Format::Abgr8888,
+            wl_shm::Format::Xbgr8888,
+            wl_shm::Format::Argb8888,
+            wl_shm::Format::Xrgb8888,
+        ]
+    }
+}
+
+impl Bind<MemoryBuffer> for SoftwareRenderer {
+    fn bind(&mut self, target: MemoryBuffer) -> Result<(), SoftwareError> {
+        match target.format().code {
+            Fourcc::Argb8888 | Fourcc::Xrgb8888 => {}
+            format => return Err(SoftwareError::UnsupportedFormat(format)),
+        }
+
+        slog::trace!(self.logger, "Binding buffer of size {:?}", target.size());
+        self.target = Some(Target::Memory(target));
+        Ok(())
+    }
+}
+
+impl Bind<Dmabuf> for SoftwareRenderer {
+    fn bind(&mut self, target: Dmabuf) -> Result<(), SoftwareError> {
+        match target.format().code {
+            Fourcc::Argb8888 | Fourcc::Xrgb8888 => {}
+            format => return Err(SoftwareError::UnsupportedFormat(format)),
+        }
+
+        slog::trace!(self.logger, "Binding dmabuf of size {:?}", target.size());
+        self.target = Some(Target::Dmabuf(DmabufMapping::new(target)?));
+        Ok(())
+    }
+}
+
+impl ExportMem for SoftwareRenderer {
+    fn copy_framebuffer(&mut self, region: Rectangle<i32, Buffer>) -> Result<MemoryBuffer, SoftwareError> {
+        let target = self.target.as_ref().ok_or(SoftwareError::NoTarget)?;
+        target.sync(DMA_BUF_SYNC_START | DMA_BUF_SYNC_READ)?;
+        let result = copy_region(
+            target.data(),
+            target.stride(),
+            target.size(),
+            target.format(),
+            region,
+        );
+        target.sync(DMA_BUF_SYNC_END | DMA_BUF_SYNC_READ)?;
+        result
+    }
+
+    fn copy_texture(
+        &mut self,
+        texture: &SoftwareTexture,
+        region: Rectangle<i32, Buffer>,
+    ) -> Result<MemoryBuffer, SoftwareError> {
+        let format = if texture.0.opaque {
+            Fourcc::Xrgb8888
+        } else {
+            Fourcc::Argb8888
+        };
+        copy_region(
+            &texture.0.data,
+            texture.0.size.w as usize * 4,
+            texture.0.size,
+            format,
+            region,
+        )
+    }
+}
+
+impl Offscreen<MemoryBuffer> for SoftwareRenderer {
+    fn create_buffer(
+        &mut self,
+        size: Size<i32, Buffer>,
+        format: Fourcc,
+    ) -> Result<MemoryBuffer, SoftwareError> {
+        match format {
+            Fourcc::Argb8888 | Fourcc::Xrgb8888 => Ok(MemoryBuffer::new(size, format)),
+            format => Err(SoftwareError::UnsupportedFormat(format)),
+        }
+    }
+}
+
+impl Unbind for SoftwareRenderer {
+    fn unbind(&mut self) -> Result<(), SoftwareError> {
+        self.target = None;
+        Ok(())
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    type Error = SoftwareError;
+    type TextureId = SoftwareTexture;
+    type Frame = SoftwareFrame;
+
+    fn render<F, R>(
+        &mut self,
+        size: Size<i32, Physical>,
+        transform: Transform,
+        rendering: F,
+    ) -> Result<R, Self::Error>
+    where
+        F: FnOnce(&mut Self, &mut Self::Frame) -> R,
+    {
+        let target = self.target.take().ok_or(SoftwareError::NoTarget)?;
+        if let Err(err) = target.sync(DMA_BUF_SYNC_START | DMA_BUF_SYNC_READ | DMA_BUF_SYNC_WRITE) {
+            self.target = Some(target);
+            return Err(err);
+        }
+        let buffer_size = target.size();
+
+        // `Transform::matrix` operates on normalized coordinates, scale it to the size of the frame
+        let matrix = transform.matrix();
+        let (w, h) = (size.w as f32, size.h as f32);
+        let (a, b, c, d) = (matrix.x.x, matrix.y.x * w / h, matrix.x.y * h / w, matrix.y.y);
+        let projection = Matrix3::new(
+            a,
+            c,
+            0.0,
+            b,
+            d,
+            0.0,
+            w / 2.0 - (a * w + b * h) / 2.0,
+            h / 2.0 - (c * w + d * h) / 2.0,
+            1.0,
+        );
+
+        let mut frame = SoftwareFrame {
+            target,
+            size: (size.w.min(buffer_size.w), size.h.min(buffer_size.h)).into(),
+            projection,
+            filter: self.filter,
+        };
+
+        let result = rendering(self, &mut frame);
+
+        if let Err(err) = frame
+            .target
+            .sync(DMA_BUF_SYNC_END | DMA_BUF_SYNC_READ | DMA_BUF_SYNC_WRITE)
+        {
+            slog::warn!(self.logger, "Failed to end the access to the dmabuf: {}", err);
+        }
+
+        // only restore the target, if it was not replaced during rendering
+        if self.target.is_none() {
+            self.target = Some(frame.target);
+        }
+
+        Ok(result)
+    }
+}
+
+impl Frame for SoftwareFrame {
+    type Error = SoftwareError;
+    type TextureId = SoftwareTexture;
+
+    fn clear(&mut self, color: [f32; 4]) -> Result<(), Self::Error> {
+        self.fill(color, Rectangle::from_loc_and_size((0, 0), self.size));
+        Ok(())
+    }
+
+    fn clear_damaged(
+        &mut self,
+        color: [f32; 4],
+        damage: &[Rectangle<i32, Physical>],
+    ) -> Result<(), Self::Error> {
+        for rect in self.damage_to_pixels(damage) {
+            self.fill(color, rect);
+        }
+        Ok(())
+    }
+
+    fn render_texture(
+        &mut self,
+        texture: &Self::TextureId,
+        matrix: Matrix3<f32>,
+        tex_coords: [Vector2<f32>; 4],
+        alpha: f32,
+    ) -> Result<(), Self::Error> {
+        let clip = Rectangle::from_loc_and_size((0, 0), self.size);
+        self.draw(texture, matrix, tex_coords, alpha, clip);
+        Ok(())
+    }
+
+    fn render_texture_damaged(
+        &mut self,
+        texture: &Self::TextureId,
+        matrix: Matrix3<f32>,
+        tex_coords: [Vector2<f32>; 4],
+        damage: &[Rectangle<i32, Physical>],
+        alpha: f32,
+    ) -> Result<(), Self::Error> {
+        for clip in self.damage_to_pixels(damage) {
+            self.draw(texture, matrix, tex_coords, alpha, clip);
+        }
+        Ok(())
+    }
+}
+
+impl SoftwareFrame {
+    /// Maps damage through the projection to find the affected pixels of the framebuffer
+    ///
+    /// The returned rectangles do not overlap.
+    fn damage_to_pixels(&self, damage: &[Rectangle<i32, Physical>]) -> Vec<Rectangle<i32, Physical>> {
+        let bounds = Rectangle::from_loc_and_size((0, 0), self.size);
+        split_overlapping(damage.iter().filter_map(|rect| {
+            let first = self.projection * Vector3::new(rect.loc.x as f32, rect.loc.y as f32, 1.0);
+            let second = self.projection
+                * Vector3::new(
+                    (rect.loc.x + rect.size.w) as f32,
+                    (rect.loc.y + rect.size.h) as f32,
+                    1.0,
+                );
+            let pixels = Rectangle::from_extemities(
+                (
+                    first.x.min(second.x).floor() as i32,
+                    first.y.min(second.y).floor() as i32,
+                ),
+                (
+                    first.x.max(second.x).ceil() as i32,
+                    first.y.max(second.y).ceil() as i32,
+                ),
+            );
+            // damage outside of the framebuffer must not be rasterised
+            pixels.intersection(bounds)
+        }))
+    }
+
+    /// Fills the pixels inside of `rect` with a color
+    ///
+    /// `rect` must be contained in the framebuffer.
+    fn fill(&mut self, color: [f32; 4], rect: Rectangle<i32, Physical>) {
+        let pixel = to_bytes([color[2], color[1], color[0], color[3]]);
+        let stride = self.target.stride();
+        let data = self.target.data_mut();
+
+        for row in rect.loc.y as usize..(rect.loc.y + rect.size.h) as usize {
+            let start = row * stride + rect.loc.x as usize * 4;
+            for dst in data[start..start + rect.size.w as usize * 4].chunks_exact_mut(4) {
+                dst.copy_from_slice(&pixel);
+            }
+        }
+    }
+
+    /// Draws the texture, only touching the pixels inside of `clip`
+    ///
+    /// `clip` must be contained in the framebuffer.
+    fn draw(
+        &mut self,
+        texture: &SoftwareTexture,
+        matrix: Matrix3<f32>,
+        tex_coords: [Vector2<f32>; 4],
+        alpha: f32,
+        clip: Rectangle<i32, Physical>,
+    ) {
+        // the matrix maps the unit square onto the target, find the pixels it covers
+        let matrix = self.projection * matrix;
+        let corners = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+            .iter()
+            .map(|&(x, y)| matrix * Vector3::new(x, y, 1.0))
+            .collect::<Vec<_>>();
+        let min_x = corners
+            .iter()
+            .map(|c| c.x)
+            .fold(f32::INFINITY, f32::min)
+            .floor()
+            .max(clip.loc.x as f32) as i32;
+        let min_y = corners
+            .iter()
+            .map(|c| c.y)
+            .fold(f32::INFINITY, f32::min)
+            .floor()
+            .max(clip.loc.y as f32) as i32;
+        let max_x = (corners
+            .iter()
+            .map(|c| c.x)
+            .fold(f32::NEG_INFINITY, f32::max)
+            .ceil() as i32)
+            .min(clip.loc.x + clip.size.w);
+        let max_y = (corners
+            .iter()
+            .map(|c| c.y)
+            .fold(f32::NEG_INFINITY, f32::max)
+            .ceil() as i32)
+            .min(clip.loc.y + clip.size.h);
+
+        let inverse = match matrix.invert() {
+            Some(inverse) => inverse,
+            // the texture is degenerated to a line or point, nothing to draw
+            None => return,
+        };
+
+        // vertices are ordered top-right, top-left, bottom-right, bottom-left
+        let tex_origin = tex_coords[1];
+        let tex_x = tex_coords[0] - tex_coords[1];
+        let tex_y = tex_coords[3] - tex_coords[1];
+        let tex_size = Vector2::new(texture.0.size.w as f32, texture.0.size.h as f32);
+
+        let stride = self.target.stride();
+        let filter = self.filter;
+        let data = self.target.data_mut();
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                // sample at the center of the pixel
+                let unit = inverse * Vector3::new(x as f32 + 0.5, y as f32 + 0.5, 1.0);
+                if unit.x < 0.0 || unit.x >= 1.0 || unit.y < 0.0 || unit.y >= 1.0 {
+                    continue;
+                }
+
+                let tex_pos = tex_origin + tex_x * unit.x + tex_y * unit.y;
+                let src = texture.sample(tex_pos.x * tex_size.x, tex_pos.y * tex_size.y, filter);
+
+                let offset = y as usize * stride + x as usize * 4;
+                let dst = &mut data[offset..offset + 4];
+                let mut color = [0.0; 4];
+                for (i, channel) in color.iter_mut().enumerate() {
+                    // premultiplied alpha blending, see `glBlendFunc(GL_ONE, GL_ONE_MINUS_SRC_ALPHA)`
+                    *channel = src[i] * alpha + dst[i] as f32 / 255.0 * (1.0 - src[3] * alpha);
+                }
+                dst.copy_from_slice(&to_bytes(color));
+            }
+        }
+    }
+}
+
+fn copy_region(
+    data: &[u8],
+    stride: usize,
+    size: Size<i32, Buffer>,
+    format: Fourcc,
+    region: Rectangle<i32, Buffer>,
+) -> Result<MemoryBuffer, SoftwareError> {
+    if region.loc.x < 0
+        || region.loc.y < 0
+        || region.size.w < 0
+        || region.size.h < 0
+        || region.loc.x + region.size.w > size.w
+        || region.loc.y + region.size.h > size.h
+    {
+        return Err(SoftwareError::InvalidRegion(region));
+    }
+
+    let mut buffer = MemoryBuffer::new(region.size, format);
+    let row_len = region.size.w as usize * 4;
+    for (row, dst) in buffer.data_mut().chunks_exact_mut(row_len).enumerate() {
+        let offset = (region.loc.y as usize + row) * stride + region.loc.x as usize * 4;
+        dst.copy_from_slice(&data[offset..offset + row_len]);
+    }
+    Ok(buffer)
+}
+
+fn to_bytes(color: [f32; 4]) -> [u8; 4] {
+    let byte = |value: f32| (value.max(0.0).min(1.0) * 255.0).round() as u8;
+    [byte(color[0]), byte(color[1]), byte(color[2]), byte(color[3])]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::{Point, Rectangle};
+
+    fn texture(
+        renderer: &mut SoftwareRenderer,
+        size: (i32, i32),
+        format: Fourcc,
+        pixel: [u8; 4],
+    ) -> SoftwareTexture {
+        let mut buffer = MemoryBuffer::new(size, format);
+        for dst in buffer.data_mut().chunks_exact_mut(4) {
+            dst.copy_from_slice(&pixel);
+        }
+        renderer.import_memory(&buffer).unwrap()
+    }
+
+    fn pixel(buffer: &MemoryBuffer, x: usize, y: usize) -> [u8; 4] {
+        let offset = y * buffer.stride() as usize + x * 4;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&buffer.data()[offset..offset + 4]);
+        pixel
+    }
+
+    #[test]
+    fn render_without_target() {
+        let mut renderer = SoftwareRenderer::new(None);
+        assert!(matches!(
+            renderer.render((10, 10).into(), Transform::Normal, |_, _| ()),
+            Err(SoftwareError::NoTarget)
+        ));
+    }
+
+    #[test]
+    fn clear_and_draw_texture() {
+        let mut renderer = SoftwareRenderer::new(None);
+        let red = texture(&mut renderer, (2, 2), Fourcc::Argb8888, [0, 0, 255, 255]);
+
+        renderer
+            .bind(MemoryBuffer::new((8, 8), Fourcc::Argb8888))
+            .unwrap();
+        renderer
+            .render((8, 8).into(), Transform::Normal, |_, frame| {
+                frame.clear([0.0, 0.0, 1.0, 1.0])?;
+                frame.render_texture_at(&red, Point::from((2.0, 4.0)), 1, 1.0, Transform::Normal, 1.0)
+            })
+            .unwrap()
+            .unwrap();
+
+        let buffer = renderer.take_buffer().unwrap();
+        assert_eq!(pixel(&buffer, 0, 0), [255, 0, 0, 255]);
+        assert_eq!(pixel(&buffer, 2, 4), [0, 0, 255, 255]);
+        assert_eq!(pixel(&buffer, 3, 5), [0, 0, 255, 255]);
+        assert_eq!(pixel(&buffer, 4, 5), [255, 0, 0, 255]);
+        assert_eq!(pixel(&buffer, 2, 6), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn alpha_blending() {
+        let mut renderer = SoftwareRenderer::new(None);
+        // half transparent white, premultiplied
+        let translucent = texture(&mut renderer, (4, 4), Fourcc::Argb8888, [128, 128, 128, 128]);
+        // the alpha channel of xrgb textures is ignored
+        let opaque = texture(&mut renderer, (4, 4), Fourcc::Xrgb8888, [255, 255, 255, 0]);
+
+        renderer
+            .bind(MemoryBuffer::new((8, 4), Fourcc::Argb8888))
+            .unwrap();
+        renderer
+            .render((8, 4).into(), Transform::Normal, |_, frame| {
+                frame.clear([0.0, 0.0, 0.0, 1.0])?;
+                frame.render_texture_at(
+                    &translucent,
+                    Point::from((0.0, 0.0)),
+                    1,
+                    1.0,
+                    Transform::Normal,
+                    1.0,
+                )?;
+                frame.render_texture_from_to(
+                    &opaque,
+                    Rectangle::from_loc_and_size((0, 0), (4, 4)),
+                    Rectangle::from_loc_and_size((4.0, 0.0), (4.0, 4.0)),
+                    Transform::Normal,
+                    0.5,
+                )
+            })
+            .unwrap()
+            .unwrap();
+
+        let buffer = renderer.take_buffer().unwrap();
+        assert_eq!(pixel(&buffer, 1, 1), [128, 128, 128, 255]);
+        assert_eq!(pixel(&buffer, 5, 1), [128, 128, 128, 255]);
+    }
+
+    #[test]
+    fn output_transforms() {
+        const A: [u8; 4] = [255, 0, 0, 255];
+        const B: [u8; 4] = [0, 255, 0, 255];
+        const C: [u8; 4] = [0, 0, 255, 255];
+        const D: [u8; 4] = [255, 255, 255, 255];
+
+        let mut renderer = SoftwareRenderer::new(None);
+        let mut buffer = MemoryBuffer::new((2, 2), Fourcc::Argb8888);
+        buffer.data_mut().copy_from_slice(&[A, B, C, D].concat());
+        let texture = renderer.import_memory(&buffer).unwrap();
+
+        let mut render = |transform| {
+            renderer
+                .bind(MemoryBuffer::new((2, 2), Fourcc::Argb8888))
+                .unwrap();
+            renderer
+                .render((2, 2).into(), transform, |_, frame| {
+                    frame.render_texture_at(&texture, Point::from((0.0, 0.0)), 1, 1.0, Transform::Normal, 1.0)
+                })
+                .unwrap()
+                .unwrap();
+            let buffer = renderer.take_buffer().unwrap();
+            [
+                pixel(&buffer, 0, 0),
+                pixel(&buffer, 1, 0),
+                pixel(&buffer, 0, 1),
+                pixel(&buffer, 1, 1),
+            ]
+        };
+
+        assert_eq!(render(Transform::Normal), [A, B, C, D]);
+        assert_eq!(render(Transform::_90), [B, D, A, C]);
+        assert_eq!(render(Transform::_180), [D, C, B, A]);
+        assert_eq!(render(Transform::Flipped), [B, A, D, C]);
+        assert_eq!(render(Transform::Flipped180), [C, D, A, B]);
+    }
+
+    #[test]
+    fn nearest_filtering() {
+        let mut renderer = SoftwareRenderer::new(None);
+        let mut buffer = MemoryBuffer::new((2, 1), Fourcc::Argb8888);
+        buffer
+            .data_mut()
+            .copy_from_slice(&[0, 0, 0, 255, 255, 255, 255, 255]);
+        let texture = renderer.import_memory(&buffer).unwrap();
+
+        renderer.set_filter(TextureFilter::Nearest);
+        renderer
+            .bind(MemoryBuffer::new((4, 1), Fourcc::Argb8888))
+            .unwrap();
+        renderer
+            .render((4, 1).into(), Transform::Normal, |_, frame| {
+                frame.render_texture_from_to(
+                    &texture,
+                    Rectangle::from_loc_and_size((0, 0), (2, 1)),
+                    Rectangle::from_loc_and_size((0.0, 0.0), (4.0, 1.0)),
+                    Transform::Normal,
+                    1.0,
+                )
+            })
+            .unwrap()
+            .unwrap();
+
+        let buffer = renderer.take_buffer().unwrap();
+        assert_eq!(pixel(&buffer, 1, 0), [0, 0, 0, 255]);
+        assert_eq!(pixel(&buffer, 2, 0), [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn readback() {
+        let mut renderer = SoftwareRenderer::new(None);
+        let red = texture(&mut renderer, (1, 1), Fourcc::Argb8888, [0, 0, 255, 255]);
+        renderer
+            .bind(MemoryBuffer::new((4, 4), Fourcc::Xrgb8888))
+            .unwrap();
+        renderer
+            .render((4, 4).into(), Transform::_180, |_, frame| {
+                frame.clear([0.0, 0.0, 0.0, 1.0])?;
+                frame.render_texture_at(&red, Point::from((0.0, 0.0)), 1, 1.0, Transform::Normal, 1.0)
+            })
+            .unwrap()
+            .unwrap();
+
+        let copy = renderer
+            .copy_framebuffer(Rectangle::from_loc_and_size((2, 2), (2, 2)))
+            .unwrap();
+        assert_eq!(copy.size(), (2, 2).into());
+        assert_eq!(copy.format().code, Fourcc::Xrgb8888);
+        assert_eq!(pixel(&copy, 0, 0), [0, 0, 0, 255]);
+        // the output transform is applied to the rendered content
+        assert_eq!(pixel(&copy, 1, 1), [0, 0, 255, 255]);
+        assert!(matches!(
+            renderer.copy_framebuffer(Rectangle::from_loc_and_size((2, 2), (4, 4))),
+            Err(SoftwareError::InvalidRegion(_))
+        ));
+
+        let texture = texture(&mut renderer, (4, 4), Fourcc::Argb8888, [1, 2, 3, 4]);
+        let copy = renderer
+            .copy_texture(&texture, Rectangle::from_loc_and_size((1, 1), (3, 2)))
+            .unwrap();
+        assert_eq!(copy.data(), &[1, 2, 3, 4].repeat(6)[..]);
+    }
+
+    #[test]
+    fn clear_damaged_regions() {
+        let mut renderer = SoftwareRenderer::new(None);
+        renderer
+            .bind(MemoryBuffer::new((4, 4), Fourcc::Argb8888))
+            .unwrap();
+        renderer
+            .render((4, 4).into(), Transform::_180, |_, frame| {
+                frame.clear([0.0, 0.0, 0.0, 1.0])?;
+                frame.clear_damaged(
+                    [1.0, 0.0, 0.0, 1.0],
+                    &[
+                        Rectangle::from_loc_and_size((0, 0), (1, 1)),
+                        Rectangle::from_loc_and_size((-8, -8), (1, 1)),
+                    ],
+                )
+            })
+            .unwrap()
+            .unwrap();
+
+        let buffer = renderer.take_buffer().unwrap();
+        // the damage is transformed like the rendered content
+        assert_eq!(pixel(&buffer, 3, 3), [0, 0, 255, 255]);
+        assert_eq!(pixel(&buffer, 0, 0), [0, 0, 0, 255]);
+        assert_eq!(pixel(&buffer, 2, 3), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn damage_is_clipped_to_the_framebuffer() {
+        let mut renderer = SoftwareRenderer::new(None);
+        // half transparent white, premultiplied
+        let translucent = texture(&mut renderer, (8, 8), Fourcc::Argb8888, [128, 128, 128, 128]);
+
+        renderer
+            .bind(MemoryBuffer::new((4, 4), Fourcc::Argb8888))
+            .unwrap();
+        renderer
+            .render((4, 4).into(), Transform::Normal, |_, frame| {
+                frame.clear([0.0, 0.0, 0.0, 1.0])?;
+                frame.render_texture_from_to_damaged(
+                    &translucent,
+                    Rectangle::from_loc_and_size((0, 0), (8, 8)),
+                    Rectangle::from_loc_and_size((-2.0, -2.0), (8.0, 8.0)),
+                    &[
+                        Rectangle::from_loc_and_size((-2, -2), (4, 4)),
+                        Rectangle::from_loc_and_size((0, 0), (2, 8)),
+                        Rectangle::from_loc_and_size((3, 3), (10, 10)),
+                    ],
+                    Transform::Normal,
+                    1.0,
+                )
+            })
+            .unwrap()
+            .unwrap();
+
+        let buffer = renderer.take_buffer().unwrap();
+        // overlapping damage is only blended once
+        assert_eq!(pixel(&buffer, 1, 1), [128, 128, 128, 255]);
+        assert_eq!(pixel(&buffer, 1, 3), [128, 128, 128, 255]);
+        assert_eq!(pixel(&buffer, 3, 3), [128, 128, 128, 255]);
+        // outside of the damage
+        assert_eq!(pixel(&buffer, 2, 1), [0, 0, 0, 255]);
+        assert_eq!(pixel(&buffer, 3, 2), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn unsupported_formats() {
+        let mut renderer = SoftwareRenderer::new(None);
+        assert!(matches!(
+            renderer.bind(MemoryBuffer::new((8, 8), Fourcc::Nv12)),
+            Err(SoftwareError::UnsupportedFormat(Fourcc::Nv12))
+        ));
+        assert!(renderer.buffer().is_none());
+        assert!(matches!(
+            renderer.copy_framebuffer(Rectangle::from_loc_and_size((0, 0), (1, 1))),
+            Err(SoftwareError::NoTarget)
+        ));
+    }
+}
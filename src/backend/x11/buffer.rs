@@ -2,7 +2,8 @@
 //!
 //! Buffers imported into X11 are represented as X pixmaps which are then presented to the window.
 //!
-//! At the moment only [`Dmabuf`] backed pixmaps are supported.
+//! At the moment only [`Dmabuf`] backed pixmaps are supported. If the X server is not capable of
+//! direct rendering, the contents of a [`MemoryBuffer`] are uploaded to the window instead.
 //!
 //! ## Dmabuf pixmaps
 //!
@@ -14,6 +15,12 @@
 //! If you do need to modify any of the logic pertaining to the Dmabuf presentation, do ensure you
 //! read the `dri3proto.txt` file (link in the non-public comments of the x11 mod.rs).
 //!
+//! ## Memory buffers
+//!
+//! Without DRI3 the contents of a [`MemoryBuffer`] are sent to the X server using `PutImage`
+//! requests. This works with any X server, including remote ones, but copies the whole buffer
+//! over the connection for every frame.
+//!
 //! ## Presentation to the window
 //!
 //! Presentation to the window is handled through the [`Present`](x11rb::protocol::present)
@@ -29,32 +36,38 @@
 
 use std::sync::atomic::Ordering;
 
-use super::{Window, X11Error};
+use super::{PresentMode, Window, X11Error};
 use drm_fourcc::DrmFourcc;
 use nix::fcntl;
 use x11rb::connection::Connection;
 use x11rb::protocol::dri3::ConnectionExt as _;
 use x11rb::protocol::present::{self, ConnectionExt};
-use x11rb::protocol::xproto::PixmapWrapper;
+use x11rb::protocol::xproto::{ConnectionExt as _, Gcontext, ImageFormat, PixmapWrapper};
 use x11rb::rust_connection::{ConnectionError, ReplyOrIdError};
 use x11rb::utils::RawFdContainer;
 
 use crate::backend::allocator::dmabuf::Dmabuf;
+use crate::backend::allocator::memory::MemoryBuffer;
 use crate::backend::allocator::Buffer;
 
 // Shm can be easily supported in the future using, xcb_shm_create_pixmap.
 
+/// An error which may occur when creating a pixmap from a buffer.
 #[derive(Debug, thiserror::Error)]
 pub enum CreatePixmapError {
+    /// An X11 protocol error occured.
     #[error("An x11 protocol error occured")]
     Protocol(X11Error),
 
+    /// The dmabuf had more planes than supported by the X server.
     #[error("The Dmabuf had too many planes")]
     TooManyPlanes,
 
+    /// Duplicating the file descriptors of the dmabuf failed.
     #[error("Duplicating the file descriptors for the dmabuf handles failed")]
     DupFailed(String),
 
+    /// The format of the buffer does not match the format of the window.
     #[error("Buffer had incorrect format, expected: {0}")]
     IncorrectFormat(DrmFourcc),
 }
@@ -97,7 +110,7 @@ where
     ///
     /// The pixmap will be automatically dropped when it bubbles up in the X11 event loop after the
     /// X server has finished presentation with the buffer behind the pixmap.
-    fn present(self, connection: &C, window: &Window) -> Result<u32, X11Error>;
+    fn present(self, connection: &C, window: &Window, mode: PresentMode) -> Result<u32, X11Error>;
 }
 
 impl<'c, C> PixmapWrapperExt<'c, C> for PixmapWrapper<'c, C>
@@ -129,7 +142,7 @@ where
         }
 
         // We need dri3 >= 1.2 in order to use the enhanced dri3_pixmap_from_buffers function.
-        let xid = if window_inner.extensions.dri3 >= (1, 2) {
+        let xid = if window_inner.extensions.dri3 >= Some((1, 2)) {
             if dmabuf.num_planes() > 4 {
                 return Err(CreatePixmapError::TooManyPlanes);
             }
@@ -197,14 +210,23 @@ where
         Ok(PixmapWrapper::for_pixmap(connection, xid))
     }
 
-    fn present(self, connection: &C, window: &Window) -> Result<u32, X11Error> {
+    fn present(self, connection: &C, window: &Window, mode: PresentMode) -> Result<u32, X11Error> {
         let window_inner = window.0.upgrade().unwrap(); // We have the connection and window alive.
         let next_serial = window_inner.next_serial.fetch_add(1, Ordering::SeqCst);
-        // We want to present as soon as possible, so wait 1ms so the X server will present when next convenient.
-        let msc = window_inner.last_msc.load(Ordering::SeqCst) + 1;
+        let last_msc = window_inner.last_msc.load(Ordering::SeqCst);
 
-        // options parameter does not take the enum but a u32.
-        const OPTIONS: present::Option = present::Option::NONE;
+        let (msc, options) = match mode {
+            // Queue the pixmap for the vblank after the one of the previously presented pixmap.
+            PresentMode::Fifo => {
+                let msc = u64::max(last_msc, window_inner.target_msc.load(Ordering::SeqCst)) + 1;
+                (msc, present::Option::NONE)
+            }
+            // Present on the next vblank, a pixmap presented later for the same vblank replaces this one.
+            PresentMode::Mailbox => (last_msc + 1, present::Option::NONE),
+            // A target msc in the past presents immediately, the async option allows tearing.
+            PresentMode::Immediate => (0, present::Option::ASYNC),
+        };
+        window_inner.target_msc.store(msc, Ordering::SeqCst);
 
         connection.present_pixmap(
             window.id(),
@@ -217,7 +239,7 @@ where
             x11rb::NONE,    // Let the X server pick the most suitable crtc
             x11rb::NONE,    // Do not wait to present
             x11rb::NONE,    // We will wait for the X server to tell us when it is done with the pixmap.
-            OPTIONS.into(), // No special presentation options.
+            options.into(), // options parameter does not take the enum but a u32.
             msc,
             0,
             0,
@@ -229,3 +251,44 @@ where
         Ok(self.pixmap())
     }
 }
+
+/// Uploads the contents of a memory buffer to the window.
+///
+/// Since no pixmap is presented, the X server is asked to notify us on the next msc instead, so a
+/// `PresentCompleteNotify` event is received just like when presenting a pixmap.
+pub fn put_memory_buffer<C: Connection>(
+    connection: &C,
+    window: &Window,
+    gc: Gcontext,
+    buffer: &MemoryBuffer,
+) -> Result<(), X11Error> {
+    let window_inner = window.0.upgrade().unwrap(); // We have the connection and window alive.
+    let stride = buffer.stride() as usize;
+
+    if stride != 0 {
+        // Split the upload into multiple requests if the buffer does not fit into a single request.
+        // The header of a PutImage request is 24 bytes.
+        let rows_per_request = ((connection.maximum_request_bytes() - 24) / stride).max(1);
+
+        for (index, rows) in buffer.data().chunks(rows_per_request * stride).enumerate() {
+            connection.put_image(
+                ImageFormat::Z_PIXMAP,
+                window.id(),
+                gc,
+                buffer.width() as u16,
+                (rows.len() / stride) as u16,
+                0,
+                (index * rows_per_request) as i16,
+                0,
+                window_inner.depth.depth,
+                rows,
+            )?;
+        }
+    }
+
+    let next_serial = window_inner.next_serial.fetch_add(1, Ordering::SeqCst);
+    let msc = window_inner.last_msc.load(Ordering::SeqCst) + 1;
+    connection.present_notify_msc(window.id(), next_serial, msc, 0, 0)?;
+
+    Ok(())
+}
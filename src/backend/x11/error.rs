@@ -1,8 +1,10 @@
 use std::io;
 
+use drm_fourcc::DrmFourcc;
 use nix::errno::Errno;
 use x11rb::rust_connection::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError};
 
+use super::CreatePixmapError;
 use crate::backend::{allocator::gbm::GbmConvertError, drm::CreateDrmNodeError};
 
 /// An error emitted by the X11 backend during setup.
@@ -125,6 +127,10 @@ pub enum AllocateBuffersError {
     /// Exporting a dmabuf failed.
     #[error("Exporting a dmabuf failed.")]
     ExportDmabuf(GbmConvertError),
+
+    /// The format of the window is not supported to allocate buffers.
+    #[error("The format {0:?} of the window is not supported to allocate buffers.")]
+    UnsupportedFormat(DrmFourcc),
 }
 
 impl From<Errno> for AllocateBuffersError {
@@ -159,3 +165,54 @@ impl From<AllocateBuffersError> for X11Error {
         Self::Allocation(err)
     }
 }
+
+/// An error which may occur when presenting to an X11 window.
+#[derive(Debug, thiserror::Error)]
+pub enum X11PresentError {
+    /// The connection to the X server was lost.
+    #[error("The connection to the X server was lost")]
+    ConnectionLost,
+
+    /// Failed to allocate buffers needed to present to the window.
+    #[error("Failed to allocate buffers needed to present to the window")]
+    Allocation(AllocateBuffersError),
+
+    /// Creating a pixmap from the presented buffer failed.
+    #[error("Creating a pixmap from the presented buffer failed")]
+    CreatePixmap(CreatePixmapError),
+
+    /// Some protocol error occurred while presenting.
+    #[error("Some protocol error occurred while presenting")]
+    Protocol(X11Error),
+
+    /// All buffers are still used by the X server.
+    ///
+    /// The X server releases a buffer once it no longer displays it, so presenting should be retried
+    /// after the next [`X11Event::PresentCompleted`](super::X11Event::PresentCompleted).
+    #[error("All buffers are still used by the X server")]
+    NoFreeBuffer,
+}
+
+impl From<AllocateBuffersError> for X11PresentError {
+    fn from(err: AllocateBuffersError) -> Self {
+        Self::Allocation(err)
+    }
+}
+
+impl From<CreatePixmapError> for X11PresentError {
+    fn from(err: CreatePixmapError) -> Self {
+        Self::CreatePixmap(err)
+    }
+}
+
+impl From<X11Error> for X11PresentError {
+    fn from(err: X11Error) -> Self {
+        Self::Protocol(err)
+    }
+}
+
+impl From<ConnectionError> for X11PresentError {
+    fn from(err: ConnectionError) -> Self {
+        Self::Protocol(err.into())
+    }
+}
@@ -3,7 +3,7 @@ use super::{MissingExtensionError, X11Error};
 /// The extension macro.
 ///
 /// This macro generates a struct which checks for the presence of some X11 extensions and stores
-/// the version supplied by the X server.
+/// the version supplied by the X server, or `None` if an optional extension is not available.
 ///
 /// ```rust
 /// extensions! {
@@ -15,6 +15,8 @@ use super::{MissingExtensionError, X11Error};
 ///         minimum: (4, 0),
 ///         // The version of the extension to request.
 ///         request: (4, 0),
+///         // Whether the backend cannot work without the extension.
+///         required: true,
 ///     },
 /// }
 ///
@@ -27,6 +29,7 @@ macro_rules! extensions {
                 $extension_fn:ident, // Function used to look up the version of the extension
                 minimum: ($min_major:expr, $min_minor:expr),
                 request: ($req_major:expr, $req_minor:expr),
+                required: $required:expr,
             },
         )*
     ) => {
@@ -34,13 +37,14 @@ macro_rules! extensions {
         pub struct Extensions {
             $(
                 #[doc = concat!(" The version of the `", stringify!($extension), "` extension.")]
-                pub $extension: (u32, u32),
+                pub $extension: Option<(u32, u32)>,
             )*
         }
 
         impl Extensions {
             pub fn check_extensions<C: x11rb::connection::Connection>(connection: &C, logger: &slog::Logger) -> Result<Extensions, X11Error> {
                 $(
+                    #[allow(clippy::useless_conversion)] // Extensions use differently sized version numbers
                     let $extension = {
                         use x11rb::protocol::$extension::{ConnectionExt as _, X11_EXTENSION_NAME};
 
@@ -59,8 +63,8 @@ macro_rules! extensions {
                                     version.minor_version,
                                 );
 
-                                (version.major_version, version.minor_version)
-                            } else {
+                                Some((version.major_version.into(), version.minor_version.into()))
+                            } else if $required {
                                 slog::error!(
                                     logger,
                                     "{} extension version is too low (have {}.{}, expected {}.{})",
@@ -75,11 +79,23 @@ macro_rules! extensions {
                                     name: X11_EXTENSION_NAME,
                                     required_major: $req_major,
                                     required_minor: $req_minor,
-                                    available_major: version.major_version,
-                                    available_minor: version.minor_version,
+                                    available_major: version.major_version.into(),
+                                    available_minor: version.minor_version.into(),
                                 }.into());
+                            } else {
+                                slog::warn!(
+                                    logger,
+                                    "{} extension version is too low (have {}.{}, expected {}.{})",
+                                    X11_EXTENSION_NAME,
+                                    version.major_version,
+                                    version.minor_version,
+                                    $req_major,
+                                    $req_minor,
+                                );
+
+                                None
                             }
-                        } else {
+                        } else if $required {
                             slog::error!(logger, "{} extension not found", X11_EXTENSION_NAME);
 
                             return Err(MissingExtensionError::NotFound {
@@ -88,6 +104,9 @@ macro_rules! extensions {
                                 minor: $min_minor,
                             }
                             .into());
+                        } else {
+                            slog::warn!(logger, "{} extension not found", X11_EXTENSION_NAME);
+                            None
                         }
                     };
                 )*
@@ -107,17 +126,41 @@ extensions! {
         present_query_version,
         minimum: (1, 0),
         request: (1, 0),
+        required: true,
     },
 
     xfixes {
         xfixes_query_version,
         minimum: (4, 0),
         request: (4, 0),
+        required: true,
     },
 
     dri3 {
         dri3_query_version,
         minimum: (1, 0),
         request: (1, 2),
+        required: false,
+    },
+
+    xinput {
+        xinput_xi_query_version,
+        minimum: (2, 0),
+        request: (2, 2),
+        required: false,
+    },
+
+    render {
+        render_query_version,
+        minimum: (0, 5),
+        request: (0, 11),
+        required: false,
+    },
+
+    randr {
+        randr_query_version,
+        minimum: (1, 3),
+        request: (1, 3),
+        required: false,
     },
 }
@@ -4,7 +4,9 @@ use super::X11Error;
 use crate::{
     backend::input::{
         self, Axis, AxisSource, ButtonState, Device, DeviceCapability, InputBackend, InputEvent, KeyState,
-        KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent, UnusedEvent,
+        KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent,
+        PointerMotionEvent, ProximityState, TabletToolAxisEvent, TabletToolButtonEvent, TabletToolDescriptor,
+        TabletToolEvent, TabletToolProximityEvent, TabletToolTipEvent, TabletToolTipState, UnusedEvent,
     },
     utils::{Logical, Size},
 };
@@ -29,7 +31,10 @@ impl Device for X11VirtualDevice {
     fn has_capability(&self, capability: DeviceCapability) -> bool {
         matches!(
             capability,
-            DeviceCapability::Keyboard | DeviceCapability::Pointer | DeviceCapability::Touch
+            DeviceCapability::Keyboard
+                | DeviceCapability::Pointer
+                | DeviceCapability::Touch
+                | DeviceCapability::TabletTool
         )
     }
 
@@ -181,6 +186,303 @@ impl PointerMotionAbsoluteEvent<X11Input> for X11MouseMovedEvent {
     }
 }
 
+/// X11-Backend internal event wrapping `XInput2` raw motion events into a [`PointerMotionEvent`]
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct X11RelativeMotionEvent {
+    pub(crate) time: u32,
+    pub(crate) delta: (f64, f64),
+    pub(crate) delta_unaccel: (f64, f64),
+}
+
+impl input::Event<X11Input> for X11RelativeMotionEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> X11VirtualDevice {
+        X11VirtualDevice
+    }
+}
+
+impl PointerMotionEvent<X11Input> for X11RelativeMotionEvent {
+    fn delta_x(&self) -> f64 {
+        self.delta.0
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.delta.1
+    }
+
+    fn delta_x_unaccel(&self) -> f64 {
+        self.delta_unaccel.0
+    }
+
+    fn delta_y_unaccel(&self) -> f64 {
+        self.delta_unaccel.1
+    }
+}
+
+/// X11-Backend internal event wrapping `XInput2` device events of a tablet tool into a [`TabletToolAxisEvent`]
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct X11TabletToolEvent {
+    pub(crate) time: u32,
+    pub(crate) tool: TabletToolDescriptor,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) delta: (f64, f64),
+    pub(crate) size: Size<u16, Logical>,
+    pub(crate) pressure: f64,
+    pub(crate) pressure_changed: bool,
+    pub(crate) tilt: (f64, f64),
+    pub(crate) tilt_changed: (bool, bool),
+}
+
+impl input::Event<X11Input> for X11TabletToolEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> X11VirtualDevice {
+        X11VirtualDevice
+    }
+}
+
+impl TabletToolEvent<X11Input> for X11TabletToolEvent {
+    fn tool(&self) -> TabletToolDescriptor {
+        self.tool.clone()
+    }
+
+    fn delta_x(&self) -> f64 {
+        self.delta.0
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.delta.1
+    }
+
+    fn x(&self) -> f64 {
+        self.x
+    }
+
+    fn y(&self) -> f64 {
+        self.y
+    }
+
+    fn x_transformed(&self, width: i32) -> f64 {
+        f64::max(self.x * width as f64 / self.size.w as f64, 0.0)
+    }
+
+    fn y_transformed(&self, height: i32) -> f64 {
+        f64::max(self.y * height as f64 / self.size.h as f64, 0.0)
+    }
+
+    fn distance(&self) -> f64 {
+        0.0
+    }
+
+    fn distance_has_changed(&self) -> bool {
+        false
+    }
+
+    fn pressure(&self) -> f64 {
+        self.pressure
+    }
+
+    fn pressure_has_changed(&self) -> bool {
+        self.pressure_changed
+    }
+
+    fn slider_position(&self) -> f64 {
+        0.0
+    }
+
+    fn slider_has_changed(&self) -> bool {
+        false
+    }
+
+    fn tilt_x(&self) -> f64 {
+        self.tilt.0
+    }
+
+    fn tilt_x_has_changed(&self) -> bool {
+        self.tilt_changed.0
+    }
+
+    fn tilt_y(&self) -> f64 {
+        self.tilt.1
+    }
+
+    fn tilt_y_has_changed(&self) -> bool {
+        self.tilt_changed.1
+    }
+
+    fn rotation(&self) -> f64 {
+        0.0
+    }
+
+    fn rotation_has_changed(&self) -> bool {
+        false
+    }
+
+    fn wheel_delta(&self) -> f64 {
+        0.0
+    }
+
+    fn wheel_delta_discrete(&self) -> i32 {
+        0
+    }
+
+    fn wheel_has_changed(&self) -> bool {
+        false
+    }
+}
+
+impl TabletToolAxisEvent<X11Input> for X11TabletToolEvent {}
+
+/// Implements [`TabletToolEvent`] for a wrapper around [`X11TabletToolEvent`]
+macro_rules! delegate_tablet_tool_event {
+    ($ty:ty) => {
+        impl input::Event<X11Input> for $ty {
+            fn time(&self) -> u32 {
+                self.event.time
+            }
+
+            fn device(&self) -> X11VirtualDevice {
+                X11VirtualDevice
+            }
+        }
+
+        impl TabletToolEvent<X11Input> for $ty {
+            fn tool(&self) -> TabletToolDescriptor {
+                self.event.tool()
+            }
+            fn delta_x(&self) -> f64 {
+                self.event.delta_x()
+            }
+            fn delta_y(&self) -> f64 {
+                self.event.delta_y()
+            }
+            fn x(&self) -> f64 {
+                self.event.x()
+            }
+            fn y(&self) -> f64 {
+                self.event.y()
+            }
+            fn x_transformed(&self, width: i32) -> f64 {
+                self.event.x_transformed(width)
+            }
+            fn y_transformed(&self, height: i32) -> f64 {
+                self.event.y_transformed(height)
+            }
+            fn distance(&self) -> f64 {
+                self.event.distance()
+            }
+            fn distance_has_changed(&self) -> bool {
+                self.event.distance_has_changed()
+            }
+            fn pressure(&self) -> f64 {
+                self.event.pressure()
+            }
+            fn pressure_has_changed(&self) -> bool {
+                self.event.pressure_has_changed()
+            }
+            fn slider_position(&self) -> f64 {
+                self.event.slider_position()
+            }
+            fn slider_has_changed(&self) -> bool {
+                self.event.slider_has_changed()
+            }
+            fn tilt_x(&self) -> f64 {
+                self.event.tilt_x()
+            }
+            fn tilt_x_has_changed(&self) -> bool {
+                self.event.tilt_x_has_changed()
+            }
+            fn tilt_y(&self) -> f64 {
+                self.event.tilt_y()
+            }
+            fn tilt_y_has_changed(&self) -> bool {
+                self.event.tilt_y_has_changed()
+            }
+            fn rotation(&self) -> f64 {
+                self.event.rotation()
+            }
+            fn rotation_has_changed(&self) -> bool {
+                self.event.rotation_has_changed()
+            }
+            fn wheel_delta(&self) -> f64 {
+                self.event.wheel_delta()
+            }
+            fn wheel_delta_discrete(&self) -> i32 {
+                self.event.wheel_delta_discrete()
+            }
+            fn wheel_has_changed(&self) -> bool {
+                self.event.wheel_has_changed()
+            }
+        }
+    };
+}
+
+/// X11-Backend internal event of a tablet tool entering or leaving the window, see [`TabletToolProximityEvent`]
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct X11TabletToolProximityEvent {
+    pub(crate) event: X11TabletToolEvent,
+    pub(crate) state: ProximityState,
+}
+
+delegate_tablet_tool_event!(X11TabletToolProximityEvent);
+
+impl TabletToolProximityEvent<X11Input> for X11TabletToolProximityEvent {
+    fn state(&self) -> ProximityState {
+        self.state
+    }
+}
+
+/// X11-Backend internal event of a tablet tool touching the tablet, see [`TabletToolTipEvent`]
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct X11TabletToolTipEvent {
+    pub(crate) event: X11TabletToolEvent,
+    pub(crate) state: TabletToolTipState,
+}
+
+delegate_tablet_tool_event!(X11TabletToolTipEvent);
+
+impl TabletToolTipEvent<X11Input> for X11TabletToolTipEvent {
+    fn tip_state(&self) -> TabletToolTipState {
+        self.state
+    }
+}
+
+/// X11-Backend internal event of a button on a tablet tool, see [`TabletToolButtonEvent`]
+#[allow(missing_docs)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct X11TabletToolButtonEvent {
+    pub(crate) event: X11TabletToolEvent,
+    pub(crate) button: u32,
+    pub(crate) seat_button_count: u32,
+    pub(crate) state: ButtonState,
+}
+
+delegate_tablet_tool_event!(X11TabletToolButtonEvent);
+
+impl TabletToolButtonEvent<X11Input> for X11TabletToolButtonEvent {
+    fn button(&self) -> u32 {
+        self.button
+    }
+
+    fn seat_button_count(&self) -> u32 {
+        self.seat_button_count
+    }
+
+    fn button_state(&self) -> ButtonState {
+        self.state
+    }
+}
+
 impl InputBackend for X11Input {
     type EventError = X11Error;
 
@@ -189,7 +491,7 @@ impl InputBackend for X11Input {
     type PointerAxisEvent = X11MouseWheelEvent;
     type PointerButtonEvent = X11MouseInputEvent;
 
-    type PointerMotionEvent = UnusedEvent;
+    type PointerMotionEvent = X11RelativeMotionEvent;
 
     type PointerMotionAbsoluteEvent = X11MouseMovedEvent;
 
@@ -198,10 +500,10 @@ impl InputBackend for X11Input {
     type TouchMotionEvent = UnusedEvent;
     type TouchCancelEvent = UnusedEvent;
     type TouchFrameEvent = UnusedEvent;
-    type TabletToolAxisEvent = UnusedEvent;
-    type TabletToolProximityEvent = UnusedEvent;
-    type TabletToolTipEvent = UnusedEvent;
-    type TabletToolButtonEvent = UnusedEvent;
+    type TabletToolAxisEvent = X11TabletToolEvent;
+    type TabletToolProximityEvent = X11TabletToolProximityEvent;
+    type TabletToolTipEvent = X11TabletToolTipEvent;
+    type TabletToolButtonEvent = X11TabletToolButtonEvent;
 
     type SpecialEvent = UnusedEvent;
 
@@ -33,9 +33,18 @@
 //! }
 //! ```
 //!
+//! Additional windows may be opened using [`X11Backend::new_window`]. Events of all windows are
+//! emitted by the same backend along with the [`Window`] they belong to.
+//!
 //! ## EGL
 //!
 //! When using [`EGL`](crate::backend::egl), an [`X11Surface`] may be used to create an [`EGLDisplay`](crate::backend::egl::EGLDisplay).
+//!
+//! ## Software presentation
+//!
+//! If the X server is not capable of direct rendering, the [`X11Surface`] falls back to buffers in
+//! system memory, which cannot be used with EGL. Use [`X11Surface::is_hw_accelerated`] to check
+//! which kind of buffers is used.
 
 /*
 A note for future contributors and maintainers:
@@ -52,24 +61,31 @@ mod error;
 #[macro_use]
 mod extension;
 mod input;
+mod tablet;
 mod window_inner;
 
-use self::{buffer::PixmapWrapperExt, window_inner::WindowInner};
+use self::{buffer::PixmapWrapperExt, tablet::TabletTool, window_inner::WindowInner};
 use crate::{
     backend::{
-        allocator::dmabuf::{AsDmabuf, Dmabuf},
+        allocator::{
+            dmabuf::{AsDmabuf, Dmabuf},
+            gbm::GbmBuffer,
+            memory::MemoryBuffer,
+            Allocator, Format, Modifier,
+        },
         drm::{DrmNode, NodeType},
-        input::{Axis, ButtonState, InputEvent, KeyState},
+        input::{Axis, ButtonState, InputEvent, KeyState, ProximityState},
     },
-    utils::{x11rb::X11Source, Logical, Size},
+    utils::{x11rb::X11Source, Logical, Rectangle, Size},
 };
 use calloop::{EventSource, Poll, PostAction, Readiness, Token, TokenFactory};
 use drm_fourcc::DrmFourcc;
 use gbm::BufferObjectFlags;
 use nix::fcntl;
-use slog::{error, info, o, Logger};
+use slog::{error, info, o, warn, Logger};
 use std::{
-    io, mem,
+    collections::{HashMap, VecDeque},
+    io,
     os::unix::prelude::AsRawFd,
     sync::{
         atomic::{AtomicU32, Ordering},
@@ -83,15 +99,24 @@ use x11rb::{
     protocol::{
         self as x11,
         dri3::ConnectionExt as _,
-        xproto::{ColormapAlloc, ConnectionExt, Depth, PixmapWrapper, VisualClass},
+        randr::{ConnectionExt as _, ModeFlag, ModeInfo},
+        xinput::{ConnectionExt as _, Device, DeviceId, EventMask, Fp3232, RawMotionEvent, XIEventMask},
+        xkb::{self, ConnectionExt as _},
+        xproto::{
+            AtomEnum, ColormapAlloc, ConnectionExt, CreateGCAux, Depth, Gcontext, NotifyDetail, Pixmap,
+            PixmapWrapper, VisualClass,
+        },
         ErrorKind,
     },
-    rust_connection::{ReplyError, RustConnection},
+    rust_connection::{ConnectionError, ReplyError, RustConnection},
 };
 
+pub use self::buffer::CreatePixmapError;
 pub use self::error::*;
 use self::extension::Extensions;
 pub use self::input::*;
+#[cfg(feature = "wayland_frontend")]
+use crate::wayland::seat::XkbConfig;
 
 /// An event emitted by the X11 backend.
 #[derive(Debug)]
@@ -108,10 +133,20 @@ pub enum X11Event {
     /// The last buffer presented to the window has been displayed.
     ///
     /// When this event is scheduled, the next frame may be rendered.
-    PresentCompleted,
+    PresentCompleted {
+        /// Serial of the completed presentation, incremented with every presented buffer.
+        serial: u32,
+        /// Time in microseconds at which the buffer was displayed, as reported by the X server.
+        ust: u64,
+        /// Value of the media stream counter of the display at which the buffer was displayed.
+        msc: u64,
+    },
 
     /// The window has received a request to be closed.
     CloseRequested,
+
+    /// The window gained (`true`) or lost (`false`) keyboard focus.
+    Focus(bool),
 }
 
 /// Represents an active connection to the X to manage events on the Window provided by the backend.
@@ -122,10 +157,23 @@ pub struct X11Backend {
     source: X11Source,
     screen_number: usize,
     window: Arc<WindowInner>,
-    resize: Sender<Size<u16, Logical>>,
+    windows: HashMap<x11::Window, WindowEntry>,
     key_counter: Arc<AtomicU32>,
     depth: Depth,
     visual_id: u32,
+    colormap: u32,
+    tablet_tools: HashMap<DeviceId, TabletTool>,
+    tablet_buttons: HashMap<u32, u32>,
+    announce_tablet: bool,
+}
+
+/// A window managed by the backend and the channels used to notify its surface about resizes and
+/// pixmaps released by the X server.
+#[derive(Debug)]
+struct WindowEntry {
+    inner: Arc<WindowInner>,
+    resize: Sender<Size<u16, Logical>>,
+    idle: Sender<Pixmap>,
 }
 
 atom_manager! {
@@ -133,8 +181,21 @@ atom_manager! {
         WM_PROTOCOLS,
         WM_DELETE_WINDOW,
         _NET_WM_NAME,
+        _NET_WM_WINDOW_TYPE,
+        _NET_WM_WINDOW_TYPE_DESKTOP,
+        _NET_WM_WINDOW_TYPE_DOCK,
+        _NET_WM_WINDOW_TYPE_TOOLBAR,
+        _NET_WM_WINDOW_TYPE_MENU,
+        _NET_WM_WINDOW_TYPE_UTILITY,
+        _NET_WM_WINDOW_TYPE_SPLASH,
+        _NET_WM_WINDOW_TYPE_DIALOG,
+        _NET_WM_WINDOW_TYPE_NORMAL,
         UTF8_STRING,
+        _XKB_RULES_NAMES,
         _SMITHAY_X11_BACKEND_CLOSE,
+        ABS_PRESSURE: b"Abs Pressure",
+        ABS_TILT_X: b"Abs Tilt X",
+        ABS_TILT_Y: b"Abs Tilt Y",
     }
 }
 
@@ -246,25 +307,102 @@ impl X11Backend {
 
         info!(logger, "Window created");
 
+        let tablet_tools = if window.extensions.xinput.is_some() {
+            tablet::init_tablet_tools(&*connection, &window.atoms, &logger)?
+        } else {
+            HashMap::new()
+        };
+        tablet::select_tablet_events(&*connection, window.id, &tablet_tools)?;
+
+        if window.extensions.xinput.is_some() {
+            // Raw events are only delivered to the root window.
+            connection.xinput_xi_select_events(
+                screen.root,
+                &[EventMask {
+                    deviceid: Device::ALL_MASTER.into(),
+                    mask: vec![XIEventMask::RAW_MOTION.into()],
+                }],
+            )?;
+        }
+
         let (resize_send, resize_recv) = mpsc::channel();
+        let (idle_send, idle_recv) = mpsc::channel();
+
+        let mut windows = HashMap::new();
+        windows.insert(
+            window.id,
+            WindowEntry {
+                inner: window.clone(),
+                resize: resize_send,
+                idle: idle_send,
+            },
+        );
 
         let backend = X11Backend {
             log: logger,
             source,
             connection,
-            window,
+            window: window.clone(),
+            windows,
             key_counter: Arc::new(AtomicU32::new(0)),
             depth,
             visual_id,
+            colormap,
             screen_number,
-            resize: resize_send,
+            announce_tablet: !tablet_tools.is_empty(),
+            tablet_tools,
+            tablet_buttons: HashMap::new(),
         };
 
-        let surface = X11Surface::new(&backend, format, resize_recv)?;
+        let surface = X11Surface::new(&backend, &window, format, resize_recv, idle_recv)?;
 
         Ok((backend, surface))
     }
 
+    /// Creates an additional window using the connection of the backend.
+    ///
+    /// The events of all windows are emitted by the backend, the [`Window`] passed as metadata along
+    /// with an event tells which window the event belongs to. The window is destroyed when the backend
+    /// is dropped.
+    pub fn new_window(
+        &mut self,
+        size: Size<u16, Logical>,
+        title: &str,
+    ) -> Result<(Window, X11Surface), X11Error> {
+        let screen = &self.connection.setup().roots[self.screen_number];
+
+        let window = Arc::new(WindowInner::new(
+            Arc::downgrade(&self.connection),
+            screen,
+            size,
+            title,
+            self.window.format,
+            self.window.atoms,
+            self.depth.clone(),
+            self.visual_id,
+            self.colormap,
+            self.window.extensions,
+        )?);
+        tablet::select_tablet_events(&*self.connection, window.id, &self.tablet_tools)?;
+
+        info!(self.log, "Window {} created", window.id);
+
+        let (resize_send, resize_recv) = mpsc::channel();
+        let (idle_send, idle_recv) = mpsc::channel();
+        self.windows.insert(
+            window.id,
+            WindowEntry {
+                inner: window.clone(),
+                resize: resize_send,
+                idle: idle_send,
+            },
+        );
+
+        let surface = X11Surface::new(self, &window, window.format, resize_recv, idle_recv)?;
+
+        Ok((window.into(), surface))
+    }
+
     /// Returns the default screen number of the X server.
     pub fn screen(&self) -> usize {
         self.screen_number
@@ -275,34 +413,455 @@ impl X11Backend {
         &*self.connection
     }
 
-    /// Returns a handle to the X11 window created by the backend.
+    /// Returns a handle to the primary X11 window created along with the backend.
     pub fn window(&self) -> Window {
         self.window.clone().into()
     }
+
+    /// Returns the keyboard configuration of the X server.
+    ///
+    /// The names are read from the `_XKB_RULES_NAMES` property of the root window, which is
+    /// maintained by the X server and tools like `setxkbmap`. If the property does not name a layout,
+    /// the layouts and variants are derived from the symbols of the core keyboard, as reported by the
+    /// XKB extension. If neither is available, the returned names are empty and the xkbcommon
+    /// defaults are used.
+    ///
+    /// Use [`XkbNames::config`] to initialize the keyboard of a seat, so that it matches the layout
+    /// of the host session.
+    #[cfg(feature = "wayland_frontend")]
+    pub fn xkb_config(&self) -> Result<XkbNames, X11Error> {
+        let screen = &self.connection.setup().roots[self.screen_number];
+        let reply = self
+            .connection
+            .get_property(
+                false,
+                screen.root,
+                self.window.atoms._XKB_RULES_NAMES,
+                AtomEnum::STRING,
+                0,
+                1024,
+            )?
+            .reply()?;
+
+        let mut names = XkbNames::parse(&reply.value);
+
+        if names.layout.is_empty() {
+            if let Some(symbols) = self.xkb_symbols()? {
+                let derived = XkbNames::from_symbols(&symbols);
+                names.layout = derived.layout;
+                names.variant = derived.variant;
+            }
+        }
+
+        Ok(names)
+    }
+
+    /// Returns the name of the symbols of the core keyboard, if the XKB extension is available.
+    #[cfg(feature = "wayland_frontend")]
+    fn xkb_symbols(&self) -> Result<Option<String>, X11Error> {
+        match self.connection.xkb_use_extension(1, 0) {
+            Ok(cookie) => {
+                if !cookie.reply()?.supported {
+                    return Ok(None);
+                }
+            }
+            Err(ConnectionError::UnsupportedExtension) => return Ok(None),
+            Err(err) => return Err(err.into()),
+        }
+
+        let names = self
+            .connection
+            .xkb_get_names(xkb::ID::USE_CORE_KBD.into(), xkb::NameDetail::SYMBOLS)?
+            .reply()?;
+
+        match names.value_list.symbols_name {
+            Some(atom) if atom != x11rb::NONE => {
+                let name = self.connection.get_atom_name(atom)?.reply()?.name;
+                Ok(Some(String::from_utf8_lossy(&name).into_owned()))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the refresh rate of the monitor the window is shown on, in mHz.
+    ///
+    /// The refresh rate is queried using the RandR extension. If the extension is not available, the
+    /// window spans multiple monitors or the query fails, [`DEFAULT_REFRESH_RATE`] is returned.
+    pub fn refresh_rate(&self) -> u32 {
+        match self.query_refresh_rate() {
+            Ok(Some(refresh_rate)) => refresh_rate,
+            Ok(None) => DEFAULT_REFRESH_RATE,
+            Err(err) => {
+                warn!(
+                    self.log,
+                    "Failed to query the refresh rate of the monitor: {}", err
+                );
+                DEFAULT_REFRESH_RATE
+            }
+        }
+    }
+
+    fn query_refresh_rate(&self) -> Result<Option<u32>, X11Error> {
+        if self.window.extensions.randr.is_none() {
+            return Ok(None);
+        }
+
+        let screen = &self.connection.setup().roots[self.screen_number];
+        let position = self
+            .connection
+            .translate_coordinates(self.window.id, screen.root, 0, 0)?
+            .reply()?;
+        let size = self.window.size();
+        let window = Rectangle::<i32, Logical>::from_loc_and_size(
+            (position.dst_x as i32, position.dst_y as i32),
+            (size.w as i32, size.h as i32),
+        );
+
+        let resources = self
+            .connection
+            .randr_get_screen_resources_current(screen.root)?
+            .reply()?;
+
+        let mut modes = Vec::new();
+        for &crtc in &resources.crtcs {
+            let info = self
+                .connection
+                .randr_get_crtc_info(crtc, resources.config_timestamp)?
+                .reply()?;
+
+            // Disabled crtcs have no mode
+            if info.mode == 0 {
+                continue;
+            }
+
+            let geometry = Rectangle::from_loc_and_size(
+                (info.x as i32, info.y as i32),
+                (info.width as i32, info.height as i32),
+            );
+
+            if geometry.overlaps(window) {
+                modes.push(info.mode);
+            }
+        }
+
+        match modes[..] {
+            [mode] => Ok(resources
+                .modes
+                .iter()
+                .find(|info| info.id == mode)
+                .and_then(mode_refresh_rate)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// The refresh rate reported by [`X11Backend::refresh_rate`] if the actual refresh rate is unknown, in mHz.
+pub const DEFAULT_REFRESH_RATE: u32 = 60_000;
+
+/// Calculates the refresh rate of a RandR mode in mHz.
+fn mode_refresh_rate(mode: &ModeInfo) -> Option<u32> {
+    let mut vtotal = mode.vtotal as u64;
+
+    if mode.mode_flags & u32::from(ModeFlag::DOUBLE_SCAN) != 0 {
+        vtotal *= 2;
+    }
+    if mode.mode_flags & u32::from(ModeFlag::INTERLACE) != 0 {
+        vtotal /= 2;
+    }
+
+    let pixels = mode.htotal as u64 * vtotal;
+    if pixels == 0 {
+        return None;
+    }
+
+    Some(((mode.dot_clock as u64 * 1000 + pixels / 2) / pixels) as u32)
+}
+
+/// The xkb rule names of the X server, as returned by [`X11Backend::xkb_config`].
+///
+/// Multiple layouts, variants and options are separated by commas.
+#[cfg(feature = "wayland_frontend")]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct XkbNames {
+    /// The rules file
+    pub rules: String,
+    /// The keyboard model
+    pub model: String,
+    /// The layouts
+    pub layout: String,
+    /// The variants of the layouts
+    pub variant: String,
+    /// The options
+    pub options: String,
+}
+
+#[cfg(feature = "wayland_frontend")]
+impl XkbNames {
+    /// Parses the value of the `_XKB_RULES_NAMES` property, which contains the names separated by
+    /// null bytes.
+    fn parse(value: &[u8]) -> XkbNames {
+        let mut names = value
+            .split(|&byte| byte == 0)
+            .map(|name| String::from_utf8_lossy(name).into_owned());
+
+        XkbNames {
+            rules: names.next().unwrap_or_default(),
+            model: names.next().unwrap_or_default(),
+            layout: names.next().unwrap_or_default(),
+            variant: names.next().unwrap_or_default(),
+            options: names.next().unwrap_or_default(),
+        }
+    }
+
+    /// Derives the layouts and variants from the name of the xkb symbols, e.g.
+    /// `pc+us+de(nodeadkeys):2+inet(evdev)`.
+    ///
+    /// The first layout directly follows the base symbols, the other ones are suffixed with their
+    /// group index. Symbols without a group index after the first layout come from the model or the
+    /// options and are skipped.
+    fn from_symbols(symbols: &str) -> XkbNames {
+        let mut layouts = Vec::new();
+        let mut variants = Vec::new();
+
+        for (index, part) in symbols.split('+').enumerate().skip(1) {
+            let (part, has_group) = match part.find(':') {
+                Some(colon) => (&part[..colon], true),
+                None => (part, false),
+            };
+            if index > 1 && !has_group {
+                continue;
+            }
+
+            let (layout, variant) = match part.find('(') {
+                Some(paren) => (&part[..paren], part[paren + 1..].trim_end_matches(')')),
+                None => (part, ""),
+            };
+            layouts.push(layout);
+            variants.push(variant);
+        }
+
+        XkbNames {
+            layout: layouts.join(","),
+            variant: if variants.iter().all(|variant| variant.is_empty()) {
+                String::new()
+            } else {
+                variants.join(",")
+            },
+            ..XkbNames::default()
+        }
+    }
+
+    /// Returns the names as an [`XkbConfig`], e.g. for [`Seat::add_keyboard`](crate::wayland::seat::Seat::add_keyboard).
+    pub fn config(&self) -> XkbConfig<'_> {
+        XkbConfig {
+            rules: &self.rules,
+            model: &self.model,
+            layout: &self.layout,
+            variant: &self.variant,
+            options: if self.options.is_empty() {
+                None
+            } else {
+                Some(self.options.clone())
+            },
+        }
+    }
 }
 
 /// An X11 surface which uses GBM to allocate and present buffers.
+///
+/// If the X server is not capable of direct rendering (e.g. when the DRI3 extension is missing,
+/// like with X forwarding or inside of Xvfb), buffers in system memory are used instead. See
+/// [`X11Surface::is_hw_accelerated`].
 #[derive(Debug)]
 pub struct X11Surface {
     connection: Weak<RustConnection>,
     window: Window,
     resize: Receiver<Size<u16, Logical>>,
-    device: gbm::Device<DrmNode>,
+    idle: Receiver<Pixmap>,
     format: DrmFourcc,
     width: u16,
     height: u16,
-    current: Dmabuf,
-    next: Dmabuf,
+    buffers: Buffers,
+    buffer_count: usize,
+    present_mode: PresentMode,
+    /// Number of frames presented to the window.
+    frame: u64,
+    present_error: Option<X11PresentError>,
+}
+
+fn allocate_dmabuf(
+    device: &mut gbm::Device<DrmNode>,
+    size: Size<u16, Logical>,
+    format: DrmFourcc,
+    modifiers: &[Modifier],
+) -> Result<Dmabuf, AllocateBuffersError> {
+    let buffer_object = if modifiers.iter().all(|modifier| *modifier == Modifier::Invalid) {
+        device.create_buffer_object::<()>(size.w as u32, size.h as u32, format, BufferObjectFlags::empty())?
+    } else {
+        Allocator::<GbmBuffer<()>>::create_buffer(device, size.w as u32, size.h as u32, format, modifiers)?
+    };
+
+    Ok(buffer_object.export()?)
+}
+
+/// Number of buffers used by an [`X11Surface`] by default.
+const BUFFER_COUNT: usize = 2;
+
+/// Number of buffers used by an [`X11Surface`] with triple buffering enabled.
+const TRIPLE_BUFFER_COUNT: usize = 3;
+
+/// A buffer of a surface and the state of its last presentation.
+#[derive(Debug)]
+struct Slot<B> {
+    buffer: B,
+    /// The frame the contents of the buffer were presented in.
+    frame: Option<u64>,
+    /// The pixmap the buffer was presented with, until the X server notified that it is idle.
+    pixmap: Option<Pixmap>,
+}
+
+impl<B> Slot<B> {
+    fn new(buffer: B) -> Slot<B> {
+        Slot {
+            buffer,
+            frame: None,
+            pixmap: None,
+        }
+    }
+}
+
+/// The buffers of a surface, ordered from the least to the most recently presented buffer.
+///
+/// The front buffer is the next buffer to render into.
+#[derive(Debug)]
+enum Buffers {
+    Dmabuf {
+        device: gbm::Device<DrmNode>,
+        modifiers: Vec<Modifier>,
+        buffers: VecDeque<Slot<Dmabuf>>,
+    },
+    Memory {
+        gc: Gcontext,
+        buffers: VecDeque<Slot<MemoryBuffer>>,
+    },
+}
+
+fn allocate_dmabufs(
+    device: &mut gbm::Device<DrmNode>,
+    size: Size<u16, Logical>,
+    format: DrmFourcc,
+    modifiers: &[Modifier],
+    count: usize,
+) -> Result<VecDeque<Slot<Dmabuf>>, AllocateBuffersError> {
+    (0..count)
+        .map(|_| allocate_dmabuf(device, size, format, modifiers).map(Slot::new))
+        .collect()
+}
+
+fn allocate_memory_buffers(
+    size: Size<u16, Logical>,
+    format: DrmFourcc,
+    count: usize,
+) -> VecDeque<Slot<MemoryBuffer>> {
+    (0..count)
+        .map(|_| Slot::new(MemoryBuffer::new((size.w as i32, size.h as i32), format)))
+        .collect()
+}
+
+/// Marks the buffers presented with the given pixmap as idle and moves the least recently presented
+/// idle buffer to the front.
+///
+/// Returns `false` if all buffers are still used by the X server.
+fn select_idle_buffer<B>(buffers: &mut VecDeque<Slot<B>>, idle: &[Pixmap]) -> bool {
+    for slot in buffers.iter_mut() {
+        if slot.pixmap.map(|pixmap| idle.contains(&pixmap)).unwrap_or(false) {
+            slot.pixmap = None;
+        }
+    }
+
+    match buffers.iter().position(|slot| slot.pixmap.is_none()) {
+        Some(index) => {
+            let slot = buffers.remove(index).unwrap();
+            buffers.push_front(slot);
+            true
+        }
+        None => false,
+    }
 }
 
 impl X11Surface {
     fn new(
         backend: &X11Backend,
+        window: &Arc<WindowInner>,
         format: DrmFourcc,
         resize: Receiver<Size<u16, Logical>>,
+        idle: Receiver<Pixmap>,
     ) -> Result<X11Surface, X11Error> {
         let connection = &backend.connection;
-        let window = backend.window();
+        let size = window.size();
+
+        let device = if window.extensions.dri3.is_some() {
+            match Self::open_device(backend) {
+                Ok(device) => Some(device),
+                Err(X11Error::CannotDirectRender) => {
+                    slog::warn!(
+                        backend.log,
+                        "The X server is not capable of direct rendering, falling back to memory buffers"
+                    );
+                    None
+                }
+                Err(err) => return Err(err),
+            }
+        } else {
+            slog::warn!(
+                backend.log,
+                "DRI3 is not available, falling back to memory buffers"
+            );
+            None
+        };
+
+        let buffers = match device {
+            Some(mut device) => {
+                // Use an implicit modifier unless other modifiers were negotiated.
+                let modifiers = vec![Modifier::Invalid];
+                let buffers = allocate_dmabufs(&mut device, size, format, &modifiers, BUFFER_COUNT)?;
+
+                Buffers::Dmabuf {
+                    device,
+                    modifiers,
+                    buffers,
+                }
+            }
+
+            None => {
+                let gc = connection.generate_id()?;
+                connection.create_gc(gc, window.id, &CreateGCAux::new())?;
+
+                Buffers::Memory {
+                    gc,
+                    buffers: allocate_memory_buffers(size, format, BUFFER_COUNT),
+                }
+            }
+        };
+
+        Ok(X11Surface {
+            connection: Arc::downgrade(connection),
+            window: window.clone().into(),
+            format,
+            width: size.w,
+            height: size.h,
+            buffers,
+            buffer_count: BUFFER_COUNT,
+            present_mode: PresentMode::default(),
+            frame: 0,
+            present_error: None,
+            resize,
+            idle,
+        })
+    }
+
+    fn open_device(backend: &X11Backend) -> Result<gbm::Device<DrmNode>, X11Error> {
+        let connection = &backend.connection;
 
         // Determine which drm-device the Display is using.
         let screen = &connection.setup().roots[backend.screen()];
@@ -360,7 +919,7 @@ impl X11Surface {
                 match DrmNode::from_node_with_type(drm_node, NodeType::Render) {
                     Ok(node) => node,
                     Err(err) => {
-                        slog::warn!(&backend.log, "Could not create render node from existing DRM node, falling back to primary node");
+                        slog::warn!(&backend.log, "Could not create render node from existing DRM node ({}), falling back to primary node", err);
                         err.node()
                     }
                 }
@@ -375,38 +934,35 @@ impl X11Surface {
             drm_node
         };
 
+        info!(
+            backend.log,
+            "Allocating buffers using DRM node {} ({:?})",
+            drm_node,
+            drm_node.dev_path()
+        );
+
         // Finally create a GBMDevice to manage the buffers.
         let device = gbm::Device::new(drm_node).map_err(Into::<AllocateBuffersError>::into)?;
 
-        let size = backend.window().size();
-        let current = device
-            .create_buffer_object::<()>(size.w as u32, size.h as u32, format, BufferObjectFlags::empty())
-            .map_err(Into::<AllocateBuffersError>::into)?
-            .export()
-            .map_err(Into::<AllocateBuffersError>::into)?;
-
-        let next = device
-            .create_buffer_object::<()>(size.w as u32, size.h as u32, format, BufferObjectFlags::empty())
-            .map_err(Into::<AllocateBuffersError>::into)?
-            .export()
-            .map_err(Into::<AllocateBuffersError>::into)?;
-
-        Ok(X11Surface {
-            connection: Arc::downgrade(connection),
-            window,
-            device,
-            format,
-            width: size.w,
-            height: size.h,
-            current,
-            next,
-            resize,
-        })
+        Ok(device)
     }
 
     /// Returns a handle to the GBM device used to allocate buffers.
-    pub fn device(&self) -> &gbm::Device<DrmNode> {
-        &self.device
+    ///
+    /// Returns `None` if the surface is not hardware accelerated.
+    pub fn device(&self) -> Option<&gbm::Device<DrmNode>> {
+        match self.buffers {
+            Buffers::Dmabuf { ref device, .. } => Some(device),
+            Buffers::Memory { .. } => None,
+        }
+    }
+
+    /// Returns whether the surface uses hardware accelerated buffers.
+    ///
+    /// If this returns `false`, the X server is not capable of direct rendering and buffers in
+    /// system memory are used instead, which may be accessed through [`Present::memory_buffer`].
+    pub fn is_hw_accelerated(&self) -> bool {
+        matches!(self.buffers, Buffers::Dmabuf { .. })
     }
 
     /// Returns the format of the buffers the surface accepts.
@@ -414,47 +970,185 @@ impl X11Surface {
         self.format
     }
 
+    /// Restricts the modifiers used to allocate buffers to the given formats and reallocates the buffers.
+    ///
+    /// Only formats with the code returned by [`X11Surface::format`] are considered. Of those, the
+    /// modifiers supported by the X server for the window are used, which requires version 1.2 of the
+    /// DRI3 extension. If no modifier remains, buffers are allocated using an implicit modifier, like
+    /// they are by default.
+    ///
+    /// Returns the modifiers now used for allocation, which is empty if the surface is not hardware
+    /// accelerated.
+    pub fn set_formats(
+        &mut self,
+        formats: impl IntoIterator<Item = Format>,
+    ) -> Result<Vec<Modifier>, X11Error> {
+        let connection = match self.connection.upgrade() {
+            Some(connection) => connection,
+            None => return Ok(Vec::new()),
+        };
+
+        if !self.is_hw_accelerated() {
+            return Ok(Vec::new());
+        }
+
+        let mut modifiers = formats
+            .into_iter()
+            .filter(|format| format.code == self.format)
+            .map(|format| format.modifier)
+            .collect::<Vec<_>>();
+
+        let dri3 = self.window.0.upgrade().and_then(|inner| inner.extensions.dri3);
+        if dri3 >= Some((1, 2)) {
+            // both formats use 32 bits per pixel, Xrgb8888 just ignores the alpha channel
+            let bpp = match self.format {
+                DrmFourcc::Argb8888 | DrmFourcc::Xrgb8888 => 32,
+                format => return Err(AllocateBuffersError::UnsupportedFormat(format).into()),
+            };
+
+            let supported = connection
+                .dri3_get_supported_modifiers(self.window.id(), self.window.depth(), bpp)?
+                .reply()?;
+
+            modifiers.retain(|modifier| {
+                let modifier = u64::from(*modifier);
+                supported.window_modifiers.contains(&modifier)
+                    || supported.screen_modifiers.contains(&modifier)
+            });
+        } else {
+            // Without DRI3 1.2 pixmaps may only be created from buffers with an implicit modifier.
+            modifiers.clear();
+        }
+
+        if modifiers.is_empty() {
+            modifiers.push(Modifier::Invalid);
+        }
+
+        if let Buffers::Dmabuf {
+            modifiers: ref mut current_modifiers,
+            ..
+        } = self.buffers
+        {
+            *current_modifiers = modifiers.clone();
+        }
+
+        self.resize((self.width, self.height).into())?;
+
+        Ok(modifiers)
+    }
+
+    /// Enables or disables triple buffering and reallocates the buffers.
+    ///
+    /// By default the surface is double buffered. With triple buffering, the surface cycles through
+    /// three buffers instead of two, always handing out the least recently presented buffer the X server
+    /// no longer uses. This allows rendering the next frame while the previous frame is still being
+    /// presented, at the cost of the memory of an additional buffer.
+    pub fn set_triple_buffering(&mut self, enabled: bool) -> Result<(), AllocateBuffersError> {
+        let buffer_count = if enabled {
+            TRIPLE_BUFFER_COUNT
+        } else {
+            BUFFER_COUNT
+        };
+
+        if buffer_count != self.buffer_count {
+            self.buffer_count = buffer_count;
+            self.resize((self.width, self.height).into())?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns whether the surface is triple buffered, see [`X11Surface::set_triple_buffering`].
+    pub fn is_triple_buffered(&self) -> bool {
+        self.buffer_count == TRIPLE_BUFFER_COUNT
+    }
+
+    /// Returns the mode used to present the buffers of the surface.
+    pub fn present_mode(&self) -> PresentMode {
+        self.present_mode
+    }
+
+    /// Sets the mode used to present the buffers of the surface, starting with the next presented buffer.
+    ///
+    /// The mode may be changed for every frame, e.g. to honor the tearing hints of the client shown in
+    /// fullscreen. It only applies to hardware accelerated surfaces, the contents of memory buffers are
+    /// always copied to the window immediately.
+    pub fn set_present_mode(&mut self, mode: PresentMode) {
+        self.present_mode = mode;
+    }
+
     /// Returns an RAII scoped object which provides the next buffer.
     ///
+    /// The next buffer is the least recently presented buffer the X server no longer uses. If all
+    /// buffers are still in use, [`X11PresentError::NoFreeBuffer`] is returned instead of blocking:
+    /// the X server notifies that a buffer is idle through an event, which is only received when the
+    /// event loop dispatches the [`X11Backend`], so waiting for it here would never return.
+    ///
     /// When the object is dropped, the contents of the buffer are swapped and then presented.
-    pub fn present(&mut self) -> Result<Present<'_>, AllocateBuffersError> {
+    /// Errors during presentation are stored and may be retrieved using [`X11Surface::take_present_error`].
+    pub fn present(&mut self) -> Result<Present<'_>, X11PresentError> {
+        if self.connection.strong_count() == 0 {
+            return Err(X11PresentError::ConnectionLost);
+        }
+
         if let Some(new_size) = self.resize.try_iter().last() {
             self.resize(new_size)?;
         }
 
+        let idle = self.idle.try_iter().collect::<Vec<_>>();
+        let found = match self.buffers {
+            Buffers::Dmabuf { ref mut buffers, .. } => select_idle_buffer(buffers, &idle),
+            Buffers::Memory { ref mut buffers, .. } => select_idle_buffer(buffers, &idle),
+        };
+        if !found {
+            return Err(X11PresentError::NoFreeBuffer);
+        }
+
         Ok(Present { surface: self })
     }
 
+    /// Returns the error which occurred while presenting the last buffer, if presenting it failed.
+    ///
+    /// The error is cleared by calling this function.
+    pub fn take_present_error(&mut self) -> Option<X11PresentError> {
+        self.present_error.take()
+    }
+
     fn resize(&mut self, size: Size<u16, Logical>) -> Result<(), AllocateBuffersError> {
-        let current = self
-            .device
-            .create_buffer_object::<()>(
-                size.w as u32,
-                size.h as u32,
-                self.format,
-                BufferObjectFlags::empty(),
-            )?
-            .export()?;
-
-        let next = self
-            .device
-            .create_buffer_object::<()>(
-                size.w as u32,
-                size.h as u32,
-                self.format,
-                BufferObjectFlags::empty(),
-            )?
-            .export()?;
+        let format = self.format;
+        let count = self.buffer_count;
+
+        match self.buffers {
+            Buffers::Dmabuf {
+                ref mut device,
+                ref modifiers,
+                ref mut buffers,
+            } => {
+                *buffers = allocate_dmabufs(device, size, format, modifiers, count)?;
+            }
+
+            Buffers::Memory { ref mut buffers, .. } => {
+                *buffers = allocate_memory_buffers(size, format, count);
+            }
+        }
 
         self.width = size.w;
         self.height = size.h;
-        self.current = current;
-        self.next = next;
 
         Ok(())
     }
 }
 
+impl Drop for X11Surface {
+    fn drop(&mut self) {
+        if let Buffers::Memory { gc, .. } = self.buffers {
+            if let Some(connection) = self.connection.upgrade() {
+                let _ = connection.free_gc(gc);
+            }
+        }
+    }
+}
+
 /// An RAII scope containing the next buffer that will be presented to the window. Presentation
 /// occurs when the `Present` is dropped.
 ///
@@ -466,7 +1160,7 @@ impl X11Surface {
 /// let present = surface.present()?;
 ///
 /// // Bind the buffer to the renderer in order to render.
-/// renderer.bind(present.buffer())?;
+/// renderer.bind(present.buffer().unwrap())?;
 ///
 /// // Rendering here!
 ///
@@ -484,30 +1178,143 @@ impl Present<'_> {
     /// Returns the next buffer that will be presented to the Window.
     ///
     /// You may bind this buffer to a renderer to render.
-    pub fn buffer(&self) -> Dmabuf {
-        self.surface.next.clone()
+    ///
+    /// Returns `None` if the surface is not hardware accelerated, see [`Present::memory_buffer`].
+    pub fn buffer(&self) -> Option<Dmabuf> {
+        match self.surface.buffers {
+            Buffers::Dmabuf { ref buffers, .. } => buffers.front().map(|slot| slot.buffer.clone()),
+            Buffers::Memory { .. } => None,
+        }
+    }
+
+    /// Returns the next buffer in system memory that will be presented to the Window.
+    ///
+    /// Returns `None` if the surface is hardware accelerated, see [`Present::buffer`].
+    pub fn memory_buffer(&mut self) -> Option<&mut MemoryBuffer> {
+        match self.surface.buffers {
+            Buffers::Memory { ref mut buffers, .. } => buffers.front_mut().map(|slot| &mut slot.buffer),
+            Buffers::Dmabuf { .. } => None,
+        }
+    }
+
+    /// Returns the age of the next buffer.
+    ///
+    /// The age is the number of frames ago the contents of the buffer were presented, or `0` if the
+    /// contents of the buffer are undefined, e.g. after the window was resized. Damage of the last
+    /// `age` frames needs to be redrawn to bring the buffer up to date.
+    pub fn buffer_age(&self) -> usize {
+        let frame = match self.surface.buffers {
+            Buffers::Dmabuf { ref buffers, .. } => buffers.front().and_then(|slot| slot.frame),
+            Buffers::Memory { ref buffers, .. } => buffers.front().and_then(|slot| slot.frame),
+        };
+        // the next frame is `self.surface.frame + 1`
+        frame
+            .map(|frame| (self.surface.frame + 1 - frame) as usize)
+            .unwrap_or(0)
     }
 }
 
-impl Drop for Present<'_> {
-    fn drop(&mut self) {
+impl Present<'_> {
+    fn present(&mut self) -> Result<(), X11PresentError> {
         let surface = &mut self.surface;
+        let connection = surface
+            .connection
+            .upgrade()
+            .ok_or(X11PresentError::ConnectionLost)?;
+
+        let frame = surface.frame + 1;
 
-        if let Some(connection) = surface.connection.upgrade() {
-            // Swap the buffers
-            mem::swap(&mut surface.next, &mut surface.current);
+        match surface.buffers {
+            Buffers::Dmabuf { ref mut buffers, .. } => {
+                // The rendered buffer becomes the most recently presented one
+                buffers.rotate_left(1);
 
-            if let Ok(pixmap) = PixmapWrapper::with_dmabuf(&*connection, &surface.window, &surface.current) {
-                // Now present the current buffer
-                let _ = pixmap.present(&*connection, &surface.window);
+                // Now present the buffer
+                let slot = buffers.back_mut().expect("X11Surface without buffers");
+                let pixmap = PixmapWrapper::with_dmabuf(&*connection, &surface.window, &slot.buffer)?;
+                // The buffer may not be rendered to until the X server notifies that the pixmap is idle
+                slot.pixmap = Some(pixmap.present(&*connection, &surface.window, surface.present_mode)?);
+                slot.frame = Some(frame);
             }
 
-            // Flush the connection after presenting to the window to ensure we don't run out of buffer space in the X11 connection.
-            let _ = connection.flush();
+            Buffers::Memory { gc, ref mut buffers } => {
+                // The rendered buffer becomes the most recently presented one
+                buffers.rotate_left(1);
+
+                // The contents are copied, so the buffer may be reused right away
+                let slot = buffers.back_mut().expect("X11Surface without buffers");
+                buffer::put_memory_buffer(&*connection, &surface.window, gc, &slot.buffer)?;
+                slot.frame = Some(frame);
+            }
+        }
+
+        surface.frame = frame;
+
+        // Flush the connection after presenting to the window to ensure we don't run out of buffer space in the X11 connection.
+        connection.flush()?;
+
+        Ok(())
+    }
+}
+
+impl Drop for Present<'_> {
+    fn drop(&mut self) {
+        let result = self.present();
+        self.surface.present_error = result.err();
+
+        // Resizes received while the buffer was in use were deferred until it was presented.
+        if let Some(new_size) = self.surface.resize.try_iter().last() {
+            if let Err(err) = self.surface.resize(new_size) {
+                self.surface.present_error.get_or_insert(err.into());
+            }
         }
     }
 }
 
+/// The mode used to present the buffers of an [`X11Surface`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PresentMode {
+    /// Every buffer is shown for at least one vblank, in the order they were presented
+    ///
+    /// Presenting faster than the refresh rate of the monitor increases the latency.
+    Fifo,
+    /// The buffer is shown on the next vblank, replacing buffers presented earlier for the same vblank
+    ///
+    /// This is the default, it never tears and keeps the latency low.
+    Mailbox,
+    /// The buffer is shown immediately, which may cause tearing
+    Immediate,
+}
+
+impl Default for PresentMode {
+    fn default() -> Self {
+        PresentMode::Mailbox
+    }
+}
+
+/// The functional type of a window, as described by the `_NET_WM_WINDOW_TYPE` property.
+///
+/// The window manager of the host uses the type to decide how to decorate, place and stack the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WindowType {
+    /// A desktop feature, covering the whole screen below all other windows
+    Desktop,
+    /// A dock or panel
+    Dock,
+    /// A toolbar torn off from the main window
+    Toolbar,
+    /// A pinnable menu torn off from the main window
+    Menu,
+    /// A small persistent utility window, such as a palette or toolbox
+    Utility,
+    /// A splash screen displayed while an application is starting up
+    Splash,
+    /// A dialog window
+    Dialog,
+    /// A normal, top-level window
+    Normal,
+}
+
 /// An X11 window.
 #[derive(Debug)]
 pub struct Window(Weak<WindowInner>);
@@ -520,6 +1327,15 @@ impl Window {
         }
     }
 
+    /// Sets the type of the window, which tells the window manager how to treat the window.
+    ///
+    /// Window managers treat windows without a type as [`WindowType::Normal`].
+    pub fn set_window_type(&self, window_type: WindowType) {
+        if let Some(inner) = self.0.upgrade() {
+            inner.set_window_type(window_type);
+        }
+    }
+
     /// Maps the window, making it visible.
     pub fn map(&self) {
         if let Some(inner) = self.0.upgrade() {
@@ -544,6 +1360,46 @@ impl Window {
             .unwrap_or_else(|| (0, 0).into())
     }
 
+    /// Requests the window to be resized.
+    ///
+    /// The window manager may refuse or alter the request, an [`X11Event::Resized`] is emitted once
+    /// the window has actually changed size. The buffers of the [`X11Surface`] are not reallocated
+    /// while a [`Present`] is alive, the resize is deferred until it is dropped and its buffer was
+    /// presented.
+    pub fn set_size(&self, size: Size<u16, Logical>) {
+        if let Some(inner) = self.0.upgrade() {
+            inner.set_size(size);
+        }
+    }
+
+    /// Sets the minimum size of the window the window manager should enforce.
+    ///
+    /// `None` removes the constraint.
+    pub fn set_min_size(&self, size: Option<Size<u16, Logical>>) {
+        if let Some(inner) = self.0.upgrade() {
+            inner.set_min_size(size);
+        }
+    }
+
+    /// Sets the maximum size of the window the window manager should enforce.
+    ///
+    /// `None` removes the constraint.
+    pub fn set_max_size(&self, size: Option<Size<u16, Logical>>) {
+        if let Some(inner) = self.0.upgrade() {
+            inner.set_max_size(size);
+        }
+    }
+
+    /// Sets the minimum and maximum size of the window the window manager should enforce.
+    ///
+    /// Both constraints are updated at once, `None` removes the respective constraint. Setting
+    /// both to the same size prevents the user from resizing the window.
+    pub fn set_size_hints(&self, min: Option<Size<u16, Logical>>, max: Option<Size<u16, Logical>>) {
+        if let Some(inner) = self.0.upgrade() {
+            inner.set_size_hints(min, max);
+        }
+    }
+
     /// Changes the visibility of the cursor within the confines of the window.
     ///
     /// If `false`, this will hide the cursor. If `true`, this will show the cursor.
@@ -553,6 +1409,18 @@ impl Window {
         }
     }
 
+    /// Sets the image of the cursor within the confines of the window.
+    ///
+    /// The image is expected in the premultiplied [`Argb8888`](DrmFourcc::Argb8888) format without any
+    /// padding between rows, the hotspot is relative to the top left corner of the image. An empty image
+    /// resets the cursor to the default cursor. Images not matching the given size are ignored, as are
+    /// all images if the X server does not support the RENDER extension.
+    pub fn set_cursor(&self, image: &[u8], size: Size<u16, Logical>, hotspot: (u16, u16)) {
+        if let Some(inner) = self.0.upgrade() {
+            inner.set_cursor(image, size, hotspot);
+        }
+    }
+
     /// Returns the XID of the window.
     pub fn id(&self) -> u32 {
         self.0.upgrade().map(|inner| inner.id).unwrap_or(0)
@@ -578,6 +1446,55 @@ impl PartialEq for Window {
     }
 }
 
+/// Returns the window an event is targeted at, if the event belongs to a window.
+fn event_window_id(event: &x11::Event) -> Option<x11::Window> {
+    match event {
+        x11::Event::ButtonPress(event) | x11::Event::ButtonRelease(event) => Some(event.event),
+        x11::Event::KeyPress(event) | x11::Event::KeyRelease(event) => Some(event.event),
+        x11::Event::MotionNotify(event) => Some(event.event),
+        x11::Event::EnterNotify(event) | x11::Event::LeaveNotify(event) => Some(event.event),
+        x11::Event::FocusIn(event) | x11::Event::FocusOut(event) => Some(event.event),
+        x11::Event::XinputMotion(event)
+        | x11::Event::XinputButtonPress(event)
+        | x11::Event::XinputButtonRelease(event) => Some(event.event),
+        x11::Event::ConfigureNotify(event) => Some(event.window),
+        x11::Event::ClientMessage(event) => Some(event.window),
+        x11::Event::Expose(event) => Some(event.window),
+        x11::Event::PresentCompleteNotify(event) => Some(event.window),
+        x11::Event::PresentIdleNotify(event) => Some(event.window),
+        _ => None,
+    }
+}
+
+/// Converts an `XInput2` raw motion event of a relative pointer device into a relative motion event.
+///
+/// Tablet tools report absolute coordinates as raw values and are skipped.
+fn relative_motion(
+    event: &RawMotionEvent,
+    tablet_tools: &HashMap<DeviceId, TabletTool>,
+) -> Option<X11RelativeMotionEvent> {
+    if tablet_tools.contains_key(&event.sourceid) {
+        return None;
+    }
+
+    // Valuators 0 and 1 are the x and y axis of a pointer device.
+    let value = |values: &[Fp3232], number| {
+        tablet::valuator_value(&event.valuator_mask, values, number).unwrap_or(0.0)
+    };
+    let delta = (value(&event.axisvalues, 0), value(&event.axisvalues, 1));
+    let delta_unaccel = (value(&event.axisvalues_raw, 0), value(&event.axisvalues_raw, 1));
+
+    if delta == (0.0, 0.0) && delta_unaccel == (0.0, 0.0) {
+        return None;
+    }
+
+    Some(X11RelativeMotionEvent {
+        time: event.time,
+        delta,
+        delta_unaccel,
+    })
+}
+
 impl EventSource for X11Backend {
     type Event = X11Event;
 
@@ -598,229 +1515,326 @@ impl EventSource for X11Backend {
         use self::X11Event::Input;
 
         let connection = self.connection.clone();
-        let window = self.window.clone();
+        let windows = &self.windows;
         let key_counter = self.key_counter.clone();
         let log = self.log.clone();
-        let mut event_window = window.clone().into();
-        let resize = &self.resize;
+        let tablet_tools = &mut self.tablet_tools;
+        let tablet_buttons = &mut self.tablet_buttons;
+
+        if self.announce_tablet {
+            self.announce_tablet = false;
+            callback(
+                Input(InputEvent::DeviceAdded {
+                    device: X11VirtualDevice,
+                }),
+                &mut self.window.clone().into(),
+            );
+        }
 
         self.source.process_events(readiness, token, |event, _| {
+            if let x11::Event::XinputRawMotion(ref raw_motion) = event {
+                // Raw events are not associated with any window, so dispatch them to the window
+                // currently containing the pointer.
+                let entry = windows
+                    .values()
+                    .find(|entry| entry.inner.cursor_state.lock().unwrap().inside_window);
+
+                if let Some((entry, event)) = entry.zip(relative_motion(raw_motion, tablet_tools)) {
+                    callback(
+                        Input(InputEvent::PointerMotion { event }),
+                        &mut entry.inner.clone().into(),
+                    );
+                }
+
+                return;
+            }
+
+            // Dispatch the event to the window it belongs to.
+            let entry = match event_window_id(&event).and_then(|id| windows.get(&id)) {
+                Some(entry) => entry,
+                None => {
+                    if let x11::Event::Error(e) = event {
+                        error!(log, "X11 protocol error: {:?}", e);
+                    }
+
+                    return;
+                }
+            };
+            let window = &entry.inner;
+            let mut event_window = window.clone().into();
+
             match event {
                 x11::Event::ButtonPress(button_press) => {
-                    if button_press.event == window.id {
-                        // X11 decided to associate scroll wheel with a button, 4, 5, 6 and 7 for
-                        // up, down, right and left. For scrolling, a press event is emitted and a
-                        // release is them immediately followed for scrolling. This means we can
-                        // ignore release for scrolling.
-
-                        // Ideally we would use `ButtonIndex` from XCB, however it does not cover 6 and 7
-                        // for horizontal scroll and does not work nicely in match statements, so we
-                        // use magic constants here:
-                        //
-                        // 1 => MouseButton::Left
-                        // 2 => MouseButton::Middle
-                        // 3 => MouseButton::Right
-                        // 4 => Axis::Vertical +1.0
-                        // 5 => Axis::Vertical -1.0
-                        // 6 => Axis::Horizontal -1.0
-                        // 7 => Axis::Horizontal +1.0
-                        // Others => ??
-
-                        // Scrolling
-                        if button_press.detail >= 4 && button_press.detail <= 7 {
-                            callback(
-                                Input(InputEvent::PointerAxis {
-                                    event: X11MouseWheelEvent {
-                                        time: button_press.time,
-                                        axis: match button_press.detail {
-                                            // Up | Down
-                                            4 | 5 => Axis::Vertical,
-
-                                            // Right | Left
-                                            6 | 7 => Axis::Horizontal,
-
-                                            _ => unreachable!(),
-                                        },
-                                        amount: match button_press.detail {
-                                            // Up | Right
-                                            4 | 7 => 1.0,
-
-                                            // Down | Left
-                                            5 | 6 => -1.0,
-
-                                            _ => unreachable!(),
-                                        },
-                                    },
-                                }),
-                                &mut event_window,
-                            )
-                        } else {
-                            callback(
-                                Input(InputEvent::PointerButton {
-                                    event: X11MouseInputEvent {
-                                        time: button_press.time,
-                                        raw: button_press.detail as u32,
-                                        state: ButtonState::Pressed,
+                    // X11 decided to associate scroll wheel with a button, 4, 5, 6 and 7 for
+                    // up, down, right and left. For scrolling, a press event is emitted and a
+                    // release is them immediately followed for scrolling. This means we can
+                    // ignore release for scrolling.
+
+                    // Ideally we would use `ButtonIndex` from XCB, however it does not cover 6 and 7
+                    // for horizontal scroll and does not work nicely in match statements, so we
+                    // use magic constants here:
+                    //
+                    // 1 => MouseButton::Left
+                    // 2 => MouseButton::Middle
+                    // 3 => MouseButton::Right
+                    // 4 => Axis::Vertical +1.0
+                    // 5 => Axis::Vertical -1.0
+                    // 6 => Axis::Horizontal -1.0
+                    // 7 => Axis::Horizontal +1.0
+                    // Others => ??
+
+                    // Scrolling
+                    if button_press.detail >= 4 && button_press.detail <= 7 {
+                        callback(
+                            Input(InputEvent::PointerAxis {
+                                event: X11MouseWheelEvent {
+                                    time: button_press.time,
+                                    axis: match button_press.detail {
+                                        // Up | Down
+                                        4 | 5 => Axis::Vertical,
+
+                                        // Right | Left
+                                        6 | 7 => Axis::Horizontal,
+
+                                        _ => unreachable!(),
                                     },
-                                }),
-                                &mut event_window,
-                            )
-                        }
-                    }
-                }
+                                    amount: match button_press.detail {
+                                        // Up | Right
+                                        4 | 7 => 1.0,
 
-                x11::Event::ButtonRelease(button_release) => {
-                    if button_release.event == window.id {
-                        // Ignore release tick because this event is always sent immediately after the press
-                        // tick for scrolling and the backend will dispatch release event automatically during
-                        // the press event.
-                        if button_release.detail >= 4 && button_release.detail <= 7 {
-                            return;
-                        }
+                                        // Down | Left
+                                        5 | 6 => -1.0,
 
+                                        _ => unreachable!(),
+                                    },
+                                },
+                            }),
+                            &mut event_window,
+                        )
+                    } else {
                         callback(
                             Input(InputEvent::PointerButton {
                                 event: X11MouseInputEvent {
-                                    time: button_release.time,
-                                    raw: button_release.detail as u32,
-                                    state: ButtonState::Released,
+                                    time: button_press.time,
+                                    raw: button_press.detail as u32,
+                                    state: ButtonState::Pressed,
                                 },
                             }),
                             &mut event_window,
-                        );
+                        )
                     }
                 }
 
-                x11::Event::KeyPress(key_press) => {
-                    if key_press.event == window.id {
-                        callback(
-                            Input(InputEvent::Keyboard {
-                                event: X11KeyboardInputEvent {
-                                    time: key_press.time,
-                                    // X11's keycodes are +8 relative to the libinput keycodes
-                                    // that are expected, so subtract 8 from each keycode to
-                                    // match libinput.
-                                    //
-                                    // https://github.com/freedesktop/xorg-xf86-input-libinput/blob/master/src/xf86libinput.c#L54
-                                    key: key_press.detail as u32 - 8,
-                                    count: key_counter.fetch_add(1, Ordering::SeqCst) + 1,
-                                    state: KeyState::Pressed,
-                                },
-                            }),
-                            &mut event_window,
-                        )
+                x11::Event::ButtonRelease(button_release) => {
+                    // Ignore release tick because this event is always sent immediately after the press
+                    // tick for scrolling and the backend will dispatch release event automatically during
+                    // the press event.
+                    if button_release.detail >= 4 && button_release.detail <= 7 {
+                        return;
                     }
+
+                    callback(
+                        Input(InputEvent::PointerButton {
+                            event: X11MouseInputEvent {
+                                time: button_release.time,
+                                raw: button_release.detail as u32,
+                                state: ButtonState::Released,
+                            },
+                        }),
+                        &mut event_window,
+                    );
+                }
+
+                x11::Event::KeyPress(key_press) => {
+                    callback(
+                        Input(InputEvent::Keyboard {
+                            event: X11KeyboardInputEvent {
+                                time: key_press.time,
+                                // X11's keycodes are +8 relative to the libinput keycodes
+                                // that are expected, so subtract 8 from each keycode to
+                                // match libinput.
+                                //
+                                // https://github.com/freedesktop/xorg-xf86-input-libinput/blob/master/src/xf86libinput.c#L54
+                                key: key_press.detail as u32 - 8,
+                                count: key_counter.fetch_add(1, Ordering::SeqCst) + 1,
+                                state: KeyState::Pressed,
+                            },
+                        }),
+                        &mut event_window,
+                    )
                 }
 
                 x11::Event::KeyRelease(key_release) => {
-                    if key_release.event == window.id {
-                        // atomic u32 has no checked_sub, so load and store to do the same.
-                        let mut key_counter_val = key_counter.load(Ordering::SeqCst);
-                        key_counter_val = key_counter_val.saturating_sub(1);
-                        key_counter.store(key_counter_val, Ordering::SeqCst);
+                    // atomic u32 has no checked_sub, so load and store to do the same.
+                    let mut key_counter_val = key_counter.load(Ordering::SeqCst);
+                    key_counter_val = key_counter_val.saturating_sub(1);
+                    key_counter.store(key_counter_val, Ordering::SeqCst);
+
+                    callback(
+                        Input(InputEvent::Keyboard {
+                            event: X11KeyboardInputEvent {
+                                time: key_release.time,
+                                // X11's keycodes are +8 relative to the libinput keycodes
+                                // that are expected, so subtract 8 from each keycode to
+                                // match libinput.
+                                //
+                                // https://github.com/freedesktop/xorg-xf86-input-libinput/blob/master/src/xf86libinput.c#L54
+                                key: key_release.detail as u32 - 8,
+                                count: key_counter_val,
+                                state: KeyState::Released,
+                            },
+                        }),
+                        &mut event_window,
+                    );
+                }
 
-                        callback(
-                            Input(InputEvent::Keyboard {
-                                event: X11KeyboardInputEvent {
-                                    time: key_release.time,
-                                    // X11's keycodes are +8 relative to the libinput keycodes
-                                    // that are expected, so subtract 8 from each keycode to
-                                    // match libinput.
-                                    //
-                                    // https://github.com/freedesktop/xorg-xf86-input-libinput/blob/master/src/xf86libinput.c#L54
-                                    key: key_release.detail as u32 - 8,
-                                    count: key_counter_val,
-                                    state: KeyState::Released,
-                                },
-                            }),
-                            &mut event_window,
-                        );
+                x11::Event::MotionNotify(motion_notify) => {
+                    // Use event_x/y since those are relative the the window receiving events.
+                    let x = motion_notify.event_x as f64;
+                    let y = motion_notify.event_y as f64;
+
+                    callback(
+                        Input(InputEvent::PointerMotionAbsolute {
+                            event: X11MouseMovedEvent {
+                                time: motion_notify.time,
+                                x,
+                                y,
+                                size: window.size(),
+                            },
+                        }),
+                        &mut event_window,
+                    )
+                }
+
+                x11::Event::ConfigureNotify(configure_notify) => {
+                    let previous_size = { *window.size.lock().unwrap() };
+
+                    // Did the size of the window change?
+                    let configure_notify_size: Size<u16, Logical> =
+                        (configure_notify.width, configure_notify.height).into();
+
+                    if configure_notify_size != previous_size {
+                        // Intentionally drop the lock on the size mutex incase a user
+                        // requests a resize or does something which causes a resize
+                        // inside the callback.
+                        {
+                            *window.size.lock().unwrap() = configure_notify_size;
+                        }
+
+                        (callback)(X11Event::Resized(configure_notify_size), &mut event_window);
+                        let _ = entry.resize.send(configure_notify_size);
                     }
                 }
 
-                x11::Event::MotionNotify(motion_notify) => {
-                    if motion_notify.event == window.id {
-                        // Use event_x/y since those are relative the the window receiving events.
-                        let x = motion_notify.event_x as f64;
-                        let y = motion_notify.event_y as f64;
+                x11::Event::EnterNotify(_) => {
+                    window.cursor_enter();
+                }
+
+                x11::Event::LeaveNotify(leave_notify) => {
+                    window.cursor_leave();
+
+                    // Tablet tools do not report proximity, so treat them as out of proximity once
+                    // they leave the window.
+                    for tool in tablet_tools.values_mut().filter(|tool| tool.in_proximity) {
+                        tool.in_proximity = false;
 
                         callback(
-                            Input(InputEvent::PointerMotionAbsolute {
-                                event: X11MouseMovedEvent {
-                                    time: motion_notify.time,
-                                    x,
-                                    y,
-                                    size: window.size(),
+                            Input(InputEvent::TabletToolProximity {
+                                event: X11TabletToolProximityEvent {
+                                    event: tool.event(leave_notify.time, window.size()),
+                                    state: ProximityState::Out,
                                 },
                             }),
                             &mut event_window,
-                        )
+                        );
                     }
                 }
 
-                x11::Event::ConfigureNotify(configure_notify) => {
-                    if configure_notify.window == window.id {
-                        let previous_size = { *window.size.lock().unwrap() };
-
-                        // Did the size of the window change?
-                        let configure_notify_size: Size<u16, Logical> =
-                            (configure_notify.width, configure_notify.height).into();
-
-                        if configure_notify_size != previous_size {
-                            // Intentionally drop the lock on the size mutex incase a user
-                            // requests a resize or does something which causes a resize
-                            // inside the callback.
-                            {
-                                *window.size.lock().unwrap() = configure_notify_size;
-                            }
-
-                            (callback)(X11Event::Resized(configure_notify_size), &mut event_window);
-                            let _ = resize.send(configure_notify_size);
+                x11::Event::XinputMotion(motion) => {
+                    if let Some(tool) = tablet_tools.get_mut(&motion.deviceid) {
+                        let event = tool.update(&motion, window.size());
+
+                        if !tool.in_proximity {
+                            tool.in_proximity = true;
+
+                            callback(
+                                Input(InputEvent::TabletToolProximity {
+                                    event: X11TabletToolProximityEvent {
+                                        event: event.clone(),
+                                        state: ProximityState::In,
+                                    },
+                                }),
+                                &mut event_window,
+                            );
                         }
+
+                        callback(Input(InputEvent::TabletToolAxis { event }), &mut event_window);
                     }
                 }
 
-                x11::Event::EnterNotify(enter_notify) => {
-                    if enter_notify.event == window.id {
-                        window.cursor_enter();
+                x11::Event::XinputButtonPress(button_press) => {
+                    if let Some(event) = tablet_tools.get_mut(&button_press.deviceid).and_then(|tool| {
+                        tool.button(&button_press, ButtonState::Pressed, window.size(), tablet_buttons)
+                    }) {
+                        callback(Input(event), &mut event_window);
                     }
                 }
 
-                x11::Event::LeaveNotify(leave_notify) => {
-                    if leave_notify.event == window.id {
-                        window.cursor_leave();
+                x11::Event::XinputButtonRelease(button_release) => {
+                    if let Some(event) = tablet_tools.get_mut(&button_release.deviceid).and_then(|tool| {
+                        tool.button(
+                            &button_release,
+                            ButtonState::Released,
+                            window.size(),
+                            tablet_buttons,
+                        )
+                    }) {
+                        callback(Input(event), &mut event_window);
+                    }
+                }
+
+                x11::Event::FocusIn(focus_in) => {
+                    if focus_in.detail != NotifyDetail::POINTER {
+                        (callback)(X11Event::Focus(true), &mut event_window);
+                    }
+                }
+
+                x11::Event::FocusOut(focus_out) => {
+                    if focus_out.detail != NotifyDetail::POINTER {
+                        // Keys released while unfocused are never reported to us.
+                        key_counter.store(0, Ordering::SeqCst);
+                        (callback)(X11Event::Focus(false), &mut event_window);
                     }
                 }
 
                 x11::Event::ClientMessage(client_message) => {
-                    if client_message.data.as_data32()[0] == window.atoms.WM_DELETE_WINDOW // Destroy the window?
-                            && client_message.window == window.id
-                    // Same window
-                    {
+                    // Destroy the window?
+                    if client_message.data.as_data32()[0] == window.atoms.WM_DELETE_WINDOW {
                         (callback)(X11Event::CloseRequested, &mut event_window);
                     }
                 }
 
                 x11::Event::Expose(expose) => {
-                    if expose.window == window.id && expose.count == 0 {
+                    if expose.count == 0 {
                         (callback)(X11Event::Refresh, &mut event_window);
                     }
                 }
 
                 x11::Event::PresentCompleteNotify(complete_notify) => {
-                    if complete_notify.window == window.id {
-                        window.last_msc.store(complete_notify.msc, Ordering::SeqCst);
-
-                        (callback)(X11Event::PresentCompleted, &mut event_window);
-                    }
-                }
-
-                x11::Event::PresentIdleNotify(_) => {
-                    // Pixmap is reference counted in the X server, so we do not need to take and drop.
+                    window.last_msc.store(complete_notify.msc, Ordering::SeqCst);
+
+                    (callback)(
+                        X11Event::PresentCompleted {
+                            serial: complete_notify.serial,
+                            ust: complete_notify.ust,
+                            msc: complete_notify.msc,
+                        },
+                        &mut event_window,
+                    );
                 }
 
-                x11::Event::Error(e) => {
-                    error!(log, "X11 protocol error: {:?}", e);
+                x11::Event::PresentIdleNotify(idle_notify) => {
+                    let _ = entry.idle.send(idle_notify.pixmap);
                 }
 
                 _ => (),
@@ -843,3 +1857,49 @@ impl EventSource for X11Backend {
         self.source.unregister(poll)
     }
 }
+
+#[cfg(all(test, feature = "wayland_frontend"))]
+mod tests {
+    use super::XkbNames;
+
+    #[test]
+    fn parse_xkb_rules_names() {
+        let names = XkbNames::parse(b"evdev\0pc105\0us,de\0,nodeadkeys\0grp:alt_shift_toggle\0");
+        assert_eq!(
+            names,
+            XkbNames {
+                rules: "evdev".into(),
+                model: "pc105".into(),
+                layout: "us,de".into(),
+                variant: ",nodeadkeys".into(),
+                options: "grp:alt_shift_toggle".into(),
+            }
+        );
+
+        let config = names.config();
+        assert_eq!(config.layout, "us,de");
+        assert_eq!(config.options, Some("grp:alt_shift_toggle".into()));
+    }
+
+    #[test]
+    fn parse_incomplete_xkb_rules_names() {
+        assert_eq!(XkbNames::parse(b""), XkbNames::default());
+
+        let names = XkbNames::parse(b"evdev\0pc105\0fr\0\0\0");
+        assert_eq!(names.layout, "fr");
+        assert_eq!(names.variant, "");
+        assert_eq!(names.config().options, None);
+    }
+
+    #[test]
+    fn layouts_from_xkb_symbols() {
+        let names = XkbNames::from_symbols("pc+us+inet(evdev)");
+        assert_eq!(names.layout, "us");
+        assert_eq!(names.variant, "");
+
+        let names =
+            XkbNames::from_symbols("pc+us(intl)+de(nodeadkeys):2+ru:3+inet(evdev)+group(alt_shift_toggle)");
+        assert_eq!(names.layout, "us,de,ru");
+        assert_eq!(names.variant, "intl,nodeadkeys,");
+    }
+}
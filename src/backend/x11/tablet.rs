@@ -0,0 +1,408 @@
+//! Tablet support of the X11 backend using the XInput2 extension.
+//!
+//! The X server exposes every tool of a drawing tablet (e.g. the stylus and the eraser) as a slave pointer
+//! device with additional valuators. Every slave pointer providing a pressure valuator is treated as a
+//! tablet tool.
+
+use super::{
+    input::{X11Input, X11TabletToolButtonEvent, X11TabletToolEvent, X11TabletToolTipEvent},
+    Atoms, X11Error,
+};
+use crate::{
+    backend::input::{
+        ButtonState, InputEvent, TabletToolCapabilitys, TabletToolDescriptor, TabletToolTipState,
+        TabletToolType,
+    },
+    utils::{Logical, Size},
+};
+use slog::{info, Logger};
+use std::collections::HashMap;
+use x11rb::{
+    connection::Connection,
+    protocol::xinput::{
+        ButtonPressEvent, ConnectionExt as _, Device, DeviceClassData, DeviceId, DeviceType, EventMask,
+        Fp1616, Fp3232, XIEventMask,
+    },
+    protocol::xproto::Window,
+};
+
+#[derive(Debug, Clone, Copy)]
+struct Valuator {
+    number: u16,
+    min: f64,
+    max: f64,
+}
+
+impl Valuator {
+    /// Maps a value of the valuator to the `0.0..=1.0` range.
+    fn normalize(&self, value: f64) -> f64 {
+        if self.max > self.min {
+            ((value - self.min) / (self.max - self.min)).max(0.0).min(1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Converts a value of a tilt valuator to degrees.
+    ///
+    /// The X server does not report the physical range of the axis, so the range of the valuator is
+    /// mapped onto the -64 to 64 degrees supported by wacom tablets, like libinput does for devices
+    /// without a resolution.
+    fn tilt_degrees(&self, value: f64) -> f64 {
+        if self.max > self.min {
+            (self.normalize(value) * 2.0 - 1.0) * MAX_TILT_DEGREES
+        } else {
+            0.0
+        }
+    }
+}
+
+const MAX_TILT_DEGREES: f64 = 64.0;
+
+#[derive(Debug)]
+pub(crate) struct TabletTool {
+    descriptor: TabletToolDescriptor,
+    pressure: Option<Valuator>,
+    tilt: Option<(Valuator, Valuator)>,
+    pub in_proximity: bool,
+    position: (f64, f64),
+    pressure_value: f64,
+    tilt_value: (f64, f64),
+}
+
+impl TabletTool {
+    /// Returns an event describing the current state of the tool, without any changed axes.
+    pub fn event(&self, time: u32, size: Size<u16, Logical>) -> X11TabletToolEvent {
+        X11TabletToolEvent {
+            time,
+            tool: self.descriptor.clone(),
+            x: self.position.0,
+            y: self.position.1,
+            delta: (0.0, 0.0),
+            size,
+            pressure: self.pressure_value,
+            pressure_changed: false,
+            tilt: self.tilt_value,
+            tilt_changed: (false, false),
+        }
+    }
+
+    /// Updates the state of the tool from an XInput2 device event and returns the resulting event.
+    pub fn update(&mut self, event: &ButtonPressEvent, size: Size<u16, Logical>) -> X11TabletToolEvent {
+        let valuator_mask = &event.valuator_mask[..];
+        let axisvalues = &event.axisvalues[..];
+
+        let x = fp1616_to_f64(event.event_x);
+        let y = fp1616_to_f64(event.event_y);
+        let delta = (x - self.position.0, y - self.position.1);
+        self.position = (x, y);
+
+        let pressure = self
+            .pressure
+            .and_then(|valuator| {
+                valuator_value(valuator_mask, axisvalues, valuator.number).map(|v| (valuator, v))
+            })
+            .map(|(valuator, value)| valuator.normalize(value));
+
+        if let Some(pressure) = pressure {
+            self.pressure_value = pressure;
+        }
+
+        let tilt = self.tilt.map(|(tilt_x, tilt_y)| {
+            (
+                valuator_value(valuator_mask, axisvalues, tilt_x.number).map(|v| tilt_x.tilt_degrees(v)),
+                valuator_value(valuator_mask, axisvalues, tilt_y.number).map(|v| tilt_y.tilt_degrees(v)),
+            )
+        });
+
+        let (tilt_x_changed, tilt_y_changed) = match tilt {
+            Some((tilt_x, tilt_y)) => {
+                if let Some(tilt_x) = tilt_x {
+                    self.tilt_value.0 = tilt_x;
+                }
+                if let Some(tilt_y) = tilt_y {
+                    self.tilt_value.1 = tilt_y;
+                }
+
+                (tilt_x.is_some(), tilt_y.is_some())
+            }
+            None => (false, false),
+        };
+
+        X11TabletToolEvent {
+            delta,
+            pressure_changed: pressure.is_some(),
+            tilt_changed: (tilt_x_changed, tilt_y_changed),
+            ..self.event(event.time, size)
+        }
+    }
+
+    /// Converts an XInput2 button event of the tool into a tip or button event.
+    ///
+    /// Button 1 is the tip of the tool, buttons 2 and 3 are the first and second button on the tool.
+    /// `seat_buttons` counts the pressed buttons of all tools of the seat.
+    pub fn button(
+        &mut self,
+        event: &ButtonPressEvent,
+        state: ButtonState,
+        size: Size<u16, Logical>,
+        seat_buttons: &mut HashMap<u32, u32>,
+    ) -> Option<InputEvent<X11Input>> {
+        let button = match event.detail {
+            1 => {
+                let state = match state {
+                    ButtonState::Pressed => TabletToolTipState::Down,
+                    ButtonState::Released => TabletToolTipState::Up,
+                };
+
+                return Some(InputEvent::TabletToolTip {
+                    event: X11TabletToolTipEvent {
+                        event: self.update(event, size),
+                        state,
+                    },
+                });
+            }
+            2 => BTN_STYLUS,
+            3 => BTN_STYLUS2,
+            _ => return None,
+        };
+
+        Some(InputEvent::TabletToolButton {
+            event: X11TabletToolButtonEvent {
+                event: self.update(event, size),
+                button,
+                seat_button_count: update_seat_button_count(seat_buttons, button, state),
+                state,
+            },
+        })
+    }
+}
+
+/// Tracks how often a button is pressed on all tools of the seat and returns the updated count.
+fn update_seat_button_count(seat_buttons: &mut HashMap<u32, u32>, button: u32, state: ButtonState) -> u32 {
+    let count = seat_buttons.entry(button).or_insert(0);
+    match state {
+        ButtonState::Pressed => *count += 1,
+        ButtonState::Released => *count = count.saturating_sub(1),
+    }
+    *count
+}
+
+// linux/input-event-codes.h
+const BTN_STYLUS: u32 = 0x14b;
+const BTN_STYLUS2: u32 = 0x14c;
+
+/// Queries the tablet tools known to the X server.
+pub(crate) fn init_tablet_tools<C: Connection>(
+    connection: &C,
+    atoms: &Atoms,
+    logger: &Logger,
+) -> Result<HashMap<DeviceId, TabletTool>, X11Error> {
+    let mut tools = HashMap::new();
+
+    for info in connection.xinput_xi_query_device(Device::ALL)?.reply()?.infos {
+        if info.type_ != DeviceType::SLAVE_POINTER {
+            continue;
+        }
+
+        let mut pressure = None;
+        let mut tilt_x = None;
+        let mut tilt_y = None;
+
+        for class in &info.classes {
+            if let DeviceClassData::Valuator(ref valuator) = class.data {
+                let value = Valuator {
+                    number: valuator.number,
+                    min: fp3232_to_f64(valuator.min),
+                    max: fp3232_to_f64(valuator.max),
+                };
+
+                if valuator.label == atoms.ABS_PRESSURE {
+                    pressure = Some(value);
+                } else if valuator.label == atoms.ABS_TILT_X {
+                    tilt_x = Some(value);
+                } else if valuator.label == atoms.ABS_TILT_Y {
+                    tilt_y = Some(value);
+                }
+            }
+        }
+
+        if pressure.is_none() {
+            continue;
+        }
+
+        let name = String::from_utf8_lossy(&info.name).into_owned();
+        let tilt = tilt_x.zip(tilt_y);
+
+        let mut capabilitys = TabletToolCapabilitys::PRESSURE;
+        if tilt.is_some() {
+            capabilitys |= TabletToolCapabilitys::TILT;
+        }
+
+        info!(logger, "Found tablet tool {} (device {})", name, info.deviceid);
+
+        tools.insert(
+            info.deviceid,
+            TabletTool {
+                descriptor: TabletToolDescriptor {
+                    tool_type: if name.to_lowercase().contains("eraser") {
+                        TabletToolType::Eraser
+                    } else {
+                        TabletToolType::Pen
+                    },
+                    hardware_serial: info.deviceid as u64,
+                    hardware_id_wacom: 0,
+                    capabilitys,
+                },
+                pressure,
+                tilt,
+                in_proximity: false,
+                position: (0.0, 0.0),
+                pressure_value: 0.0,
+                tilt_value: (0.0, 0.0),
+            },
+        );
+    }
+
+    Ok(tools)
+}
+
+/// Selects the events of the given tablet tools on the window.
+pub(crate) fn select_tablet_events<C: Connection>(
+    connection: &C,
+    window: Window,
+    tools: &HashMap<DeviceId, TabletTool>,
+) -> Result<(), X11Error> {
+    if !tools.is_empty() {
+        let masks = tools
+            .keys()
+            .map(|&deviceid| EventMask {
+                deviceid,
+                mask: vec![
+                    (XIEventMask::MOTION | XIEventMask::BUTTON_PRESS | XIEventMask::BUTTON_RELEASE).into(),
+                ],
+            })
+            .collect::<Vec<_>>();
+
+        connection.xinput_xi_select_events(window, &masks)?;
+    }
+
+    Ok(())
+}
+
+fn fp1616_to_f64(value: Fp1616) -> f64 {
+    value as f64 / 65536.0
+}
+
+fn fp3232_to_f64(value: Fp3232) -> f64 {
+    value.integral as f64 + value.frac as f64 / (1u64 << 32) as f64
+}
+
+/// Returns the value of the valuator with the given number, if it is part of the event.
+///
+/// The values of an event only contain the valuators set in the mask, in ascending order.
+pub(super) fn valuator_value(valuator_mask: &[u32], axisvalues: &[Fp3232], number: u16) -> Option<f64> {
+    let is_set = |n: usize| {
+        valuator_mask
+            .get(n / 32)
+            .map_or(false, |bits| bits & (1 << (n % 32)) != 0)
+    };
+    let number = number as usize;
+
+    if !is_set(number) {
+        return None;
+    }
+
+    let index = (0..number).filter(|&n| is_set(n)).count();
+    axisvalues.get(index).copied().map(fp3232_to_f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fp(value: i32) -> Fp3232 {
+        Fp3232 {
+            integral: value,
+            frac: 0,
+        }
+    }
+
+    #[test]
+    fn valuator_values_follow_mask() {
+        // valuators 0, 2 and 33 are set
+        let mask = [0b101, 0b10];
+        let values = [fp(10), fp(20), fp(30)];
+
+        assert_eq!(valuator_value(&mask, &values, 0), Some(10.0));
+        assert_eq!(valuator_value(&mask, &values, 1), None);
+        assert_eq!(valuator_value(&mask, &values, 2), Some(20.0));
+        assert_eq!(valuator_value(&mask, &values, 33), Some(30.0));
+        assert_eq!(valuator_value(&mask, &values, 64), None);
+    }
+
+    #[test]
+    fn tilt_in_degrees() {
+        let valuator = Valuator {
+            number: 3,
+            min: -64.0,
+            max: 63.0,
+        };
+        assert_eq!(valuator.tilt_degrees(-64.0), -64.0);
+        assert_eq!(valuator.tilt_degrees(63.0), 64.0);
+        assert_eq!(valuator.tilt_degrees(-0.5), 0.0);
+
+        let valuator = Valuator {
+            number: 3,
+            min: 0.0,
+            max: 127.0,
+        };
+        assert_eq!(valuator.tilt_degrees(0.0), -64.0);
+        assert_eq!(valuator.tilt_degrees(127.0), 64.0);
+        // out of range values are clamped
+        assert_eq!(valuator.tilt_degrees(200.0), 64.0);
+    }
+
+    #[test]
+    fn seat_button_count() {
+        let mut seat_buttons = HashMap::new();
+        assert_eq!(
+            update_seat_button_count(&mut seat_buttons, BTN_STYLUS, ButtonState::Pressed),
+            1
+        );
+        // the same button pressed on a second tool
+        assert_eq!(
+            update_seat_button_count(&mut seat_buttons, BTN_STYLUS, ButtonState::Pressed),
+            2
+        );
+        assert_eq!(
+            update_seat_button_count(&mut seat_buttons, BTN_STYLUS2, ButtonState::Pressed),
+            1
+        );
+        assert_eq!(
+            update_seat_button_count(&mut seat_buttons, BTN_STYLUS, ButtonState::Released),
+            1
+        );
+        assert_eq!(
+            update_seat_button_count(&mut seat_buttons, BTN_STYLUS, ButtonState::Released),
+            0
+        );
+        // releases of buttons pressed before the backend was started
+        assert_eq!(
+            update_seat_button_count(&mut seat_buttons, BTN_STYLUS, ButtonState::Released),
+            0
+        );
+    }
+
+    #[test]
+    fn fixed_point_conversion() {
+        assert_eq!(fp1616_to_f64(3 << 16 | 0x8000), 3.5);
+        assert_eq!(
+            fp3232_to_f64(Fp3232 {
+                integral: -2,
+                frac: 1 << 31
+            }),
+            -1.5
+        );
+    }
+}
@@ -11,7 +11,7 @@ A link to the ICCCM Section 4: https://tronche.com/gui/x/icccm/sec-4.html
 */
 use crate::utils::{Logical, Size};
 
-use super::{extension::Extensions, Atoms, Window, X11Error};
+use super::{extension::Extensions, Atoms, Window, WindowType, X11Error};
 use drm_fourcc::DrmFourcc;
 use std::sync::{
     atomic::{AtomicU32, AtomicU64},
@@ -19,11 +19,14 @@ use std::sync::{
 };
 use x11rb::{
     connection::Connection,
+    properties::WmSizeHints,
     protocol::{
         present::{self, ConnectionExt as _},
+        render::{ConnectionExt as _, CreatePictureAux, PictType},
         xfixes::ConnectionExt as _,
         xproto::{
-            self as x11, AtomEnum, ConnectionExt, CreateWindowAux, Depth, EventMask, PropMode, Screen,
+            self as x11, AtomEnum, ChangeWindowAttributesAux, ConfigureWindowAux, ConnectionExt, CreateGCAux,
+            CreateWindowAux, Depth, EventMask, GcontextWrapper, ImageFormat, PixmapWrapper, PropMode, Screen,
             UnmapNotifyEvent, WindowClass,
         },
     },
@@ -61,8 +64,11 @@ pub(crate) struct WindowInner {
     pub atoms: Atoms,
     pub cursor_state: Arc<Mutex<CursorState>>,
     pub size: Mutex<Size<u16, Logical>>,
+    size_hints: Mutex<WmSizeHints>,
     pub next_serial: AtomicU32,
     pub last_msc: Arc<AtomicU64>,
+    /// The msc targeted by the most recently presented pixmap.
+    pub target_msc: AtomicU64,
     pub format: DrmFourcc,
     pub depth: Depth,
     pub extensions: Extensions,
@@ -113,6 +119,7 @@ impl WindowInner {
             | EventMask::POINTER_MOTION // Mouse movement
             | EventMask::ENTER_WINDOW // Track whether the cursor enters of leaves the window.
             | EventMask::LEAVE_WINDOW
+            | EventMask::FOCUS_CHANGE // Track whether the window has keyboard focus
             | EventMask::EXPOSURE
             | EventMask::NO_EVENT,
             )
@@ -151,8 +158,10 @@ impl WindowInner {
             atoms,
             cursor_state: Arc::new(Mutex::new(CursorState::default())),
             size: Mutex::new(size),
+            size_hints: Mutex::new(WmSizeHints::new()),
             next_serial: AtomicU32::new(0),
             last_msc: Arc::new(AtomicU64::new(0)),
+            target_msc: AtomicU64::new(0),
             format,
             depth,
             extensions,
@@ -219,6 +228,45 @@ impl WindowInner {
         *self.size.lock().unwrap()
     }
 
+    pub fn set_size(&self, size: Size<u16, Logical>) {
+        if let Some(connection) = self.connection.upgrade() {
+            // The window manager answers with a ConfigureNotify event, which takes care of
+            // resizing the buffers.
+            let _ = connection.configure_window(
+                self.id,
+                &ConfigureWindowAux::new()
+                    .width(size.w as u32)
+                    .height(size.h as u32),
+            );
+        }
+    }
+
+    pub fn set_min_size(&self, size: Option<Size<u16, Logical>>) {
+        let mut hints = self.size_hints.lock().unwrap();
+        hints.min_size = size.map(|size| (size.w as i32, size.h as i32));
+        self.update_size_hints(&hints);
+    }
+
+    pub fn set_max_size(&self, size: Option<Size<u16, Logical>>) {
+        let mut hints = self.size_hints.lock().unwrap();
+        hints.max_size = size.map(|size| (size.w as i32, size.h as i32));
+        self.update_size_hints(&hints);
+    }
+
+    pub fn set_size_hints(&self, min: Option<Size<u16, Logical>>, max: Option<Size<u16, Logical>>) {
+        let mut hints = self.size_hints.lock().unwrap();
+        hints.min_size = min.map(|size| (size.w as i32, size.h as i32));
+        hints.max_size = max.map(|size| (size.w as i32, size.h as i32));
+        self.update_size_hints(&hints);
+    }
+
+    fn update_size_hints(&self, hints: &WmSizeHints) {
+        if let Some(connection) = self.connection.upgrade() {
+            // ICCCM - WM_NORMAL_HINTS Property
+            let _ = hints.set_normal_hints(&*connection, self.id);
+        }
+    }
+
     pub fn set_title(&self, title: &str) {
         if let Some(connection) = self.connection.upgrade() {
             // _NET_WM_NAME should be preferred by window managers, but set both properties.
@@ -240,6 +288,29 @@ impl WindowInner {
         }
     }
 
+    pub fn set_window_type(&self, window_type: WindowType) {
+        if let Some(connection) = self.connection.upgrade() {
+            let atom = match window_type {
+                WindowType::Desktop => self.atoms._NET_WM_WINDOW_TYPE_DESKTOP,
+                WindowType::Dock => self.atoms._NET_WM_WINDOW_TYPE_DOCK,
+                WindowType::Toolbar => self.atoms._NET_WM_WINDOW_TYPE_TOOLBAR,
+                WindowType::Menu => self.atoms._NET_WM_WINDOW_TYPE_MENU,
+                WindowType::Utility => self.atoms._NET_WM_WINDOW_TYPE_UTILITY,
+                WindowType::Splash => self.atoms._NET_WM_WINDOW_TYPE_SPLASH,
+                WindowType::Dialog => self.atoms._NET_WM_WINDOW_TYPE_DIALOG,
+                WindowType::Normal => self.atoms._NET_WM_WINDOW_TYPE_NORMAL,
+            };
+
+            let _ = connection.change_property32(
+                PropMode::REPLACE,
+                self.id,
+                self.atoms._NET_WM_WINDOW_TYPE,
+                AtomEnum::ATOM,
+                &[atom],
+            );
+        }
+    }
+
     pub fn set_cursor_visible(&self, visible: bool) {
         if let Some(connection) = self.connection.upgrade() {
             let mut state = self.cursor_state.lock().unwrap();
@@ -252,6 +323,82 @@ impl WindowInner {
         }
     }
 
+    pub fn set_cursor(&self, image: &[u8], size: Size<u16, Logical>, hotspot: (u16, u16)) {
+        if let Some(connection) = self.connection.upgrade() {
+            let _ = self.update_cursor_image(&*connection, image, size, hotspot);
+        }
+    }
+
+    fn update_cursor_image(
+        &self,
+        connection: &RustConnection,
+        image: &[u8],
+        size: Size<u16, Logical>,
+        hotspot: (u16, u16),
+    ) -> Result<(), X11Error> {
+        // Without a cursor the window uses the cursor of its parent, which is the default cursor.
+        if image.is_empty() {
+            connection
+                .change_window_attributes(self.id, &ChangeWindowAttributesAux::new().cursor(x11rb::NONE))?;
+            return Ok(());
+        }
+
+        if self.extensions.render.is_none() || image.len() != size.w as usize * size.h as usize * 4 {
+            return Ok(());
+        }
+
+        // Cursors are created from a picture using the ARGB32 standard format.
+        let format = connection
+            .render_query_pict_formats()?
+            .reply()?
+            .formats
+            .into_iter()
+            .find(|format| {
+                let direct = &format.direct;
+
+                format.type_ == PictType::DIRECT
+                    && format.depth == 32
+                    && (direct.alpha_shift, direct.alpha_mask) == (24, 0xff)
+                    && (direct.red_shift, direct.red_mask) == (16, 0xff)
+                    && (direct.green_shift, direct.green_mask) == (8, 0xff)
+                    && (direct.blue_shift, direct.blue_mask) == (0, 0xff)
+            })
+            .map(|format| format.id);
+
+        let format = match format {
+            Some(format) => format,
+            None => return Ok(()),
+        };
+
+        let pixmap = PixmapWrapper::create_pixmap(connection, 32, self.id, size.w, size.h)?;
+        let gc = GcontextWrapper::create_gc(connection, pixmap.pixmap(), &CreateGCAux::new())?;
+        connection.put_image(
+            ImageFormat::Z_PIXMAP,
+            pixmap.pixmap(),
+            gc.gcontext(),
+            size.w,
+            size.h,
+            0,
+            0,
+            0,
+            32,
+            image,
+        )?;
+
+        let picture = connection.generate_id()?;
+        connection.render_create_picture(picture, pixmap.pixmap(), format, &CreatePictureAux::new())?;
+
+        let cursor = connection.generate_id()?;
+        connection.render_create_cursor(cursor, picture, hotspot.0, hotspot.1)?;
+        connection.render_free_picture(picture)?;
+
+        // The X server keeps the cursor alive as long as it is used by the window.
+        connection.change_window_attributes(self.id, &ChangeWindowAttributesAux::new().cursor(cursor))?;
+        connection.free_cursor(cursor)?;
+
+        Ok(())
+    }
+
     pub fn cursor_enter(&self) {
         if let Some(connection) = self.connection.upgrade() {
             let mut state = self.cursor_state.lock().unwrap();
@@ -1,6 +1,9 @@
 use std::fmt;
 use std::ops::{Add, AddAssign, Sub, SubAssign};
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 /// Type-level marker for the logical coordinate space
 #[derive(Debug)]
 pub struct Logical;
@@ -185,11 +188,13 @@ floating_point_coordinate_impl! {
  */
 
 /// A point as defined by its x and y coordinates
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Point<N, Kind> {
     /// horizontal coordinate
     pub x: N,
     /// vertical coordinate
     pub y: N,
+    #[cfg_attr(feature = "serde", serde(skip))]
     _kind: std::marker::PhantomData<Kind>,
 }
 
@@ -448,11 +453,13 @@ impl<N: Default, Kind> Default for Point<N, Kind> {
 /// Constructors of this type ensure that the values are always positive via
 /// `debug_assert!()`, however manually changing the values of the fields
 /// can break this invariant.
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Size<N, Kind> {
     /// horizontal coordinate
     pub w: N,
     /// vertical coordinate
     pub h: N,
+    #[cfg_attr(feature = "serde", serde(skip))]
     _kind: std::marker::PhantomData<Kind>,
 }
 
@@ -693,6 +700,7 @@ impl<N: Sub<Output = N>, Kind> Sub<Size<N, Kind>> for Point<N, Kind> {
 }
 
 /// A rectangle defined by its top-left corner and dimensions
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Rectangle<N, Kind> {
     /// Location of the top-left corner of the rectangle
     pub loc: Point<N, Kind>,
@@ -809,6 +817,64 @@ impl<N: Coordinate, Kind> Rectangle<N, Kind> {
     pub fn merge(self, other: Self) -> Self {
         Self::bounding_box([self.loc, self.loc + self.size, other.loc, other.loc + other.size])
     }
+
+    /// Compute the intersection of two [`Rectangle`]s
+    ///
+    /// Returns `None` if the rectangles do not overlap or only share an edge.
+    pub fn intersection(self, other: Self) -> Option<Self> {
+        let max = |a: N, b: N| if a > b { a } else { b };
+        let min = |a: N, b: N| if a < b { a } else { b };
+
+        let topleft = Point::<N, Kind>::from((max(self.loc.x, other.loc.x), max(self.loc.y, other.loc.y)));
+        let bottomright = Point::<N, Kind>::from((
+            min(self.loc.x + self.size.w, other.loc.x + other.size.w),
+            min(self.loc.y + self.size.h, other.loc.y + other.size.h),
+        ));
+        if topleft.x < bottomright.x && topleft.y < bottomright.y {
+            Some(Rectangle::from_extemities(topleft, bottomright))
+        } else {
+            None
+        }
+    }
+
+    /// Compute the parts of this [`Rectangle`] not covered by another one
+    ///
+    /// Returns at most four non-overlapping rectangles.
+    pub fn subtract_rect(self, other: Self) -> Vec<Self> {
+        let intersection = match self.intersection(other) {
+            Some(intersection) => intersection,
+            None => return vec![self],
+        };
+
+        let (left, top) = (self.loc.x, self.loc.y);
+        let (right, bottom) = (self.loc.x + self.size.w, self.loc.y + self.size.h);
+        let (inner_left, inner_top) = (intersection.loc.x, intersection.loc.y);
+        let (inner_right, inner_bottom) = (
+            intersection.loc.x + intersection.size.w,
+            intersection.loc.y + intersection.size.h,
+        );
+
+        let mut rects = Vec::with_capacity(4);
+        if inner_top > top {
+            rects.push(Rectangle::from_extemities((left, top), (right, inner_top)));
+        }
+        if inner_bottom < bottom {
+            rects.push(Rectangle::from_extemities((left, inner_bottom), (right, bottom)));
+        }
+        if inner_left > left {
+            rects.push(Rectangle::from_extemities(
+                (left, inner_top),
+                (inner_left, inner_bottom),
+            ));
+        }
+        if inner_right < right {
+            rects.push(Rectangle::from_extemities(
+                (inner_right, inner_top),
+                (right, inner_bottom),
+            ));
+        }
+        rects
+    }
 }
 
 impl<N: Coordinate> Rectangle<N, Logical> {
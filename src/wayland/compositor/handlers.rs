@@ -9,7 +9,7 @@ use std::{
 };
 
 use wayland_server::{
-    protocol::{wl_compositor, wl_region, wl_subcompositor, wl_subsurface, wl_surface},
+    protocol::{wl_buffer, wl_compositor, wl_region, wl_subcompositor, wl_subsurface, wl_surface},
     DispatchData, Filter, Main,
 };
 
@@ -80,6 +80,10 @@ impl SurfaceImplem {
         match req {
             wl_surface::Request::Attach { buffer, x, y } => {
                 PrivateSurfaceData::with_states(&surface, |states| {
+                    *states.cached_state.pending::<SurfaceBuffer>() = SurfaceBuffer {
+                        attached: true,
+                        buffer: buffer.clone(),
+                    };
                     states.cached_state.pending::<SurfaceAttributes>().buffer = Some(match buffer {
                         Some(buffer) => BufferAssignment::NewBuffer {
                             buffer,
@@ -130,12 +134,16 @@ impl SurfaceImplem {
             }
             wl_surface::Request::Commit => {
                 let mut user_impl = self.implem.borrow_mut();
-                PrivateSurfaceData::invoke_commit_hooks(&surface);
+                PrivateSurfaceData::invoke_pre_commit_hooks(&surface);
                 if !surface.as_ref().is_alive() {
                     // the client was killed by a hook, abort
                     return;
                 }
                 PrivateSurfaceData::commit(&surface);
+                if !surface.as_ref().is_alive() {
+                    // the client was killed by a post-commit hook, abort
+                    return;
+                }
                 trace!(self.log, "Calling user implementation for wl_surface.commit");
                 (&mut *user_impl)(surface, ddata);
             }
@@ -201,6 +209,30 @@ impl Cacheable for SurfaceAttributes {
     }
 }
 
+/// The buffer of a surface
+///
+/// Unlike the buffer of the [`SurfaceAttributes`], this is not taken by the compositor, so that
+/// protocol extensions can check their state against the buffer of the surface, even if it was
+/// committed before.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct SurfaceBuffer {
+    /// Whether a buffer was attached since the last commit
+    pub(crate) attached: bool,
+    /// The attached buffer, if any
+    pub(crate) buffer: Option<wl_buffer::WlBuffer>,
+}
+
+impl Cacheable for SurfaceBuffer {
+    fn commit(&mut self) -> Self {
+        std::mem::take(self)
+    }
+    fn merge_into(self, into: &mut Self) {
+        if self.attached {
+            *into = self;
+        }
+    }
+}
+
 fn implement_surface<Impl>(
     surface: Main<wl_surface::WlSurface>,
     log: ::slog::Logger,
@@ -213,7 +245,10 @@ where
         let mut implem = SurfaceImplem::make(log, implem);
         move |surface, req, ddata| implem.receive_surface_request(req, surface.deref().clone(), ddata)
     });
-    surface.assign_destructor(Filter::new(|surface, _, _| PrivateSurfaceData::cleanup(&surface)));
+    surface.assign_destructor(Filter::new(|surface, _, _| {
+        PrivateSurfaceData::invoke_destruction_hooks(&surface);
+        PrivateSurfaceData::cleanup(&surface)
+    }));
     surface
         .as_ref()
         .user_data()
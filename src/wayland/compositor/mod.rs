@@ -60,14 +60,20 @@
 //! On commit of a surface several steps are taken to update the state of the surface. Actions
 //! are taken by smithay in the following order:
 //!
-//! 1. Commit hooks registered to this surface are invoked. Such hooks can be registered using
-//!    the [`add_commit_hook`] function. They are typically used by protocol extensions that
+//! 1. Pre-commit hooks registered to this surface are invoked. Such hooks can be registered using
+//!    the [`add_pre_commit_hook`] function. They are typically used by protocol extensions that
 //!    add state to a surface and need to check on commit that client did not request an
 //!    illegal state before it is applied on commit.
 //! 2. The pending state is either applied and made current, or cached for later application
 //!    is the surface is a synchronize subsurface. If the current state is applied, state
 //!    of the synchronized children subsurface are applied as well at this point.
-//! 3. Your user callback provided to [`compositor_init`] is invoked, so that you can access
+//! 3. Post-commit hooks are invoked on every surface whose state was applied at the previous
+//!    step, including the synchronized children subsurfaces. Such hooks can be registered
+//!    using the [`add_post_commit_hook`] function. They are typically used by protocol
+//!    extensions that need to react to the newly applied state before the compositor does.
+//!    The hooks of a synchronized subsurface whose state was only cached are not invoked on
+//!    its commit, but on the commit of its parent applying this state.
+//! 4. Your user callback provided to [`compositor_init`] is invoked, so that you can access
 //!    the new current state of the surface. The state of sync children subsurfaces of your
 //!    surface may have changed as well, so this is the place to check it, using functions
 //!    like [`with_surface_tree_upward`] or [`with_surface_tree_downward`]. On the other hand,
@@ -91,9 +97,10 @@ mod tree;
 
 pub use self::cache::{Cacheable, MultiCache};
 pub use self::handlers::SubsurfaceCachedState;
+pub(crate) use self::handlers::SurfaceBuffer;
 use self::tree::PrivateSurfaceData;
 pub use self::tree::{AlreadyHasRole, TraversalAction};
-use crate::utils::{Buffer, DeadResource, Logical, Point, Rectangle};
+use crate::utils::{Buffer, DeadResource, Logical, Point, Rectangle, Size};
 use wayland_server::{
     protocol::{
         wl_buffer, wl_callback, wl_compositor, wl_output, wl_region, wl_subcompositor, wl_surface::WlSurface,
@@ -113,6 +120,21 @@ pub enum Damage {
     Buffer(Rectangle<i32, Buffer>),
 }
 
+impl Damage {
+    /// Convert this damage into buffer coordinates
+    ///
+    /// `buffer_scale` is the scale the buffer was committed with (see [`SurfaceAttributes::buffer_scale`]).
+    /// Damage already expressed in buffer coordinates is returned unchanged.
+    ///
+    /// Note: Buffer transforms are not taken into account.
+    pub fn to_buffer(&self, buffer_scale: i32) -> Rectangle<i32, Buffer> {
+        match *self {
+            Damage::Buffer(rect) => rect,
+            Damage::Surface(rect) => rect.to_buffer(buffer_scale),
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, Default)]
 struct Marker<R> {
     _r: ::std::marker::PhantomData<R>,
@@ -333,6 +355,50 @@ pub fn with_surface_tree_downward<F1, F2, F3, T>(
     PrivateSurfaceData::map_tree(surface, &initial, filter, processor, post_filter, true);
 }
 
+/// Compute the bounding box of a surface tree, including all its subsurfaces
+///
+/// `location` is the position of the root surface, the subsurfaces are placed relative to their parent
+/// according to their [`SubsurfaceCachedState`]. As the size of a surface depends on the buffer attached
+/// to it, which is managed by the compositor, it is queried using the `surface_size` closure. Surfaces
+/// without a size are considered unmapped and are skipped together with their children.
+pub fn bbox_of_surface_tree<P, F>(
+    surface: &WlSurface,
+    location: P,
+    mut surface_size: F,
+) -> Rectangle<i32, Logical>
+where
+    P: Into<Point<i32, Logical>>,
+    F: FnMut(&WlSurface, &SurfaceData) -> Option<Size<i32, Logical>>,
+{
+    let location = location.into();
+    let mut bounding_box = Rectangle::from_loc_and_size(location, (0, 0));
+    with_surface_tree_downward(
+        surface,
+        location,
+        |wl_surface, states, &loc| {
+            let mut loc = loc;
+
+            if let Some(size) = surface_size(wl_surface, states) {
+                if states.role == Some("subsurface") {
+                    let current = states.cached_state.current::<SubsurfaceCachedState>();
+                    loc += current.location;
+                }
+
+                bounding_box = bounding_box.merge(Rectangle::from_loc_and_size(loc, size));
+
+                TraversalAction::DoChildren(loc)
+            } else {
+                // If the parent surface is unmapped, then the child surfaces are hidden as
+                // well, no need to consider them here.
+                TraversalAction::SkipChildren
+            }
+        },
+        |_, _, _| {},
+        |_, _, _| true,
+    );
+    bounding_box
+}
+
 /// Retrieve the parent of this surface
 ///
 /// Returns `None` is this surface is a root surface
@@ -401,14 +467,47 @@ pub fn get_region_attributes(region: &wl_region::WlRegion) -> RegionAttributes {
     }
 }
 
-/// Register a commit hook to be invoked on surface commit
+/// Register a pre-commit hook to be invoked on surface commit
 ///
-/// For its precise semantics, see module-level documentation.
+/// Pre-commit hooks are invoked before the pending state is applied, in the order they were
+/// registered. For their precise semantics, see module-level documentation.
+pub fn add_pre_commit_hook(surface: &WlSurface, hook: fn(&WlSurface)) {
+    if !surface.as_ref().is_alive() {
+        return;
+    }
+    PrivateSurfaceData::add_pre_commit_hook(surface, hook)
+}
+
+/// Register a pre-commit hook to be invoked on surface commit
+#[deprecated(note = "Use `add_pre_commit_hook` instead")]
 pub fn add_commit_hook(surface: &WlSurface, hook: fn(&WlSurface)) {
+    add_pre_commit_hook(surface, hook)
+}
+
+/// Register a post-commit hook to be invoked when the state of a surface is applied
+///
+/// Post-commit hooks are invoked after the pending state has been applied and before your
+/// user callback, in the order they were registered. For synchronized subsurfaces, this
+/// happens on the commit of the parent applying their cached state. For their precise
+/// semantics, see module-level documentation.
+pub fn add_post_commit_hook(surface: &WlSurface, hook: fn(&WlSurface)) {
+    if !surface.as_ref().is_alive() {
+        return;
+    }
+    PrivateSurfaceData::add_post_commit_hook(surface, hook)
+}
+
+/// Register a destruction hook to be invoked when a surface is destroyed
+///
+/// Destruction hooks are invoked in the order they were registered. As the surface is already
+/// dead, they are given its [`SurfaceData`] directly, and must not call any function of this
+/// module on the surface. They are typically used by protocol extensions that need to drop the
+/// references to the surface they keep elsewhere.
+pub fn add_destruction_hook(surface: &WlSurface, hook: fn(&WlSurface, &SurfaceData)) {
     if !surface.as_ref().is_alive() {
         return;
     }
-    PrivateSurfaceData::add_commit_hook(surface, hook)
+    PrivateSurfaceData::add_destruction_hook(surface, hook)
 }
 
 /// Create new [`wl_compositor`](wayland_server::protocol::wl_compositor)
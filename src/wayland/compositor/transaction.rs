@@ -204,11 +204,18 @@ impl Transaction {
     }
 
     pub(crate) fn apply(self) {
-        for (surface, id) in self.surfaces {
-            PrivateSurfaceData::with_states(&surface, |states| {
-                states.cached_state.apply_state(id);
+        for (surface, id) in self.surfaces.iter() {
+            PrivateSurfaceData::with_states(surface, |states| {
+                states.cached_state.apply_state(*id);
             })
         }
+        // only invoke the hooks once the state of all surfaces was applied, as the hooks
+        // may inspect the state of the other surfaces of the transaction
+        for (surface, _) in self.surfaces {
+            if surface.as_ref().is_alive() {
+                PrivateSurfaceData::invoke_post_commit_hooks(&surface);
+            }
+        }
     }
 }
 
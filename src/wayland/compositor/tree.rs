@@ -28,7 +28,9 @@ pub struct PrivateSurfaceData {
     public_data: SurfaceData,
     pending_transaction: PendingTransaction,
     current_txid: Serial,
-    commit_hooks: Vec<fn(&WlSurface)>,
+    pre_commit_hooks: Vec<fn(&WlSurface)>,
+    post_commit_hooks: Vec<fn(&WlSurface)>,
+    destruction_hooks: Vec<fn(&WlSurface, &SurfaceData)>,
 }
 
 /// An error type signifying that the surface already has a role and
@@ -75,7 +77,9 @@ impl PrivateSurfaceData {
             },
             pending_transaction: Default::default(),
             current_txid: Serial(0),
-            commit_hooks: Vec::new(),
+            pre_commit_hooks: Vec::new(),
+            post_commit_hooks: Vec::new(),
+            destruction_hooks: Vec::new(),
         })
     }
 
@@ -162,17 +166,37 @@ impl PrivateSurfaceData {
         f(&my_data.public_data)
     }
 
-    pub fn add_commit_hook(surface: &WlSurface, hook: fn(&WlSurface)) {
+    pub fn add_pre_commit_hook(surface: &WlSurface, hook: fn(&WlSurface)) {
         let my_data_mutex = surface
             .as_ref()
             .user_data()
             .get::<Mutex<PrivateSurfaceData>>()
             .unwrap();
         let mut my_data = my_data_mutex.lock().unwrap();
-        my_data.commit_hooks.push(hook);
+        my_data.pre_commit_hooks.push(hook);
     }
 
-    pub fn invoke_commit_hooks(surface: &WlSurface) {
+    pub fn add_post_commit_hook(surface: &WlSurface, hook: fn(&WlSurface)) {
+        let my_data_mutex = surface
+            .as_ref()
+            .user_data()
+            .get::<Mutex<PrivateSurfaceData>>()
+            .unwrap();
+        let mut my_data = my_data_mutex.lock().unwrap();
+        my_data.post_commit_hooks.push(hook);
+    }
+
+    pub fn add_destruction_hook(surface: &WlSurface, hook: fn(&WlSurface, &SurfaceData)) {
+        let my_data_mutex = surface
+            .as_ref()
+            .user_data()
+            .get::<Mutex<PrivateSurfaceData>>()
+            .unwrap();
+        let mut my_data = my_data_mutex.lock().unwrap();
+        my_data.destruction_hooks.push(hook);
+    }
+
+    pub fn invoke_pre_commit_hooks(surface: &WlSurface) {
         // don't hold the mutex while the hooks are invoked
         let hooks = {
             let my_data_mutex = surface
@@ -181,13 +205,41 @@ impl PrivateSurfaceData {
                 .get::<Mutex<PrivateSurfaceData>>()
                 .unwrap();
             let my_data = my_data_mutex.lock().unwrap();
-            my_data.commit_hooks.clone()
+            my_data.pre_commit_hooks.clone()
         };
         for hook in hooks {
             hook(surface);
         }
     }
 
+    pub fn invoke_post_commit_hooks(surface: &WlSurface) {
+        // don't hold the mutex while the hooks are invoked
+        let hooks = {
+            let my_data_mutex = surface
+                .as_ref()
+                .user_data()
+                .get::<Mutex<PrivateSurfaceData>>()
+                .unwrap();
+            let my_data = my_data_mutex.lock().unwrap();
+            my_data.post_commit_hooks.clone()
+        };
+        for hook in hooks {
+            hook(surface);
+        }
+    }
+
+    pub fn invoke_destruction_hooks(surface: &WlSurface) {
+        let my_data_mutex = surface
+            .as_ref()
+            .user_data()
+            .get::<Mutex<PrivateSurfaceData>>()
+            .unwrap();
+        let mut my_data = my_data_mutex.lock().unwrap();
+        for hook in std::mem::take(&mut my_data.destruction_hooks) {
+            hook(surface, &my_data.public_data);
+        }
+    }
+
     pub fn commit(surface: &WlSurface) {
         let is_sync = is_effectively_sync(surface);
         let children = get_children(surface);
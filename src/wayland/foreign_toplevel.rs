@@ -0,0 +1,603 @@
+//! Utilities for handling the `wlr-foreign-toplevel-management` protocol
+//!
+//! This protocol lets privileged clients such as taskbars and docks list the toplevels of the
+//! compositor, and ask the compositor to activate, close, maximize, minimize or fullscreen them.
+//!
+//! The [`ForeignToplevelManagerState`] returned by [`init_foreign_toplevel_manager_global`] lets you
+//! announce each of your windows to these clients using [`ForeignToplevelManagerState::new_toplevel`].
+//! You then keep the title, app id, outputs and states of the returned [`ForeignToplevelHandle`] up to
+//! date. Changes are double-buffered: they are only sent to the clients on
+//! [`ForeignToplevelHandle::send_state`], so that they observe them atomically.
+//!
+//! The requests of the clients are forwarded to your callback as [`ForeignToplevelRequest`]s. The
+//! compositor is free to honor them or not, for example by focusing the window on
+//! [`ForeignToplevelRequest::Activate`] and then marking it as activated.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::wayland::foreign_toplevel::{init_foreign_toplevel_manager_global, ForeignToplevelRequest};
+//!
+//! # let mut display = wayland_server::Display::new();
+//! let (state, _) = init_foreign_toplevel_manager_global(
+//!     &mut display,
+//!     |request, _dispatch_data| match request {
+//!         ForeignToplevelRequest::Activate { toplevel, .. } => {
+//!             /* focus the corresponding window */
+//!             toplevel.set_activated(true);
+//!             toplevel.send_state();
+//!         }
+//!         _ => { /* ignore the other requests */ }
+//!     },
+//!     None, // put a logger if you want
+//! );
+//!
+//! // for every window of the compositor
+//! let toplevel = state.lock().unwrap().new_toplevel("Terminal", "org.example.terminal");
+//!
+//! // once the window is closed
+//! toplevel.close();
+//! ```
+
+use std::{
+    cell::RefCell,
+    ops::Deref as _,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+use wayland_protocols::wlr::unstable::foreign_toplevel::v1::server::{
+    zwlr_foreign_toplevel_handle_v1::{self, ZwlrForeignToplevelHandleV1},
+    zwlr_foreign_toplevel_manager_v1::{self, ZwlrForeignToplevelManagerV1},
+};
+use wayland_server::{
+    protocol::{wl_output::WlOutput, wl_seat::WlSeat, wl_surface::WlSurface},
+    DispatchData, Display, Filter, Global, Main,
+};
+
+use crate::{
+    utils::{Logical, Rectangle},
+    wayland::output::Output,
+};
+
+/// Requests of the clients regarding a toplevel
+#[derive(Debug)]
+pub enum ForeignToplevelRequest {
+    /// A client asked to activate the toplevel
+    Activate {
+        /// The toplevel to activate
+        toplevel: ForeignToplevelHandle,
+        /// The seat the activation was requested on
+        seat: WlSeat,
+    },
+    /// A client asked to close the toplevel
+    Close {
+        /// The toplevel to close
+        toplevel: ForeignToplevelHandle,
+    },
+    /// A client asked to maximize or unmaximize the toplevel
+    SetMaximized {
+        /// The toplevel
+        toplevel: ForeignToplevelHandle,
+        /// Whether it should be maximized
+        maximized: bool,
+    },
+    /// A client asked to minimize or unminimize the toplevel
+    SetMinimized {
+        /// The toplevel
+        toplevel: ForeignToplevelHandle,
+        /// Whether it should be minimized
+        minimized: bool,
+    },
+    /// A client asked to make the toplevel fullscreen or not
+    SetFullscreen {
+        /// The toplevel
+        toplevel: ForeignToplevelHandle,
+        /// Whether it should be fullscreen
+        fullscreen: bool,
+        /// The output the client would like the toplevel to be fullscreen on
+        output: Option<WlOutput>,
+    },
+    /// A client set the rectangle representing the toplevel in its own surface
+    ///
+    /// It can be used as a hint, for example to animate the minimization of the toplevel.
+    SetRectangle {
+        /// The toplevel
+        toplevel: ForeignToplevelHandle,
+        /// The surface of the client representing the toplevel
+        surface: WlSurface,
+        /// The rectangle in surface-local coordinates, `None` if it was removed
+        rectangle: Option<Rectangle<i32, Logical>>,
+    },
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct ToplevelState {
+    title: String,
+    app_id: String,
+    outputs: Vec<Output>,
+    maximized: bool,
+    minimized: bool,
+    activated: bool,
+    fullscreen: bool,
+}
+
+impl ToplevelState {
+    // the `state` event encodes the states as an array of native-endian u32
+    fn states(&self, version: u32) -> Vec<u8> {
+        use zwlr_foreign_toplevel_handle_v1::State;
+        let states = [
+            (self.maximized, State::Maximized),
+            (self.minimized, State::Minimized),
+            (self.activated, State::Activated),
+            (self.fullscreen && version >= 2, State::Fullscreen),
+        ];
+        states
+            .iter()
+            .filter(|(set, _)| *set)
+            .flat_map(|(_, state)| (*state as u32).to_ne_bytes().to_vec())
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+struct ToplevelInner {
+    instances: Vec<ZwlrForeignToplevelHandleV1>,
+    current: ToplevelState,
+    pending: ToplevelState,
+    closed: bool,
+}
+
+/// A toplevel of the compositor, as advertised to the clients of the protocol
+///
+/// It can be cloned and all clones refer to the same toplevel.
+#[derive(Debug, Clone)]
+pub struct ForeignToplevelHandle {
+    inner: Arc<Mutex<ToplevelInner>>,
+}
+
+impl PartialEq for ForeignToplevelHandle {
+    fn eq(&self, other: &ForeignToplevelHandle) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl ForeignToplevelHandle {
+    /// Set the title of the toplevel
+    pub fn set_title(&self, title: impl Into<String>) {
+        self.inner.lock().unwrap().pending.title = title.into();
+    }
+
+    /// Set the app id of the toplevel
+    pub fn set_app_id(&self, app_id: impl Into<String>) {
+        self.inner.lock().unwrap().pending.app_id = app_id.into();
+    }
+
+    /// Mark the toplevel as visible on an output
+    pub fn output_enter(&self, output: &Output) {
+        let mut inner = self.inner.lock().unwrap();
+        if !inner.pending.outputs.contains(output) {
+            inner.pending.outputs.push(output.clone());
+        }
+    }
+
+    /// Mark the toplevel as no longer visible on an output
+    pub fn output_leave(&self, output: &Output) {
+        self.inner.lock().unwrap().pending.outputs.retain(|o| o != output);
+    }
+
+    /// Set whether the toplevel is maximized
+    pub fn set_maximized(&self, maximized: bool) {
+        self.inner.lock().unwrap().pending.maximized = maximized;
+    }
+
+    /// Set whether the toplevel is minimized
+    pub fn set_minimized(&self, minimized: bool) {
+        self.inner.lock().unwrap().pending.minimized = minimized;
+    }
+
+    /// Set whether the toplevel is activated, usually meaning it has the keyboard focus
+    pub fn set_activated(&self, activated: bool) {
+        self.inner.lock().unwrap().pending.activated = activated;
+    }
+
+    /// Set whether the toplevel is fullscreen
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        self.inner.lock().unwrap().pending.fullscreen = fullscreen;
+    }
+
+    /// Whether the toplevel is activated, as last sent to the clients
+    pub fn activated(&self) -> bool {
+        self.inner.lock().unwrap().current.activated
+    }
+
+    /// Send the pending changes to the clients
+    ///
+    /// Nothing is sent if the pending state is the same as the one last sent.
+    pub fn send_state(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed || inner.pending == inner.current {
+            return;
+        }
+        for instance in inner.instances.iter() {
+            send_changes(instance, &inner.current, &inner.pending);
+        }
+        inner.current = inner.pending.clone();
+    }
+
+    /// Notify the clients that the toplevel was closed
+    ///
+    /// The toplevel is no longer advertised to new clients, and the requests of the clients about it
+    /// are ignored.
+    pub fn close(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.closed {
+            return;
+        }
+        inner.closed = true;
+        for instance in inner.instances.drain(..) {
+            instance.closed();
+        }
+    }
+
+    /// Whether [`ForeignToplevelHandle::close`] was called on this toplevel
+    pub fn is_closed(&self) -> bool {
+        self.inner.lock().unwrap().closed
+    }
+
+    fn from_resource(resource: &ZwlrForeignToplevelHandleV1) -> Option<ForeignToplevelHandle> {
+        resource
+            .as_ref()
+            .user_data()
+            .get::<ForeignToplevelHandle>()
+            .cloned()
+    }
+}
+
+// send the events turning `old` into `new`, followed by `done`
+fn send_changes(instance: &ZwlrForeignToplevelHandleV1, old: &ToplevelState, new: &ToplevelState) {
+    if old.title != new.title {
+        instance.title(new.title.clone());
+    }
+    if old.app_id != new.app_id {
+        instance.app_id(new.app_id.clone());
+    }
+    if let Some(client) = instance.as_ref().client() {
+        for output in new.outputs.iter().filter(|o| !old.outputs.contains(o)) {
+            output.with_client_outputs(client.clone(), |wl_output| instance.output_enter(wl_output));
+        }
+        for output in old.outputs.iter().filter(|o| !new.outputs.contains(o)) {
+            output.with_client_outputs(client.clone(), |wl_output| instance.output_leave(wl_output));
+        }
+    }
+    let version = instance.as_ref().version();
+    let states = new.states(version);
+    if old.states(version) != states {
+        instance.state(states);
+    }
+    instance.done();
+}
+
+type Impl = dyn FnMut(ForeignToplevelRequest, DispatchData<'_>);
+
+/// State of the foreign toplevel manager
+#[derive(Debug)]
+pub struct ForeignToplevelManagerState {
+    log: ::slog::Logger,
+    managers: Vec<ZwlrForeignToplevelManagerV1>,
+    toplevels: Vec<ForeignToplevelHandle>,
+}
+
+impl ForeignToplevelManagerState {
+    /// Advertise a new toplevel to the clients
+    pub fn new_toplevel(
+        &mut self,
+        title: impl Into<String>,
+        app_id: impl Into<String>,
+    ) -> ForeignToplevelHandle {
+        let state = ToplevelState {
+            title: title.into(),
+            app_id: app_id.into(),
+            ..Default::default()
+        };
+        let toplevel = ForeignToplevelHandle {
+            inner: Arc::new(Mutex::new(ToplevelInner {
+                instances: Vec::new(),
+                current: state.clone(),
+                pending: state,
+                closed: false,
+            })),
+        };
+        slog::debug!(self.log, "New foreign toplevel");
+
+        self.toplevels.retain(|toplevel| !toplevel.is_closed());
+        self.toplevels.push(toplevel.clone());
+        for manager in self.managers.iter() {
+            announce_toplevel(manager, &toplevel);
+        }
+        toplevel
+    }
+
+    /// Iterate over the toplevels that were not closed
+    ///
+    /// They are ordered from bottom to top, see [`ForeignToplevelManagerState::set_order`].
+    pub fn toplevels(&self) -> impl Iterator<Item = &ForeignToplevelHandle> {
+        self.toplevels.iter().filter(|toplevel| !toplevel.is_closed())
+    }
+
+    /// Set the stacking order of the toplevels, from bottom to top
+    ///
+    /// Clients binding the manager receive the toplevels in this order. Toplevels missing from
+    /// `order` keep their relative order below the provided ones.
+    ///
+    /// The protocol has no event to reorder the toplevels already known to a client, it only learns
+    /// the order in which they are announced. Clients that already bound the manager are thus not
+    /// notified, as doing so would require closing and announcing again all their toplevels.
+    pub fn set_order(&mut self, order: &[ForeignToplevelHandle]) {
+        self.toplevels
+            .retain(|toplevel| !toplevel.is_closed() && !order.contains(toplevel));
+        self.toplevels
+            .extend(order.iter().filter(|toplevel| !toplevel.is_closed()).cloned());
+    }
+
+    /// Move a toplevel to the top of the stacking order
+    pub fn raise_toplevel(&mut self, toplevel: &ForeignToplevelHandle) {
+        if let Some(index) = self.toplevels.iter().position(|t| t == toplevel) {
+            let toplevel = self.toplevels.remove(index);
+            self.toplevels.push(toplevel);
+        }
+    }
+
+    /// Move a toplevel to the bottom of the stacking order
+    pub fn lower_toplevel(&mut self, toplevel: &ForeignToplevelHandle) {
+        if let Some(index) = self.toplevels.iter().position(|t| t == toplevel) {
+            let toplevel = self.toplevels.remove(index);
+            self.toplevels.insert(0, toplevel);
+        }
+    }
+}
+
+// create a handle for the toplevel in the client of the manager and send its current state
+fn announce_toplevel(manager: &ZwlrForeignToplevelManagerV1, toplevel: &ForeignToplevelHandle) {
+    let (client, implementation) = match (
+        manager.as_ref().client(),
+        manager.as_ref().user_data().get::<Rc<RefCell<Impl>>>(),
+    ) {
+        (Some(client), Some(implementation)) => (client, implementation.clone()),
+        _ => return,
+    };
+    let instance = match client.create_resource::<ZwlrForeignToplevelHandleV1>(manager.as_ref().version()) {
+        Some(instance) => instance,
+        None => return,
+    };
+
+    instance.quick_assign(move |instance, request, ddata| {
+        let toplevel = match ForeignToplevelHandle::from_resource(&instance) {
+            Some(toplevel) if !toplevel.is_closed() => toplevel,
+            // requests on closed toplevels are ignored
+            _ => return,
+        };
+        let request = match request {
+            zwlr_foreign_toplevel_handle_v1::Request::Activate { seat } => {
+                ForeignToplevelRequest::Activate { toplevel, seat }
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::Close => ForeignToplevelRequest::Close { toplevel },
+            zwlr_foreign_toplevel_handle_v1::Request::SetMaximized => ForeignToplevelRequest::SetMaximized {
+                toplevel,
+                maximized: true,
+            },
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetMaximized => {
+                ForeignToplevelRequest::SetMaximized {
+                    toplevel,
+                    maximized: false,
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::SetMinimized => ForeignToplevelRequest::SetMinimized {
+                toplevel,
+                minimized: true,
+            },
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetMinimized => {
+                ForeignToplevelRequest::SetMinimized {
+                    toplevel,
+                    minimized: false,
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::SetFullscreen { output } => {
+                ForeignToplevelRequest::SetFullscreen {
+                    toplevel,
+                    fullscreen: true,
+                    output,
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::UnsetFullscreen => {
+                ForeignToplevelRequest::SetFullscreen {
+                    toplevel,
+                    fullscreen: false,
+                    output: None,
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::SetRectangle {
+                surface,
+                x,
+                y,
+                width,
+                height,
+            } => {
+                if width < 0 || height < 0 {
+                    instance.as_ref().post_error(
+                        zwlr_foreign_toplevel_handle_v1::Error::InvalidRectangle as u32,
+                        format!("Invalid rectangle size {}x{}.", width, height),
+                    );
+                    return;
+                }
+                let rectangle = if width == 0 && height == 0 {
+                    None
+                } else {
+                    Some(Rectangle::from_loc_and_size((x, y), (width, height)))
+                };
+                ForeignToplevelRequest::SetRectangle {
+                    toplevel,
+                    surface,
+                    rectangle,
+                }
+            }
+            zwlr_foreign_toplevel_handle_v1::Request::Destroy => {
+                // all is handled by our destructor
+                return;
+            }
+            _ => unreachable!(),
+        };
+        (&mut *implementation.borrow_mut())(request, ddata);
+    });
+    instance.assign_destructor(Filter::new(|instance: ZwlrForeignToplevelHandleV1, _, _| {
+        if let Some(toplevel) = ForeignToplevelHandle::from_resource(&instance) {
+            toplevel
+                .inner
+                .lock()
+                .unwrap()
+                .instances
+                .retain(|i| !i.as_ref().equals(instance.as_ref()));
+        }
+    }));
+    let instance = instance.deref().clone();
+    let handle = toplevel.clone();
+    instance.as_ref().user_data().set(move || handle);
+
+    manager.toplevel(&instance);
+    let mut inner = toplevel.inner.lock().unwrap();
+    // send the whole current state, as a change from an empty state
+    send_changes(&instance, &ToplevelState::default(), &inner.current);
+    inner.instances.push(instance);
+}
+
+/// Creates a new `zwlr_foreign_toplevel_manager_v1` global
+///
+/// The provided callback is invoked with the requests of the clients about the toplevels.
+///
+/// This protocol exposes all the windows of the compositor, you may want to only expose it to
+/// trusted clients.
+pub fn init_foreign_toplevel_manager_global<L, F>(
+    display: &mut Display,
+    implementation: F,
+    logger: L,
+) -> (
+    Arc<Mutex<ForeignToplevelManagerState>>,
+    Global<ZwlrForeignToplevelManagerV1>,
+)
+where
+    L: Into<Option<::slog::Logger>>,
+    F: FnMut(ForeignToplevelRequest, DispatchData<'_>) + 'static,
+{
+    let log = crate::slog_or_fallback(logger);
+    let implementation = Rc::new(RefCell::new(implementation)) as Rc<RefCell<Impl>>;
+
+    let state = Arc::new(Mutex::new(ForeignToplevelManagerState {
+        log: log.new(slog::o!("smithay_module" => "foreign_toplevel_handler")),
+        managers: Vec::new(),
+        toplevels: Vec::new(),
+    }));
+
+    let global_state = state.clone();
+    let global = display.create_global(
+        2,
+        Filter::new(
+            move |(manager, _version): (Main<ZwlrForeignToplevelManagerV1>, _), _, _| {
+                let state = global_state.clone();
+                manager.quick_assign(move |manager, request, _| match request {
+                    zwlr_foreign_toplevel_manager_v1::Request::Stop => {
+                        state
+                            .lock()
+                            .unwrap()
+                            .managers
+                            .retain(|m| !m.as_ref().equals(manager.as_ref()));
+                        manager.finished();
+                    }
+                    _ => unreachable!(),
+                });
+                let state = global_state.clone();
+                manager.assign_destructor(Filter::new(move |manager: ZwlrForeignToplevelManagerV1, _, _| {
+                    state
+                        .lock()
+                        .unwrap()
+                        .managers
+                        .retain(|m| !m.as_ref().equals(manager.as_ref()));
+                }));
+
+                let implementation = implementation.clone();
+                manager.as_ref().user_data().set(move || implementation);
+
+                let mut guard = global_state.lock().unwrap();
+                slog::debug!(guard.log, "New foreign toplevel manager");
+                let manager = manager.deref().clone();
+                guard.toplevels.retain(|toplevel| !toplevel.is_closed());
+                for toplevel in guard.toplevels.iter() {
+                    announce_toplevel(&manager, toplevel);
+                }
+                guard.managers.push(manager);
+            },
+        ),
+    );
+
+    (state, global)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_state(toplevels: Vec<ForeignToplevelHandle>) -> ForeignToplevelManagerState {
+        ForeignToplevelManagerState {
+            log: crate::slog_or_fallback(None),
+            managers: Vec::new(),
+            toplevels,
+        }
+    }
+
+    fn test_toplevel() -> ForeignToplevelHandle {
+        ForeignToplevelHandle {
+            inner: Arc::new(Mutex::new(ToplevelInner {
+                instances: Vec::new(),
+                current: ToplevelState::default(),
+                pending: ToplevelState::default(),
+                closed: false,
+            })),
+        }
+    }
+
+    #[test]
+    fn stacking_order() {
+        let (a, b, c) = (test_toplevel(), test_toplevel(), test_toplevel());
+        let mut state = test_state(vec![a.clone(), b.clone(), c.clone()]);
+
+        state.raise_toplevel(&a);
+        assert_eq!(
+            state.toplevels().cloned().collect::<Vec<_>>(),
+            vec![b.clone(), c.clone(), a.clone()]
+        );
+        state.lower_toplevel(&c);
+        assert_eq!(
+            state.toplevels().cloned().collect::<Vec<_>>(),
+            vec![c.clone(), b.clone(), a.clone()]
+        );
+
+        // closed toplevels are dropped, missing ones stay below the ordered ones
+        b.close();
+        state.set_order(&[a.clone(), b.clone()]);
+        assert_eq!(state.toplevels().cloned().collect::<Vec<_>>(), vec![c, a]);
+    }
+
+    #[test]
+    fn states_encoding() {
+        let state = ToplevelState {
+            activated: true,
+            fullscreen: true,
+            ..Default::default()
+        };
+        let activated = 2u32.to_ne_bytes().to_vec();
+        let fullscreen = 3u32.to_ne_bytes().to_vec();
+
+        assert_eq!(state.states(2), [activated.clone(), fullscreen].concat());
+        // the fullscreen state only exists since version 2
+        assert_eq!(state.states(1), activated);
+        assert!(ToplevelState::default().states(2).is_empty());
+    }
+}
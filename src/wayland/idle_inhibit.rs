@@ -0,0 +1,202 @@
+//! Utilities for handling the `idle_inhibit` protocol
+//!
+//! Clients such as video players can use this protocol to prevent the compositor from blanking the
+//! screen or locking the session while one of their surfaces is visible. Each inhibitor is attached
+//! to a surface, and should only be honored while this surface is visible to the user.
+//!
+//! The [`IdleInhibitState`] returned by [`init_idle_inhibit_manager_global`] tracks all inhibitors,
+//! and lets you check whether any of them inhibits idling before firing your idle timeout. You can
+//! also provide a callback to be notified of new and destroyed inhibitors, for example to reset
+//! your idle timer.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::wayland::idle_inhibit::{init_idle_inhibit_manager_global, IdleInhibitEvent};
+//!
+//! # let mut display = wayland_server::Display::new();
+//! let (state, _) = init_idle_inhibit_manager_global(
+//!     &mut display,
+//!     |event, _dispatch_data| match event {
+//!         IdleInhibitEvent::Inhibit { surface } => { /* reset the idle timer */ }
+//!         IdleInhibitEvent::Uninhibit { surface } => { /* the surface no longer inhibits idling */ }
+//!     },
+//!     None, // put a logger if you want
+//! );
+//!
+//! // when the idle timeout is about to fire
+//! let inhibited = state.lock().unwrap().is_inhibited(|surface| {
+//!     // check whether the surface is currently visible
+//! #   true
+//! });
+//! ```
+
+use std::{
+    cell::RefCell,
+    ops::Deref as _,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
+
+use wayland_protocols::unstable::idle_inhibit::v1::server::{
+    zwp_idle_inhibit_manager_v1::{self, ZwpIdleInhibitManagerV1},
+    zwp_idle_inhibitor_v1::{self, ZwpIdleInhibitorV1},
+};
+use wayland_server::{protocol::wl_surface::WlSurface, DispatchData, Display, Filter, Global, Main};
+
+use crate::wayland::compositor::{self, SurfaceData};
+
+/// Events generated by the idle inhibit manager
+#[derive(Debug)]
+pub enum IdleInhibitEvent {
+    /// A client attached a new idle inhibitor to a surface
+    Inhibit {
+        /// The surface the inhibitor is attached to
+        surface: WlSurface,
+    },
+    /// An idle inhibitor was destroyed
+    ///
+    /// The inhibitors of a destroyed surface are dropped from the [`IdleInhibitState`] without
+    /// this event, as the surface can no longer be visible.
+    Uninhibit {
+        /// The surface the inhibitor was attached to
+        surface: WlSurface,
+    },
+}
+
+/// Tracks the idle inhibitors of the clients
+#[derive(Debug)]
+pub struct IdleInhibitState {
+    log: ::slog::Logger,
+    inhibitors: Vec<(ZwpIdleInhibitorV1, WlSurface)>,
+}
+
+impl IdleInhibitState {
+    /// Check whether idling is inhibited
+    ///
+    /// Only inhibitors attached to surfaces matching the provided filter are taken into account,
+    /// you should use it to check whether the surface is currently visible.
+    pub fn is_inhibited<F>(&self, filter: F) -> bool
+    where
+        F: Fn(&WlSurface) -> bool,
+    {
+        self.surfaces().any(|surface| filter(surface))
+    }
+
+    /// Iterate over the surfaces that have an idle inhibitor attached
+    pub fn surfaces(&self) -> impl Iterator<Item = &WlSurface> {
+        self.inhibitors.iter().map(|(_, surface)| surface)
+    }
+}
+
+// Stored in the data of the surfaces with inhibitors, to drop them once the surface is destroyed
+struct InhibitedSurface(Arc<Mutex<IdleInhibitState>>);
+
+fn destruction_hook(surface: &WlSurface, states: &SurfaceData) {
+    if let Some(InhibitedSurface(state)) = states.data_map.get::<InhibitedSurface>() {
+        let mut guard = state.lock().unwrap();
+        slog::debug!(guard.log, "Dropping the idle inhibitors of a destroyed surface");
+        guard
+            .inhibitors
+            .retain(|(_, s)| !s.as_ref().equals(surface.as_ref()));
+    }
+}
+
+type Impl = dyn FnMut(IdleInhibitEvent, DispatchData<'_>);
+
+/// Creates a new `zwp_idle_inhibit_manager_v1` global
+///
+/// The provided callback is notified of every new and destroyed inhibitor.
+pub fn init_idle_inhibit_manager_global<L, F>(
+    display: &mut Display,
+    implementation: F,
+    logger: L,
+) -> (Arc<Mutex<IdleInhibitState>>, Global<ZwpIdleInhibitManagerV1>)
+where
+    L: Into<Option<::slog::Logger>>,
+    F: FnMut(IdleInhibitEvent, DispatchData<'_>) + 'static,
+{
+    let log = crate::slog_or_fallback(logger);
+    let implementation = Rc::new(RefCell::new(implementation)) as Rc<RefCell<Impl>>;
+
+    let state = Arc::new(Mutex::new(IdleInhibitState {
+        log: log.new(slog::o!("smithay_module" => "idle_inhibit_handler")),
+        inhibitors: Vec::new(),
+    }));
+
+    let global_state = state.clone();
+    let global = display.create_global(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<ZwpIdleInhibitManagerV1>, _), _, _| {
+                let state = global_state.clone();
+                let implementation = implementation.clone();
+                manager.quick_assign(move |_, request, ddata| match request {
+                    zwp_idle_inhibit_manager_v1::Request::CreateInhibitor { id, surface } => {
+                        implement_inhibitor(id, surface, state.clone(), implementation.clone(), ddata);
+                    }
+                    zwp_idle_inhibit_manager_v1::Request::Destroy => {
+                        // all is already handled by our destructor
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    );
+
+    (state, global)
+}
+
+fn implement_inhibitor(
+    inhibitor: Main<ZwpIdleInhibitorV1>,
+    surface: WlSurface,
+    state: Arc<Mutex<IdleInhibitState>>,
+    implementation: Rc<RefCell<Impl>>,
+    ddata: DispatchData<'_>,
+) {
+    inhibitor.quick_assign(|_, request, _| match request {
+        zwp_idle_inhibitor_v1::Request::Destroy => {
+            // all is already handled by our destructor
+        }
+        _ => unreachable!(),
+    });
+
+    let first = compositor::with_states(&surface, |states| {
+        let first = states.data_map.get::<InhibitedSurface>().is_none();
+        states
+            .data_map
+            .insert_if_missing(|| InhibitedSurface(state.clone()));
+        first
+    });
+    match first {
+        Ok(first) => {
+            if first {
+                compositor::add_destruction_hook(&surface, destruction_hook);
+            }
+        }
+        // the surface is already destroyed, the inhibitor is inert
+        Err(_) => return,
+    }
+
+    {
+        let mut guard = state.lock().unwrap();
+        slog::debug!(guard.log, "New idle inhibitor");
+        guard
+            .inhibitors
+            .push((inhibitor.deref().clone(), surface.clone()));
+    }
+    (&mut *implementation.borrow_mut())(IdleInhibitEvent::Inhibit { surface }, ddata);
+
+    inhibitor.assign_destructor(Filter::new(move |inhibitor: ZwpIdleInhibitorV1, _, ddata| {
+        let removed = {
+            let mut guard = state.lock().unwrap();
+            let index = guard
+                .inhibitors
+                .iter()
+                .position(|(i, _)| i.as_ref().equals(inhibitor.as_ref()));
+            index.map(|index| guard.inhibitors.remove(index).1)
+        };
+        if let Some(surface) = removed {
+            (&mut *implementation.borrow_mut())(IdleInhibitEvent::Uninhibit { surface }, ddata);
+        }
+    }));
+}
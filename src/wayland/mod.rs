@@ -58,14 +58,24 @@ pub mod compositor;
 pub mod data_device;
 pub mod dmabuf;
 pub mod explicit_synchronization;
+pub mod foreign_toplevel;
+pub mod idle_inhibit;
 pub mod output;
+pub mod pointer_constraints;
+pub mod presentation;
+pub mod primary_selection;
+pub mod relative_pointer;
 pub mod seat;
 pub mod shell;
 pub mod shm;
 pub mod tablet_manager;
+pub mod viewporter;
 pub mod xdg_activation;
 pub mod xdg_foreign;
 
+#[cfg(test)]
+mod test_client;
+
 /// A global [`SerialCounter`] for use in your compositor.
 ///
 /// Is is also used internally by some parts of Smithay.
@@ -66,6 +66,9 @@ use wayland_server::{
 
 use slog::{info, o, trace, warn};
 
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
 use crate::utils::{Logical, Physical, Point, Raw, Size};
 
 use self::xdg::XdgOutput;
@@ -77,6 +80,7 @@ use self::xdg::XdgOutput;
 /// This should only describe the characteristics of the video driver,
 /// not taking into account any global scaling.
 #[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Mode {
     /// The size of the mode, in pixels
     pub size: Size<i32, Physical>,
@@ -99,6 +103,77 @@ pub struct PhysicalProperties {
     pub model: String,
 }
 
+/// A snapshot of the configuration of an output
+///
+/// It only consists of plain data, so compositors may store it (e.g. to remember the arrangement
+/// of the outputs across restarts) and re-apply it later using [`Output::apply_state`] or
+/// [`restore_layout`].
+///
+/// With the `serde` feature, it can be serialized and deserialized.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct OutputState {
+    /// Name of the output
+    pub name: String,
+    /// Textual representation of the make of the monitor
+    pub make: String,
+    /// Textual representation of the model of the monitor
+    pub model: String,
+    /// Current mode of the output
+    pub mode: Option<Mode>,
+    /// Global transformation of the output
+    #[cfg_attr(feature = "serde", serde(with = "serde_transform"))]
+    pub transform: Transform,
+    /// Global scaling factor of the output
+    pub scale: i32,
+    /// Location of the output in the global space
+    pub location: Point<i32, Logical>,
+}
+
+impl OutputState {
+    /// Checks if this state was taken from an output with the same name and monitor
+    pub fn matches(&self, output: &Output) -> bool {
+        let inner = output.inner.lock().unwrap();
+        inner.name == self.name && inner.physical.make == self.make && inner.physical.model == self.model
+    }
+}
+
+/// `wl_output::Transform` is generated by the scanner, it is stored as its protocol value
+#[cfg(feature = "serde")]
+mod serde_transform {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use wayland_server::protocol::wl_output::Transform;
+
+    pub fn serialize<S: Serializer>(transform: &Transform, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(transform.to_raw())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Transform, D::Error> {
+        let raw = u32::deserialize(deserializer)?;
+        Transform::from_raw(raw).ok_or_else(|| D::Error::custom(format!("invalid output transform {}", raw)))
+    }
+}
+
+/// Applies a saved output layout to the given outputs
+///
+/// Every state is applied to the output it [matches](OutputState::matches), if any.
+/// The states that could not be applied to any output are returned.
+pub fn restore_layout<'a>(
+    outputs: impl IntoIterator<Item = &'a Output>,
+    layout: &[OutputState],
+) -> Vec<OutputState> {
+    let outputs = outputs.into_iter().collect::<Vec<_>>();
+
+    let mut unmatched = Vec::new();
+    for state in layout {
+        match outputs.iter().find(|output| state.matches(output)) {
+            Some(output) => output.apply_state(state),
+            None => unmatched.push(state.clone()),
+        }
+    }
+    unmatched
+}
+
 #[derive(Debug)]
 struct Inner {
     name: String,
@@ -166,11 +241,19 @@ impl Inner {
 ///
 /// This handle is stored in the event loop, and allows you to notify clients
 /// about any change in the properties of this output.
-#[derive(Debug)]
+///
+/// It can be cloned and all clones refer to the same output.
+#[derive(Debug, Clone)]
 pub struct Output {
     inner: Arc<Mutex<Inner>>,
 }
 
+impl PartialEq for Output {
+    fn eq(&self, other: &Output) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
 impl Output {
     /// Create a new output global with given name and physical properties
     ///
@@ -281,6 +364,9 @@ impl Output {
     /// If the provided mode was not previously known to this output, it is added to its
     /// internal list.
     ///
+    /// Values equal to the current state are ignored as well, so clients only receive the
+    /// events of the properties that actually changed. If nothing changed, no events are sent.
+    ///
     /// By default, transform status is `Normal`, and scale is `1`.
     pub fn change_current_state(
         &self,
@@ -290,6 +376,15 @@ impl Output {
         new_location: Option<Point<i32, Logical>>,
     ) {
         let mut inner = self.inner.lock().unwrap();
+
+        let new_mode = new_mode.filter(|&mode| inner.current_mode != Some(mode));
+        let new_transform = new_transform.filter(|&transform| inner.transform != transform);
+        let new_scale = new_scale.filter(|&scale| inner.scale != scale);
+        let new_location = new_location.filter(|&location| inner.location != location);
+        if new_mode.is_none() && new_transform.is_none() && new_scale.is_none() && new_location.is_none() {
+            return;
+        }
+
         if let Some(mode) = new_mode {
             if inner.modes.iter().all(|&m| m != mode) {
                 inner.modes.push(mode);
@@ -334,6 +429,38 @@ impl Output {
         }
     }
 
+    /// Returns the name of the output
+    pub fn name(&self) -> String {
+        self.inner.lock().unwrap().name.clone()
+    }
+
+    /// Returns a snapshot of the current configuration of the output
+    pub fn state(&self) -> OutputState {
+        let inner = self.inner.lock().unwrap();
+        OutputState {
+            name: inner.name.clone(),
+            make: inner.physical.make.clone(),
+            model: inner.physical.model.clone(),
+            mode: inner.current_mode,
+            transform: inner.transform,
+            scale: inner.scale,
+            location: inner.location,
+        }
+    }
+
+    /// Applies a previously taken snapshot of the configuration to this output
+    ///
+    /// Only the mode, transform, scale and location are changed, see
+    /// [`Output::change_current_state`].
+    pub fn apply_state(&self, state: &OutputState) {
+        self.change_current_state(
+            state.mode,
+            Some(state.transform),
+            Some(state.scale),
+            Some(state.location),
+        );
+    }
+
     /// Check is given [`wl_output`](WlOutput) instance is managed by this [`Output`].
     pub fn owns(&self, output: &WlOutput) -> bool {
         self.inner
@@ -378,3 +505,73 @@ impl Output {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(display: &mut Display, name: &str, model: &str) -> Output {
+        let (output, _global) = Output::new(
+            display,
+            name.into(),
+            PhysicalProperties {
+                size: (600, 340).into(),
+                subpixel: Subpixel::Unknown,
+                make: "Smithay".into(),
+                model: model.into(),
+            },
+            None,
+        );
+        output
+    }
+
+    #[test]
+    fn state_matches_same_name_and_monitor() {
+        let mut display = Display::new();
+        let output = output(&mut display, "HDMI-A-1", "Monitor A");
+        let state = output.state();
+        assert!(state.matches(&output));
+
+        let other_monitor = self::output(&mut display, "HDMI-A-1", "Monitor B");
+        assert!(!state.matches(&other_monitor));
+        let other_connector = self::output(&mut display, "DP-1", "Monitor A");
+        assert!(!state.matches(&other_connector));
+    }
+
+    #[test]
+    fn restore_saved_layout() {
+        let mut display = Display::new();
+        let left = output(&mut display, "HDMI-A-1", "Monitor A");
+        let right = output(&mut display, "DP-1", "Monitor B");
+
+        let mode = Mode {
+            size: (1920, 1080).into(),
+            refresh: 60000,
+        };
+        left.change_current_state(Some(mode), None, None, Some((0, 0).into()));
+        right.change_current_state(Some(mode), Some(Transform::_90), Some(2), Some((1920, 0).into()));
+        let unplugged = OutputState {
+            name: "DP-2".into(),
+            ..left.state()
+        };
+        let layout = vec![left.state(), right.state(), unplugged.clone()];
+
+        let other_mode = Mode {
+            size: (1280, 720).into(),
+            refresh: 60000,
+        };
+        for output in [&left, &right].iter() {
+            output.change_current_state(
+                Some(other_mode),
+                Some(Transform::Normal),
+                Some(1),
+                Some((-1280, 0).into()),
+            );
+        }
+
+        let unmatched = restore_layout(vec![&right, &left], &layout);
+        assert_eq!(unmatched, vec![unplugged]);
+        assert_eq!(left.state(), layout[0]);
+        assert_eq!(right.state(), layout[1]);
+    }
+}
@@ -0,0 +1,654 @@
+//! Utilities for handling pointer locks and confinements with the `pointer_constraints` protocol
+//!
+//! Clients such as games or 3D applications can request the pointer to be locked in place or
+//! confined to a region of one of their surfaces. Each such request creates an inactive
+//! [`PointerConstraint`], and it is up to the compositor to decide when to activate it,
+//! usually once the constrained surface has gained the pointer focus. You are notified of every
+//! new constraint through the callback given to [`init_pointer_constraints_global`], and can
+//! query the constraint of a surface with [`get_pointer_constraint`] at any time.
+//!
+//! While a constraint is active, the constrained surface keeps the pointer focus and
+//! [`PointerHandle::motion`] ignores any motion of a locked pointer, while a confined pointer
+//! that would leave its region is moved to the nearest point inside of it instead. Use
+//! [`PointerHandle::current_location`] to get the location the pointer ended up at.
+//!
+//! A constraint is deactivated when its surface loses the pointer focus or is destroyed, or when
+//! the compositor calls [`PointerConstraint::deactivate`]. A persistent constraint can then be
+//! activated again, while a oneshot constraint becomes defunct.
+//!
+//! ### Example
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::wayland::pointer_constraints::init_pointer_constraints_global;
+//!
+//! # let mut display = wayland_server::Display::new();
+//! init_pointer_constraints_global(
+//!     &mut display,
+//!     |constraint, _dispatch_data| {
+//!         // check whether the surface of the constraint currently has the pointer focus,
+//!         // and activate the constraint if it does
+//!         constraint.activate();
+//!     },
+//!     None, // put a logger if you want
+//! );
+//! ```
+
+use std::{cell::RefCell, ops::Deref as _, rc::Rc};
+
+use wayland_protocols::unstable::pointer_constraints::v1::server::{
+    zwp_confined_pointer_v1::{self, ZwpConfinedPointerV1},
+    zwp_locked_pointer_v1::{self, ZwpLockedPointerV1},
+    zwp_pointer_constraints_v1::{self, ZwpPointerConstraintsV1},
+};
+use wayland_server::{
+    protocol::{wl_pointer::WlPointer, wl_region::WlRegion, wl_surface::WlSurface},
+    DispatchData, Display, Filter, Global, Main,
+};
+
+pub use zwp_pointer_constraints_v1::Lifetime;
+
+use crate::{
+    utils::{Logical, Point, Rectangle},
+    wayland::{
+        compositor::{self, RectangleKind, RegionAttributes, SurfaceAttributes},
+        seat::PointerHandle,
+    },
+};
+
+/// The kind of a [`PointerConstraint`]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PointerConstraintKind {
+    /// The pointer is locked in place, it does not move while the constraint is active
+    Locked,
+    /// The pointer is confined to the region of the constraint
+    Confined,
+}
+
+#[derive(Debug)]
+enum ConstraintResource {
+    Locked(ZwpLockedPointerV1),
+    Confined(ZwpConfinedPointerV1),
+}
+
+#[derive(Debug)]
+struct ConstraintInner {
+    resource: ConstraintResource,
+    surface: WlSurface,
+    pointer: WlPointer,
+    lifetime: Lifetime,
+    region: Option<RegionAttributes>,
+    pending_region: Option<Option<RegionAttributes>>,
+    // committed by the client, but not yet applied if the surface is a synchronized subsurface
+    committed_region: Option<Option<RegionAttributes>>,
+    cursor_position_hint: Option<Point<f64, Logical>>,
+    pending_cursor_position_hint: Option<Point<f64, Logical>>,
+    committed_cursor_position_hint: Option<Point<f64, Logical>>,
+    active: bool,
+    defunct: bool,
+}
+
+/// A pointer lock or confinement requested by a client
+///
+/// It can be cloned and all clones refer to the same constraint.
+#[derive(Debug, Clone)]
+pub struct PointerConstraint {
+    inner: Rc<RefCell<ConstraintInner>>,
+}
+
+impl PartialEq for PointerConstraint {
+    fn eq(&self, other: &PointerConstraint) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl PointerConstraint {
+    /// The kind of this constraint
+    pub fn kind(&self) -> PointerConstraintKind {
+        match self.inner.borrow().resource {
+            ConstraintResource::Locked(_) => PointerConstraintKind::Locked,
+            ConstraintResource::Confined(_) => PointerConstraintKind::Confined,
+        }
+    }
+
+    /// The surface this constraint applies to
+    pub fn surface(&self) -> WlSurface {
+        self.inner.borrow().surface.clone()
+    }
+
+    /// The pointer this constraint applies to
+    ///
+    /// Returns `None` if the seat of the pointer has no pointer capability.
+    pub fn pointer(&self) -> Option<PointerHandle> {
+        PointerHandle::from_resource(&self.inner.borrow().pointer)
+    }
+
+    /// Whether this constraint is oneshot or persistent
+    pub fn lifetime(&self) -> Lifetime {
+        self.inner.borrow().lifetime
+    }
+
+    /// The region of the surface the constraint applies to, in surface-local coordinates
+    ///
+    /// `None` means the whole surface. The constraint is further restricted by the input
+    /// region of the surface.
+    pub fn region(&self) -> Option<RegionAttributes> {
+        self.inner.borrow().region.clone()
+    }
+
+    /// Where the client expects the cursor to be once a lock is deactivated
+    ///
+    /// This is in surface-local coordinates, and is always `None` for confinements. You
+    /// may want to warp the pointer there once the lock is deactivated.
+    pub fn cursor_position_hint(&self) -> Option<Point<f64, Logical>> {
+        self.inner.borrow().cursor_position_hint
+    }
+
+    /// Whether this constraint is currently active
+    pub fn is_active(&self) -> bool {
+        self.inner.borrow().active
+    }
+
+    /// Whether this oneshot constraint has been deactivated and can no longer be activated
+    pub fn is_defunct(&self) -> bool {
+        self.inner.borrow().defunct
+    }
+
+    /// Activate this constraint
+    ///
+    /// Any other active constraint of the same pointer is deactivated. This does nothing if the
+    /// constraint is already active or defunct, or if its surface has been destroyed.
+    ///
+    /// The surface of the constraint should have the pointer focus, otherwise the constraint is
+    /// deactivated again on the next pointer motion.
+    pub fn activate(&self) {
+        let pointer = match self.pointer() {
+            Some(pointer) => pointer,
+            None => return,
+        };
+
+        {
+            let mut inner = self.inner.borrow_mut();
+            if inner.active || inner.defunct || !inner.surface.as_ref().is_alive() {
+                return;
+            }
+            inner.active = true;
+            match inner.resource {
+                ConstraintResource::Locked(ref locked) => locked.locked(),
+                ConstraintResource::Confined(ref confined) => confined.confined(),
+            }
+        }
+
+        if let Some(previous) = pointer.set_constraint(Some(self.clone())) {
+            if previous != *self {
+                previous.set_inactive();
+            }
+        }
+    }
+
+    /// Deactivate this constraint
+    ///
+    /// A oneshot constraint becomes defunct and can no longer be activated.
+    pub fn deactivate(&self) {
+        self.set_inactive();
+        if let Some(pointer) = self.pointer() {
+            pointer.unset_constraint(self);
+        }
+    }
+
+    /// Deactivates the constraint without touching the state of its pointer
+    pub(crate) fn set_inactive(&self) {
+        let mut inner = self.inner.borrow_mut();
+        if !inner.active {
+            return;
+        }
+        inner.active = false;
+        if inner.lifetime == Lifetime::Oneshot {
+            inner.defunct = true;
+        }
+        match inner.resource {
+            ConstraintResource::Locked(ref locked) => {
+                if locked.as_ref().is_alive() {
+                    locked.unlocked();
+                }
+            }
+            ConstraintResource::Confined(ref confined) => {
+                if confined.as_ref().is_alive() {
+                    confined.unconfined();
+                }
+            }
+        }
+    }
+
+    /// Returns the surface-local location the pointer moves to, when it is moved to `location`
+    ///
+    /// Returns `None` if the pointer does not move, because it is locked, the region is empty or
+    /// the surface is dead.
+    pub(crate) fn constrain(&self, location: Point<f64, Logical>) -> Option<Point<f64, Logical>> {
+        let kind = self.kind();
+        let inner = self.inner.borrow();
+        let input_region = compositor::with_states(&inner.surface, |states| {
+            states
+                .cached_state
+                .current::<SurfaceAttributes>()
+                .input_region
+                .clone()
+        })
+        .ok()?;
+
+        constrain_location(kind, location, inner.region.as_ref(), input_region.as_ref())
+    }
+
+    fn commit(&self) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(region) = inner.pending_region.take() {
+            inner.committed_region = Some(region);
+        }
+        if let Some(hint) = inner.pending_cursor_position_hint.take() {
+            inner.committed_cursor_position_hint = Some(hint);
+        }
+    }
+
+    fn apply(&self) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some(region) = inner.committed_region.take() {
+            inner.region = region;
+        }
+        if let Some(hint) = inner.committed_cursor_position_hint.take() {
+            inner.cursor_position_hint = Some(hint);
+        }
+    }
+}
+
+/// Moves the location into the constraint region, which is the intersection of the region of
+/// the constraint and the input region of the surface
+///
+/// A location outside of the constraint region is clamped to the nearest point inside of it, so
+/// that a confined pointer slides along the edges of the region. `None` regions are unbounded.
+fn constrain_location(
+    kind: PointerConstraintKind,
+    location: Point<f64, Logical>,
+    region: Option<&RegionAttributes>,
+    input_region: Option<&RegionAttributes>,
+) -> Option<Point<f64, Logical>> {
+    if kind == PointerConstraintKind::Locked {
+        return None;
+    }
+
+    let point = location.to_i32_floor::<i32>();
+    let contains = |region: Option<&RegionAttributes>| region.map_or(true, |region| region.contains(point));
+    if contains(region) && contains(input_region) {
+        return Some(location);
+    }
+
+    let rects = match (region, input_region) {
+        (Some(region), Some(input_region)) => {
+            let input_rects = region_rects(input_region);
+            region_rects(region)
+                .into_iter()
+                .flat_map(|rect| {
+                    input_rects
+                        .iter()
+                        .filter_map(move |input_rect| rect.intersection(*input_rect))
+                })
+                .collect()
+        }
+        (Some(region), None) | (None, Some(region)) => region_rects(region),
+        (None, None) => unreachable!(),
+    };
+
+    rects
+        .into_iter()
+        .map(|rect| {
+            // the location must stay inside of the rectangle once floored, keep it a wl_fixed
+            // step away from the right and bottom edges
+            let max = (rect.loc + rect.size).to_f64() - Point::from((1.0 / 256.0, 1.0 / 256.0));
+            let rect = rect.to_f64();
+            Point::<f64, Logical>::from((
+                location.x.max(rect.loc.x).min(max.x),
+                location.y.max(rect.loc.y).min(max.y),
+            ))
+        })
+        .min_by(|a, b| {
+            let distance =
+                |point: &Point<f64, Logical>| (point.x - location.x).powi(2) + (point.y - location.y).powi(2);
+            distance(a).partial_cmp(&distance(b)).unwrap()
+        })
+}
+
+/// Splits the region into non-overlapping rectangles
+fn region_rects(region: &RegionAttributes) -> Vec<Rectangle<i32, Logical>> {
+    let mut rects: Vec<Rectangle<i32, Logical>> = Vec::new();
+    for &(kind, rect) in &region.rects {
+        rects = rects.into_iter().flat_map(|r| r.subtract_rect(rect)).collect();
+        if let RectangleKind::Add = kind {
+            rects.push(rect);
+        }
+    }
+    rects
+}
+
+type SurfaceConstraints = RefCell<Vec<PointerConstraint>>;
+
+/// Returns the constraint the client requested on this surface for the given pointer, if any
+pub fn get_pointer_constraint(surface: &WlSurface, pointer: &PointerHandle) -> Option<PointerConstraint> {
+    compositor::with_states(surface, |states| {
+        states
+            .data_map
+            .get::<SurfaceConstraints>()
+            .and_then(|constraints| {
+                constraints
+                    .borrow()
+                    .iter()
+                    .find(|constraint| constraint.pointer().as_ref() == Some(pointer))
+                    .cloned()
+            })
+    })
+    .ok()
+    .flatten()
+}
+
+fn pre_commit_hook(surface: &WlSurface) {
+    let _ = compositor::with_states(surface, |states| {
+        if let Some(constraints) = states.data_map.get::<SurfaceConstraints>() {
+            for constraint in constraints.borrow().iter() {
+                constraint.commit();
+            }
+        }
+    });
+}
+
+// the region and cursor position hint are double-buffered state of the surface, they
+// become current once the state of the surface is applied
+fn post_commit_hook(surface: &WlSurface) {
+    let _ = compositor::with_states(surface, |states| {
+        if let Some(constraints) = states.data_map.get::<SurfaceConstraints>() {
+            for constraint in constraints.borrow().iter() {
+                constraint.apply();
+            }
+        }
+    });
+}
+
+fn remove_constraint(constraint: &PointerConstraint) {
+    constraint.deactivate();
+    let _ = compositor::with_states(&constraint.surface(), |states| {
+        if let Some(constraints) = states.data_map.get::<SurfaceConstraints>() {
+            constraints.borrow_mut().retain(|c| c != constraint);
+        }
+    });
+}
+
+/// Creates a new `zwp_pointer_constraints_v1` global
+///
+/// The provided callback is invoked with every new constraint requested by a client, which is
+/// initially inactive.
+pub fn init_pointer_constraints_global<F, L>(
+    display: &mut Display,
+    implementation: F,
+    logger: L,
+) -> Global<ZwpPointerConstraintsV1>
+where
+    F: FnMut(PointerConstraint, DispatchData<'_>) + 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log =
+        crate::slog_or_fallback(logger).new(slog::o!("smithay_module" => "pointer_constraints_handler"));
+    let implementation = Rc::new(RefCell::new(implementation));
+
+    display.create_global(
+        1,
+        Filter::new(
+            move |(constraints, _version): (Main<ZwpPointerConstraintsV1>, u32), _, _| {
+                let implementation = implementation.clone();
+                let log = log.clone();
+                constraints.quick_assign(move |constraints, request, ddata| {
+                    let constraint = match request {
+                        zwp_pointer_constraints_v1::Request::LockPointer {
+                            id,
+                            surface,
+                            pointer,
+                            region,
+                            lifetime,
+                        } => new_constraint(
+                            &constraints,
+                            NewConstraint::Locked(id),
+                            surface,
+                            pointer,
+                            region,
+                            lifetime,
+                        ),
+                        zwp_pointer_constraints_v1::Request::ConfinePointer {
+                            id,
+                            surface,
+                            pointer,
+                            region,
+                            lifetime,
+                        } => new_constraint(
+                            &constraints,
+                            NewConstraint::Confined(id),
+                            surface,
+                            pointer,
+                            region,
+                            lifetime,
+                        ),
+                        zwp_pointer_constraints_v1::Request::Destroy => {
+                            // all is already handled by our destructor
+                            return;
+                        }
+                        _ => unreachable!(),
+                    };
+
+                    if let Some(constraint) = constraint {
+                        slog::trace!(log, "New {:?} pointer constraint", constraint.kind());
+                        (&mut *implementation.borrow_mut())(constraint, ddata);
+                    }
+                });
+            },
+        ),
+    )
+}
+
+enum NewConstraint {
+    Locked(Main<ZwpLockedPointerV1>),
+    Confined(Main<ZwpConfinedPointerV1>),
+}
+
+fn new_constraint(
+    constraints: &ZwpPointerConstraintsV1,
+    id: NewConstraint,
+    surface: WlSurface,
+    pointer: WlPointer,
+    region: Option<WlRegion>,
+    lifetime: Lifetime,
+) -> Option<PointerConstraint> {
+    let already_constrained = compositor::with_states(&surface, |states| {
+        states
+            .data_map
+            .get::<SurfaceConstraints>()
+            .map_or(false, |constraints| {
+                constraints.borrow().iter().any(|constraint| {
+                    constraint
+                        .inner
+                        .borrow()
+                        .pointer
+                        .as_ref()
+                        .equals(pointer.as_ref())
+                })
+            })
+    })
+    .unwrap_or(false);
+    if already_constrained {
+        constraints.as_ref().post_error(
+            zwp_pointer_constraints_v1::Error::AlreadyConstrained as u32,
+            "The surface is already constrained for this pointer.".into(),
+        );
+        return None;
+    }
+
+    let resource = match id {
+        NewConstraint::Locked(ref locked) => ConstraintResource::Locked(locked.deref().clone()),
+        NewConstraint::Confined(ref confined) => ConstraintResource::Confined(confined.deref().clone()),
+    };
+    let constraint = PointerConstraint {
+        inner: Rc::new(RefCell::new(ConstraintInner {
+            resource,
+            surface: surface.clone(),
+            pointer,
+            lifetime,
+            region: region.map(|region| compositor::get_region_attributes(&region)),
+            pending_region: None,
+            committed_region: None,
+            cursor_position_hint: None,
+            pending_cursor_position_hint: None,
+            committed_cursor_position_hint: None,
+            active: false,
+            defunct: false,
+        })),
+    };
+
+    match id {
+        NewConstraint::Locked(locked) => {
+            let handle = constraint.clone();
+            locked.quick_assign(move |_, request, _| match request {
+                zwp_locked_pointer_v1::Request::SetCursorPositionHint { surface_x, surface_y } => {
+                    handle.inner.borrow_mut().pending_cursor_position_hint =
+                        Some((surface_x, surface_y).into());
+                }
+                zwp_locked_pointer_v1::Request::SetRegion { region } => {
+                    handle.inner.borrow_mut().pending_region =
+                        Some(region.map(|region| compositor::get_region_attributes(&region)));
+                }
+                zwp_locked_pointer_v1::Request::Destroy => {
+                    // all is already handled by our destructor
+                }
+                _ => unreachable!(),
+            });
+            let handle = constraint.clone();
+            locked.assign_destructor(Filter::new(move |_: ZwpLockedPointerV1, _, _| {
+                remove_constraint(&handle);
+            }));
+        }
+        NewConstraint::Confined(confined) => {
+            let handle = constraint.clone();
+            confined.quick_assign(move |_, request, _| match request {
+                zwp_confined_pointer_v1::Request::SetRegion { region } => {
+                    handle.inner.borrow_mut().pending_region =
+                        Some(region.map(|region| compositor::get_region_attributes(&region)));
+                }
+                zwp_confined_pointer_v1::Request::Destroy => {
+                    // all is already handled by our destructor
+                }
+                _ => unreachable!(),
+            });
+            let handle = constraint.clone();
+            confined.assign_destructor(Filter::new(move |_: ZwpConfinedPointerV1, _, _| {
+                remove_constraint(&handle);
+            }));
+        }
+    }
+
+    let first = compositor::with_states(&surface, |states| {
+        let first = states.data_map.get::<SurfaceConstraints>().is_none();
+        states.data_map.insert_if_missing(SurfaceConstraints::default);
+        states
+            .data_map
+            .get::<SurfaceConstraints>()
+            .unwrap()
+            .borrow_mut()
+            .push(constraint.clone());
+        first
+    })
+    .ok()?;
+    if first {
+        compositor::add_pre_commit_hook(&surface, pre_commit_hook);
+        compositor::add_post_commit_hook(&surface, post_commit_hook);
+    }
+
+    Some(constraint)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region_from(rects: Vec<(RectangleKind, (i32, i32), (i32, i32))>) -> RegionAttributes {
+        RegionAttributes {
+            rects: rects
+                .into_iter()
+                .map(|(kind, loc, size)| (kind, Rectangle::from_loc_and_size(loc, size)))
+                .collect(),
+        }
+    }
+
+    fn confine(
+        location: (f64, f64),
+        region: Option<&RegionAttributes>,
+        input_region: Option<&RegionAttributes>,
+    ) -> Option<(f64, f64)> {
+        constrain_location(
+            PointerConstraintKind::Confined,
+            location.into(),
+            region,
+            input_region,
+        )
+        .map(Into::into)
+    }
+
+    #[test]
+    fn locked_pointer_does_not_move() {
+        assert_eq!(
+            constrain_location(PointerConstraintKind::Locked, (5.0, 5.0).into(), None, None),
+            None
+        );
+    }
+
+    #[test]
+    fn confined_pointer_moves_inside_of_region() {
+        let region = region_from(vec![(RectangleKind::Add, (0, 0), (10, 10))]);
+
+        assert_eq!(confine((5.5, 2.0), Some(&region), None), Some((5.5, 2.0)));
+        assert_eq!(confine((20.0, 20.0), None, None), Some((20.0, 20.0)));
+    }
+
+    #[test]
+    fn confined_pointer_is_clamped_to_region() {
+        let region = region_from(vec![(RectangleKind::Add, (0, 0), (10, 10))]);
+        let edge = 10.0 - 1.0 / 256.0;
+
+        // slides along the edges instead of stopping
+        assert_eq!(confine((15.0, 5.0), Some(&region), None), Some((edge, 5.0)));
+        assert_eq!(confine((-3.0, 5.0), None, Some(&region)), Some((0.0, 5.0)));
+        assert_eq!(confine((12.0, -4.0), Some(&region), None), Some((edge, 0.0)));
+    }
+
+    #[test]
+    fn confined_pointer_is_clamped_to_nearest_rectangle() {
+        // two separate squares, with a hole in the right one
+        let region = region_from(vec![
+            (RectangleKind::Add, (0, 0), (10, 10)),
+            (RectangleKind::Add, (20, 0), (10, 10)),
+            (RectangleKind::Subtract, (20, 0), (5, 10)),
+        ]);
+
+        assert_eq!(
+            confine((13.0, 5.0), Some(&region), None),
+            Some((10.0 - 1.0 / 256.0, 5.0))
+        );
+        assert_eq!(confine((22.0, 5.0), Some(&region), None), Some((25.0, 5.0)));
+    }
+
+    #[test]
+    fn confined_pointer_is_clamped_to_input_region() {
+        let region = region_from(vec![(RectangleKind::Add, (0, 0), (10, 10))]);
+        let input_region = region_from(vec![(RectangleKind::Add, (5, 5), (10, 10))]);
+
+        assert_eq!(
+            confine((2.0, 7.0), Some(&region), Some(&input_region)),
+            Some((5.0, 7.0))
+        );
+        // the regions do not intersect
+        let input_region = region_from(vec![(RectangleKind::Add, (20, 20), (10, 10))]);
+        assert_eq!(confine((2.0, 7.0), Some(&region), Some(&input_region)), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! Utilities for handling the `presentation_time` protocol
+//!
+//! This protocol lets clients know when their content updates were actually displayed, which is
+//! needed to measure the latency of the compositor or to synchronize audio and video.
+//!
+//! When committing, a client can request a feedback for this content update. These feedbacks are
+//! collected in the cached state of the surface, and you take them with [`take_presentation_feedback`]
+//! once you render the surface. After the page flip of the output displaying it, you send the
+//! presentation time with [`PresentationFeedback::presented`]. Feedbacks of content updates that were
+//! never displayed are discarded automatically.
+//!
+//! The timestamps you send must be measured with the clock advertised when initializing the global,
+//! which for DRM devices is `CLOCK_MONOTONIC`.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use std::time::Duration;
+//! use smithay::wayland::presentation::{
+//!     init_presentation_global, take_presentation_feedback, PresentationFeedback, PresentationFlags,
+//! };
+//!
+//! # use wayland_server::protocol::wl_surface::WlSurface;
+//! # let mut display = wayland_server::Display::new();
+//! // CLOCK_MONOTONIC
+//! init_presentation_global(&mut display, 1, None);
+//!
+//! // when rendering a surface
+//! fn render(surface: &WlSurface) -> PresentationFeedback {
+//!     // draw the surface...
+//!     take_presentation_feedback(surface)
+//! }
+//!
+//! // in the page flip handler of the output, for example from the `DrmEvent::VBlank` event
+//! // and its `DrmEventMetadata`
+//! fn page_flip(mut pending: PresentationFeedback, time: Duration, sequence: u32, refresh: Duration) {
+//!     pending.presented(
+//!         time,
+//!         refresh,
+//!         sequence as u64,
+//!         PresentationFlags::VSYNC | PresentationFlags::HW_CLOCK | PresentationFlags::HW_COMPLETION,
+//!     );
+//! }
+//! ```
+
+use std::{ops::Deref as _, time::Duration};
+
+use wayland_protocols::presentation_time::server::{
+    wp_presentation::{self, WpPresentation},
+    wp_presentation_feedback::{self, WpPresentationFeedback},
+};
+use wayland_server::{
+    protocol::{wl_output::WlOutput, wl_surface::WlSurface},
+    Display, Filter, Global, Main,
+};
+
+use crate::wayland::compositor::{self, Cacheable};
+
+bitflags::bitflags! {
+    /// Flags describing how a content update was presented
+    pub struct PresentationFlags: u32 {
+        /// The presentation was synchronized to the vertical retrace
+        const VSYNC = 1;
+        /// The timestamp was provided by the display hardware
+        const HW_CLOCK = 2;
+        /// The display hardware signalled the completion of the presentation
+        const HW_COMPLETION = 4;
+        /// The buffer of the client was scanned out directly, without any copy
+        const ZERO_COPY = 8;
+    }
+}
+
+/// The presentation feedbacks requested by a client for a content update
+///
+/// This state is double-buffered, feedbacks of a content update that is replaced by a new one before
+/// being taken by [`take_presentation_feedback`] are discarded.
+#[derive(Debug, Default)]
+pub struct PresentationFeedbackCachedState {
+    callbacks: Vec<WpPresentationFeedback>,
+}
+
+impl Cacheable for PresentationFeedbackCachedState {
+    fn commit(&mut self) -> Self {
+        std::mem::take(self)
+    }
+    fn merge_into(mut self, into: &mut Self) {
+        // the previous content update was never presented
+        for callback in into.callbacks.drain(..) {
+            if callback.as_ref().is_alive() {
+                callback.discarded();
+            }
+        }
+        into.callbacks = std::mem::take(&mut self.callbacks);
+    }
+}
+
+impl Drop for PresentationFeedbackCachedState {
+    fn drop(&mut self) {
+        for callback in self.callbacks.drain(..) {
+            if callback.as_ref().is_alive() {
+                callback.discarded();
+            }
+        }
+    }
+}
+
+/// Pending presentation feedbacks, to be sent once the corresponding content was presented
+///
+/// Dropping it discards the feedbacks that were not sent.
+#[derive(Debug, Default)]
+pub struct PresentationFeedback {
+    callbacks: Vec<WpPresentationFeedback>,
+}
+
+impl PresentationFeedback {
+    /// Whether there is no feedback to send
+    pub fn is_empty(&self) -> bool {
+        self.callbacks.is_empty()
+    }
+
+    /// Add the feedbacks of another content update presented at the same time
+    ///
+    /// This is useful to collect the feedbacks of all the surfaces displayed on an output.
+    pub fn merge(&mut self, mut other: PresentationFeedback) {
+        self.callbacks.append(&mut other.callbacks);
+    }
+
+    /// Notify the clients of the output the content was presented on
+    ///
+    /// This must be called before [`PresentationFeedback::presented`].
+    pub fn sync_output(&self, output: &crate::wayland::output::Output) {
+        for callback in &self.callbacks {
+            if let Some(client) = callback.as_ref().client() {
+                output.with_client_outputs(client, |wl_output: &WlOutput| callback.sync_output(wl_output));
+            }
+        }
+    }
+
+    /// Notify the clients that the content was presented
+    ///
+    /// - `time` is the time at which the content turned into light, measured with the clock advertised
+    ///   by the global
+    /// - `refresh` is the duration until the next expected presentation, or zero if unknown
+    /// - `sequence` is the vblank sequence number of the presentation, or zero if unknown
+    pub fn presented(&mut self, time: Duration, refresh: Duration, sequence: u64, flags: PresentationFlags) {
+        let secs = time.as_secs();
+        let flags = wp_presentation_feedback::Kind::from_bits_truncate(flags.bits());
+        for callback in self.callbacks.drain(..) {
+            if callback.as_ref().is_alive() {
+                callback.presented(
+                    (secs >> 32) as u32,
+                    secs as u32,
+                    time.subsec_nanos(),
+                    refresh.as_nanos() as u32,
+                    (sequence >> 32) as u32,
+                    sequence as u32,
+                    flags,
+                );
+            }
+        }
+    }
+
+    /// Notify the clients that the content was never presented
+    pub fn discarded(&mut self) {
+        for callback in self.callbacks.drain(..) {
+            if callback.as_ref().is_alive() {
+                callback.discarded();
+            }
+        }
+    }
+}
+
+impl Drop for PresentationFeedback {
+    fn drop(&mut self) {
+        self.discarded();
+    }
+}
+
+/// Take the presentation feedbacks of the current content of a surface
+///
+/// You should call this when rendering the current content of the surface, and send the feedbacks
+/// once it was presented.
+pub fn take_presentation_feedback(surface: &WlSurface) -> PresentationFeedback {
+    compositor::with_states(surface, |states| PresentationFeedback {
+        callbacks: std::mem::take(
+            &mut states
+                .cached_state
+                .current::<PresentationFeedbackCachedState>()
+                .callbacks,
+        ),
+    })
+    .unwrap_or_default()
+}
+
+/// Creates a new `wp_presentation` global
+///
+/// `clk_id` is the clock used for the presentation timestamps, as one of the `CLOCK_*` constants of
+/// `clock_gettime`.
+pub fn init_presentation_global<L>(display: &mut Display, clk_id: u32, logger: L) -> Global<WpPresentation>
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(slog::o!("smithay_module" => "presentation_handler"));
+
+    display.create_global(
+        1,
+        Filter::new(
+            move |(presentation, _version): (Main<WpPresentation>, u32), _, _| {
+                let log = log.clone();
+                presentation.quick_assign(move |_presentation, request, _| match request {
+                    wp_presentation::Request::Feedback { surface, callback } => {
+                        slog::trace!(log, "New presentation feedback");
+                        let callback = callback.deref().clone();
+                        let result = compositor::with_states(&surface, |states| {
+                            states
+                                .cached_state
+                                .pending::<PresentationFeedbackCachedState>()
+                                .callbacks
+                                .push(callback.clone());
+                        });
+                        if result.is_err() {
+                            // the surface is already destroyed, its content will never be presented
+                            callback.discarded();
+                        }
+                    }
+                    wp_presentation::Request::Destroy => {
+                        // nothing to do, the feedbacks outlive the global object
+                    }
+                    _ => unreachable!(),
+                });
+                presentation.clock_id(clk_id);
+            },
+        ),
+    )
+}
@@ -0,0 +1,355 @@
+//! Utilities for manipulating the primary selection
+//!
+//! The primary selection is an additional clipboard, which is usually set by selecting some text and
+//! pasted by a middle click. Like the selection of the [`data_device`](super::data_device), it is
+//! a per-seat notion. This module provides logic to handle the `primary_selection` protocol.
+//!
+//! This module provides 2 main freestanding functions:
+//!
+//! - [`init_primary_selection`]: this function must be called
+//!   during the compositor startup to initialize the primary selection logic
+//! - [`set_primary_focus`]: this function sets
+//!   the primary selection focus for a given seat; you'd typically call it whenever the keyboard focus
+//!   changes, to follow it (for example in the focus hook of your keyboards)
+//!
+//! Using these two functions is enough for your clients to be able to share their primary selection.
+//!
+//! The module also provides additional mechanisms allowing your compositor to see and interact with
+//! the contents of the primary selection:
+//!
+//! - You can provide a callback closure to [`init_primary_selection`]
+//!   to peek into the the actions of your clients
+//! - the freestanding function [`set_primary_selection`]
+//!   allows you to set the contents of the primary selection for your clients
+//!
+//! ## Initialization
+//!
+//! ```
+//! # extern crate wayland_server;
+//! use smithay::wayland::primary_selection::init_primary_selection;
+//!
+//! # let mut display = wayland_server::Display::new();
+//! // init the primary selection:
+//! init_primary_selection(
+//!     &mut display,  // the display
+//!     |event| { /* a callback to react to client selection actions */ },
+//!     None           // insert a logger here
+//! );
+//! ```
+
+use std::{cell::RefCell, ops::Deref as _, os::unix::io::RawFd, rc::Rc};
+
+use wayland_protocols::unstable::primary_selection::v1::server::{
+    zwp_primary_selection_device_manager_v1::{self, ZwpPrimarySelectionDeviceManagerV1},
+    zwp_primary_selection_device_v1::{self, ZwpPrimarySelectionDeviceV1},
+    zwp_primary_selection_offer_v1::{self, ZwpPrimarySelectionOfferV1},
+    zwp_primary_selection_source_v1::ZwpPrimarySelectionSourceV1,
+};
+use wayland_server::{Client, Display, Filter, Global, Main};
+
+use slog::{debug, error, o};
+
+use crate::wayland::seat::Seat;
+
+mod source;
+
+pub use self::source::{with_source_metadata, SourceMetadata};
+
+/// Events that are generated by interactions of the clients with the primary selection
+#[derive(Debug)]
+pub enum PrimarySelectionEvent {
+    /// A client has set the primary selection
+    NewSelection(Option<ZwpPrimarySelectionSourceV1>),
+    /// A client requested to read the server-set primary selection
+    SendSelection {
+        /// the requested mime type
+        mime_type: String,
+        /// the fd to write into
+        fd: RawFd,
+    },
+}
+
+enum Selection {
+    Empty,
+    Client(ZwpPrimarySelectionSourceV1),
+    Compositor(SourceMetadata),
+}
+
+struct SeatData {
+    known_devices: Vec<ZwpPrimarySelectionDeviceV1>,
+    selection: Selection,
+    log: ::slog::Logger,
+    current_focus: Option<Client>,
+}
+
+impl SeatData {
+    fn new(log: ::slog::Logger) -> SeatData {
+        SeatData {
+            known_devices: Vec::new(),
+            selection: Selection::Empty,
+            log,
+            current_focus: None,
+        }
+    }
+
+    fn set_selection(&mut self, new_selection: Selection) {
+        // the previous source is no longer used
+        if let Selection::Client(ref source) = self.selection {
+            let replaced = match new_selection {
+                Selection::Client(ref new_source) => !new_source.as_ref().equals(source.as_ref()),
+                _ => true,
+            };
+            if replaced && source.as_ref().is_alive() {
+                source.cancelled();
+            }
+        }
+        self.selection = new_selection;
+        self.send_selection();
+    }
+
+    fn set_focus(&mut self, new_focus: Option<Client>) {
+        self.current_focus = new_focus;
+        self.send_selection();
+    }
+
+    fn send_selection(&mut self) {
+        let client = match self.current_focus.as_ref() {
+            Some(c) => c,
+            None => return,
+        };
+        // first sanitize the selection, reseting it to null if the client holding
+        // it dropped it
+        let cleanup = if let Selection::Client(ref source) = self.selection {
+            !source.as_ref().is_alive()
+        } else {
+            false
+        };
+        if cleanup {
+            self.selection = Selection::Empty;
+        }
+        // then send it if appropriate
+        for device in &self.known_devices {
+            // skip devices not belonging to our client
+            if device
+                .as_ref()
+                .client()
+                .map(|c| !c.equals(client))
+                .unwrap_or(true)
+            {
+                continue;
+            }
+            let mime_types = match self.selection {
+                Selection::Empty => {
+                    // send an empty selection
+                    device.selection(None);
+                    continue;
+                }
+                Selection::Client(ref source) => {
+                    with_source_metadata(source, |meta| meta.mime_types.clone()).unwrap()
+                }
+                Selection::Compositor(ref meta) => meta.mime_types.clone(),
+            };
+            // create a corresponding offer
+            let offer = client
+                .create_resource::<ZwpPrimarySelectionOfferV1>(device.as_ref().version())
+                .unwrap();
+            let log = self.log.clone();
+            match self.selection {
+                Selection::Client(ref source) => {
+                    let source = source.clone();
+                    offer.quick_assign(move |_offer, req, _| {
+                        if let zwp_primary_selection_offer_v1::Request::Receive { mime_type, fd } = req {
+                            // check if the source and associated mime type is still valid
+                            let valid =
+                                with_source_metadata(&source, |meta| meta.mime_types.contains(&mime_type))
+                                    .unwrap_or(false)
+                                    && source.as_ref().is_alive();
+                            if !valid {
+                                // deny the receive
+                                debug!(log, "Denying a primary selection receive with invalid source.");
+                            } else {
+                                source.send(mime_type, fd);
+                            }
+                            let _ = ::nix::unistd::close(fd);
+                        }
+                    });
+                }
+                Selection::Compositor(ref meta) => {
+                    let offer_meta = meta.clone();
+                    let callback = device
+                        .as_ref()
+                        .user_data()
+                        .get::<DeviceData>()
+                        .unwrap()
+                        .callback
+                        .clone();
+                    offer.quick_assign(move |_offer, req, _| {
+                        if let zwp_primary_selection_offer_v1::Request::Receive { mime_type, fd } = req {
+                            // check if the associated mime type is valid
+                            if !offer_meta.mime_types.contains(&mime_type) {
+                                // deny the receive
+                                debug!(log, "Denying a primary selection receive with invalid source.");
+                                let _ = ::nix::unistd::close(fd);
+                            } else {
+                                (&mut *callback.borrow_mut())(PrimarySelectionEvent::SendSelection {
+                                    mime_type,
+                                    fd,
+                                });
+                            }
+                        }
+                    });
+                }
+                Selection::Empty => unreachable!(),
+            }
+            // advertize the offer to the client
+            device.data_offer(&offer);
+            for mime_type in mime_types {
+                offer.offer(mime_type);
+            }
+            device.selection(Some(&offer));
+        }
+    }
+}
+
+/// Initialize the primary selection global
+///
+/// You can provide a callback to peek into the actions of your clients over the primary selection
+/// (allowing you to retrieve the current selection buffer). See the [`PrimarySelectionEvent`] type
+/// for details about what notifications you can receive.
+pub fn init_primary_selection<C, L>(
+    display: &mut Display,
+    callback: C,
+    logger: L,
+) -> Global<ZwpPrimarySelectionDeviceManagerV1>
+where
+    C: FnMut(PrimarySelectionEvent) + 'static,
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "primary_selection_mgr"));
+    let callback = Rc::new(RefCell::new(callback));
+    display.create_global(
+        1,
+        Filter::new(move |(manager, _version), _, _| {
+            implement_manager(manager, callback.clone(), log.clone());
+        }),
+    )
+}
+
+/// Set the primary selection focus to a certain client for a given seat
+///
+/// The current primary selection is sent to the newly focused client.
+pub fn set_primary_focus(seat: &Seat, client: Option<Client>) {
+    // ensure the seat user_data is ready
+    seat.user_data().insert_if_missing(|| {
+        RefCell::new(SeatData::new(
+            seat.arc.log.new(o!("smithay_module" => "primary_selection_mgr")),
+        ))
+    });
+    let seat_data = seat.user_data().get::<RefCell<SeatData>>().unwrap();
+    seat_data.borrow_mut().set_focus(client);
+}
+
+/// Set a compositor-provided primary selection for this seat
+///
+/// You need to provide the available mime types for this selection.
+///
+/// Whenever a client requests to read the selection, your callback will
+/// receive a [`PrimarySelectionEvent::SendSelection`] event.
+pub fn set_primary_selection(seat: &Seat, mime_types: Vec<String>) {
+    seat.user_data().insert_if_missing(|| {
+        RefCell::new(SeatData::new(
+            seat.arc.log.new(o!("smithay_module" => "primary_selection_mgr")),
+        ))
+    });
+    let seat_data = seat.user_data().get::<RefCell<SeatData>>().unwrap();
+    seat_data
+        .borrow_mut()
+        .set_selection(Selection::Compositor(SourceMetadata { mime_types }));
+}
+
+fn implement_manager<C>(
+    manager: Main<ZwpPrimarySelectionDeviceManagerV1>,
+    callback: Rc<RefCell<C>>,
+    log: ::slog::Logger,
+) -> ZwpPrimarySelectionDeviceManagerV1
+where
+    C: FnMut(PrimarySelectionEvent) + 'static,
+{
+    use self::zwp_primary_selection_device_manager_v1::Request;
+    manager.quick_assign(move |_manager, req, _data| match req {
+        Request::CreateSource { id } => {
+            self::source::implement_source(id);
+        }
+        Request::GetDevice { id, seat } => match Seat::from_resource(&seat) {
+            Some(seat) => {
+                // ensure the seat user_data is ready
+                seat.user_data()
+                    .insert_if_missing(|| RefCell::new(SeatData::new(log.clone())));
+                let seat_data = seat.user_data().get::<RefCell<SeatData>>().unwrap();
+                let device = implement_device(id, seat.clone(), callback.clone(), log.clone());
+                seat_data.borrow_mut().known_devices.push(device);
+            }
+            None => {
+                error!(log, "Unmanaged seat given to a primary selection device.");
+            }
+        },
+        Request::Destroy => {}
+        _ => unreachable!(),
+    });
+
+    manager.deref().clone()
+}
+
+struct DeviceData {
+    callback: Rc<RefCell<dyn FnMut(PrimarySelectionEvent) + 'static>>,
+}
+
+fn implement_device<C>(
+    device: Main<ZwpPrimarySelectionDeviceV1>,
+    seat: Seat,
+    callback: Rc<RefCell<C>>,
+    log: ::slog::Logger,
+) -> ZwpPrimarySelectionDeviceV1
+where
+    C: FnMut(PrimarySelectionEvent) + 'static,
+{
+    use self::zwp_primary_selection_device_v1::Request;
+    let device_data = DeviceData {
+        callback: callback.clone(),
+    };
+    device.quick_assign(move |device, req, _| match req {
+        Request::SetSelection { source, .. } => {
+            if let Some(keyboard) = seat.get_keyboard() {
+                if device
+                    .as_ref()
+                    .client()
+                    .as_ref()
+                    .map(|c| keyboard.has_focus(c))
+                    .unwrap_or(false)
+                {
+                    let seat_data = seat.user_data().get::<RefCell<SeatData>>().unwrap();
+                    (&mut *callback.borrow_mut())(PrimarySelectionEvent::NewSelection(source.clone()));
+                    // The client has kbd focus, it can set the selection
+                    seat_data
+                        .borrow_mut()
+                        .set_selection(source.map(Selection::Client).unwrap_or(Selection::Empty));
+                    return;
+                }
+            }
+            debug!(log, "denying setting primary selection by a non-focused client");
+        }
+        Request::Destroy => {
+            // Clean up the known devices
+            seat.user_data()
+                .get::<RefCell<SeatData>>()
+                .unwrap()
+                .borrow_mut()
+                .known_devices
+                .retain(|d| d.as_ref().is_alive() && (!d.as_ref().equals(device.as_ref())))
+        }
+        _ => unreachable!(),
+    });
+    device.as_ref().user_data().set(|| device_data);
+
+    device.deref().clone()
+}
@@ -0,0 +1,43 @@
+use std::{cell::RefCell, ops::Deref as _};
+
+use wayland_protocols::unstable::primary_selection::v1::server::zwp_primary_selection_source_v1::{
+    Request, ZwpPrimarySelectionSourceV1,
+};
+use wayland_server::Main;
+
+/// The metadata describing a primary selection source
+#[derive(Debug, Clone)]
+pub struct SourceMetadata {
+    /// The MIME types supported by this source
+    pub mime_types: Vec<String>,
+}
+
+pub(crate) fn implement_source(src: Main<ZwpPrimarySelectionSourceV1>) -> ZwpPrimarySelectionSourceV1 {
+    src.quick_assign(|me, req, _| {
+        let data: &RefCell<SourceMetadata> = me.as_ref().user_data().get().unwrap();
+        let mut guard = data.borrow_mut();
+        match req {
+            Request::Offer { mime_type } => guard.mime_types.push(mime_type),
+            Request::Destroy => {}
+            _ => unreachable!(),
+        }
+    });
+    src.as_ref().user_data().set(|| {
+        RefCell::new(SourceMetadata {
+            mime_types: Vec::new(),
+        })
+    });
+
+    src.deref().clone()
+}
+
+/// Access the metadata of a primary selection source
+pub fn with_source_metadata<T, F: FnOnce(&SourceMetadata) -> T>(
+    source: &ZwpPrimarySelectionSourceV1,
+    f: F,
+) -> Result<T, crate::utils::UnmanagedResource> {
+    match source.as_ref().user_data().get::<RefCell<SourceMetadata>>() {
+        Some(data) => Ok(f(&data.borrow())),
+        None => Err(crate::utils::UnmanagedResource),
+    }
+}
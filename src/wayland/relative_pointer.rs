@@ -0,0 +1,80 @@
+//! Utilities for handling the `relative_pointer` protocol
+//!
+//! This protocol lets clients such as games receive the relative motion of the pointer device,
+//! including the unaccelerated deltas, independently of the position of the pointer. It is
+//! usually combined with the [`pointer_constraints`](super::pointer_constraints) protocol to
+//! lock the pointer in place.
+//!
+//! Once the global is initialized, the relative pointers of the clients are tracked by the
+//! [`PointerHandle`] of their seat. You just need to call [`PointerHandle::relative_motion`]
+//! alongside [`PointerHandle::motion`] when your pointer device moved.
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::wayland::relative_pointer::init_relative_pointer_manager_global;
+//!
+//! # let mut display = wayland_server::Display::new();
+//! init_relative_pointer_manager_global(
+//!     &mut display,
+//!     None, // put a logger if you want
+//! );
+//! ```
+
+use std::ops::Deref as _;
+
+use wayland_protocols::unstable::relative_pointer::v1::server::{
+    zwp_relative_pointer_manager_v1::{self, ZwpRelativePointerManagerV1},
+    zwp_relative_pointer_v1::{self, ZwpRelativePointerV1},
+};
+use wayland_server::{Display, Filter, Global, Main};
+
+use crate::wayland::seat::PointerHandle;
+
+/// Creates a new `zwp_relative_pointer_manager_v1` global
+pub fn init_relative_pointer_manager_global<L>(
+    display: &mut Display,
+    logger: L,
+) -> Global<ZwpRelativePointerManagerV1>
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(slog::o!("smithay_module" => "relative_pointer_handler"));
+
+    display.create_global(
+        1,
+        Filter::new(
+            move |(manager, _version): (Main<ZwpRelativePointerManagerV1>, u32), _, _| {
+                let log = log.clone();
+                manager.quick_assign(move |_manager, request, _| match request {
+                    zwp_relative_pointer_manager_v1::Request::GetRelativePointer { id, pointer } => {
+                        let handle = PointerHandle::from_resource(&pointer);
+                        slog::trace!(log, "New relative pointer"; "has_pointer" => handle.is_some());
+                        implement_relative_pointer(id, handle);
+                    }
+                    zwp_relative_pointer_manager_v1::Request::Destroy => {
+                        // all is already handled by our destructor
+                    }
+                    _ => unreachable!(),
+                });
+            },
+        ),
+    )
+}
+
+fn implement_relative_pointer(relative_pointer: Main<ZwpRelativePointerV1>, handle: Option<PointerHandle>) {
+    relative_pointer.quick_assign(|_, request, _| match request {
+        zwp_relative_pointer_v1::Request::Destroy => {
+            // all is already handled by our destructor
+        }
+        _ => unreachable!(),
+    });
+
+    if let Some(handle) = handle {
+        handle.new_relative_pointer(relative_pointer.deref().clone());
+        relative_pointer.assign_destructor(Filter::new(
+            move |relative_pointer: ZwpRelativePointerV1, _, _| {
+                handle.remove_relative_pointer(&relative_pointer);
+            },
+        ));
+    }
+}
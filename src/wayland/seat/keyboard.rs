@@ -5,6 +5,7 @@ use std::{
     cell::RefCell,
     default::Default,
     fmt,
+    fs::File,
     io::{Error as IoError, Write},
     ops::Deref as _,
     os::unix::io::AsRawFd,
@@ -98,12 +99,107 @@ impl<'a> Default for XkbConfig<'a> {
     }
 }
 
+/// The keymap of a keyboard, as shared with the clients
+#[derive(Debug)]
+struct KeymapFile {
+    keymap: String,
+    // A single sealed file is shared with all clients, as they cannot modify it
+    sealed: Option<File>,
+}
+
+impl KeymapFile {
+    fn new(keymap: String, log: &::slog::Logger) -> KeymapFile {
+        let sealed = match sealed_file(&keymap) {
+            Ok(file) => Some(file),
+            Err(err) => {
+                debug!(log,
+                    "Failed to create a sealed file for the keymap, using a file per keyboard";
+                    "err" => format!("{:?}", err)
+                );
+                None
+            }
+        };
+
+        KeymapFile { keymap, sealed }
+    }
+
+    /// Send the keymap to a keyboard
+    fn send(&self, kbd: &WlKeyboard) -> Result<(), IoError> {
+        let size = self.keymap.as_bytes().len() as u32;
+
+        if let Some(ref file) = self.sealed {
+            kbd.keymap(KeymapFormat::XkbV1, file.as_raw_fd(), size);
+            return Ok(());
+        }
+
+        // prepare a tempfile with the keymap, to send it to the client
+        let mut file = tempfile()?;
+        file.write_all(self.keymap.as_bytes())?;
+        file.flush()?;
+        kbd.keymap(KeymapFormat::XkbV1, file.as_raw_fd(), size);
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn sealed_file(keymap: &str) -> Result<File, IoError> {
+    use nix::{
+        fcntl::{fcntl, FcntlArg, SealFlag},
+        sys::memfd::{memfd_create, MemFdCreateFlag},
+    };
+    use std::{ffi::CStr, os::unix::io::FromRawFd};
+
+    let name = CStr::from_bytes_with_nul(b"smithay-keymap\0").unwrap();
+    let fd = memfd_create(
+        name,
+        MemFdCreateFlag::MFD_CLOEXEC | MemFdCreateFlag::MFD_ALLOW_SEALING,
+    )?;
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    file.write_all(keymap.as_bytes())?;
+    file.flush()?;
+    fcntl(
+        file.as_raw_fd(),
+        FcntlArg::F_ADD_SEALS(
+            SealFlag::F_SEAL_SEAL | SealFlag::F_SEAL_SHRINK | SealFlag::F_SEAL_GROW | SealFlag::F_SEAL_WRITE,
+        ),
+    )?;
+    Ok(file)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn sealed_file(_keymap: &str) -> Result<File, IoError> {
+    Err(IoError::new(
+        std::io::ErrorKind::Other,
+        "sealed files are not supported on this platform",
+    ))
+}
+
+fn compile_keymap(xkb_config: XkbConfig<'_>) -> Option<xkb::Keymap> {
+    // we create a new contex for each keymap because libxkbcommon is actually NOT threadsafe
+    // so confining it inside the KbdInternal allows us to use Rusts mutability rules to make
+    // sure nothing goes wrong.
+    //
+    // FIXME: This is an issue with the xkbcommon-rs crate that does not reflect this
+    // non-threadsafety properly.
+    let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
+    xkb::Keymap::new_from_names(
+        &context,
+        &xkb_config.rules,
+        &xkb_config.model,
+        &xkb_config.layout,
+        &xkb_config.variant,
+        xkb_config.options,
+        xkb::KEYMAP_COMPILE_NO_FLAGS,
+    )
+}
+
 struct KbdInternal {
     known_kbds: Vec<WlKeyboard>,
     focus: Option<WlSurface>,
     pressed_keys: Vec<u32>,
     mods_state: ModifiersState,
     keymap: xkb::Keymap,
+    keymap_file: KeymapFile,
     state: xkb::State,
     repeat_rate: i32,
     repeat_delay: i32,
@@ -119,6 +215,7 @@ impl fmt::Debug for KbdInternal {
             .field("pressed_keys", &self.pressed_keys)
             .field("mods_state", &self.mods_state)
             .field("keymap", &self.keymap.get_raw_ptr())
+            .field("keymap_file", &self.keymap_file)
             .field("state", &self.state.get_raw_ptr())
             .field("repeat_rate", &self.repeat_rate)
             .field("repeat_delay", &self.repeat_delay)
@@ -133,40 +230,38 @@ unsafe impl Send for KbdInternal {}
 
 impl KbdInternal {
     fn new(
-        xkb_config: XkbConfig<'_>,
+        keymap: xkb::Keymap,
+        keymap_file: KeymapFile,
         repeat_rate: i32,
         repeat_delay: i32,
         focus_hook: Box<dyn FnMut(Option<&WlSurface>)>,
-    ) -> Result<KbdInternal, ()> {
-        // we create a new contex for each keyboard because libxkbcommon is actually NOT threadsafe
-        // so confining it inside the KbdInternal allows us to use Rusts mutability rules to make
-        // sure nothing goes wrong.
-        //
-        // FIXME: This is an issue with the xkbcommon-rs crate that does not reflect this
-        // non-threadsafety properly.
-        let context = xkb::Context::new(xkb::CONTEXT_NO_FLAGS);
-        let keymap = xkb::Keymap::new_from_names(
-            &context,
-            &xkb_config.rules,
-            &xkb_config.model,
-            &xkb_config.layout,
-            &xkb_config.variant,
-            xkb_config.options,
-            xkb::KEYMAP_COMPILE_NO_FLAGS,
-        )
-        .ok_or(())?;
+    ) -> KbdInternal {
         let state = xkb::State::new(&keymap);
-        Ok(KbdInternal {
+        KbdInternal {
             known_kbds: Vec::new(),
             focus: None,
             pressed_keys: Vec::new(),
             mods_state: ModifiersState::default(),
             keymap,
+            keymap_file,
             state,
             repeat_rate,
             repeat_delay,
             focus_hook,
-        })
+        }
+    }
+
+    fn change_keymap(&mut self, keymap: xkb::Keymap, keymap_file: KeymapFile) {
+        let mut state = xkb::State::new(&keymap);
+        // keep the keys pressed, so held modifiers stay active
+        for &keycode in &self.pressed_keys {
+            state.update_key(keycode + 8, xkb::KeyDirection::Down);
+        }
+        self.mods_state.update_with(&state);
+
+        self.keymap = keymap;
+        self.keymap_file = keymap_file;
+        self.state = state;
     }
 
     // return true if modifier state has changed
@@ -256,20 +351,25 @@ where
         "rules" => xkb_config.rules, "model" => xkb_config.model, "layout" => xkb_config.layout,
         "variant" => xkb_config.variant, "options" => &xkb_config.options
     );
-    let internal =
-        KbdInternal::new(xkb_config, repeat_rate, repeat_delay, Box::new(focus_hook)).map_err(|_| {
-            debug!(log, "Loading keymap failed");
-            Error::BadKeymap
-        })?;
-
-    info!(log, "Loaded Keymap"; "name" => internal.keymap.layouts().next());
-
-    let keymap = internal.keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1);
+    let keymap = compile_keymap(xkb_config).ok_or_else(|| {
+        debug!(log, "Loading keymap failed");
+        Error::BadKeymap
+    })?;
+
+    info!(log, "Loaded Keymap"; "name" => keymap.layouts().next());
+
+    let keymap_file = KeymapFile::new(keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1), &log);
+    let internal = KbdInternal::new(
+        keymap,
+        keymap_file,
+        repeat_rate,
+        repeat_delay,
+        Box::new(focus_hook),
+    );
 
     Ok(KeyboardHandle {
         arc: Rc::new(KbdRc {
             internal: RefCell::new(internal),
-            keymap,
             logger: log,
         }),
     })
@@ -278,7 +378,6 @@ where
 #[derive(Debug)]
 struct KbdRc {
     internal: RefCell<KbdInternal>,
-    keymap: String,
     logger: ::slog::Logger,
 }
 
@@ -489,19 +588,8 @@ impl KeyboardHandle {
     pub(crate) fn new_kbd(&self, kbd: WlKeyboard) {
         trace!(self.arc.logger, "Sending keymap to client");
 
-        // prepare a tempfile with the keymap, to send it to the client
-        let ret = tempfile().and_then(|mut f| {
-            f.write_all(self.arc.keymap.as_bytes())?;
-            f.flush()?;
-            kbd.keymap(
-                KeymapFormat::XkbV1,
-                f.as_raw_fd(),
-                self.arc.keymap.as_bytes().len() as u32,
-            );
-            Ok(())
-        });
-
-        if let Err(e) = ret {
+        let mut guard = self.arc.internal.borrow_mut();
+        if let Err(e) = guard.keymap_file.send(&kbd) {
             warn!(self.arc.logger,
                 "Failed write keymap to client in a tempfile";
                 "err" => format!("{:?}", e)
@@ -509,13 +597,61 @@ impl KeyboardHandle {
             return;
         };
 
-        let mut guard = self.arc.internal.borrow_mut();
         if kbd.as_ref().version() >= 4 {
             kbd.repeat_info(guard.repeat_rate, guard.repeat_delay);
         }
         guard.known_kbds.push(kbd);
     }
 
+    /// Change the keymap of this keyboard
+    ///
+    /// The new keymap is compiled from the given RMLVO rules and sent to all clients, the keyboard
+    /// and its focus are kept. Keys that are currently pressed stay pressed: the focused client
+    /// re-enters its surface with the pressed keys and receives the modifiers of the new keymap.
+    /// The given `serial` is used for these leave, enter and modifiers events.
+    ///
+    /// On error, the previous keymap stays active.
+    pub fn set_keymap(&self, xkb_config: XkbConfig<'_>, serial: Serial) -> Result<(), Error> {
+        info!(self.arc.logger, "Changing keymap";
+            "rules" => xkb_config.rules, "model" => xkb_config.model, "layout" => xkb_config.layout,
+            "variant" => xkb_config.variant, "options" => &xkb_config.options
+        );
+        let keymap = compile_keymap(xkb_config).ok_or_else(|| {
+            debug!(self.arc.logger, "Loading keymap failed");
+            Error::BadKeymap
+        })?;
+        let keymap_file = KeymapFile::new(keymap.get_as_string(xkb::KEYMAP_FORMAT_TEXT_V1), &self.arc.logger);
+
+        let mut guard = self.arc.internal.borrow_mut();
+        guard.change_keymap(keymap, keymap_file);
+
+        let (dep, la, lo, gr) = guard.serialize_modifiers();
+        let keys = guard.serialize_pressed_keys();
+        for kbd in &guard.known_kbds {
+            let focus = guard
+                .focus
+                .as_ref()
+                .filter(|surface| kbd.as_ref().same_client_as(surface.as_ref()));
+
+            if let Some(surface) = focus {
+                kbd.leave(serial.into(), surface);
+            }
+            if let Err(e) = guard.keymap_file.send(kbd) {
+                warn!(self.arc.logger,
+                    "Failed write keymap to client in a tempfile";
+                    "err" => format!("{:?}", e)
+                );
+            }
+            if let Some(surface) = focus {
+                kbd.enter(serial.into(), surface, keys.clone());
+                // Modifiers must be send after enter event.
+                kbd.modifiers(serial.into(), dep, la, lo, gr);
+            }
+        }
+
+        Ok(())
+    }
+
     /// Change the repeat info configured for this keyboard
     pub fn change_repeat_info(&self, rate: i32, delay: i32) {
         let mut guard = self.arc.internal.borrow_mut();
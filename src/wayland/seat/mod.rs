@@ -24,10 +24,10 @@
 //!
 //! Once the seat is initialized, you can add capabilities to it.
 //!
-//! Currently, only pointer and keyboard capabilities are supported by smithay.
+//! Currently, pointer, keyboard and touch capabilities are supported by smithay.
 //!
 //! You can add these capabilities via methods of the [`Seat`] struct:
-//! [`Seat::add_keyboard`] and [`Seat::add_pointer`].
+//! [`Seat::add_keyboard`], [`Seat::add_pointer`] and [`Seat::add_touch`].
 //! These methods return handles that can be cloned and sent across thread, so you can keep one around
 //! in your event-handling code to forward inputs to your clients.
 //!
@@ -38,6 +38,7 @@ use std::{cell::RefCell, fmt, ops::Deref as _, rc::Rc};
 
 mod keyboard;
 mod pointer;
+mod touch;
 
 pub use self::{
     keyboard::{
@@ -47,6 +48,7 @@ pub use self::{
         AxisFrame, CursorImageAttributes, CursorImageStatus, GrabStartData, PointerGrab, PointerHandle,
         PointerInnerHandle,
     },
+    touch::TouchHandle,
 };
 
 use wayland_server::{
@@ -58,6 +60,7 @@ use wayland_server::{
 struct Inner {
     pointer: Option<PointerHandle>,
     keyboard: Option<KeyboardHandle>,
+    touch: Option<TouchHandle>,
     known_seats: Vec<wl_seat::WlSeat>,
 }
 
@@ -89,6 +92,9 @@ impl Inner {
         if self.keyboard.is_some() {
             caps |= wl_seat::Capability::Keyboard;
         }
+        if self.touch.is_some() {
+            caps |= wl_seat::Capability::Touch;
+        }
         caps
     }
 
@@ -133,6 +139,7 @@ impl Seat {
             inner: RefCell::new(Inner {
                 pointer: None,
                 keyboard: None,
+                touch: None,
                 known_seats: Vec::new(),
             }),
             log: log.new(slog::o!("smithay_module" => "seat_handler", "seat_name" => name.clone())),
@@ -312,6 +319,60 @@ impl Seat {
         }
     }
 
+    /// Adds the touch capability to this seat
+    ///
+    /// You are provided a [`TouchHandle`], which allows you to send input events
+    /// to this touch device. This handle can be cloned.
+    ///
+    /// Calling this method on a seat that already has a touch capability
+    /// will overwrite it, and will be seen by the clients as if the
+    /// touchscreen was unplugged and a new one was plugged.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate wayland_server;
+    /// #
+    /// # use smithay::wayland::seat::Seat;
+    /// #
+    /// # let mut display = wayland_server::Display::new();
+    /// # let (mut seat, seat_global) = Seat::new(
+    /// #     &mut display,
+    /// #     "seat-0".into(),
+    /// #     None
+    /// # );
+    /// let touch_handle = seat.add_touch();
+    /// ```
+    pub fn add_touch(&mut self) -> TouchHandle {
+        let mut inner = self.arc.inner.borrow_mut();
+        let touch = self::touch::create_touch_handler();
+        if inner.touch.is_some() {
+            // there is already a touch device, remove it and notify the clients
+            // of the change
+            inner.touch = None;
+            inner.send_all_caps();
+        }
+        inner.touch = Some(touch.clone());
+        inner.send_all_caps();
+        touch
+    }
+
+    /// Access the touch device of this seat if any
+    pub fn get_touch(&self) -> Option<TouchHandle> {
+        self.arc.inner.borrow_mut().touch.clone()
+    }
+
+    /// Remove the touch capability from this seat
+    ///
+    /// Clients will be appropriately notified.
+    pub fn remove_touch(&mut self) {
+        let mut inner = self.arc.inner.borrow_mut();
+        if inner.touch.is_some() {
+            inner.touch = None;
+            inner.send_all_caps();
+        }
+    }
+
     /// Checks whether a given [`WlSeat`](wl_seat::WlSeat) is associated with this [`Seat`]
     pub fn owns(&self, seat: &wl_seat::WlSeat) -> bool {
         let inner = self.arc.inner.borrow_mut();
@@ -348,8 +409,13 @@ fn implement_seat(seat: Main<wl_seat::WlSeat>, arc: Rc<SeatRc>) -> wl_seat::WlSe
                     // same as pointer, should error but cannot
                 }
             }
-            wl_seat::Request::GetTouch { .. } => {
-                // TODO
+            wl_seat::Request::GetTouch { id } => {
+                let touch = self::touch::implement_touch(id, inner.touch.as_ref());
+                if let Some(ref touch_handle) = inner.touch {
+                    touch_handle.new_touch(touch);
+                } else {
+                    // same as pointer, should error but cannot
+                }
             }
             wl_seat::Request::Release => {
                 // Our destructors already handle it
@@ -1,5 +1,6 @@
 use std::{cell::RefCell, fmt, ops::Deref as _, rc::Rc, sync::Mutex};
 
+use wayland_protocols::unstable::relative_pointer::v1::server::zwp_relative_pointer_v1::ZwpRelativePointerV1;
 use wayland_server::{
     protocol::{
         wl_pointer::{self, Axis, AxisSource, ButtonState, Request, WlPointer},
@@ -10,7 +11,7 @@ use wayland_server::{
 
 use crate::{
     utils::{Logical, Point},
-    wayland::{compositor, Serial},
+    wayland::{compositor, pointer_constraints::PointerConstraint, Serial},
 };
 
 static CURSOR_IMAGE_ROLE: &str = "cursor_image";
@@ -52,11 +53,15 @@ impl fmt::Debug for GrabStatus {
 
 struct PointerInternal {
     known_pointers: Vec<WlPointer>,
+    relative_pointers: Vec<ZwpRelativePointerV1>,
     focus: Option<(WlSurface, Point<i32, Logical>)>,
     pending_focus: Option<(WlSurface, Point<i32, Logical>)>,
     location: Point<f64, Logical>,
     grab: GrabStatus,
     pressed_buttons: Vec<u32>,
+    constraint: Option<PointerConstraint>,
+    // relative motion was sent to the focused client, but not yet terminated by a frame event
+    pending_frame: bool,
     image_callback: Box<dyn FnMut(CursorImageStatus)>,
 }
 
@@ -65,11 +70,14 @@ impl fmt::Debug for PointerInternal {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("PointerInternal")
             .field("known_pointers", &self.known_pointers)
+            .field("relative_pointers", &self.relative_pointers)
             .field("focus", &self.focus)
             .field("pending_focus", &self.pending_focus)
             .field("location", &self.location)
             .field("grab", &self.grab)
             .field("pressed_buttons", &self.pressed_buttons)
+            .field("constraint", &self.constraint)
+            .field("pending_frame", &self.pending_frame)
             .field("image_callback", &"...")
             .finish()
     }
@@ -82,11 +90,14 @@ impl PointerInternal {
     {
         PointerInternal {
             known_pointers: Vec::new(),
+            relative_pointers: Vec::new(),
             focus: None,
             pending_focus: None,
             location: (0.0, 0.0).into(),
             grab: GrabStatus::None,
             pressed_buttons: Vec::new(),
+            constraint: None,
+            pending_frame: false,
             image_callback: Box::new(cb) as Box<_>,
         }
     }
@@ -107,6 +118,35 @@ impl PointerInternal {
         }
     }
 
+    // Terminates the relative motion sent to the focused client with a frame event
+    fn send_pending_frame(&mut self) {
+        if !self.pending_frame {
+            return;
+        }
+        self.pending_frame = false;
+        self.with_focused_pointers(|pointer, _| {
+            if pointer.as_ref().version() >= 5 {
+                pointer.frame();
+            }
+        });
+    }
+
+    // Deactivates the active constraint if its surface lost the pointer focus
+    fn check_constraint(&mut self) {
+        if let Some(constraint) = self.constraint.clone() {
+            let focused = match self.focus {
+                Some((ref surface, _)) => {
+                    surface.as_ref().is_alive() && surface.as_ref().equals(constraint.surface().as_ref())
+                }
+                None => false,
+            };
+            if !focused {
+                self.constraint = None;
+                constraint.set_inactive();
+            }
+        }
+    }
+
     fn with_grab<F>(&mut self, f: F)
     where
         F: FnOnce(PointerInnerHandle<'_>, &mut dyn PointerGrab),
@@ -151,12 +191,50 @@ pub struct PointerHandle {
     inner: Rc<RefCell<PointerInternal>>,
 }
 
+impl PartialEq for PointerHandle {
+    fn eq(&self, other: &PointerHandle) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
 impl PointerHandle {
     pub(crate) fn new_pointer(&self, pointer: WlPointer) {
         let mut guard = self.inner.borrow_mut();
         guard.known_pointers.push(pointer);
     }
 
+    pub(crate) fn new_relative_pointer(&self, relative_pointer: ZwpRelativePointerV1) {
+        self.inner.borrow_mut().relative_pointers.push(relative_pointer);
+    }
+
+    pub(crate) fn remove_relative_pointer(&self, relative_pointer: &ZwpRelativePointerV1) {
+        self.inner
+            .borrow_mut()
+            .relative_pointers
+            .retain(|p| !p.as_ref().equals(relative_pointer.as_ref()));
+    }
+
+    pub(crate) fn from_resource(pointer: &WlPointer) -> Option<PointerHandle> {
+        pointer.as_ref().user_data().get::<PointerHandle>().cloned()
+    }
+
+    /// Replaces the active constraint of this pointer, returning the previous one
+    pub(crate) fn set_constraint(&self, constraint: Option<PointerConstraint>) -> Option<PointerConstraint> {
+        ::std::mem::replace(&mut self.inner.borrow_mut().constraint, constraint)
+    }
+
+    pub(crate) fn unset_constraint(&self, constraint: &PointerConstraint) {
+        let mut inner = self.inner.borrow_mut();
+        if inner.constraint.as_ref() == Some(constraint) {
+            inner.constraint = None;
+        }
+    }
+
+    /// Returns the currently active [`PointerConstraint`] of this pointer, if any
+    pub fn active_constraint(&self) -> Option<PointerConstraint> {
+        self.inner.borrow().constraint.clone()
+    }
+
     /// Change the current grab on this pointer to the provided grab
     ///
     /// Overwrites any current grab.
@@ -204,18 +282,89 @@ impl PointerHandle {
     ///
     /// This will internally take care of notifying the appropriate client objects
     /// of enter/motion/leave events.
+    ///
+    /// If a [`PointerConstraint`] is active, the constrained surface keeps the focus, and the motion
+    /// is ignored if the pointer is locked. A confined pointer that would leave its region is moved
+    /// to the nearest point of the region instead, use [`PointerHandle::current_location`] to get
+    /// the resulting location. The constraint is deactivated if its surface lost the focus in the
+    /// meantime.
     pub fn motion(
         &self,
-        location: Point<f64, Logical>,
-        focus: Option<(WlSurface, Point<i32, Logical>)>,
+        mut location: Point<f64, Logical>,
+        mut focus: Option<(WlSurface, Point<i32, Logical>)>,
         serial: Serial,
         time: u32,
     ) {
         let mut inner = self.inner.borrow_mut();
         inner.pending_focus = focus.clone();
+        inner.check_constraint();
+        if let Some(constraint) = inner.constraint.clone() {
+            let (surface, surface_location) = inner.focus.clone().unwrap();
+            let origin = surface_location.to_f64();
+            location = match constraint.constrain(location - origin) {
+                Some(location) => location + origin,
+                None => {
+                    inner.send_pending_frame();
+                    return;
+                }
+            };
+            focus = Some((surface, surface_location));
+        }
         inner.with_grab(move |mut handle, grab| {
             grab.motion(&mut handle, location, focus, serial, time);
         });
+        inner.send_pending_frame();
+        inner.check_constraint();
+    }
+
+    /// Notify that the pointer device moved
+    ///
+    /// You provide the accelerated and unaccelerated deltas of the motion, as well as a timestamp
+    /// with microsecond granularity. Backends that cannot tell the unaccelerated delta apart can
+    /// provide the same delta twice.
+    ///
+    /// This is sent to the `relative_pointer` objects of the client owning the currently focused
+    /// surface. It is not affected by grabs or [`PointerConstraint`]s, the deltas are still sent
+    /// if the pointer is locked.
+    ///
+    /// The relative motion belongs to the same frame as the absolute motion, so this should be
+    /// called right before [`PointerHandle::motion`], which terminates the frame. If the motion is
+    /// not followed by an absolute motion, call [`PointerHandle::frame`] instead.
+    pub fn relative_motion(
+        &self,
+        delta: Point<f64, Logical>,
+        delta_unaccel: Point<f64, Logical>,
+        utime: u64,
+    ) {
+        let mut inner = self.inner.borrow_mut();
+        let focus = match inner.focus {
+            Some((ref focus, _)) if focus.as_ref().is_alive() => focus.clone(),
+            _ => return,
+        };
+        let mut sent = false;
+        for relative_pointer in &inner.relative_pointers {
+            if relative_pointer.as_ref().same_client_as(focus.as_ref()) {
+                sent = true;
+                relative_pointer.relative_motion(
+                    (utime >> 32) as u32,
+                    utime as u32,
+                    delta.x,
+                    delta.y,
+                    delta_unaccel.x,
+                    delta_unaccel.y,
+                );
+            }
+        }
+        inner.pending_frame |= sent;
+    }
+
+    /// Terminate the frame of the relative motion sent with [`PointerHandle::relative_motion`]
+    ///
+    /// This is only needed if the relative motion is not followed by a call to
+    /// [`PointerHandle::motion`], and does nothing if no relative motion was sent since the
+    /// last frame.
+    pub fn frame(&self) {
+        self.inner.borrow_mut().send_pending_frame();
     }
 
     /// Notify that a button was pressed
@@ -368,6 +517,11 @@ impl<'a> PointerInnerHandle<'a> {
         serial: Serial,
         time: u32,
     ) {
+        // the frame events sent below to the focused client also terminate its relative motion
+        if self.inner.focus.is_some() {
+            self.inner.pending_frame = false;
+        }
+
         // do we leave a surface ?
         let mut leave = true;
         self.inner.location = location;
@@ -434,25 +588,27 @@ impl<'a> PointerInnerHandle<'a> {
     /// objects matching with the currently focused surface.
     pub fn axis(&mut self, details: AxisFrame) {
         self.inner.with_focused_pointers(|pointer, _| {
-            // axis
-            if details.axis.0 != 0.0 {
-                pointer.axis(details.time, Axis::HorizontalScroll, details.axis.0);
-            }
-            if details.axis.1 != 0.0 {
-                pointer.axis(details.time, Axis::VerticalScroll, details.axis.1);
-            }
-            if pointer.as_ref().version() >= 5 {
-                // axis source
+            let has_frames = pointer.as_ref().version() >= 5;
+            // axis source, it has to precede the other axis events of the frame
+            if has_frames {
                 if let Some(source) = details.source {
                     pointer.axis_source(source);
                 }
-                // axis discrete
-                if details.discrete.0 != 0 {
+            }
+            // axis, each preceded by its discrete steps
+            if details.axis.0 != 0.0 {
+                if has_frames && details.discrete.0 != 0 {
                     pointer.axis_discrete(Axis::HorizontalScroll, details.discrete.0);
                 }
-                if details.discrete.1 != 0 {
+                pointer.axis(details.time, Axis::HorizontalScroll, details.axis.0);
+            }
+            if details.axis.1 != 0.0 {
+                if has_frames && details.discrete.1 != 0 {
                     pointer.axis_discrete(Axis::VerticalScroll, details.discrete.1);
                 }
+                pointer.axis(details.time, Axis::VerticalScroll, details.axis.1);
+            }
+            if has_frames {
                 // stop
                 if details.stop.0 {
                     pointer.axis_stop(details.time, Axis::HorizontalScroll);
@@ -638,6 +794,8 @@ pub(crate) fn implement_pointer(pointer: Main<WlPointer>, handle: Option<&Pointe
     });
 
     if let Some(h) = handle {
+        let handle = h.clone();
+        pointer.as_ref().user_data().set(move || handle);
         let inner = h.inner.clone();
         pointer.assign_destructor(Filter::new(move |pointer: WlPointer, _, _| {
             inner
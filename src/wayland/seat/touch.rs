@@ -0,0 +1,186 @@
+use std::{cell::RefCell, collections::HashMap, ops::Deref as _, rc::Rc};
+
+use wayland_server::{
+    protocol::{
+        wl_surface::WlSurface,
+        wl_touch::{Request, WlTouch},
+    },
+    Filter, Main,
+};
+
+use crate::{
+    utils::{Logical, Point},
+    wayland::Serial,
+};
+
+#[derive(Debug, Default)]
+struct TouchInternal {
+    known_touches: Vec<WlTouch>,
+    // The surface each touch point went down on, along with its location
+    focus: HashMap<i32, (WlSurface, Point<i32, Logical>)>,
+    // Touch objects that received events since the last frame
+    pending_frame: Vec<WlTouch>,
+}
+
+impl TouchInternal {
+    fn with_focused_touches<F>(&mut self, surface: &WlSurface, mut f: F)
+    where
+        F: FnMut(&WlTouch),
+    {
+        if !surface.as_ref().is_alive() {
+            return;
+        }
+        for touch in &self.known_touches {
+            if touch.as_ref().same_client_as(surface.as_ref()) {
+                f(touch);
+                if !self
+                    .pending_frame
+                    .iter()
+                    .any(|t| t.as_ref().equals(touch.as_ref()))
+                {
+                    self.pending_frame.push(touch.clone());
+                }
+            }
+        }
+    }
+}
+
+/// An handle to a touch handler
+///
+/// It can be cloned and all clones manipulate the same internal state.
+///
+/// This handle gives you access to an interface to send touch events to your
+/// clients.
+///
+/// Every touch point is identified by a slot id and stays attached to the surface
+/// it went down on until it is released or the touch sequence is cancelled, like
+/// a pointer is during a click.
+#[derive(Debug, Clone)]
+pub struct TouchHandle {
+    inner: Rc<RefCell<TouchInternal>>,
+}
+
+impl TouchHandle {
+    pub(crate) fn new_touch(&self, touch: WlTouch) {
+        self.inner.borrow_mut().known_touches.push(touch);
+    }
+
+    /// Notify that a new touch point appeared
+    ///
+    /// You provide the surface the touch point went down on along with its location in the global
+    /// compositor space, or `None` if it is not on any surface, and the location of the touch point
+    /// in the global compositor space. Following events of this slot are sent to the same surface.
+    pub fn down(
+        &self,
+        serial: Serial,
+        time: u32,
+        focus: Option<(WlSurface, Point<i32, Logical>)>,
+        location: Point<f64, Logical>,
+        slot_id: i32,
+    ) {
+        let mut inner = self.inner.borrow_mut();
+        let (surface, surface_location) = match focus {
+            Some(focus) => focus,
+            None => {
+                inner.focus.remove(&slot_id);
+                return;
+            }
+        };
+
+        let (x, y) = (location - surface_location.to_f64()).into();
+        inner.with_focused_touches(&surface, |touch| {
+            touch.down(serial.into(), time, &surface, slot_id, x, y)
+        });
+        inner.focus.insert(slot_id, (surface, surface_location));
+    }
+
+    /// Notify that a touch point was released
+    pub fn up(&self, serial: Serial, time: u32, slot_id: i32) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some((surface, _)) = inner.focus.remove(&slot_id) {
+            inner.with_focused_touches(&surface, |touch| touch.up(serial.into(), time, slot_id));
+        }
+    }
+
+    /// Notify that a touch point moved
+    ///
+    /// The location is given in the global compositor space.
+    pub fn motion(&self, time: u32, location: Point<f64, Logical>, slot_id: i32) {
+        let mut inner = self.inner.borrow_mut();
+        if let Some((surface, surface_location)) = inner.focus.get(&slot_id).cloned() {
+            let (x, y) = (location - surface_location.to_f64()).into();
+            inner.with_focused_touches(&surface, |touch| touch.motion(time, slot_id, x, y));
+        }
+    }
+
+    /// Notify that a set of touch events, that logically belong together, is complete
+    ///
+    /// This needs to be called after [`TouchHandle::down`], [`TouchHandle::up`] and
+    /// [`TouchHandle::motion`] to let the clients process the events.
+    pub fn frame(&self) {
+        let mut inner = self.inner.borrow_mut();
+        for touch in inner.pending_frame.drain(..) {
+            if touch.as_ref().is_alive() {
+                touch.frame();
+            }
+        }
+    }
+
+    /// Notify that the compositor took over the current touch sequence
+    ///
+    /// All touch points are released, the clients will not receive any further events for them.
+    pub fn cancel(&self) {
+        let mut inner = self.inner.borrow_mut();
+        let surfaces = inner
+            .focus
+            .drain()
+            .map(|(_, (surface, _))| surface)
+            .collect::<Vec<_>>();
+        let mut cancelled = Vec::<WlTouch>::new();
+        for surface in surfaces {
+            inner.with_focused_touches(&surface, |touch| {
+                if !cancelled.iter().any(|t| t.as_ref().equals(touch.as_ref())) {
+                    touch.cancel();
+                    cancelled.push(touch.clone());
+                }
+            });
+        }
+        // A cancelled sequence does not need to be terminated by a frame
+        inner.pending_frame.clear();
+    }
+
+    /// Returns the surface the touch point with the given slot id is attached to, if any
+    pub fn current_focus(&self, slot_id: i32) -> Option<WlSurface> {
+        self.inner
+            .borrow()
+            .focus
+            .get(&slot_id)
+            .map(|(surface, _)| surface.clone())
+    }
+}
+
+pub(crate) fn create_touch_handler() -> TouchHandle {
+    TouchHandle {
+        inner: Rc::new(RefCell::new(TouchInternal::default())),
+    }
+}
+
+pub(crate) fn implement_touch(touch: Main<WlTouch>, handle: Option<&TouchHandle>) -> WlTouch {
+    touch.quick_assign(|_touch, request, _data| match request {
+        Request::Release => {
+            // Our destructors already handle it
+        }
+        _ => unreachable!(),
+    });
+
+    if let Some(h) = handle {
+        let inner = h.inner.clone();
+        touch.assign_destructor(Filter::new(move |touch: WlTouch, _, _| {
+            let mut inner = inner.borrow_mut();
+            inner.known_touches.retain(|t| !t.as_ref().equals(touch.as_ref()));
+            inner.pending_frame.retain(|t| !t.as_ref().equals(touch.as_ref()));
+        }))
+    }
+
+    touch.deref().clone()
+}
@@ -0,0 +1,186 @@
+use crate::{
+    utils::{Logical, Rectangle},
+    wayland::compositor,
+};
+
+use super::{Anchor, ExclusiveZone, LayerSurface, LayerSurfaceCachedState};
+
+/// Result of the arrangement of the layer surfaces of an output
+///
+/// See [`arrange_layers`].
+#[derive(Debug)]
+pub struct LayerArrangement {
+    /// The geometry of every arranged layer surface, in the global compositor space
+    ///
+    /// You should configure each surface with the size of its geometry, and map it at its location.
+    pub geometries: Vec<(LayerSurface, Rectangle<i32, Logical>)>,
+    /// The area of the output not covered by any exclusive zone
+    ///
+    /// This is where your other windows should be placed.
+    pub usable_area: Rectangle<i32, Logical>,
+}
+
+/// Arrange the layer surfaces of an output
+///
+/// Computes the geometry of the provided layer surfaces according to their current anchors, margins,
+/// size and exclusive zone, as well as the area of the output left usable for the other windows.
+///
+/// Surfaces with an exclusive zone are placed first, in the provided order, and each of them reserves
+/// its zone at the edge of the output it is anchored to. Surfaces without exclusive zone are then placed
+/// in the remaining usable area, or in the whole output if they asked to ignore the exclusive zones.
+///
+/// Dead surfaces are skipped.
+pub fn arrange_layers<'a, I>(output_geometry: Rectangle<i32, Logical>, surfaces: I) -> LayerArrangement
+where
+    I: IntoIterator<Item = &'a LayerSurface>,
+{
+    let states = surfaces
+        .into_iter()
+        .filter_map(|layer| {
+            let surface = layer.get_surface()?;
+            let state = compositor::with_states(surface, |states| {
+                *states.cached_state.current::<LayerSurfaceCachedState>()
+            })
+            .ok()?;
+            Some((layer.clone(), state))
+        })
+        .collect::<Vec<_>>();
+
+    let mut usable_area = output_geometry;
+    let mut geometries = Vec::with_capacity(states.len());
+
+    for (layer, state) in states.iter() {
+        if let ExclusiveZone::Exclusive(zone) = state.exclusive_zone {
+            geometries.push((layer.clone(), layer_geometry(state, usable_area)));
+            exclude_zone(&mut usable_area, state, zone as i32);
+        }
+    }
+
+    for (layer, state) in states.iter() {
+        let bounds = match state.exclusive_zone {
+            ExclusiveZone::Exclusive(_) => continue,
+            ExclusiveZone::Neutral => usable_area,
+            ExclusiveZone::DontCare => output_geometry,
+        };
+        geometries.push((layer.clone(), layer_geometry(state, bounds)));
+    }
+
+    LayerArrangement {
+        geometries,
+        usable_area,
+    }
+}
+
+// Compute the geometry of a layer surface placed inside the given bounds
+fn layer_geometry(
+    state: &LayerSurfaceCachedState,
+    bounds: Rectangle<i32, Logical>,
+) -> Rectangle<i32, Logical> {
+    let margin = state.margin;
+    let mut geometry = Rectangle::from_loc_and_size(bounds.loc, state.size);
+
+    if geometry.size.w == 0 {
+        geometry.size.w = bounds.size.w - margin.left - margin.right;
+    }
+    geometry.loc.x += if state.anchor.anchored_horizontally() {
+        margin.left + (bounds.size.w - margin.left - margin.right - geometry.size.w) / 2
+    } else if state.anchor.contains(Anchor::LEFT) {
+        margin.left
+    } else if state.anchor.contains(Anchor::RIGHT) {
+        bounds.size.w - geometry.size.w - margin.right
+    } else {
+        (bounds.size.w - geometry.size.w) / 2
+    };
+
+    if geometry.size.h == 0 {
+        geometry.size.h = bounds.size.h - margin.top - margin.bottom;
+    }
+    geometry.loc.y += if state.anchor.anchored_vertically() {
+        margin.top + (bounds.size.h - margin.top - margin.bottom - geometry.size.h) / 2
+    } else if state.anchor.contains(Anchor::TOP) {
+        margin.top
+    } else if state.anchor.contains(Anchor::BOTTOM) {
+        bounds.size.h - geometry.size.h - margin.bottom
+    } else {
+        (bounds.size.h - geometry.size.h) / 2
+    };
+
+    geometry
+}
+
+// Remove the exclusive zone of a layer surface from the usable area
+//
+// The zone is only meaningful if the surface is anchored to a single edge, or to an edge and
+// both perpendicular edges.
+fn exclude_zone(usable_area: &mut Rectangle<i32, Logical>, state: &LayerSurfaceCachedState, zone: i32) {
+    let anchor = state.anchor;
+    let margin = state.margin;
+    let horizontal = Anchor::LEFT | Anchor::RIGHT;
+    let vertical = Anchor::TOP | Anchor::BOTTOM;
+
+    if anchor == Anchor::TOP || anchor == Anchor::TOP | horizontal {
+        let zone = zone + margin.top;
+        usable_area.loc.y += zone;
+        usable_area.size.h -= zone;
+    } else if anchor == Anchor::BOTTOM || anchor == Anchor::BOTTOM | horizontal {
+        usable_area.size.h -= zone + margin.bottom;
+    } else if anchor == Anchor::LEFT || anchor == Anchor::LEFT | vertical {
+        let zone = zone + margin.left;
+        usable_area.loc.x += zone;
+        usable_area.size.w -= zone;
+    } else if anchor == Anchor::RIGHT || anchor == Anchor::RIGHT | vertical {
+        usable_area.size.w -= zone + margin.right;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wayland::shell::wlr_layer::Margins;
+
+    #[test]
+    fn top_panel_geometry_and_zone() {
+        let output = Rectangle::from_loc_and_size((0, 0), (1920, 1080));
+        let state = LayerSurfaceCachedState {
+            size: (0, 30).into(),
+            anchor: Anchor::TOP | Anchor::LEFT | Anchor::RIGHT,
+            exclusive_zone: ExclusiveZone::Exclusive(30),
+            margin: Margins {
+                top: 5,
+                right: 10,
+                bottom: 0,
+                left: 10,
+            },
+            ..Default::default()
+        };
+
+        assert_eq!(
+            layer_geometry(&state, output),
+            Rectangle::from_loc_and_size((10, 5), (1900, 30))
+        );
+
+        let mut usable_area = output;
+        exclude_zone(&mut usable_area, &state, 30);
+        assert_eq!(usable_area, Rectangle::from_loc_and_size((0, 35), (1920, 1045)));
+    }
+
+    #[test]
+    fn corner_anchor_has_no_zone() {
+        let output = Rectangle::from_loc_and_size((0, 0), (800, 600));
+        let state = LayerSurfaceCachedState {
+            size: (100, 50).into(),
+            anchor: Anchor::BOTTOM | Anchor::RIGHT,
+            exclusive_zone: ExclusiveZone::Exclusive(50),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            layer_geometry(&state, output),
+            Rectangle::from_loc_and_size((700, 550), (100, 50))
+        );
+
+        let mut usable_area = output;
+        exclude_zone(&mut usable_area, &state, 50);
+        assert_eq!(usable_area, output);
+    }
+}
@@ -51,8 +51,15 @@ pub(super) fn layer_shell_implementation(
             })
             .unwrap();
 
-            compositor::add_commit_hook(&surface, |surface| {
+            compositor::add_pre_commit_hook(&surface, |surface| {
                 compositor::with_states(surface, |states| {
+                    let has_buffer = matches!(
+                        states
+                            .cached_state
+                            .pending::<compositor::SurfaceAttributes>()
+                            .buffer,
+                        Some(compositor::BufferAssignment::NewBuffer { .. })
+                    );
                     let mut guard = states
                         .data_map
                         .get::<Mutex<LayerSurfaceAttributes>>()
@@ -60,6 +67,15 @@ pub(super) fn layer_shell_implementation(
                         .lock()
                         .unwrap();
 
+                    // A buffer must not be attached before the initial configure was acked
+                    if has_buffer && !guard.configured {
+                        guard.surface.as_ref().post_error(
+                            zwlr_layer_surface_v1::Error::InvalidSurfaceState as u32,
+                            "layer_surface has never been configured".into(),
+                        );
+                        return;
+                    }
+
                     let pending = states.cached_state.pending::<LayerSurfaceCachedState>();
 
                     if pending.size.w == 0 && !pending.anchor.anchored_horizontally() {
@@ -90,7 +106,7 @@ pub(super) fn layer_shell_implementation(
             });
 
             id.assign_destructor(Filter::new(
-                |layer_surface: zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, _, _| {
+                |layer_surface: zwlr_layer_surface_v1::ZwlrLayerSurfaceV1, _, dispatch_data| {
                     let data = layer_surface
                         .as_ref()
                         .user_data()
@@ -104,6 +120,14 @@ pub(super) fn layer_shell_implementation(
                         .unwrap()
                         .known_layers
                         .retain(|other| other.alive());
+
+                    let mut user_impl = data.shell_data.user_impl.borrow_mut();
+                    (&mut *user_impl)(
+                        LayerShellRequest::Destroyed {
+                            surface: data.wl_surface.clone(),
+                        },
+                        dispatch_data,
+                    );
                 },
             ));
 
@@ -24,6 +24,12 @@
 //!
 //! // You're now ready to go!
 //! ```
+//!
+//! ### Arrangement
+//!
+//! Once you know on which output each layer surface is displayed, the [`arrange_layers`] function
+//! computes the geometry of the layer surfaces of an output according to their anchors, margins and
+//! exclusive zones, as well as the area left usable for your other windows.
 
 use std::{
     cell::RefCell,
@@ -45,9 +51,11 @@ use crate::{
     },
 };
 
+mod arrange;
 mod handlers;
 mod types;
 
+pub use arrange::{arrange_layers, LayerArrangement};
 pub use types::{Anchor, ExclusiveZone, KeyboardInteractivity, Layer, Margins};
 
 /// The role of a wlr_layer_shell_surface
@@ -451,4 +459,12 @@ pub enum LayerShellRequest {
         /// The configure serial.
         configure: LayerSurfaceConfigure,
     },
+
+    /// A layer surface was destroyed
+    ///
+    /// You should stop displaying it and arrange the remaining layer surfaces of its output.
+    Destroyed {
+        /// The surface the layer surface was attached to
+        surface: wl_surface::WlSurface,
+    },
 }
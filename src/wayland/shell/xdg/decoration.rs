@@ -4,6 +4,9 @@
 //!
 //! A client can use this protocol to request being decorated by a supporting compositor.
 //!
+//! The compositor provides the decoration mode it prefers when initializing the global. This mode is
+//! sent to the client as soon as it creates a decoration object for a toplevel, so a compositor that
+//! decorates all windows the same way does not need to handle any request.
 //!
 //! ```no_run
 //! # extern crate wayland_server;
@@ -15,17 +18,10 @@
 //!
 //! init_xdg_decoration_manager(
 //!     &mut display,
+//!     // Advertise server side decoration
+//!     Mode::ServerSide,
 //!     |req, _ddata| match req {
-//!         XdgDecorationRequest::NewToplevelDecoration { toplevel } => {
-//!             let res = toplevel.with_pending_state(|state| {
-//!                   // Advertise server side decoration
-//!                 state.decoration_mode = Some(Mode::ServerSide);
-//!             });
-//!
-//!             if res.is_ok() {
-//!                 toplevel.send_configure();
-//!             }
-//!         }
+//!         XdgDecorationRequest::NewToplevelDecoration { .. } => {}
 //!         XdgDecorationRequest::SetMode { .. } => {}
 //!         XdgDecorationRequest::UnsetMode { .. } => {}
 //!     },
@@ -33,20 +29,25 @@
 //! );
 //!
 
-use std::{cell::RefCell, ops::Deref, rc::Rc};
+use std::{cell::RefCell, ops::Deref, rc::Rc, sync::Mutex};
 use wayland_protocols::unstable::xdg_decoration::v1::server::{
     zxdg_decoration_manager_v1::{self, ZxdgDecorationManagerV1},
     zxdg_toplevel_decoration_v1::{self, Mode, ZxdgToplevelDecorationV1},
 };
 use wayland_server::{DispatchData, Display, Filter, Global, Main};
 
-use super::ToplevelSurface;
-use crate::wayland::shell::xdg::xdg_handlers::ShellSurfaceUserData;
+use super::{ToplevelSurface, XdgToplevelSurfaceRoleAttributes};
+use crate::wayland::{
+    compositor::{self, BufferAssignment, SurfaceAttributes},
+    shell::xdg::xdg_handlers::ShellSurfaceUserData,
+};
 
 /// Events generated by xdg decoration manager
 #[derive(Debug)]
 pub enum XdgDecorationRequest {
     /// A new toplevel decoration was instantiated
+    ///
+    /// The default mode of the compositor has already been set in the pending state of the toplevel.
     NewToplevelDecoration {
         /// The toplevel asosiated with decoration
         toplevel: ToplevelSurface,
@@ -66,8 +67,12 @@ pub enum XdgDecorationRequest {
 }
 
 /// Create a new XDG Decoration Manager global
+///
+/// `default_mode` is the decoration mode preferred by the compositor. It is sent to the client
+/// along with the next configure of a toplevel, once a decoration was created for it.
 pub fn init_xdg_decoration_manager<L, Impl>(
     display: &mut Display,
+    default_mode: Mode,
     implementation: Impl,
     _logger: L,
 ) -> Global<ZxdgDecorationManagerV1>
@@ -88,11 +93,35 @@ where
                         }
                         zxdg_decoration_manager_v1::Request::GetToplevelDecoration { id, toplevel } => {
                             if let Some(data) = toplevel.as_ref().user_data().get::<ShellSurfaceUserData>() {
+                                use wayland_protocols::unstable::xdg_decoration::v1::server::zxdg_toplevel_decoration_v1::Error;
+
+                                // The decoration has to be created before any buffer is attached
+                                let has_buffer = compositor::with_states(&data.wl_surface, |states| {
+                                    let attached = matches!(
+                                        states.cached_state.pending::<SurfaceAttributes>().buffer,
+                                        Some(BufferAssignment::NewBuffer { .. })
+                                    );
+                                    attached
+                                        || states
+                                            .data_map
+                                            .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                                            .unwrap()
+                                            .lock()
+                                            .unwrap()
+                                            .has_buffer
+                                })
+                                .unwrap_or(false);
+                                if has_buffer {
+                                    id.as_ref().post_error(Error::UnconfiguredBuffer as u32, "toplevel already has a buffer attached".to_string());
+                                    return;
+                                }
+
                                 if data.decoration.borrow().is_none() {
                                     *data.decoration.borrow_mut() = Some(id.deref().clone());
                                 } else {
-                                    use wayland_protocols::unstable::xdg_decoration::v1::server::zxdg_toplevel_decoration_v1::Error;
                                     id.as_ref().post_error(Error::AlreadyConstructed as u32, "toplevel decoration is already constructed".to_string());
+                                    // Do not let the rejected object detach the existing decoration
+                                    return;
                                 }
 
                                 let toplevel = ToplevelSurface {
@@ -100,6 +129,19 @@ where
                                     wl_surface: data.wl_surface.clone(),
                                 };
 
+                                let res = toplevel.with_pending_state(|state| {
+                                    state.decoration_mode = Some(default_mode);
+                                });
+                                let _ = compositor::with_states(&data.wl_surface, |states| {
+                                    states
+                                        .data_map
+                                        .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                                        .unwrap()
+                                        .lock()
+                                        .unwrap()
+                                        .unconfigured_decoration = Some(id.deref().clone());
+                                });
+
                                 (&mut *cb.borrow_mut())(
                                     XdgDecorationRequest::NewToplevelDecoration {
                                         toplevel: toplevel.clone(),
@@ -107,6 +149,29 @@ where
                                     ddata,
                                 );
 
+                                // Before the initial configure, the mode is sent along with it
+                                if res.is_ok() && toplevel.initial_configure_sent() {
+                                    toplevel.send_configure();
+
+                                    // No configure was needed, as the mode did not change since the
+                                    // previous decoration, but the decoration still expects one
+                                    let unconfigured = compositor::with_states(&data.wl_surface, |states| {
+                                        let mut attributes = states
+                                            .data_map
+                                            .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                                            .unwrap()
+                                            .lock()
+                                            .unwrap();
+                                        attributes.unconfigured_decoration.take().map(|_| {
+                                            attributes.current.decoration_mode.unwrap_or(Mode::ClientSide)
+                                        })
+                                    })
+                                    .unwrap_or(None);
+                                    if let Some(mode) = unconfigured {
+                                        send_decoration_configure(&id, mode);
+                                    }
+                                }
+
                                 let cb = cb.clone();
                                 id.quick_assign(move |_, request, ddata| match request {
                                     zxdg_toplevel_decoration_v1::Request::SetMode { mode } => {
@@ -136,6 +201,15 @@ where
                                         toplevel.as_ref().user_data().get::<ShellSurfaceUserData>()
                                     {
                                         *data.decoration.borrow_mut() = None;
+                                        let _ = compositor::with_states(&data.wl_surface, |states| {
+                                            states
+                                                .data_map
+                                                .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                                                .unwrap()
+                                                .lock()
+                                                .unwrap()
+                                                .unconfigured_decoration = None;
+                                        });
                                     }
                                 },
                             ));
@@ -151,3 +225,126 @@ where
 pub(super) fn send_decoration_configure(id: &ZxdgToplevelDecorationV1, mode: Mode) {
     id.configure(mode)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wayland::{
+        compositor::compositor_init,
+        shell::xdg::{xdg_shell_init, XdgRequest},
+        shm::init_shm_global,
+        test_client::TestClient,
+    };
+    use std::{fs::File, io::Write, os::unix::io::AsRawFd};
+    use wayland_client::{
+        protocol::{
+            wl_buffer::WlBuffer as ClientBuffer,
+            wl_compositor::WlCompositor,
+            wl_shm::{Format, WlShm},
+            wl_surface::WlSurface as ClientSurface,
+        },
+        Main as ClientMain, ProtocolError,
+    };
+    use wayland_protocols::{
+        unstable::xdg_decoration::v1::client::zxdg_decoration_manager_v1::ZxdgDecorationManagerV1 as ClientDecorationManager,
+        xdg_shell::client::{
+            xdg_toplevel::XdgToplevel as ClientToplevel, xdg_wm_base::XdgWmBase as ClientWmBase,
+        },
+    };
+
+    // A client with the globals to create decorated toplevels with shm buffers, the toplevels are
+    // added to `toplevels`
+    fn test_client(toplevels: Rc<RefCell<Vec<ToplevelSurface>>>) -> TestClient {
+        TestClient::new(|display| {
+            compositor_init(display, |_, _| {}, None);
+            init_shm_global(display, Vec::new(), None);
+            xdg_shell_init(
+                display,
+                move |request, _| {
+                    if let XdgRequest::NewToplevel { surface } = request {
+                        toplevels.borrow_mut().push(surface);
+                    }
+                },
+                None,
+            );
+            init_xdg_decoration_manager(display, Mode::ServerSide, |_, _| {}, None);
+        })
+    }
+
+    fn toplevel(test_client: &TestClient) -> (ClientMain<ClientSurface>, ClientMain<ClientToplevel>) {
+        let compositor = test_client.globals.instantiate_exact::<WlCompositor>(4).unwrap();
+        let wm_base = test_client.globals.instantiate_exact::<ClientWmBase>(1).unwrap();
+        let surface = compositor.create_surface();
+        let toplevel = wm_base.get_xdg_surface(&surface).get_toplevel();
+        (surface, toplevel)
+    }
+
+    // A 4x4 argb buffer, the file has to be kept open until the pool is sent
+    fn buffer(test_client: &TestClient) -> (File, ClientMain<ClientBuffer>) {
+        let shm = test_client.globals.instantiate_exact::<WlShm>(1).unwrap();
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&[0; 64]).unwrap();
+        let pool = shm.create_pool(file.as_raw_fd(), 64);
+        let buffer = pool.create_buffer(0, 4, 4, 16, Format::Argb8888);
+        (file, buffer)
+    }
+
+    fn decorate(test_client: &TestClient, toplevel: &ClientToplevel) {
+        test_client
+            .globals
+            .instantiate_exact::<ClientDecorationManager>(1)
+            .unwrap()
+            .get_toplevel_decoration(toplevel);
+    }
+
+    fn is_unconfigured_buffer(error: ProtocolError) -> bool {
+        error.object_interface == "zxdg_toplevel_decoration_v1"
+            && error.code == zxdg_toplevel_decoration_v1::Error::UnconfiguredBuffer as u32
+    }
+
+    #[test]
+    fn decoration_of_toplevel_with_buffer() {
+        let mut test_client = test_client(Rc::new(RefCell::new(Vec::new())));
+        let (surface, toplevel) = toplevel(&test_client);
+        let (_file, buffer) = buffer(&test_client);
+        surface.attach(Some(&buffer), 0, 0);
+        surface.commit();
+        test_client.roundtrip().unwrap();
+
+        decorate(&test_client, &toplevel);
+        assert!(is_unconfigured_buffer(test_client.roundtrip().unwrap_err()));
+    }
+
+    #[test]
+    fn buffer_before_decoration_configure() {
+        let mut test_client = test_client(Rc::new(RefCell::new(Vec::new())));
+        let (surface, toplevel) = toplevel(&test_client);
+        decorate(&test_client, &toplevel);
+        surface.commit();
+        test_client.roundtrip().unwrap();
+
+        let (_file, buffer) = buffer(&test_client);
+        surface.attach(Some(&buffer), 0, 0);
+        surface.commit();
+        assert!(is_unconfigured_buffer(test_client.roundtrip().unwrap_err()));
+    }
+
+    #[test]
+    fn buffer_after_decoration_configure() {
+        let toplevels = Rc::new(RefCell::new(Vec::new()));
+        let mut test_client = test_client(toplevels.clone());
+        let (surface, toplevel) = toplevel(&test_client);
+        decorate(&test_client, &toplevel);
+        surface.commit();
+        test_client.roundtrip().unwrap();
+
+        // the initial configure of the toplevel also configures the decoration
+        toplevels.borrow()[0].send_configure();
+        test_client.roundtrip().unwrap();
+
+        let (_file, buffer) = buffer(&test_client);
+        surface.attach(Some(&buffer), 0, 0);
+        surface.commit();
+        test_client.roundtrip().unwrap();
+    }
+}
@@ -270,7 +270,12 @@ xdg_role!(
         pub last_acked: Option<ToplevelState>,
         /// Holds the current state of the toplevel after a successful
         /// commit.
-        pub current: ToplevelState
+        pub current: ToplevelState,
+        /// Whether a buffer is attached to the surface
+        pub(crate) has_buffer: bool,
+        /// The decoration object of this toplevel, until it received its
+        /// first configure
+        pub(crate) unconfigured_decoration: Option<zxdg_toplevel_decoration_v1::ZxdgToplevelDecorationV1>
     },
     |attributes, configure| {
         attributes.last_acked = Some(configure.state);
@@ -483,8 +488,8 @@ impl PositionerState {
     /// The position is calculated according to the rules defined
     /// in the `xdg_shell` protocol.
     /// The `constraint_adjustment` will not be considered by this
-    /// implementation and the position and size should be re-calculated
-    /// in the compositor if the compositor implements `constraint_adjustment`
+    /// implementation, use [`PositionerState::get_unconstrained_geometry`]
+    /// to keep the popup inside of a given area.
     pub fn get_geometry(&self) -> Rectangle<i32, Logical> {
         // From the `xdg_shell` prococol specification:
         //
@@ -542,6 +547,181 @@ impl PositionerState {
 
         geometry
     }
+
+    /// Get the geometry for a popup as defined by this positioner, after
+    /// applying the `constraint_adjustment` to keep it inside of the work area.
+    ///
+    /// `anchor_rect_in_root` is the anchor rectangle of the positioner in the
+    /// coordinate space of `work_area`, e.g. the global compositor space. The
+    /// work area is usually the geometry of the output the parent is mapped on,
+    /// minus exclusive zones.
+    ///
+    /// Like with [`PositionerState::get_geometry`], the returned rectangle is
+    /// relative to the parent surface `window_geometry`.
+    ///
+    /// The adjustments are applied per axis in the order mandated by the
+    /// `xdg_shell` protocol: flip, slide and finally resize. An axis, that
+    /// is not constrained, is left untouched.
+    pub fn get_unconstrained_geometry(
+        &self,
+        anchor_rect_in_root: Rectangle<i32, Logical>,
+        work_area: Rectangle<i32, Logical>,
+    ) -> Rectangle<i32, Logical> {
+        use xdg_positioner::ConstraintAdjustment;
+
+        let parent_offset = anchor_rect_in_root.loc - self.anchor_rect.loc;
+        let mut positioner = PositionerState {
+            anchor_rect: anchor_rect_in_root,
+            ..*self
+        };
+        let mut geometry = positioner.get_geometry();
+
+        if constrained_x(geometry, work_area) {
+            let mut adjusted = false;
+
+            if self.constraint_adjustment.contains(ConstraintAdjustment::FlipX) {
+                let flipped = PositionerState {
+                    anchor_edges: flip_anchor_x(positioner.anchor_edges),
+                    gravity: flip_gravity_x(positioner.gravity),
+                    ..positioner
+                };
+                let flipped_geometry = flipped.get_geometry();
+
+                // A flip, that does not resolve the constraint, is reverted
+                if !constrained_x(flipped_geometry, work_area) {
+                    positioner = flipped;
+                    geometry = flipped_geometry;
+                    adjusted = true;
+                }
+            }
+
+            if !adjusted && self.constraint_adjustment.contains(ConstraintAdjustment::SlideX) {
+                let right = work_area.loc.x + work_area.size.w;
+                if geometry.loc.x + geometry.size.w > right {
+                    geometry.loc.x = right - geometry.size.w;
+                }
+                // If the popup does not fit, its left edge is kept visible
+                if geometry.loc.x < work_area.loc.x {
+                    geometry.loc.x = work_area.loc.x;
+                }
+                adjusted = !constrained_x(geometry, work_area);
+            }
+
+            if !adjusted && self.constraint_adjustment.contains(ConstraintAdjustment::ResizeX) {
+                let left = geometry.loc.x.max(work_area.loc.x);
+                let right = (geometry.loc.x + geometry.size.w).min(work_area.loc.x + work_area.size.w);
+                if right > left {
+                    geometry.loc.x = left;
+                    geometry.size.w = right - left;
+                }
+            }
+        }
+
+        if constrained_y(geometry, work_area) {
+            let mut adjusted = false;
+
+            if self.constraint_adjustment.contains(ConstraintAdjustment::FlipY) {
+                let flipped = PositionerState {
+                    anchor_edges: flip_anchor_y(positioner.anchor_edges),
+                    gravity: flip_gravity_y(positioner.gravity),
+                    ..positioner
+                };
+                // Keep the horizontal adjustments made above
+                let mut flipped_geometry = flipped.get_geometry();
+                flipped_geometry.loc.x = geometry.loc.x;
+                flipped_geometry.size.w = geometry.size.w;
+
+                if !constrained_y(flipped_geometry, work_area) {
+                    geometry = flipped_geometry;
+                    adjusted = true;
+                }
+            }
+
+            if !adjusted && self.constraint_adjustment.contains(ConstraintAdjustment::SlideY) {
+                let bottom = work_area.loc.y + work_area.size.h;
+                if geometry.loc.y + geometry.size.h > bottom {
+                    geometry.loc.y = bottom - geometry.size.h;
+                }
+                // If the popup does not fit, its top edge is kept visible
+                if geometry.loc.y < work_area.loc.y {
+                    geometry.loc.y = work_area.loc.y;
+                }
+                adjusted = !constrained_y(geometry, work_area);
+            }
+
+            if !adjusted && self.constraint_adjustment.contains(ConstraintAdjustment::ResizeY) {
+                let top = geometry.loc.y.max(work_area.loc.y);
+                let bottom = (geometry.loc.y + geometry.size.h).min(work_area.loc.y + work_area.size.h);
+                if bottom > top {
+                    geometry.loc.y = top;
+                    geometry.size.h = bottom - top;
+                }
+            }
+        }
+
+        geometry.loc -= parent_offset;
+        geometry
+    }
+}
+
+fn constrained_x(geometry: Rectangle<i32, Logical>, work_area: Rectangle<i32, Logical>) -> bool {
+    geometry.loc.x < work_area.loc.x || geometry.loc.x + geometry.size.w > work_area.loc.x + work_area.size.w
+}
+
+fn constrained_y(geometry: Rectangle<i32, Logical>, work_area: Rectangle<i32, Logical>) -> bool {
+    geometry.loc.y < work_area.loc.y || geometry.loc.y + geometry.size.h > work_area.loc.y + work_area.size.h
+}
+
+fn flip_anchor_x(anchor: xdg_positioner::Anchor) -> xdg_positioner::Anchor {
+    use xdg_positioner::Anchor;
+    match anchor {
+        Anchor::Left => Anchor::Right,
+        Anchor::Right => Anchor::Left,
+        Anchor::TopLeft => Anchor::TopRight,
+        Anchor::TopRight => Anchor::TopLeft,
+        Anchor::BottomLeft => Anchor::BottomRight,
+        Anchor::BottomRight => Anchor::BottomLeft,
+        anchor => anchor,
+    }
+}
+
+fn flip_anchor_y(anchor: xdg_positioner::Anchor) -> xdg_positioner::Anchor {
+    use xdg_positioner::Anchor;
+    match anchor {
+        Anchor::Top => Anchor::Bottom,
+        Anchor::Bottom => Anchor::Top,
+        Anchor::TopLeft => Anchor::BottomLeft,
+        Anchor::BottomLeft => Anchor::TopLeft,
+        Anchor::TopRight => Anchor::BottomRight,
+        Anchor::BottomRight => Anchor::TopRight,
+        anchor => anchor,
+    }
+}
+
+fn flip_gravity_x(gravity: xdg_positioner::Gravity) -> xdg_positioner::Gravity {
+    use xdg_positioner::Gravity;
+    match gravity {
+        Gravity::Left => Gravity::Right,
+        Gravity::Right => Gravity::Left,
+        Gravity::TopLeft => Gravity::TopRight,
+        Gravity::TopRight => Gravity::TopLeft,
+        Gravity::BottomLeft => Gravity::BottomRight,
+        Gravity::BottomRight => Gravity::BottomLeft,
+        gravity => gravity,
+    }
+}
+
+fn flip_gravity_y(gravity: xdg_positioner::Gravity) -> xdg_positioner::Gravity {
+    use xdg_positioner::Gravity;
+    match gravity {
+        Gravity::Top => Gravity::Bottom,
+        Gravity::Bottom => Gravity::Top,
+        Gravity::TopLeft => Gravity::BottomLeft,
+        Gravity::BottomLeft => Gravity::TopLeft,
+        Gravity::TopRight => Gravity::BottomRight,
+        Gravity::BottomRight => Gravity::TopRight,
+        gravity => gravity,
+    }
 }
 
 /// State of a regular toplevel surface
@@ -991,18 +1171,21 @@ impl ToplevelSurface {
             })
             .unwrap_or(None);
             if let Some(configure) = configure {
-                let decoration_mode = compositor::with_states(surface, |states| {
-                    let attributes = states
+                let (decoration_mode, unconfigured_decoration) = compositor::with_states(surface, |states| {
+                    let mut attributes = states
                         .data_map
                         .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
                         .unwrap()
                         .lock()
                         .unwrap();
-                    attributes.current.decoration_mode
+                    (
+                        attributes.current.decoration_mode,
+                        attributes.unconfigured_decoration.take().is_some(),
+                    )
                 })
-                .unwrap_or(None);
+                .unwrap_or((None, false));
 
-                if configure.state.decoration_mode != decoration_mode {
+                if configure.state.decoration_mode != decoration_mode || unconfigured_decoration {
                     if let Some(data) = self
                         .shell_surface
                         .as_ref()
@@ -1030,7 +1213,16 @@ impl ToplevelSurface {
     /// This should be called when the underlying WlSurface
     /// handles a wl_surface.commit request.
     pub(crate) fn commit_hook(surface: &wl_surface::WlSurface) {
-        compositor::with_states(surface, |states| {
+        let unconfigured_decoration = compositor::with_states(surface, |states| {
+            let has_buffer = match states
+                .cached_state
+                .pending::<compositor::SurfaceAttributes>()
+                .buffer
+            {
+                Some(compositor::BufferAssignment::NewBuffer { .. }) => Some(true),
+                Some(compositor::BufferAssignment::Removed) => Some(false),
+                None => None,
+            };
             let mut guard = states
                 .data_map
                 .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
@@ -1040,8 +1232,24 @@ impl ToplevelSurface {
             if let Some(state) = guard.last_acked.clone() {
                 guard.current = state;
             }
+            if let Some(has_buffer) = has_buffer {
+                guard.has_buffer = has_buffer;
+            }
+            // A buffer must not be attached before the decoration was configured
+            if has_buffer == Some(true) {
+                guard.unconfigured_decoration.take()
+            } else {
+                None
+            }
         })
         .unwrap();
+
+        if let Some(decoration) = unconfigured_decoration {
+            decoration.as_ref().post_error(
+                zxdg_toplevel_decoration_v1::Error::UnconfiguredBuffer as u32,
+                "A buffer was attached before the decoration was configured.".into(),
+            );
+        }
     }
 
     /// Make sure this surface was configured
@@ -1153,6 +1361,76 @@ impl ToplevelSurface {
         )
     }
 
+    /// Returns the serials of the configures sent to the client, that have not been acknowledged yet
+    ///
+    /// The serials are ordered from the oldest to the newest configure. Returns an empty list if the
+    /// underlying surface has been destroyed.
+    pub fn pending_configures(&self) -> Vec<Serial> {
+        if !self.alive() {
+            return Vec::new();
+        }
+
+        compositor::with_states(&self.wl_surface, |states| {
+            let attributes = states
+                .data_map
+                .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                .unwrap()
+                .lock()
+                .unwrap();
+
+            attributes
+                .pending_configures
+                .iter()
+                .map(|configure| configure.serial)
+                .collect()
+        })
+        .unwrap()
+    }
+
+    /// Returns the serial of the last configure acknowledged by the client
+    ///
+    /// Returns `None` if the client did not acknowledge any configure yet or if the underlying
+    /// surface has been destroyed.
+    pub fn last_acked(&self) -> Option<Serial> {
+        if !self.alive() {
+            return None;
+        }
+
+        compositor::with_states(&self.wl_surface, |states| {
+            states
+                .data_map
+                .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .configure_serial
+        })
+        .unwrap()
+    }
+
+    /// Returns whether the initial configure has been sent to the client
+    ///
+    /// Like any other configure, the initial configure is listed by
+    /// [`pending_configures`](#method.pending_configures) until the client acknowledges it, so it is
+    /// the oldest pending configure if this returns `true` and [`last_acked`](#method.last_acked)
+    /// returns `None`. Returns `false` if the underlying surface has been destroyed.
+    pub fn initial_configure_sent(&self) -> bool {
+        if !self.alive() {
+            return false;
+        }
+
+        compositor::with_states(&self.wl_surface, |states| {
+            states
+                .data_map
+                .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                .unwrap()
+                .lock()
+                .unwrap()
+                .initial_configure_sent
+        })
+        .unwrap()
+    }
+
     /// Returns the parent of this toplevel surface.
     pub fn parent(&self) -> Option<wl_surface::WlSurface> {
         xdg_handlers::get_parent(&self.shell_surface)
@@ -1351,6 +1629,24 @@ impl PopupSurface {
         self.send_configure_internal(Some(token))
     }
 
+    /// Send a configure event with the geometry of the popup adjusted to fit into the work area
+    ///
+    /// The geometry is calculated by [`PositionerState::get_unconstrained_geometry`] from the
+    /// current positioner of the popup, see there for the meaning of the arguments. The result
+    /// is stored in the pending state and sent using [`PopupSurface::send_configure`].
+    pub fn send_unconstrained_configure(
+        &self,
+        anchor_rect_in_root: Rectangle<i32, Logical>,
+        work_area: Rectangle<i32, Logical>,
+    ) -> Result<(), PopupConfigureError> {
+        let _ = self.with_pending_state(|state| {
+            state.geometry = state
+                .positioner
+                .get_unconstrained_geometry(anchor_rect_in_root, work_area);
+        });
+        self.send_configure()
+    }
+
     /// Handles the role specific commit logic
     ///
     /// This should be called when the underlying WlSurface
@@ -1698,3 +1994,209 @@ pub enum XdgRequest {
         token: u32,
     },
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wayland::{compositor::compositor_init, test_client::TestClient};
+    use std::{cell::RefCell, rc::Rc};
+    use wayland_client::protocol::wl_compositor::WlCompositor;
+    use wayland_protocols::xdg_shell::client::{
+        xdg_surface as client_xdg_surface, xdg_wm_base::XdgWmBase as ClientWmBase,
+    };
+    use xdg_positioner::{Anchor, ConstraintAdjustment, Gravity};
+
+    // The parent is located at (850, 850) in a work area of 1000x1000, the popup is
+    // anchored at its bottom right corner and overflows the work area on both axes.
+    fn unconstrained(constraint_adjustment: ConstraintAdjustment) -> Rectangle<i32, Logical> {
+        unconstrained_in(
+            constraint_adjustment,
+            Rectangle::from_loc_and_size((0, 0), (1000, 1000)),
+        )
+    }
+
+    fn unconstrained_in(
+        constraint_adjustment: ConstraintAdjustment,
+        work_area: Rectangle<i32, Logical>,
+    ) -> Rectangle<i32, Logical> {
+        let positioner = PositionerState {
+            rect_size: (100, 100).into(),
+            anchor_rect: Rectangle::from_loc_and_size((90, 90), (10, 10)),
+            anchor_edges: Anchor::BottomRight,
+            gravity: Gravity::BottomRight,
+            constraint_adjustment,
+            ..Default::default()
+        };
+        let anchor_rect_in_root = Rectangle::from_loc_and_size((940, 940), (10, 10));
+
+        positioner.get_unconstrained_geometry(anchor_rect_in_root, work_area)
+    }
+
+    fn rect(x: i32, y: i32, w: i32, h: i32) -> Rectangle<i32, Logical> {
+        Rectangle::from_loc_and_size((x, y), (w, h))
+    }
+
+    #[test]
+    fn no_adjustment() {
+        assert_eq!(
+            unconstrained(ConstraintAdjustment::empty()),
+            rect(100, 100, 100, 100)
+        );
+    }
+
+    #[test]
+    fn flip() {
+        assert_eq!(
+            unconstrained(ConstraintAdjustment::FlipX),
+            rect(-10, 100, 100, 100)
+        );
+        assert_eq!(
+            unconstrained(ConstraintAdjustment::FlipY),
+            rect(100, -10, 100, 100)
+        );
+        assert_eq!(
+            unconstrained(ConstraintAdjustment::FlipX | ConstraintAdjustment::FlipY),
+            rect(-10, -10, 100, 100)
+        );
+    }
+
+    #[test]
+    fn slide() {
+        assert_eq!(
+            unconstrained(ConstraintAdjustment::SlideX),
+            rect(50, 100, 100, 100)
+        );
+        assert_eq!(
+            unconstrained(ConstraintAdjustment::SlideY),
+            rect(100, 50, 100, 100)
+        );
+        assert_eq!(
+            unconstrained(ConstraintAdjustment::SlideX | ConstraintAdjustment::SlideY),
+            rect(50, 50, 100, 100)
+        );
+    }
+
+    #[test]
+    fn resize() {
+        assert_eq!(
+            unconstrained(ConstraintAdjustment::ResizeX),
+            rect(100, 100, 50, 100)
+        );
+        assert_eq!(
+            unconstrained(ConstraintAdjustment::ResizeY),
+            rect(100, 100, 100, 50)
+        );
+        assert_eq!(
+            unconstrained(ConstraintAdjustment::ResizeX | ConstraintAdjustment::ResizeY),
+            rect(100, 100, 50, 50)
+        );
+    }
+
+    #[test]
+    fn combined_adjustments() {
+        let all = ConstraintAdjustment::FlipX
+            | ConstraintAdjustment::FlipY
+            | ConstraintAdjustment::SlideX
+            | ConstraintAdjustment::SlideY
+            | ConstraintAdjustment::ResizeX
+            | ConstraintAdjustment::ResizeY;
+
+        // flipping takes precedence
+        assert_eq!(unconstrained(all), rect(-10, -10, 100, 100));
+        assert_eq!(
+            unconstrained(ConstraintAdjustment::SlideX | ConstraintAdjustment::ResizeY),
+            rect(50, 100, 100, 50)
+        );
+
+        // the flipped popup would still be constrained, so it is slid instead
+        let narrow = rect(880, 0, 120, 1000);
+        assert_eq!(
+            unconstrained_in(ConstraintAdjustment::FlipX | ConstraintAdjustment::SlideX, narrow),
+            rect(50, 100, 100, 100)
+        );
+        // and resized, if sliding is not allowed
+        assert_eq!(
+            unconstrained_in(
+                ConstraintAdjustment::FlipX | ConstraintAdjustment::ResizeX,
+                narrow
+            ),
+            rect(100, 100, 50, 100)
+        );
+    }
+
+    #[test]
+    fn toplevel_configure_serials() {
+        let toplevels = Rc::new(RefCell::new(Vec::new()));
+        let new_toplevels = toplevels.clone();
+        let mut test_client = TestClient::new(|display| {
+            compositor_init(display, |_, _| {}, None);
+            xdg_shell_init(
+                display,
+                move |request, _| {
+                    if let XdgRequest::NewToplevel { surface } = request {
+                        new_toplevels.borrow_mut().push(surface);
+                    }
+                },
+                None,
+            );
+        });
+
+        let compositor = test_client.globals.instantiate_exact::<WlCompositor>(4).unwrap();
+        let wm_base = test_client.globals.instantiate_exact::<ClientWmBase>(1).unwrap();
+        let surface = compositor.create_surface();
+        let xdg_surface = wm_base.get_xdg_surface(&surface);
+        let serials = Rc::new(RefCell::new(Vec::new()));
+        let received = serials.clone();
+        xdg_surface.quick_assign(move |_, event, _| {
+            if let client_xdg_surface::Event::Configure { serial } = event {
+                received.borrow_mut().push(Serial::from(serial));
+            }
+        });
+        let xdg_toplevel = xdg_surface.get_toplevel();
+        surface.commit();
+        test_client.roundtrip().unwrap();
+
+        let toplevel = toplevels.borrow_mut().pop().unwrap();
+        assert!(!toplevel.initial_configure_sent());
+        assert!(toplevel.pending_configures().is_empty());
+        assert_eq!(toplevel.last_acked(), None);
+
+        // the initial configure is pending until the client acknowledges it
+        toplevel.send_configure();
+        test_client.roundtrip().unwrap();
+        let initial = serials.borrow()[0];
+        assert!(toplevel.initial_configure_sent());
+        assert_eq!(toplevel.pending_configures(), vec![initial]);
+        assert_eq!(toplevel.last_acked(), None);
+
+        for &width in &[100, 200] {
+            toplevel
+                .with_pending_state(|state| state.size = Some((width, 100).into()))
+                .unwrap();
+            toplevel.send_configure();
+        }
+        test_client.roundtrip().unwrap();
+        let (first, second) = (serials.borrow()[1], serials.borrow()[2]);
+        assert_eq!(toplevel.pending_configures(), vec![initial, first, second]);
+
+        // acknowledging a configure discards the older ones
+        xdg_surface.ack_configure(first.into());
+        test_client.roundtrip().unwrap();
+        assert_eq!(toplevel.pending_configures(), vec![second]);
+        assert_eq!(toplevel.last_acked(), Some(first));
+
+        // committing the surface does not change the serials
+        surface.commit();
+        test_client.roundtrip().unwrap();
+        assert_eq!(toplevel.pending_configures(), vec![second]);
+        assert_eq!(toplevel.last_acked(), Some(first));
+
+        xdg_toplevel.destroy();
+        xdg_surface.destroy();
+        surface.destroy();
+        test_client.roundtrip().unwrap();
+        assert!(toplevel.pending_configures().is_empty());
+        assert_eq!(toplevel.last_acked(), None);
+        assert!(!toplevel.initial_configure_sent());
+    }
+}
@@ -251,7 +251,7 @@ fn xdg_surface_implementation(
             })
             .unwrap();
 
-            compositor::add_commit_hook(surface, super::ToplevelSurface::commit_hook);
+            compositor::add_pre_commit_hook(surface, super::ToplevelSurface::commit_hook);
 
             id.quick_assign(toplevel_implementation);
             id.assign_destructor(Filter::new(|toplevel, _, _data| destroy_toplevel(toplevel)));
@@ -327,7 +327,7 @@ fn xdg_surface_implementation(
             })
             .unwrap();
 
-            compositor::add_commit_hook(surface, super::PopupSurface::commit_hook);
+            compositor::add_pre_commit_hook(surface, super::PopupSurface::commit_hook);
 
             id.quick_assign(xdg_popup_implementation);
             id.assign_destructor(Filter::new(|popup, _, _data| destroy_popup(popup)));
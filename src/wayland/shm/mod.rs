@@ -26,11 +26,14 @@
 //! // Insert the ShmGlobal into your event loop
 //! // Here, we specify that Yuyv and C8 format are supported
 //! // additionally to the standard Argb8888 and Xrgb8888.
-//! init_shm_global(
+//! let (mut shm_state, _global) = init_shm_global(
 //!     &mut display,
 //!     vec![Format::Yuyv, Format::C8],
 //!     None // we don't provide a logger here
 //! );
+//!
+//! // The advertised formats may be changed later on, e.g. once the renderer is known.
+//! shm_state.update_formats(vec![Format::Abgr8888]);
 //! ```
 //!
 //! Then, when you have a [`WlBuffer`](wayland_server::protocol::wl_buffer::WlBuffer)
@@ -61,6 +64,9 @@
 //!         /* The client supplied invalid content specification for this buffer,
 //!            and was killed.
 //!          */
+//!     },
+//!     Err(BufferAccessError::UnsupportedFormat(_)) => {
+//!         /* Only returned by helpers that need to know the layout of the format */
 //!     }
 //! }
 //! # }
@@ -74,20 +80,115 @@
 //! If you are already using an handler for this signal, you probably don't want to use this handler.
 
 use self::pool::{Pool, ResizeError};
-use std::{ops::Deref as _, rc::Rc, sync::Arc};
+use crate::{
+    backend::allocator::Fourcc,
+    utils::{Buffer, Rectangle},
+};
+use std::{
+    cell::{Cell, RefCell},
+    convert::TryFrom,
+    ops::Deref as _,
+    rc::Rc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 use wayland_server::{
     protocol::{wl_buffer, wl_shm, wl_shm_pool},
-    Display, Filter, Global, Main,
+    Display, Filter, Global, Interface, Main, Resource,
 };
 
 mod pool;
 
 #[derive(Debug, Clone)]
 struct ShmGlobalData {
-    formats: Rc<[wl_shm::Format]>,
+    formats: Rc<RefCell<Vec<wl_shm::Format>>>,
+    client_memory_limit: Rc<Cell<Option<usize>>>,
     log: ::slog::Logger,
 }
 
+/// Total size of the shm pools of a client, stored in the data map of the client
+#[derive(Debug, Default)]
+struct ClientMemoryUsage(Arc<AtomicUsize>);
+
+/// State of the SHM global, allowing to change the advertised formats
+#[derive(Debug)]
+pub struct ShmState {
+    formats: Vec<wl_shm::Format>,
+    shared: Rc<RefCell<Vec<wl_shm::Format>>>,
+    client_memory_limit: Rc<Cell<Option<usize>>>,
+}
+
+impl ShmState {
+    /// Returns the formats currently advertised by the global
+    pub fn formats(&self) -> &[wl_shm::Format] {
+        &self.formats
+    }
+
+    /// Changes the formats advertised by the global
+    ///
+    /// `ARGB8888` and `XRGB8888` are always added, as they are required by the protocol.
+    /// Only clients binding the global after this call are notified of the new formats, but
+    /// buffers of formats no longer part of the list are rejected for every client.
+    pub fn update_formats(&mut self, formats: impl IntoIterator<Item = wl_shm::Format>) {
+        self.formats = with_mandatory_formats(formats);
+        *self.shared.borrow_mut() = self.formats.clone();
+    }
+
+    /// Returns the maximum number of bytes a single client may map using shm pools
+    pub fn client_memory_limit(&self) -> Option<usize> {
+        self.client_memory_limit.get()
+    }
+
+    /// Limits the total size of the shm pools of every client
+    ///
+    /// A client creating or growing a pool beyond this limit is killed with a protocol error.
+    /// Pools existing when the limit is set are accounted for, but not checked. `None` removes the
+    /// limit, which is the default.
+    pub fn set_client_memory_limit(&mut self, limit: Option<usize>) {
+        self.client_memory_limit.set(limit);
+    }
+}
+
+impl ShmGlobalData {
+    /// Returns the memory usage of the client owning the given resource and whether growing it by
+    /// `additional` bytes stays within the limit.
+    fn client_usage<I: Interface>(
+        &self,
+        resource: &Resource<I>,
+        additional: usize,
+    ) -> (Arc<AtomicUsize>, bool) {
+        let usage = match resource.client() {
+            Some(client) => {
+                client.data_map().insert_if_missing(ClientMemoryUsage::default);
+                client.data_map().get::<ClientMemoryUsage>().unwrap().0.clone()
+            }
+            None => Arc::new(AtomicUsize::new(0)),
+        };
+
+        let within_limit = match self.client_memory_limit.get() {
+            Some(limit) => usage
+                .load(Ordering::SeqCst)
+                .checked_add(additional)
+                .map_or(false, |total| total <= limit),
+            None => true,
+        };
+
+        (usage, within_limit)
+    }
+}
+
+fn with_mandatory_formats(formats: impl IntoIterator<Item = wl_shm::Format>) -> Vec<wl_shm::Format> {
+    let mut formats = formats.into_iter().collect::<Vec<_>>();
+    for mandatory in [wl_shm::Format::Argb8888, wl_shm::Format::Xrgb8888].iter() {
+        if !formats.contains(mandatory) {
+            formats.push(*mandatory);
+        }
+    }
+    formats
+}
+
 /// Create a new SHM global advertizing given supported formats.
 ///
 /// This global will always advertize `ARGB8888` and `XRGB8888` format
@@ -95,27 +196,28 @@ struct ShmGlobalData {
 /// as additionally advertized.
 ///
 /// The global is directly created on the provided [`Display`](wayland_server::Display),
-/// and this function returns the global handle, in case you wish to remove this global in
-/// the future.
+/// and this function returns the [`ShmState`] to change the advertised formats later on,
+/// as well as the global handle, in case you wish to remove this global in the future.
 pub fn init_shm_global<L>(
     display: &mut Display,
-    mut formats: Vec<wl_shm::Format>,
+    formats: Vec<wl_shm::Format>,
     logger: L,
-) -> Global<wl_shm::WlShm>
+) -> (ShmState, Global<wl_shm::WlShm>)
 where
     L: Into<Option<::slog::Logger>>,
 {
     let log = crate::slog_or_fallback(logger);
 
-    // always add the mandatory formats
-    formats.push(wl_shm::Format::Argb8888);
-    formats.push(wl_shm::Format::Xrgb8888);
+    let formats = with_mandatory_formats(formats);
+    let shared = Rc::new(RefCell::new(formats.clone()));
+    let client_memory_limit = Rc::new(Cell::new(None));
     let data = ShmGlobalData {
-        formats: formats.into(),
+        formats: shared.clone(),
+        client_memory_limit: client_memory_limit.clone(),
         log: log.new(slog::o!("smithay_module" => "shm_handler")),
     };
 
-    display.create_global::<wl_shm::WlShm, _>(
+    let global = display.create_global::<wl_shm::WlShm, _>(
         1,
         Filter::new(move |(shm, _version): (Main<wl_shm::WlShm>, _), _, _| {
             shm.quick_assign({
@@ -124,13 +226,44 @@ where
             });
 
             // send the formats
-            for &f in &data.formats[..] {
+            for &f in data.formats.borrow().iter() {
                 shm.format(f);
             }
         }),
+    );
+
+    (
+        ShmState {
+            formats,
+            shared,
+            client_memory_limit,
+        },
+        global,
     )
 }
 
+/// Converts a wl_shm format into the corresponding fourcc code
+///
+/// All formats but `Argb8888` and `Xrgb8888` share their value with the fourcc code.
+pub fn shm_format_to_fourcc(format: wl_shm::Format) -> Option<Fourcc> {
+    match format {
+        wl_shm::Format::Argb8888 => Some(Fourcc::Argb8888),
+        wl_shm::Format::Xrgb8888 => Some(Fourcc::Xrgb8888),
+        format => Fourcc::try_from(format.to_raw()).ok(),
+    }
+}
+
+/// Converts a fourcc code into the corresponding wl_shm format
+///
+/// Returns `None` if the format is not known to wl_shm.
+pub fn fourcc_to_shm_format(format: Fourcc) -> Option<wl_shm::Format> {
+    match format {
+        Fourcc::Argb8888 => Some(wl_shm::Format::Argb8888),
+        Fourcc::Xrgb8888 => Some(wl_shm::Format::Xrgb8888),
+        format => wl_shm::Format::from_raw(format as u32),
+    }
+}
+
 /// Error that can occur when accessing an SHM buffer
 #[derive(Debug, thiserror::Error)]
 pub enum BufferAccessError {
@@ -145,6 +278,9 @@ pub enum BufferAccessError {
     /// If this error occurs, the client has been killed as a result.
     #[error("invalid client buffer")]
     BadMap,
+    /// The size of a pixel of the buffer's format is not known
+    #[error("unsupported shm format {0:?}")]
+    UnsupportedFormat(wl_shm::Format),
 }
 
 /// Call given closure with the contents of the given buffer
@@ -179,6 +315,144 @@ where
     }
 }
 
+/// Call given closure with the contents of the given buffer only
+///
+/// Works like [`with_buffer_contents`], but the slice passed to the closure starts at the offset of the
+/// buffer and only spans `stride * height` bytes. If the buffer does not fit into its pool (anymore), a
+/// protocol error is posted and `Err(BufferAccessError::BadMap)` is returned instead.
+pub fn with_buffer_contents_data<F, T>(buffer: &wl_buffer::WlBuffer, f: F) -> Result<T, BufferAccessError>
+where
+    F: FnOnce(&[u8], BufferData) -> T,
+{
+    with_buffer_contents(buffer, |slice, data| {
+        buffer_range(&data, slice.len()).map(|range| f(&slice[range], data))
+    })
+    .and_then(|result| {
+        result.ok_or_else(|| {
+            buffer.as_ref().post_error(
+                wl_shm::Error::InvalidStride as u32,
+                "Buffer exceeds the size of its pool.".into(),
+            );
+            BufferAccessError::BadMap
+        })
+    })
+}
+
+/// Copies a region of the given buffer into `dst`
+///
+/// `dst` is expected to hold an image of the size of the buffer with the same format, but rows of
+/// `dst_stride` bytes. Only the rows and columns covered by `region` are written, the region is clamped
+/// to the size of the buffer.
+///
+/// # Panics
+///
+/// Panics if `dst` is too small to hold the region.
+pub fn copy_region_to(
+    buffer: &wl_buffer::WlBuffer,
+    dst: &mut [u8],
+    dst_stride: usize,
+    region: Rectangle<i32, Buffer>,
+) -> Result<(), BufferAccessError> {
+    with_buffer_contents_data(buffer, |slice, data| {
+        let pixelsize =
+            bytes_per_pixel(data.format).ok_or(BufferAccessError::UnsupportedFormat(data.format))?;
+
+        let x = region.loc.x.max(0);
+        let y = region.loc.y.max(0);
+        let width = (region.loc.x + region.size.w).min(data.width) - x;
+        let height = (region.loc.y + region.size.h).min(data.height) - y;
+        if width <= 0 || height <= 0 {
+            return Ok(());
+        }
+
+        let line = width as usize * pixelsize;
+        for row in y as usize..(y + height) as usize {
+            let src_start = row * data.stride as usize + x as usize * pixelsize;
+            let dst_start = row * dst_stride + x as usize * pixelsize;
+            dst[dst_start..dst_start + line].copy_from_slice(&slice[src_start..src_start + line]);
+        }
+
+        Ok(())
+    })?
+}
+
+/// Returns the byte range of the buffer inside of a pool of the given size, if it fits
+fn buffer_range(data: &BufferData, pool_size: usize) -> Option<std::ops::Range<usize>> {
+    if data.offset < 0 || data.width <= 0 || data.height <= 0 || data.stride <= 0 {
+        return None;
+    }
+
+    // planar formats store at least one byte per pixel in their first plane
+    let row_len = (data.width as usize).checked_mul(bytes_per_pixel(data.format).unwrap_or(1))?;
+    if (data.stride as usize) < row_len {
+        return None;
+    }
+
+    let start = data.offset as usize;
+    let end = (data.stride as usize)
+        .checked_mul(data.height as usize)
+        .and_then(|len| len.checked_add(start))?;
+
+    if end <= pool_size {
+        Some(start..end)
+    } else {
+        None
+    }
+}
+
+/// Returns the number of bytes of a single pixel of the given format, for non-planar formats
+fn bytes_per_pixel(format: wl_shm::Format) -> Option<usize> {
+    use self::wl_shm::Format;
+
+    match format {
+        Format::C8 | Format::R8 | Format::Rgb332 | Format::Bgr233 => Some(1),
+        Format::Xrgb4444
+        | Format::Xbgr4444
+        | Format::Rgbx4444
+        | Format::Bgrx4444
+        | Format::Argb4444
+        | Format::Abgr4444
+        | Format::Rgba4444
+        | Format::Bgra4444
+        | Format::Xrgb1555
+        | Format::Xbgr1555
+        | Format::Rgbx5551
+        | Format::Bgrx5551
+        | Format::Argb1555
+        | Format::Abgr1555
+        | Format::Rgba5551
+        | Format::Bgra5551
+        | Format::Rgb565
+        | Format::Bgr565
+        | Format::R16
+        | Format::Rg88
+        | Format::Gr88 => Some(2),
+        Format::Rgb888 | Format::Bgr888 => Some(3),
+        Format::Argb8888
+        | Format::Xrgb8888
+        | Format::Xbgr8888
+        | Format::Rgbx8888
+        | Format::Bgrx8888
+        | Format::Abgr8888
+        | Format::Rgba8888
+        | Format::Bgra8888
+        | Format::Xrgb2101010
+        | Format::Xbgr2101010
+        | Format::Rgbx1010102
+        | Format::Bgrx1010102
+        | Format::Argb2101010
+        | Format::Abgr2101010
+        | Format::Rgba1010102
+        | Format::Bgra1010102
+        | Format::Rg1616
+        | Format::Gr1616 => Some(4),
+        Format::Xrgb16161616f | Format::Xbgr16161616f | Format::Argb16161616f | Format::Abgr16161616f => {
+            Some(8)
+        }
+        _ => None,
+    }
+}
+
 impl ShmGlobalData {
     fn receive_shm_message(&mut self, request: wl_shm::Request, shm: wl_shm::WlShm) {
         use self::wl_shm::{Error, Request};
@@ -194,7 +468,16 @@ impl ShmGlobalData {
             );
             return;
         }
-        let mmap_pool = match Pool::new(fd, size as usize, self.log.clone()) {
+        let (usage, within_limit) = self.client_usage(shm.as_ref(), size as usize);
+        if !within_limit {
+            let _ = nix::unistd::close(fd);
+            shm.as_ref().post_error(
+                Error::InvalidFd as u32,
+                "Memory limit for shm pools of the client exceeded.".into(),
+            );
+            return;
+        }
+        let mmap_pool = match Pool::new(fd, size as usize, usage, self.log.clone()) {
             Ok(p) => p,
             Err(()) => {
                 shm.as_ref().post_error(
@@ -248,41 +531,245 @@ impl ShmGlobalData {
                 stride,
                 format,
             } => {
-                if !self.formats.contains(&format) {
+                if !self.formats.borrow().contains(&format) {
                     pool.as_ref().post_error(
                         wl_shm::Error::InvalidFormat as u32,
                         format!("SHM format {:?} is not supported.", format),
                     );
                     return;
                 }
+                let data = BufferData {
+                    offset,
+                    width,
+                    height,
+                    stride,
+                    format,
+                };
+                if buffer_range(&data, arc_pool.size()).is_none() {
+                    pool.as_ref().post_error(
+                        wl_shm::Error::InvalidStride as u32,
+                        format!(
+                            "Invalid width, height or stride ({}, {}, {}).",
+                            width, height, stride
+                        ),
+                    );
+                    return;
+                }
                 let data = InternalBufferData {
                     pool: arc_pool.clone(),
-                    data: BufferData {
-                        offset,
-                        width,
-                        height,
-                        stride,
-                        format,
-                    },
+                    data,
                 };
                 buffer.quick_assign(|_, _, _| {});
                 buffer.as_ref().user_data().set(|| data);
             }
-            Request::Resize { size } => match arc_pool.resize(size) {
-                Ok(()) => {}
-                Err(ResizeError::InvalidSize) => {
+            Request::Resize { size } => {
+                let growth = (size.max(0) as usize).saturating_sub(arc_pool.size());
+                if !self.client_usage(pool.as_ref(), growth).1 {
                     pool.as_ref().post_error(
                         wl_shm::Error::InvalidFd as u32,
-                        "Invalid new size for a wl_shm_pool.".into(),
+                        "Memory limit for shm pools of the client exceeded.".into(),
                     );
+                    return;
                 }
-                Err(ResizeError::MremapFailed) => {
-                    pool.as_ref()
-                        .post_error(wl_shm::Error::InvalidFd as u32, "mremap failed.".into());
+
+                match arc_pool.resize(size) {
+                    Ok(()) => {}
+                    Err(ResizeError::InvalidSize) => {
+                        pool.as_ref().post_error(
+                            wl_shm::Error::InvalidFd as u32,
+                            "Invalid new size for a wl_shm_pool.".into(),
+                        );
+                    }
+                    Err(ResizeError::MremapFailed) => {
+                        pool.as_ref()
+                            .post_error(wl_shm::Error::InvalidFd as u32, "mremap failed.".into());
+                    }
                 }
-            },
+            }
             Request::Destroy => {}
             _ => unreachable!(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wayland::{
+        compositor::{compositor_init, with_states, BufferAssignment, SurfaceAttributes},
+        test_client::TestClient,
+    };
+    use std::{fs::File, io::Write, os::unix::io::AsRawFd};
+    use wayland_client::{
+        protocol::{
+            wl_buffer::WlBuffer as ClientBuffer, wl_compositor::WlCompositor, wl_shm::Format as ClientFormat,
+            wl_shm::WlShm as ClientShm, wl_shm_pool::WlShmPool as ClientPool,
+        },
+        Main as ClientMain,
+    };
+
+    #[test]
+    fn fourcc_conversion() {
+        assert_eq!(
+            shm_format_to_fourcc(wl_shm::Format::Argb8888),
+            Some(Fourcc::Argb8888)
+        );
+        assert_eq!(
+            shm_format_to_fourcc(wl_shm::Format::Abgr8888),
+            Some(Fourcc::Abgr8888)
+        );
+        assert_eq!(
+            fourcc_to_shm_format(Fourcc::Xrgb8888),
+            Some(wl_shm::Format::Xrgb8888)
+        );
+        assert_eq!(fourcc_to_shm_format(Fourcc::Nv12), Some(wl_shm::Format::Nv12));
+    }
+
+    #[test]
+    fn buffer_must_fit_into_pool() {
+        let data = BufferData {
+            offset: 64,
+            width: 16,
+            height: 16,
+            stride: 64,
+            format: wl_shm::Format::Argb8888,
+        };
+
+        assert_eq!(buffer_range(&data, 64 + 16 * 64), Some(64..64 + 16 * 64));
+        // e.g. a pool shrunk by the client after creating the buffer
+        assert_eq!(buffer_range(&data, 16 * 64), None);
+        assert_eq!(buffer_range(&BufferData { stride: 8, ..data }, 4096), None);
+        // the stride has to hold a row of 16 pixels of 4 bytes
+        assert_eq!(buffer_range(&BufferData { stride: 63, ..data }, 4096), None);
+        assert_eq!(
+            buffer_range(
+                &BufferData {
+                    format: wl_shm::Format::C8,
+                    stride: 16,
+                    ..data
+                },
+                4096
+            ),
+            Some(64..64 + 16 * 16)
+        );
+        assert_eq!(buffer_range(&BufferData { offset: -1, ..data }, 4096), None);
+        assert_eq!(
+            buffer_range(
+                &BufferData {
+                    height: i32::MAX,
+                    stride: i32::MAX,
+                    ..data
+                },
+                4096
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn mandatory_formats_are_kept() {
+        assert_eq!(
+            with_mandatory_formats(vec![wl_shm::Format::C8, wl_shm::Format::Argb8888]),
+            vec![
+                wl_shm::Format::C8,
+                wl_shm::Format::Argb8888,
+                wl_shm::Format::Xrgb8888
+            ]
+        );
+    }
+
+    // A client with the shm and compositor globals, the buffers of committed surfaces are added to `committed`
+    fn test_client(committed: Rc<RefCell<Vec<wl_buffer::WlBuffer>>>) -> TestClient {
+        TestClient::new(|display| {
+            init_shm_global(display, Vec::new(), None);
+            compositor_init(
+                display,
+                move |surface, _| {
+                    with_states(&surface, |states| {
+                        let buffer = states.cached_state.current::<SurfaceAttributes>().buffer.take();
+                        if let Some(BufferAssignment::NewBuffer { buffer, .. }) = buffer {
+                            committed.borrow_mut().push(buffer);
+                        }
+                    })
+                    .unwrap();
+                },
+                None,
+            );
+        })
+    }
+
+    fn create_pool(test_client: &TestClient, file: &File, size: i32) -> ClientMain<ClientPool> {
+        let shm = test_client.globals.instantiate_exact::<ClientShm>(1).unwrap();
+        shm.create_pool(file.as_raw_fd(), size)
+    }
+
+    // Attaches the buffer to a new surface and commits it
+    fn commit(test_client: &mut TestClient, buffer: &ClientBuffer) {
+        let compositor = test_client.globals.instantiate_exact::<WlCompositor>(4).unwrap();
+        let surface = compositor.create_surface();
+        surface.attach(Some(buffer), 0, 0);
+        surface.commit();
+        test_client.roundtrip().unwrap();
+    }
+
+    // A file holding a 4x4 argb image with all bytes set to the given value
+    fn image_file(value: u8) -> File {
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&[value; 64]).unwrap();
+        file
+    }
+
+    fn contents(buffer: &wl_buffer::WlBuffer) -> Result<Vec<u8>, BufferAccessError> {
+        with_buffer_contents_data(buffer, |slice, _| slice.to_vec())
+    }
+
+    #[test]
+    fn buffer_of_truncated_pool() {
+        let committed = Rc::new(RefCell::new(Vec::new()));
+        let mut test_client = test_client(committed.clone());
+        let file = image_file(0xab);
+        let pool = create_pool(&test_client, &file, 64);
+        let buffer = pool.create_buffer(0, 4, 4, 16, ClientFormat::Argb8888);
+        commit(&mut test_client, &buffer);
+        let buffer = committed.borrow_mut().pop().unwrap();
+        assert_eq!(contents(&buffer).unwrap(), vec![0xab; 64]);
+
+        // the client shrinks the file under the pool, the access fails instead of crashing
+        file.set_len(0).unwrap();
+        assert!(matches!(contents(&buffer), Err(BufferAccessError::BadMap)));
+    }
+
+    #[test]
+    fn buffer_of_replaced_pool() {
+        let committed = Rc::new(RefCell::new(Vec::new()));
+        let mut test_client = test_client(committed.clone());
+        let first_file = image_file(0xab);
+        let first_pool = create_pool(&test_client, &first_file, 64);
+        let first_buffer = first_pool.create_buffer(0, 4, 4, 16, ClientFormat::Argb8888);
+        commit(&mut test_client, &first_buffer);
+        let first_buffer = committed.borrow_mut().pop().unwrap();
+
+        // the pool is destroyed and replaced, the buffer keeps its memory alive
+        first_pool.destroy();
+        drop(first_file);
+        let second_file = image_file(0xcd);
+        let second_pool = create_pool(&test_client, &second_file, 64);
+        let second_buffer = second_pool.create_buffer(0, 4, 4, 16, ClientFormat::Argb8888);
+        commit(&mut test_client, &second_buffer);
+        let second_buffer = committed.borrow_mut().pop().unwrap();
+
+        assert_eq!(contents(&first_buffer).unwrap(), vec![0xab; 64]);
+        assert_eq!(contents(&second_buffer).unwrap(), vec![0xcd; 64]);
+    }
+
+    #[test]
+    fn stride_smaller_than_row() {
+        let committed = Rc::new(RefCell::new(Vec::new()));
+        let mut test_client = test_client(committed);
+        let file = image_file(0);
+        let pool = create_pool(&test_client, &file, 64);
+        // a row of 4 argb pixels takes 16 bytes
+        pool.create_buffer(0, 4, 4, 8, ClientFormat::Argb8888);
+        assert!(test_client.roundtrip().is_err());
+    }
+}
@@ -10,7 +10,10 @@ use std::{
     cell::Cell,
     os::unix::io::RawFd,
     ptr,
-    sync::{Once, RwLock},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Once, RwLock,
+    },
 };
 
 use slog::{debug, trace};
@@ -23,6 +26,8 @@ static mut OLD_SIGBUS_HANDLER: *mut SigAction = 0 as *mut SigAction;
 pub struct Pool {
     map: RwLock<MemMap>,
     fd: RawFd,
+    /// Total size of the pools of the client owning this pool
+    client_usage: Arc<AtomicUsize>,
     log: ::slog::Logger,
 }
 
@@ -32,12 +37,19 @@ pub enum ResizeError {
 }
 
 impl Pool {
-    pub fn new(fd: RawFd, size: usize, log: ::slog::Logger) -> Result<Pool, ()> {
+    pub fn new(
+        fd: RawFd,
+        size: usize,
+        client_usage: Arc<AtomicUsize>,
+        log: ::slog::Logger,
+    ) -> Result<Pool, ()> {
         let memmap = MemMap::new(fd, size)?;
         trace!(log, "Creating new shm pool"; "fd" => fd as i32, "size" => size);
+        client_usage.fetch_add(size, Ordering::SeqCst);
         Ok(Pool {
             map: RwLock::new(memmap),
             fd,
+            client_usage,
             log,
         })
     }
@@ -49,10 +61,20 @@ impl Pool {
             return Err(ResizeError::InvalidSize);
         }
         trace!(self.log, "Resizing shm pool"; "fd" => self.fd as i32, "oldsize" => oldsize, "newsize" => newsize);
-        guard.remap(newsize as usize).map_err(|()| {
+        let result = guard.remap(newsize as usize).map_err(|()| {
             debug!(self.log, "SHM pool resize failed"; "fd" => self.fd as i32, "oldsize" => oldsize, "newsize" => newsize);
             ResizeError::MremapFailed
-        })
+        });
+
+        // a failed remap leaves the pool empty
+        self.client_usage.fetch_sub(oldsize, Ordering::SeqCst);
+        self.client_usage.fetch_add(guard.size(), Ordering::SeqCst);
+
+        result
+    }
+
+    pub fn size(&self) -> usize {
+        self.map.read().unwrap().size()
     }
 
     pub fn with_data_slice<T, F: FnOnce(&[u8]) -> T>(&self, f: F) -> Result<T, ()> {
@@ -95,6 +117,8 @@ impl Pool {
 impl Drop for Pool {
     fn drop(&mut self) {
         trace!(self.log, "Deleting SHM pool"; "fd" => self.fd);
+        let size = self.map.read().unwrap().size();
+        self.client_usage.fetch_sub(size, Ordering::SeqCst);
         let _ = unistd::close(self.fd);
     }
 }
@@ -259,3 +283,29 @@ unsafe fn siginfo_si_addr(info: *mut libc::siginfo_t) -> *mut libc::c_void {
 unsafe fn siginfo_si_addr(info: *mut libc::siginfo_t) -> *mut libc::c_void {
     (*info).si_addr
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn truncated_pool_is_caught() {
+        use nix::sys::memfd::{memfd_create, MemFdCreateFlag};
+        use std::ffi::CString;
+
+        let name = CString::new("smithay-shm-test").unwrap();
+        let fd = memfd_create(&name, MemFdCreateFlag::MFD_CLOEXEC).unwrap();
+        unistd::ftruncate(fd, 4096).unwrap();
+        let log = ::slog::Logger::root(::slog::Discard, ::slog::o!());
+        let pool = Pool::new(fd, 4096, Arc::new(AtomicUsize::new(0)), log).unwrap();
+
+        assert_eq!(pool.with_data_slice(|slice| slice[100]), Ok(0));
+        // a client shrinking the file behind our back while we read the pool
+        let result = pool.with_data_slice(|slice| {
+            unistd::ftruncate(fd, 0).unwrap();
+            unsafe { ptr::read_volatile(&slice[100]) }
+        });
+        assert_eq!(result, Err(()));
+    }
+}
@@ -0,0 +1,63 @@
+//! A client connected to a display in the same thread, to test the protocol implementations
+
+use std::{
+    os::unix::{io::IntoRawFd, net::UnixStream},
+    time::Duration,
+};
+
+use wayland_client::{protocol::wl_display::WlDisplay, Attached, EventQueue, GlobalManager, ProtocolError};
+use wayland_server::Display;
+
+pub(crate) struct TestClient {
+    pub display: Display,
+    pub client: wayland_client::Display,
+    pub globals: GlobalManager,
+    queue: EventQueue,
+    attached: Attached<WlDisplay>,
+}
+
+impl TestClient {
+    /// Connects a client to a display with the globals created by `init`
+    pub fn new<F: FnOnce(&mut Display)>(init: F) -> TestClient {
+        let mut display = Display::new();
+        init(&mut display);
+
+        let (server_socket, client_socket) = UnixStream::pair().unwrap();
+        unsafe { display.create_client(server_socket.into_raw_fd(), &mut ()) };
+        let client = unsafe { wayland_client::Display::from_fd(client_socket.into_raw_fd()) }.unwrap();
+        let queue = client.create_event_queue();
+        let attached = (*client).clone().attach(queue.token());
+        let globals = GlobalManager::new(&attached);
+
+        let mut test_client = TestClient {
+            display,
+            client,
+            globals,
+            queue,
+            attached,
+        };
+        // receive the globals
+        test_client.roundtrip().unwrap();
+        test_client
+    }
+
+    /// Lets the server process the requests of the client, and the client the events of the server
+    ///
+    /// This can't block as a single thread drives both sides. Returns the protocol error the client
+    /// was killed with, if any.
+    pub fn roundtrip(&mut self) -> Result<(), ProtocolError> {
+        let _ = self.attached.sync();
+        self.client.flush().unwrap();
+        self.display
+            .dispatch(Duration::from_millis(100), &mut ())
+            .unwrap();
+        self.display.flush_clients(&mut ());
+        match self.queue.dispatch(&mut (), |_, _, _| {}) {
+            Ok(_) => Ok(()),
+            Err(err) => Err(self
+                .client
+                .protocol_error()
+                .unwrap_or_else(|| panic!("Connection error: {}", err))),
+        }
+    }
+}
@@ -0,0 +1,380 @@
+//! Utilities for handling the `viewporter` protocol
+//!
+//! This protocol lets clients crop and scale their surfaces independently of the size of their
+//! buffers, for example to let video players display a video at any size without scaling it
+//! themselves. Each surface can have a viewport, defining the part of the buffer to display (the
+//! source rectangle) and the size of the surface (the destination size).
+//!
+//! Once the global is initialized, the viewport of a surface is double-buffered state stored as a
+//! [`ViewportCachedState`] in the cached state of the surface. You need to take it into account when
+//! computing the size of your surfaces and rendering them, see
+//! [`surface_logical_size`](crate::backend::renderer::surface_logical_size) and
+//! [`surface_buffer_src`](crate::backend::renderer::surface_buffer_src).
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::wayland::viewporter::init_viewporter_global;
+//!
+//! # let mut display = wayland_server::Display::new();
+//! init_viewporter_global(
+//!     &mut display,
+//!     None, // put a logger if you want
+//! );
+//! ```
+
+use std::{cell::RefCell, ops::Deref as _};
+
+use wayland_protocols::viewporter::server::{
+    wp_viewport::{self, WpViewport},
+    wp_viewporter::{self, WpViewporter},
+};
+use wayland_server::{protocol::wl_surface::WlSurface, Display, Filter, Global, Main};
+
+use crate::{
+    backend::renderer::{buffer_dimensions, surface_logical_size},
+    utils::{Logical, Rectangle, Size},
+    wayland::compositor::{self, Cacheable, SurfaceAttributes, SurfaceBuffer},
+};
+
+/// The viewport of a surface
+///
+/// This state is double-buffered, the current state of a surface is available with
+/// `states.cached_state.current::<ViewportCachedState>()`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ViewportCachedState {
+    /// The part of the buffer to display, in surface-local coordinates before the viewport is applied
+    ///
+    /// If `None`, the whole buffer is displayed.
+    pub src: Option<Rectangle<f64, Logical>>,
+    /// The size of the surface
+    ///
+    /// If `None`, the size of the source rectangle is used, or the size of the buffer if the source
+    /// rectangle is also unset.
+    pub dst: Option<Size<i32, Logical>>,
+}
+
+impl ViewportCachedState {
+    /// The size of the surface defined by this viewport, if any
+    ///
+    /// Returns `None` if the size of the surface is not affected by this viewport and should be computed
+    /// from the size of its buffer.
+    pub fn size(&self) -> Option<Size<i32, Logical>> {
+        self.dst.or_else(|| self.src.map(|src| src.size.to_i32_round()))
+    }
+}
+
+impl Cacheable for ViewportCachedState {
+    fn commit(&mut self) -> Self {
+        *self
+    }
+    fn merge_into(self, into: &mut Self) {
+        *into = self;
+    }
+}
+
+#[derive(Default)]
+struct ViewportData {
+    viewport: Option<WpViewport>,
+}
+
+/// Creates a new `wp_viewporter` global
+pub fn init_viewporter_global<L>(display: &mut Display, logger: L) -> Global<WpViewporter>
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(slog::o!("smithay_module" => "viewporter_handler"));
+
+    display.create_global(
+        1,
+        Filter::new(move |(viewporter, _version): (Main<WpViewporter>, u32), _, _| {
+            let log = log.clone();
+            viewporter.quick_assign(move |viewporter, request, _| match request {
+                wp_viewporter::Request::GetViewport { id, surface } => {
+                    let exists = compositor::with_states(&surface, |states| {
+                        if states.data_map.get::<RefCell<ViewportData>>().is_none() {
+                            states
+                                .data_map
+                                .insert_if_missing(RefCell::<ViewportData>::default);
+                            compositor::add_pre_commit_hook(&surface, viewport_pre_commit_hook);
+                        }
+                        let mut data = states
+                            .data_map
+                            .get::<RefCell<ViewportData>>()
+                            .unwrap()
+                            .borrow_mut();
+                        if data.viewport.is_some() {
+                            true
+                        } else {
+                            data.viewport = Some(id.deref().clone());
+                            false
+                        }
+                    })
+                    .unwrap_or(true);
+
+                    if exists {
+                        viewporter.as_ref().post_error(
+                            wp_viewporter::Error::ViewportExists as u32,
+                            "The surface already has a viewport.".into(),
+                        );
+                        return;
+                    }
+                    slog::trace!(log, "New viewport");
+                    implement_viewport(id, surface);
+                }
+                wp_viewporter::Request::Destroy => {
+                    // all is already handled by our destructor
+                }
+                _ => unreachable!(),
+            });
+        }),
+    )
+}
+
+fn implement_viewport(viewport: Main<WpViewport>, surface: WlSurface) {
+    let destructor_surface = surface.clone();
+    viewport.quick_assign(move |viewport, request, _| {
+        if let wp_viewport::Request::Destroy = request {
+            // all is already handled by our destructor
+            return;
+        }
+        if !surface.as_ref().is_alive() {
+            viewport.as_ref().post_error(
+                wp_viewport::Error::NoSurface as u32,
+                "The surface of this viewport was destroyed.".into(),
+            );
+            return;
+        }
+        match request {
+            wp_viewport::Request::SetSource { x, y, width, height } => {
+                let src = if x == -1.0 && y == -1.0 && width == -1.0 && height == -1.0 {
+                    None
+                } else if x < 0.0 || y < 0.0 || width <= 0.0 || height <= 0.0 {
+                    viewport.as_ref().post_error(
+                        wp_viewport::Error::BadValue as u32,
+                        format!("Invalid source rectangle {}x{}+{}+{}.", width, height, x, y),
+                    );
+                    return;
+                } else {
+                    Some(Rectangle::from_loc_and_size((x, y), (width, height)))
+                };
+                compositor::with_states(&surface, |states| {
+                    states.cached_state.pending::<ViewportCachedState>().src = src;
+                })
+                .unwrap();
+            }
+            wp_viewport::Request::SetDestination { width, height } => {
+                let dst = if width == -1 && height == -1 {
+                    None
+                } else if width <= 0 || height <= 0 {
+                    viewport.as_ref().post_error(
+                        wp_viewport::Error::BadValue as u32,
+                        format!("Invalid destination size {}x{}.", width, height),
+                    );
+                    return;
+                } else {
+                    Some((width, height).into())
+                };
+                compositor::with_states(&surface, |states| {
+                    states.cached_state.pending::<ViewportCachedState>().dst = dst;
+                })
+                .unwrap();
+            }
+            _ => unreachable!(),
+        }
+    });
+
+    viewport.assign_destructor(Filter::new(move |_: WpViewport, _, _| {
+        // the viewport is removed from the surface on its next commit
+        let _ = compositor::with_states(&destructor_surface, |states| {
+            *states.cached_state.pending::<ViewportCachedState>() = Default::default();
+            if let Some(data) = states.data_map.get::<RefCell<ViewportData>>() {
+                data.borrow_mut().viewport = None;
+            }
+        });
+    }));
+}
+
+fn viewport_pre_commit_hook(surface: &WlSurface) {
+    let error = compositor::with_states(surface, |states| {
+        let viewport = states
+            .data_map
+            .get::<RefCell<ViewportData>>()
+            .unwrap()
+            .borrow()
+            .viewport
+            .clone()?;
+        let state = *states.cached_state.pending::<ViewportCachedState>();
+        let src = state.src?;
+
+        if state.dst.is_none() && (src.size.w.fract() != 0.0 || src.size.h.fract() != 0.0) {
+            return Some((
+                viewport,
+                wp_viewport::Error::BadSize,
+                "The source size is not integer and no destination size is set.",
+            ));
+        }
+        // the source rectangle can only be checked once the surface has some content, the buffer
+        // may also have been committed before the viewport was created
+        let pending = states.cached_state.pending::<SurfaceBuffer>().clone();
+        let buffer = if pending.attached {
+            pending.buffer
+        } else {
+            states.cached_state.current::<SurfaceBuffer>().buffer.clone()
+        }?;
+        let dimensions = buffer_dimensions(&buffer)?;
+        let attributes = states.cached_state.pending::<SurfaceAttributes>();
+        let buffer_size = surface_logical_size(
+            dimensions,
+            attributes.buffer_scale,
+            attributes.buffer_transform.into(),
+            &ViewportCachedState::default(),
+        )
+        .to_f64();
+        if src.loc.x + src.size.w > buffer_size.w || src.loc.y + src.size.h > buffer_size.h {
+            return Some((
+                viewport,
+                wp_viewport::Error::OutOfBuffer,
+                "The source rectangle extends outside of the buffer.",
+            ));
+        }
+        None
+    })
+    .ok()
+    .flatten();
+
+    if let Some((viewport, error, message)) = error {
+        viewport.as_ref().post_error(error as u32, message.into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wayland::{compositor::compositor_init, shm::init_shm_global, test_client::TestClient};
+    use std::{fs::File, io::Write, os::unix::io::AsRawFd};
+    use wayland_client::{
+        protocol::{
+            wl_buffer::WlBuffer as ClientBuffer,
+            wl_compositor::WlCompositor,
+            wl_output::Transform as ClientTransform,
+            wl_shm::{Format, WlShm},
+            wl_surface::WlSurface as ClientSurface,
+        },
+        Main as ClientMain,
+    };
+    use wayland_protocols::viewporter::client::{
+        wp_viewport::WpViewport as ClientViewport, wp_viewporter::WpViewporter as ClientViewporter,
+    };
+
+    struct Viewport {
+        test_client: TestClient,
+        surface: ClientMain<ClientSurface>,
+        // kept open until the pool is sent
+        _file: File,
+        buffer: ClientMain<ClientBuffer>,
+    }
+
+    impl Viewport {
+        // A surface and a 8x4 argb buffer
+        fn new() -> Viewport {
+            let test_client = TestClient::new(|display| {
+                compositor_init(display, |_, _| {}, None);
+                init_shm_global(display, Vec::new(), None);
+                init_viewporter_global(display, None);
+            });
+
+            let compositor = test_client.globals.instantiate_exact::<WlCompositor>(4).unwrap();
+            let surface = compositor.create_surface();
+            let shm = test_client.globals.instantiate_exact::<WlShm>(1).unwrap();
+            let mut file = tempfile::tempfile().unwrap();
+            file.write_all(&[0; 128]).unwrap();
+            let pool = shm.create_pool(file.as_raw_fd(), 128);
+            let buffer = pool.create_buffer(0, 8, 4, 32, Format::Argb8888);
+
+            Viewport {
+                test_client,
+                surface,
+                _file: file,
+                buffer,
+            }
+        }
+
+        fn viewport(&self) -> ClientMain<ClientViewport> {
+            let viewporter = self
+                .test_client
+                .globals
+                .instantiate_exact::<ClientViewporter>(1)
+                .unwrap();
+            viewporter.get_viewport(&self.surface)
+        }
+
+        fn attach(&self) {
+            self.surface.attach(Some(&self.buffer), 0, 0);
+        }
+
+        fn error(&mut self) -> Option<u32> {
+            self.test_client.roundtrip().err().map(|err| {
+                assert_eq!(err.object_interface, "wp_viewport");
+                err.code
+            })
+        }
+    }
+
+    #[test]
+    fn source_inside_of_buffer() {
+        let mut test = Viewport::new();
+        let viewport = test.viewport();
+        viewport.set_source(2.0, 1.0, 6.0, 3.0);
+        viewport.set_destination(12, 6);
+        test.attach();
+        test.surface.commit();
+        assert_eq!(test.error(), None);
+
+        // the buffer is rotated, the surface is 4x8
+        test.surface.set_buffer_transform(ClientTransform::_90);
+        viewport.set_source(0.0, 0.0, 4.0, 8.0);
+        test.surface.commit();
+        assert_eq!(test.error(), None);
+    }
+
+    #[test]
+    fn bad_value() {
+        let mut test = Viewport::new();
+        test.viewport().set_source(-1.0, 0.0, 4.0, 4.0);
+        assert_eq!(test.error(), Some(wp_viewport::Error::BadValue as u32));
+
+        let mut test = Viewport::new();
+        test.viewport().set_destination(0, 4);
+        assert_eq!(test.error(), Some(wp_viewport::Error::BadValue as u32));
+    }
+
+    #[test]
+    fn bad_size() {
+        let mut test = Viewport::new();
+        test.viewport().set_source(0.0, 0.0, 2.5, 2.0);
+        test.surface.commit();
+        assert_eq!(test.error(), Some(wp_viewport::Error::BadSize as u32));
+    }
+
+    #[test]
+    fn out_of_buffer() {
+        let mut test = Viewport::new();
+        let viewport = test.viewport();
+        viewport.set_source(4.0, 0.0, 6.0, 4.0);
+        test.attach();
+        test.surface.commit();
+        assert_eq!(test.error(), Some(wp_viewport::Error::OutOfBuffer as u32));
+    }
+
+    #[test]
+    fn out_of_buffer_committed_before_viewport() {
+        let mut test = Viewport::new();
+        test.attach();
+        test.surface.commit();
+        assert_eq!(test.error(), None);
+
+        test.viewport().set_source(0.0, 0.0, 8.0, 8.0);
+        test.surface.commit();
+        assert_eq!(test.error(), Some(wp_viewport::Error::OutOfBuffer as u32));
+    }
+}
@@ -125,6 +125,8 @@ fn activate(
     ddata: DispatchData<'_>,
 ) {
     let mut guard = state.lock().unwrap();
+    guard.remove_expired_tokens();
+    // A token can only be used once
     if let Some(token_data) = guard.pending_tokens.remove(&token) {
         guard
             .activation_requests
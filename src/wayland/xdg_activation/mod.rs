@@ -36,7 +36,7 @@ use std::{
     ops,
     rc::Rc,
     sync::{Arc, Mutex},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use wayland_protocols::staging::xdg_activation::v1::server::xdg_activation_v1;
@@ -137,11 +137,46 @@ pub struct XdgActivationState {
     user_data: UserDataMap,
 
     pending_tokens: HashMap<XdgActivationToken, XdgActivationTokenData>,
+    token_timeout: Option<Duration>,
 
     activation_requests: HashMap<XdgActivationToken, (XdgActivationTokenData, WlSurface)>,
 }
 
 impl XdgActivationState {
+    /// Create a token on behalf of the compositor
+    ///
+    /// You can pass this token to an application you launch, usually through the
+    /// `XDG_ACTIVATION_TOKEN` environment variable, so it can activate its first window.
+    pub fn create_external_token(&mut self, app_id: impl Into<Option<String>>) -> XdgActivationToken {
+        self.remove_expired_tokens();
+        let (token, token_data) = XdgActivationTokenData::new(None, app_id.into(), None);
+        self.pending_tokens.insert(token.clone(), token_data);
+        token
+    }
+
+    /// Remove a pending token, so it can no longer be used for an activation request
+    ///
+    /// Returns the data of the token, if it was pending.
+    pub fn remove_token(&mut self, token: &XdgActivationToken) -> Option<XdgActivationTokenData> {
+        self.pending_tokens.remove(token)
+    }
+
+    /// Set the duration after which pending tokens expire
+    ///
+    /// Activation requests using an expired token are ignored. By default tokens do not expire.
+    /// In any case a token can only be used for a single activation request.
+    pub fn set_token_timeout(&mut self, timeout: Option<Duration>) {
+        self.token_timeout = timeout;
+        self.remove_expired_tokens();
+    }
+
+    pub(super) fn remove_expired_tokens(&mut self) {
+        if let Some(timeout) = self.token_timeout {
+            self.pending_tokens
+                .retain(|_, token_data| token_data.timestamp.elapsed() < timeout);
+        }
+    }
+
     /// Get current activation requests
     ///
     /// HashMap contains token data and target surface
@@ -206,6 +241,7 @@ where
         log: log.new(slog::o!("smithay_module" => "xdg_activation_handler")),
         user_data: UserDataMap::new(),
         pending_tokens: HashMap::new(),
+        token_timeout: None,
         activation_requests: HashMap::new(),
     }));
 
@@ -10,9 +10,13 @@
 //! function properly. You'll need to treat XWayland (and all its X11 apps) as one
 //! special client, and play the role of an X11 Window Manager.
 //!
-//! Smithay does not provide any helper for doing that yet, but it is planned.
+//! The [`X11Wm`] helper takes care of becoming the window manager of XWayland and
+//! associating the X11 windows with their `wl_surface`, leaving the management
+//! policy of the windows to you.
 
 mod x11_sockets;
 mod xserver;
+mod xwm;
 
-pub use self::xserver::{XWayland, XWaylandEvent, XWaylandSource};
+pub use self::xserver::{XWayland, XWaylandConfig, XWaylandEvent, XWaylandSource};
+pub use self::xwm::{X11Wm, X11WmError, X11WmEvent, X11_SURFACE_ROLE};
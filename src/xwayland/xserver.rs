@@ -20,7 +20,7 @@
  * -> once it is started, it will check if SIGUSR1 is set to ignored. If so,
  *    if will consider its parent as "smart", and send a SIGUSR1 signal when
  *    startup completes. We want to catch this so we can launch the VM.
- * -> we need to track if the XWayland crashes, to restart it
+ * -> we need to track if the XWayland crashes, to restart it (if the user asked for it)
  *
  * cf https://github.com/swaywm/wlroots/blob/master/xwayland/xwayland.c
  *
@@ -42,12 +42,14 @@ use std::{
     any::Any,
     cell::RefCell,
     env,
+    ffi::OsString,
     io::{Read, Result as IOResult},
     os::unix::{
         io::{AsRawFd, IntoRawFd, RawFd},
         net::UnixStream,
         process::CommandExt,
     },
+    path::PathBuf,
     process::{ChildStdout, Command, Stdio},
     rc::Rc,
     sync::Arc,
@@ -59,7 +61,7 @@ use calloop::{
     Interest, LoopHandle, Mode,
 };
 
-use slog::{error, info, o};
+use slog::{error, info, o, warn};
 
 use wayland_server::{Client, Display, Filter};
 
@@ -71,6 +73,64 @@ pub struct XWayland<Data> {
     inner: Rc<RefCell<Inner<Data>>>,
 }
 
+/// Configuration of the XWayland server invocation
+///
+/// The server is always started with the `:<display> -rootless -terminate -wm <fd>` arguments, followed
+/// by one `-listen <fd>` argument for each X11 socket, as they are required for smithay to manage it.
+/// The extra arguments are appended after them.
+///
+/// The environment of the server is cleared, except for `PATH`, `XDG_RUNTIME_DIR` and the variables
+/// of the allowlist.
+#[derive(Debug, Clone)]
+pub struct XWaylandConfig {
+    /// Path of the XWayland binary
+    ///
+    /// If `None`, `Xwayland` is looked up in the `PATH`.
+    pub binary: Option<PathBuf>,
+    /// Additional arguments given to the XWayland server, like `-noTcpListen` or `-dpi 96`
+    ///
+    /// Each argument is passed as-is, without going through a shell.
+    pub extra_args: Vec<String>,
+    /// Environment variables passed on to the XWayland server
+    ///
+    /// Entries ending with a `*` match all the variables starting with the rest of the entry. `DISPLAY`
+    /// and `WAYLAND_SOCKET` are never passed on, as they are set for the server itself.
+    ///
+    /// Defaults to `HOME`, `USER`, `XDG_DATA_DIRS`, `LANG`, `LC_*` and `WAYLAND_DISPLAY`.
+    pub env_allowlist: Vec<String>,
+}
+
+impl Default for XWaylandConfig {
+    fn default() -> XWaylandConfig {
+        XWaylandConfig {
+            binary: None,
+            extra_args: Vec::new(),
+            env_allowlist: ["HOME", "USER", "XDG_DATA_DIRS", "LANG", "LC_*", "WAYLAND_DISPLAY"]
+                .iter()
+                .map(|var| var.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl XWaylandConfig {
+    // Whether this environment variable should be passed on to the XWayland server
+    fn allows_env(&self, key: &str) -> bool {
+        if key == "DISPLAY" || key == "WAYLAND_SOCKET" {
+            return false;
+        }
+        if key == "PATH" || key == "XDG_RUNTIME_DIR" {
+            return true;
+        }
+        self.env_allowlist
+            .iter()
+            .any(|allowed| match allowed.strip_suffix('*') {
+                Some(prefix) => key.starts_with(prefix),
+                None => key == allowed,
+            })
+    }
+}
+
 /// Events generated by the XWayland manager
 ///
 /// This is a very low-level interface, only notifying you when the connection
@@ -97,11 +157,31 @@ impl<Data: Any + 'static> XWayland<Data> {
     ///
     /// This function returns both the [`XWayland`] handle and an [`XWaylandSource`] that needs to be inserted
     /// into the [`calloop`] event loop, producing the Xwayland startup and shutdown events.
+    ///
+    /// The XWayland server is not restarted if it crashes, see [`XWayland::new_with_restart`].
     pub fn new<L>(
         handle: LoopHandle<'static, Data>,
         display: Rc<RefCell<Display>>,
         logger: L,
     ) -> (XWayland<Data>, XWaylandSource)
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        Self::new_with_restart(handle, display, 0, logger)
+    }
+
+    /// Create a new XWayland manager restarting the XWayland server if it crashes
+    ///
+    /// Works like [`XWayland::new`], but if the XWayland server exits without being shut down, it is
+    /// automatically started again, at most `max_restarts` times. You will receive an
+    /// [`XWaylandEvent::Exited`] event, followed by a new [`XWaylandEvent::Ready`] event with the
+    /// connection to the new server once it is ready, and `DISPLAY` is set again.
+    pub fn new_with_restart<L>(
+        handle: LoopHandle<'static, Data>,
+        display: Rc<RefCell<Display>>,
+        max_restarts: u32,
+        logger: L,
+    ) -> (XWayland<Data>, XWaylandSource)
     where
         L: Into<Option<::slog::Logger>>,
     {
@@ -112,6 +192,9 @@ impl<Data: Any + 'static> XWayland<Data> {
             handle,
             wayland_display: display,
             instance: None,
+            config: XWaylandConfig::default(),
+            generation: 0,
+            restarts_left: max_restarts,
             sender,
             log: log.new(o!("smithay_module" => "XWayland")),
         }));
@@ -126,10 +209,22 @@ impl<Data: Any + 'static> XWayland<Data> {
     /// wayland `Client` for XWayland.
     ///
     /// Does nothing if XWayland is already started or starting.
+    ///
+    /// XWayland is started with the last configuration given to [`XWayland::start_with_config`],
+    /// or the default one.
     pub fn start(&self) -> std::io::Result<()> {
         launch(&self.inner)
     }
 
+    /// Attempt to start the XWayland instance with a custom configuration
+    ///
+    /// Works like [`XWayland::start`], but sets the configuration used to invoke the XWayland server.
+    /// It is kept for the following starts and restarts of the server.
+    pub fn start_with_config(&self, config: XWaylandConfig) -> std::io::Result<()> {
+        self.inner.borrow_mut().config = config;
+        launch(&self.inner)
+    }
+
     /// Shutdown XWayland
     ///
     /// Does nothing if it was not already running, otherwise kills it and you will
@@ -160,9 +255,16 @@ struct Inner<Data> {
     handle: LoopHandle<'static, Data>,
     wayland_display: Rc<RefCell<Display>>,
     instance: Option<XWaylandInstance>,
+    config: XWaylandConfig,
+    // incremented for every launched instance, to ignore the destruction of outdated clients
+    generation: u64,
+    restarts_left: u32,
     log: ::slog::Logger,
 }
 
+// Marks the wayland client of an XWayland instance
+struct XWaylandClient(u64);
+
 // Launch an XWayland server
 //
 // Does nothing if there is already a launched instance
@@ -173,6 +275,8 @@ fn launch<Data: Any>(inner: &Rc<RefCell<Inner<Data>>>) -> std::io::Result<()> {
     }
 
     info!(guard.log, "Starting XWayland");
+    guard.generation += 1;
+    let generation = guard.generation;
 
     let (x_wm_x11, x_wm_me) = UnixStream::pair()?;
     let (wl_x11, wl_me) = UnixStream::pair()?;
@@ -196,6 +300,7 @@ fn launch<Data: Any>(inner: &Rc<RefCell<Inner<Data>>>) -> std::io::Result<()> {
                     .create_client(wl_me.into_raw_fd(), data)
             };
             client.data_map().insert_if_missing(|| idle_inner.clone());
+            client.data_map().insert_if_missing(|| XWaylandClient(generation));
             client.add_destructor(Filter::new(|e: Arc<_>, _, _| client_destroy::<Data>(&e)));
 
             instance.wayland_client = Some(client);
@@ -203,7 +308,7 @@ fn launch<Data: Any>(inner: &Rc<RefCell<Inner<Data>>>) -> std::io::Result<()> {
     });
 
     // all is ready, we can do the fork dance
-    let child_stdout = match spawn_xwayland(lock.display(), wl_x11, x_wm_x11, &x_fds) {
+    let child_stdout = match spawn_xwayland(lock.display(), wl_x11, x_wm_x11, &x_fds, &guard.config) {
         Ok(child_stdout) => child_stdout,
         Err(e) => {
             error!(guard.log, "XWayland failed to spawn"; "err" => format!("{:?}", e));
@@ -308,17 +413,44 @@ impl<Data> Inner<Data> {
     }
 }
 
-fn client_destroy<Data: 'static>(map: &::wayland_server::UserDataMap) {
+impl<Data: Any + 'static> Inner<Data> {
+    // Launch a new XWayland server after the previous one crashed, if allowed to
+    fn restart(&mut self, inner: Rc<RefCell<Inner<Data>>>) {
+        if self.restarts_left == 0 {
+            return;
+        }
+        self.restarts_left -= 1;
+        warn!(self.log, "Restarting XWayland"; "restarts_left" => self.restarts_left);
+        // launching needs to borrow the inner state, which is likely borrowed right now
+        self.handle.insert_idle(move |_| {
+            if let Err(e) = launch(&inner) {
+                error!(inner.borrow().log, "Failed to restart XWayland"; "err" => format!("{:?}", e));
+            }
+        });
+    }
+}
+
+fn client_destroy<Data: Any + 'static>(map: &::wayland_server::UserDataMap) {
     let inner = map.get::<Rc<RefCell<Inner<Data>>>>().unwrap();
+    let generation = map.get::<XWaylandClient>().unwrap().0;
     // If we are unable to take a lock we are most likely called during
     // a shutdown. This will definitely be the case when the compositor exits
     // and the XWayland instance is dropped.
     if let Ok(mut guard) = inner.try_borrow_mut() {
+        // the client of an instance that was already replaced
+        if guard.generation != generation {
+            return;
+        }
+        // XWayland exited on its own if the instance was not shut down yet
+        let crashed = guard.instance.is_some();
         guard.shutdown();
+        if crashed {
+            guard.restart(inner.clone());
+        }
     }
 }
 
-fn xwayland_ready<Data: 'static>(inner: &Rc<RefCell<Inner<Data>>>) {
+fn xwayland_ready<Data: Any + 'static>(inner: &Rc<RefCell<Inner<Data>>>) {
     // Lots of re-borrowing to please the borrow-checker
     let mut guard = inner.borrow_mut();
     let guard = &mut *guard;
@@ -352,6 +484,10 @@ fn xwayland_ready<Data: 'static>(inner: &Rc<RefCell<Inner<Data>>>) {
             connection: instance.wm_fd.take().unwrap(), // This is a bug if None
             client: instance.wayland_client.clone().unwrap(),
         });
+    } else if guard.restarts_left > 0 {
+        error!(guard.log, "XWayland crashed at startup.");
+        guard.shutdown();
+        guard.restart(inner.clone());
     } else {
         error!(
             guard.log,
@@ -368,32 +504,48 @@ fn spawn_xwayland(
     wayland_socket: UnixStream,
     wm_socket: UnixStream,
     listen_sockets: &[UnixStream],
+    config: &XWaylandConfig,
 ) -> IOResult<ChildStdout> {
     let mut command = Command::new("sh");
 
     // We use output stream to communicate because FD is easier to handle than exit code.
     command.stdout(Stdio::piped());
 
-    let mut xwayland_args = format!(":{} -rootless -terminate -wm {}", display, wm_socket.as_raw_fd());
+    let mut xwayland_args: Vec<OsString> = vec![
+        config
+            .binary
+            .clone()
+            .map(PathBuf::into_os_string)
+            .unwrap_or_else(|| "Xwayland".into()),
+        format!(":{}", display).into(),
+        "-rootless".into(),
+        "-terminate".into(),
+        "-wm".into(),
+        wm_socket.as_raw_fd().to_string().into(),
+    ];
     for socket in listen_sockets {
-        xwayland_args.push_str(&format!(" -listen {}", socket.as_raw_fd()));
+        xwayland_args.push("-listen".into());
+        xwayland_args.push(socket.as_raw_fd().to_string().into());
     }
+    xwayland_args.extend(config.extra_args.iter().map(OsString::from));
     // This command let sh to:
     // * Set up signal handler for USR1
     // * Launch Xwayland with USR1 ignored so Xwayland will signal us when it is ready (also redirect
     //   Xwayland's STDOUT to STDERR so its output, if any, won't distract us)
     // * Print "S" and exit if USR1 is received
-    command.arg("-c").arg(format!(
-        "trap 'echo S' USR1; (trap '' USR1; exec Xwayland {}) 1>&2 & wait",
-        xwayland_args
-    ));
-
-    // Setup the environment: clear everything except PATH and XDG_RUNTIME_DIR
+    // The Xwayland command line is given as the positional parameters of the script, so that it
+    // does not need to be escaped.
+    command
+        .arg("-c")
+        .arg("trap 'echo S' USR1; (trap '' USR1; exec \"$@\") 1>&2 & wait")
+        .arg("sh")
+        .args(xwayland_args);
+
+    // Setup the environment: clear everything except PATH, XDG_RUNTIME_DIR and the allowed variables
     command.env_clear();
     for (key, value) in env::vars_os() {
-        if key.to_str() == Some("PATH") || key.to_str() == Some("XDG_RUNTIME_DIR") {
+        if key.to_str().map(|key| config.allows_env(key)).unwrap_or(false) {
             command.env(key, value);
-            continue;
         }
     }
     command.env("WAYLAND_SOCKET", format!("{}", wayland_socket.as_raw_fd()));
@@ -0,0 +1,323 @@
+//! A minimal X11 window manager for XWayland
+//!
+//! The [`X11Wm`] takes over the WM connection provided by [`XWaylandEvent::Ready`](super::XWaylandEvent),
+//! becomes the window manager of the XWayland server and translates the X11 events into
+//! [`X11WmEvent`]s. In particular, it associates the X11 windows with the `wl_surface` XWayland
+//! created for them.
+//!
+//! The [`X11Source`] returned by [`X11Wm::start_wm`] must be inserted into your event loop, and the
+//! X11 events it produces given to [`X11Wm::handle_event`]. As XWayland may create the `wl_surface` of
+//! a window after announcing it, you also need to call [`X11Wm::surface_committed`] when a surface
+//! of the XWayland client is committed.
+
+use std::{collections::HashMap, io, os::unix::net::UnixStream, sync::Arc};
+
+use slog::{debug, error, info, o};
+use wayland_server::{protocol::wl_surface::WlSurface, Client};
+use x11rb::{
+    connection::Connection as _,
+    protocol::{
+        composite::{ConnectionExt as _, Redirect},
+        xproto::{
+            ChangeWindowAttributesAux, ConfigWindow, ConfigureWindowAux, ConnectionExt as _, EventMask,
+            Window, WindowClass,
+        },
+        Event,
+    },
+    rust_connection::{
+        ConnectError, ConnectionError, DefaultStream, ReplyError, ReplyOrIdError, RustConnection,
+    },
+};
+
+use crate::{
+    utils::{x11rb::X11Source, Logical, Point},
+    wayland::compositor::give_role,
+};
+
+/// The role of the surfaces of X11 windows
+pub const X11_SURFACE_ROLE: &str = "x11_surface";
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        WM_S0,
+        WL_SURFACE_ID,
+        _SMITHAY_CLOSE_CONNECTION,
+    }
+}
+
+/// An error emitted when starting the X11 window manager
+#[derive(Debug, thiserror::Error)]
+pub enum X11WmError {
+    /// Setting up the WM connection failed
+    #[error("Setting up the WM connection failed")]
+    Io(#[from] io::Error),
+    /// Connecting to the XWayland server failed
+    #[error("Connecting to the XWayland server failed")]
+    ConnectionFailed(#[from] ConnectError),
+    /// Some protocol error occurred while becoming the window manager
+    #[error("Some protocol error occurred while becoming the window manager")]
+    Protocol(#[from] ReplyOrIdError),
+}
+
+impl From<ReplyError> for X11WmError {
+    fn from(err: ReplyError) -> Self {
+        Self::Protocol(err.into())
+    }
+}
+
+impl From<ConnectionError> for X11WmError {
+    fn from(err: ConnectionError) -> Self {
+        Self::Protocol(err.into())
+    }
+}
+
+/// Events generated by the X11 window manager
+#[derive(Debug)]
+pub enum X11WmEvent {
+    /// An X11 client asked to map a window
+    ///
+    /// You can grant it with [`X11Wm::map_window`].
+    MapRequest {
+        /// The window to map
+        window: Window,
+    },
+    /// An X11 client asked to change the geometry or stacking of a window
+    ///
+    /// You can grant it, possibly after modifying it, with [`X11Wm::configure_window`].
+    ConfigureRequest {
+        /// The window to configure
+        window: Window,
+        /// The requested changes
+        changes: ConfigureWindowAux,
+    },
+    /// A window was unmapped
+    UnmapNotify {
+        /// The unmapped window
+        window: Window,
+    },
+    /// The `wl_surface` of a window was identified
+    ///
+    /// The surface was given the [`X11_SURFACE_ROLE`] role.
+    NewSurface {
+        /// The window
+        window: Window,
+        /// Its `wl_surface`
+        surface: WlSurface,
+        /// The location of the window when it was associated
+        location: Point<i32, Logical>,
+    },
+}
+
+/// The X11 window manager of an XWayland server
+#[derive(Debug)]
+pub struct X11Wm {
+    conn: Arc<RustConnection>,
+    atoms: Atoms,
+    client: Client,
+    unpaired_surfaces: HashMap<u32, (Window, Point<i32, Logical>)>,
+    log: ::slog::Logger,
+}
+
+impl X11Wm {
+    /// Become the window manager of an XWayland server
+    ///
+    /// Takes the WM connection and the wayland client provided by the `XWaylandEvent::Ready` event.
+    /// Redirects the structure requests of the root window to us, and acquires the `WM_S0` selection,
+    /// after which XWayland starts accepting X11 clients.
+    ///
+    /// Returns the window manager and the [`X11Source`] producing its X11 events.
+    pub fn start_wm<L>(
+        connection: UnixStream,
+        client: Client,
+        logger: L,
+    ) -> Result<(Self, X11Source), X11WmError>
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "xwayland_wm"));
+
+        // Create an X11 connection. XWayland only uses screen 0.
+        let screen = 0;
+        let stream = DefaultStream::from_unix_stream(connection)?;
+        let conn = RustConnection::connect_to_stream(stream, screen)?;
+        let atoms = Atoms::new(&conn)?.reply()?;
+
+        let screen = &conn.setup().roots[0];
+
+        // Actually become the WM by redirecting some operations
+        conn.change_window_attributes(
+            screen.root,
+            &ChangeWindowAttributesAux::default()
+                .event_mask(EventMask::SUBSTRUCTURE_REDIRECT | EventMask::SUBSTRUCTURE_NOTIFY),
+        )?;
+
+        // Tell XWayland that we are the WM by acquiring the WM_S0 selection. No X11 clients are accepted before this.
+        let win = conn.generate_id()?;
+        conn.create_window(
+            screen.root_depth,
+            win,
+            screen.root,
+            // x, y, width, height, border width
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &Default::default(),
+        )?;
+        conn.set_selection_owner(win, atoms.WM_S0, x11rb::CURRENT_TIME)?;
+
+        // XWayland needs the windows to be redirected to draw them into their wl_surface
+        conn.composite_redirect_subwindows(screen.root, Redirect::MANUAL)?;
+
+        conn.flush()?;
+
+        let conn = Arc::new(conn);
+        let source = X11Source::new(conn.clone(), win, atoms._SMITHAY_CLOSE_CONNECTION, log.clone());
+        let wm = X11Wm {
+            conn,
+            atoms,
+            client,
+            unpaired_surfaces: HashMap::new(),
+            log,
+        };
+
+        Ok((wm, source))
+    }
+
+    /// Access the X11 connection of the window manager
+    pub fn connection(&self) -> &Arc<RustConnection> {
+        &self.conn
+    }
+
+    /// Handle an X11 event produced by the [`X11Source`] of this window manager
+    ///
+    /// Returns the corresponding [`X11WmEvent`], if any.
+    pub fn handle_event(&mut self, event: Event) -> Result<Option<X11WmEvent>, ReplyOrIdError> {
+        debug!(self.log, "Got X11 event {:?}", event);
+        let event = match event {
+            Event::ConfigureRequest(r) => {
+                let mut changes = ConfigureWindowAux::default();
+                if r.value_mask & u16::from(ConfigWindow::STACK_MODE) != 0 {
+                    changes = changes.stack_mode(r.stack_mode);
+                }
+                if r.value_mask & u16::from(ConfigWindow::SIBLING) != 0 {
+                    changes = changes.sibling(r.sibling);
+                }
+                if r.value_mask & u16::from(ConfigWindow::X) != 0 {
+                    changes = changes.x(i32::from(r.x));
+                }
+                if r.value_mask & u16::from(ConfigWindow::Y) != 0 {
+                    changes = changes.y(i32::from(r.y));
+                }
+                if r.value_mask & u16::from(ConfigWindow::WIDTH) != 0 {
+                    changes = changes.width(u32::from(r.width));
+                }
+                if r.value_mask & u16::from(ConfigWindow::HEIGHT) != 0 {
+                    changes = changes.height(u32::from(r.height));
+                }
+                if r.value_mask & u16::from(ConfigWindow::BORDER_WIDTH) != 0 {
+                    changes = changes.border_width(u32::from(r.border_width));
+                }
+                Some(X11WmEvent::ConfigureRequest {
+                    window: r.window,
+                    changes,
+                })
+            }
+            Event::MapRequest(r) => Some(X11WmEvent::MapRequest { window: r.window }),
+            Event::UnmapNotify(n) => Some(X11WmEvent::UnmapNotify { window: n.window }),
+            Event::ClientMessage(msg) if msg.type_ == self.atoms.WL_SURFACE_ID => {
+                // We get a WL_SURFACE_ID message when Xwayland creates a WlSurface for a
+                // window. Both the creation of the surface and this client message happen at
+                // roughly the same time and are sent over different sockets (X11 socket and
+                // wayland socket). Thus, we could receive these two in any order. Hence, it
+                // can happen that we get None below when X11 was faster than Wayland.
+                let location = match self.conn.get_geometry(msg.window)?.reply() {
+                    Ok(geo) => (i32::from(geo.x), i32::from(geo.y)).into(),
+                    Err(err) => {
+                        error!(
+                            self.log,
+                            "Failed to get geometry for {:x}, perhaps the window was already destroyed?",
+                            msg.window;
+                            "err" => format!("{:?}", err),
+                        );
+                        (0, 0).into()
+                    }
+                };
+
+                let id = msg.data.as_data32()[0];
+                let surface = self.client.get_resource::<WlSurface>(id);
+                info!(
+                    self.log,
+                    "X11 surface {:x?} corresponds to WlSurface {:x} = {:?}", msg.window, id, surface,
+                );
+                match surface {
+                    None => {
+                        self.unpaired_surfaces.insert(id, (msg.window, location));
+                        None
+                    }
+                    Some(surface) => self.new_surface(msg.window, surface, location),
+                }
+            }
+            _ => None,
+        };
+        self.conn.flush()?;
+        Ok(event)
+    }
+
+    /// Notify the window manager that a surface was committed
+    ///
+    /// If this surface belongs to a window announced before the surface was created, returns the
+    /// corresponding [`X11WmEvent::NewSurface`] event.
+    pub fn surface_committed(&mut self, surface: &WlSurface) -> Option<X11WmEvent> {
+        let is_xwayland = surface
+            .as_ref()
+            .client()
+            .map(|client| client.equals(&self.client))
+            .unwrap_or(false);
+        if !is_xwayland {
+            return None;
+        }
+        let (window, location) = self.unpaired_surfaces.remove(&surface.as_ref().id())?;
+        self.new_surface(window, surface.clone(), location)
+    }
+
+    /// Map a window, to grant a [`X11WmEvent::MapRequest`]
+    pub fn map_window(&self, window: Window) -> Result<(), ConnectionError> {
+        self.conn.map_window(window)?;
+        self.conn.flush()
+    }
+
+    /// Configure a window, to grant a [`X11WmEvent::ConfigureRequest`]
+    pub fn configure_window(
+        &self,
+        window: Window,
+        changes: &ConfigureWindowAux,
+    ) -> Result<(), ConnectionError> {
+        self.conn.configure_window(window, changes)?;
+        self.conn.flush()
+    }
+
+    fn new_surface(
+        &mut self,
+        window: Window,
+        surface: WlSurface,
+        location: Point<i32, Logical>,
+    ) -> Option<X11WmEvent> {
+        debug!(self.log, "Matched X11 surface {:x?} to {:x?}", window, surface);
+
+        if give_role(&surface, X11_SURFACE_ROLE).is_err() {
+            // It makes no sense to post a protocol error here since that would only kill Xwayland
+            error!(self.log, "Surface {:x?} already has a role?!", surface);
+            return None;
+        }
+
+        Some(X11WmEvent::NewSurface {
+            window,
+            surface,
+            location,
+        })
+    }
+}
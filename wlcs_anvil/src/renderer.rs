@@ -94,6 +94,14 @@ impl Frame for DummyFrame {
         Ok(())
     }
 
+    fn clear_damaged(
+        &mut self,
+        _color: [f32; 4],
+        _damage: &[Rectangle<i32, Physical>],
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
     fn render_texture(
         &mut self,
         _texture: &Self::TextureId,